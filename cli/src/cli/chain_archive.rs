@@ -0,0 +1,94 @@
+use crate::{cli::chain_log::instance_chain_store, error::DefaultResult};
+use holochain_core::{
+    agent::{chain_archive::ChainArchive, state::AgentStateSnapshot},
+    persister::SimplePersister,
+};
+use serde_json;
+use std::{fs, path::PathBuf};
+
+/// Writes the source chain of the given instance to an archive file.
+pub fn chain_export(
+    storage_path: Option<PathBuf>,
+    instance_id: String,
+    archive_path: PathBuf,
+) -> DefaultResult<()> {
+    let (cas_path, chain_store) = instance_chain_store(storage_path, &instance_id)?;
+    let snapshot = SimplePersister::new(chain_store.content_storage())
+        .load_agent_snapshot()?
+        .ok_or_else(|| {
+            format_err!(
+                "Chain for '{}' does not exist or has not been initialized",
+                cas_path.to_string_lossy()
+            )
+        })?;
+    let archive = ChainArchive::export(&chain_store, &snapshot.top_chain_header().cloned())?;
+    fs::write(&archive_path, serde_json::to_string_pretty(&archive)?)?;
+    println!(
+        "Exported {} chain entries to '{}'",
+        archive.items.len(),
+        archive_path.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Verifies the given archive file and imports it into the source chain of the given instance.
+/// The instance must not be running and must have been started at least once, so that there
+/// is persisted state to add the imported chain to.
+pub fn chain_import(
+    storage_path: Option<PathBuf>,
+    instance_id: String,
+    archive_path: PathBuf,
+) -> DefaultResult<()> {
+    let archive: ChainArchive = serde_json::from_str(&fs::read_to_string(&archive_path)?)?;
+    let (cas_path, chain_store) = instance_chain_store(storage_path, &instance_id)?;
+    let mut persister = SimplePersister::new(chain_store.content_storage());
+    let snapshot = persister.load_agent_snapshot()?.ok_or_else(|| {
+        format_err!(
+            "No persisted state found in '{}'. Run the instance once before importing a chain into it.",
+            cas_path.to_string_lossy()
+        )
+    })?;
+    let top_chain_header =
+        archive.import_into(&chain_store, &snapshot.top_chain_header().cloned())?;
+    persister.save_agent_snapshot(AgentStateSnapshot::new(top_chain_header))?;
+    println!(
+        "Imported {} chain entries into '{}'",
+        archive.items.len(),
+        cas_path.to_string_lossy()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate tempfile;
+    use self::tempfile::tempdir;
+    use holochain_core::agent::chain_archive::CHAIN_ARCHIVE_VERSION;
+
+    #[test]
+    fn import_refuses_storage_without_state() {
+        let storage = tempdir().unwrap();
+        fs::create_dir_all(storage.path().join("instance").join("cas")).unwrap();
+        let archive_path = storage.path().join("chain.json");
+        fs::write(
+            &archive_path,
+            serde_json::to_string(&ChainArchive {
+                version: CHAIN_ARCHIVE_VERSION,
+                items: Vec::new(),
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let result = chain_import(
+            Some(storage.path().to_path_buf()),
+            "instance".to_string(),
+            archive_path,
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No persisted state found"));
+    }
+}
@@ -1,8 +1,8 @@
 use crate::error::DefaultResult;
 use colored::*;
-use holochain_core::agent::{
-    chain_store::ChainStore,
-    state::{AgentState, AgentStateSnapshot},
+use holochain_core::{
+    agent::{chain_store::ChainStore, state::AgentState},
+    persister::SimplePersister,
 };
 use holochain_core_types::{chain_header::ChainHeader, entry::Entry};
 use holochain_persistence_api::cas::content::Address;
@@ -13,23 +13,14 @@ use std::{convert::TryFrom, fs, path::PathBuf};
 const DEFAULT_CHAIN_PATH: &str = "TODO";
 
 pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultResult<()> {
-    // let storage_path = storage_path.unwrap_or_else(|| PathBuf::from(DEFAULT_CHAIN_PATH));
-    let storage_path = storage_path.ok_or(format_err!(
-        "Please specify the path to CAS storage with the --path option."
-    ))?;
-    let cas_path = storage_path.join(instance_id).join("cas");
-    let chain_store = ChainStore::new(std::sync::Arc::new(std::sync::RwLock::new(
-        FilesystemStorage::new(cas_path.clone()).expect("Could not create chain store".into()),
-    )));
+    let (cas_path, chain_store) = instance_chain_store(storage_path, &instance_id)?;
     let cas_lock = chain_store.content_storage();
-    let cas = cas_lock.read().unwrap();
 
-    let agent = cas
-        .fetch(&Address::from("AgentState"))?
-        .ok_or("Chain does not exist or has not been initialized")
-        .and_then(|snapshot_json| {
-            AgentStateSnapshot::from_json_str(&snapshot_json.to_string())
-                .map_err(|_| "AgentState is malformed")
+    let agent = SimplePersister::new(cas_lock.clone())
+        .load_agent_snapshot()
+        .map_err(|err| err.to_string())
+        .and_then(|snapshot| {
+            snapshot.ok_or_else(|| "Chain does not exist or has not been initialized".to_string())
         })
         .map(|snapshot| {
             let top_header = snapshot.top_chain_header().to_owned().clone();
@@ -39,9 +30,10 @@ pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultR
             format_err!(
                 "Could not display chain for '{}': {}",
                 cas_path.to_string_lossy(),
-                err.to_string()
+                err
             )
         })?;
+    let cas = cas_lock.read().unwrap();
 
     println!(
         "\nChain entries for '{}' (latest on top):\n",
@@ -65,6 +57,21 @@ pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultR
     Ok(())
 }
 
+/// Opens the file based chain storage of the given instance below `storage_path`.
+pub(crate) fn instance_chain_store(
+    storage_path: Option<PathBuf>,
+    instance_id: &str,
+) -> DefaultResult<(PathBuf, ChainStore)> {
+    let storage_path = storage_path.ok_or(format_err!(
+        "Please specify the path to CAS storage with the --path option."
+    ))?;
+    let cas_path = storage_path.join(instance_id).join("cas");
+    let chain_store = ChainStore::new(std::sync::Arc::new(std::sync::RwLock::new(
+        FilesystemStorage::new(cas_path.clone()).expect("Could not create chain store".into()),
+    )));
+    Ok((cas_path, chain_store))
+}
+
 pub fn chain_list(path: Option<PathBuf>) {
     let path = path.unwrap_or_else(|| PathBuf::from(DEFAULT_CHAIN_PATH));
     println!("Please specify an instance ID to view its chain.");
@@ -1,20 +1,26 @@
+mod chain_archive;
 mod chain_log;
 mod generate;
 mod hash_dna;
 mod init;
 mod keygen;
 pub mod package;
+mod replay;
 mod run;
 mod scaffold;
+mod storage;
 pub mod test;
 
 pub use self::{
+    chain_archive::{chain_export, chain_import},
     chain_log::{chain_list, chain_log},
     generate::generate,
     hash_dna::hash_dna,
     init::init,
     keygen::keygen,
     package::{package, unpack},
+    replay::replay,
     run::{get_interface_type_string, hc_run_configuration, run},
+    storage::storage_migrate,
     test::{test, TEST_DIR_NAME},
 };
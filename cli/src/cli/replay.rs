@@ -0,0 +1,42 @@
+use colored::*;
+use error::DefaultResult;
+use holochain_conductor_api::conductor::Conductor;
+use holochain_core::nucleus::ribosome::recording::{replay_zome_call, ZomeCallRecording};
+use std::path::PathBuf;
+
+/// Replays a recorded zome call against the WASM of the given DNA file, offline,
+/// and reports whether it returned the recorded result.
+pub fn replay(recording_path: &PathBuf, dna_path: &PathBuf) -> DefaultResult<()> {
+    let recording = ZomeCallRecording::load(recording_path)?;
+    let dna = Conductor::load_dna(dna_path)?;
+    let zome = dna.zomes.get(&recording.zome_name).ok_or_else(|| {
+        format_err!(
+            "DNA {} has no zome '{}'",
+            dna_path.to_string_lossy(),
+            recording.zome_name
+        )
+    })?;
+
+    println!(
+        "Replaying {}/{} with {} recorded Zome API calls",
+        recording.zome_name,
+        recording.fn_name,
+        recording.host_calls.len()
+    );
+    let result = replay_zome_call(zome.code.code.clone(), &recording).map(String::from);
+    match &result {
+        Ok(value) => println!("Result: {}", value),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    if result == recording.result {
+        println!("{}", "Replay returned the recorded result".green());
+        Ok(())
+    } else {
+        match &recording.result {
+            Ok(value) => println!("Recorded result: {}", value),
+            Err(error) => println!("Recorded error: {}", error),
+        }
+        bail!("Replay did not return the recorded result")
+    }
+}
@@ -76,11 +76,15 @@ pub fn hc_run_configuration(
     networked: bool,
     interface_type: &String,
     logging: bool,
+    record: Option<PathBuf>,
 ) -> DefaultResult<Configuration> {
     Ok(Configuration {
         agents: vec![agent_configuration()],
         dnas: vec![dna_configuration(&dna_path)],
-        instances: vec![instance_configuration(storage_configuration(persist)?)],
+        instances: vec![instance_configuration(
+            storage_configuration(persist)?,
+            recording_configuration(record),
+        )],
         interfaces: vec![interface_configuration(&interface_type, port)?],
         network: networking_configuration(networked),
         logger: logger_configuration(logging),
@@ -149,15 +153,33 @@ fn storage_configuration(persist: bool) -> DefaultResult<StorageConfiguration> {
     }
 }
 
+fn recording_configuration(record: Option<PathBuf>) -> RecordingConfiguration {
+    RecordingConfiguration {
+        path: record.map(|path| path.to_string_lossy().to_string()),
+    }
+}
+
 // INSTANCE
 const INSTANCE_CONFIG_ID: &str = "test-instance";
 
-fn instance_configuration(storage: StorageConfiguration) -> InstanceConfiguration {
+fn instance_configuration(
+    storage: StorageConfiguration,
+    recording: RecordingConfiguration,
+) -> InstanceConfiguration {
     InstanceConfiguration {
         id: INSTANCE_CONFIG_ID.into(),
         dna: DNA_CONFIG_ID.into(),
         agent: AGENT_CONFIG_ID.into(),
         storage,
+        persister: PersisterConfiguration::default(),
+        retention: RetentionConfiguration::default(),
+        quota: QuotaConfiguration::default(),
+        fuel: FuelConfiguration::default(),
+        memory: MemoryConfiguration::default(),
+        profiling: ProfilingConfiguration::default(),
+        backtraces: BacktraceConfiguration::default(),
+        recording,
+        validation: ValidationConfiguration::default(),
     }
 }
 
@@ -334,7 +356,8 @@ mod tests {
     #[test]
     fn test_instance_configuration() {
         let storage = super::storage_configuration(false).unwrap();
-        let instance = super::instance_configuration(storage);
+        let recording = super::recording_configuration(None);
+        let instance = super::instance_configuration(storage, recording);
         assert_eq!(
             instance,
             InstanceConfiguration {
@@ -342,6 +365,15 @@ mod tests {
                 dna: "hc-run-dna".to_string(),
                 agent: "hc-run-agent".to_string(),
                 storage: StorageConfiguration::Memory,
+                persister: PersisterConfiguration::default(),
+                retention: RetentionConfiguration::default(),
+                quota: QuotaConfiguration::default(),
+                fuel: FuelConfiguration::default(),
+                memory: MemoryConfiguration::default(),
+                profiling: ProfilingConfiguration::default(),
+                backtraces: BacktraceConfiguration::default(),
+                recording: RecordingConfiguration::default(),
+                validation: ValidationConfiguration::default(),
             }
         )
     }
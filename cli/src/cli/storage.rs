@@ -0,0 +1,93 @@
+use crate::error::DefaultResult;
+use holochain_conductor_api::storage::{
+    encrypted::KEY_CHECK_FILE,
+    lmdb::{EavLmdbStorage, LmdbEnvironment, LmdbStorage},
+};
+use holochain_core::persister::migration::{migrate, MigrationStorage};
+use holochain_core_types::eav::Attribute;
+use holochain_persistence_api::eav::EntityAttributeValueStorage;
+use holochain_persistence_file::{cas::file::FilesystemStorage, eav::file::EavFileStorage};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+/// Opens the file or LMDB storage of a single instance in the given directory.
+fn open_instance_storage(path: &Path) -> DefaultResult<MigrationStorage> {
+    if path.join(KEY_CHECK_FILE).exists() {
+        bail!(
+            "Storage in '{}' is encrypted and can't be migrated offline",
+            path.to_string_lossy()
+        );
+    }
+    if path.join("data.mdb").exists() {
+        let env = LmdbEnvironment::new(path, None)?;
+        let cas = Arc::new(RwLock::new(LmdbStorage::new(env.clone())));
+        return Ok(MigrationStorage {
+            cas: cas.clone(),
+            dht_cas: cas,
+            eav: Arc::new(RwLock::new(EavLmdbStorage::new(env))),
+        });
+    }
+    let cas_path = path.join("cas");
+    let eav_path = path.join("eav");
+    if !cas_path.is_dir() || !eav_path.is_dir() {
+        bail!(
+            "No file or LMDB instance storage found in '{}'",
+            path.to_string_lossy()
+        );
+    }
+    let eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavFileStorage::new(eav_path)?));
+    let cas = Arc::new(RwLock::new(FilesystemStorage::new(cas_path)?));
+    Ok(MigrationStorage {
+        cas: cas.clone(),
+        dht_cas: cas,
+        eav,
+    })
+}
+
+/// Upgrades the storage of a stopped instance to the storage format of this version
+/// of Holochain. With `dry_run` set, only reports what would change.
+pub fn storage_migrate(path: PathBuf, dry_run: bool) -> DefaultResult<()> {
+    let storage = open_instance_storage(&path)?;
+    let report = migrate(&storage, dry_run)?;
+    print!("{}", report);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate tempfile;
+    use self::tempfile::tempdir;
+    use holochain_core::persister::migration::{storage_version, CURRENT_STORAGE_VERSION};
+    use std::fs;
+
+    #[test]
+    fn migrate_refuses_directory_without_storage() {
+        let dir = tempdir().unwrap();
+        let result = storage_migrate(dir.path().to_path_buf(), true);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No file or LMDB instance storage found"));
+    }
+
+    #[test]
+    fn dry_run_does_not_write() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("cas")).unwrap();
+        fs::create_dir_all(dir.path().join("eav")).unwrap();
+
+        storage_migrate(dir.path().to_path_buf(), true).unwrap();
+        let storage = open_instance_storage(dir.path()).unwrap();
+        assert_eq!(storage_version(&storage.cas).unwrap(), None);
+
+        storage_migrate(dir.path().to_path_buf(), false).unwrap();
+        assert_eq!(
+            storage_version(&storage.cas).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+    }
+}
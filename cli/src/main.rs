@@ -119,6 +119,11 @@ enum Cli {
             default_value = "websocket"
         )]
         interface: String,
+        #[structopt(
+            long,
+            help = "Record every zome call into the given directory, to replay it with `hc replay`"
+        )]
+        record: Option<PathBuf>,
     },
     #[structopt(
         name = "test",
@@ -166,7 +171,7 @@ enum Cli {
         #[structopt(long, short, help = "Don't ask for passphrase")]
         nullpass: bool,
     },
-    #[structopt(name = "chain", about = "View the contents of a source chain")]
+    #[structopt(name = "chain", about = "View, export or import the contents of a source chain")]
     ChainLog {
         #[structopt(name = "INSTANCE", help = "Instance ID to view")]
         instance_id: Option<String>,
@@ -174,6 +179,24 @@ enum Cli {
         path: Option<PathBuf>,
         #[structopt(long, short, help = "List available instances")]
         list: bool,
+        #[structopt(
+            long,
+            parse(from_os_str),
+            conflicts_with = "import",
+            help = "Export the chain to the given archive file"
+        )]
+        export: Option<PathBuf>,
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help = "Verify the given archive file and import its chain (the instance must not be running)"
+        )]
+        import: Option<PathBuf>,
+    },
+    #[structopt(name = "storage", about = "Maintain the storage of an instance")]
+    Storage {
+        #[structopt(subcommand)]
+        command: StorageCommand,
     },
     #[structopt(
         name = "hash",
@@ -187,6 +210,38 @@ enum Cli {
         )]
         path: Option<PathBuf>,
     },
+    #[structopt(
+        name = "replay",
+        about = "Replays a zome call recorded with `hc run --record` offline against the DNA"
+    )]
+    Replay {
+        #[structopt(name = "RECORDING", parse(from_os_str), help = "Recording file to replay")]
+        recording: PathBuf,
+        #[structopt(
+            long = "dna",
+            short = "d",
+            help = "Path to .dna.json file [default: dist/<dna-name>.dna.json]"
+        )]
+        dna_path: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum StorageCommand {
+    #[structopt(
+        name = "migrate",
+        about = "Upgrades the file or LMDB storage of an instance to the current storage format. The instance must not be running."
+    )]
+    Migrate {
+        #[structopt(
+            name = "PATH",
+            parse(from_os_str),
+            help = "Storage directory of the instance, as configured in its storage path"
+        )]
+        path: PathBuf,
+        #[structopt(long, help = "Only report what would be migrated without changing anything")]
+        dry_run: bool,
+    },
 }
 
 fn main() {
@@ -230,6 +285,7 @@ fn run() -> HolochainResult<()> {
             networked,
             interface,
             logging,
+            record,
         } => {
             let dna_path = dna_path
                 .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
@@ -241,6 +297,7 @@ fn run() -> HolochainResult<()> {
                 networked,
                 &interface_type,
                 logging,
+                record,
             )
             .map_err(HolochainError::Default)?;
             cli::run(dna_path, package, port, interface_type, conductor_config)
@@ -277,17 +334,33 @@ fn run() -> HolochainResult<()> {
             instance_id,
             list,
             path,
-        } => match (list, instance_id) {
-            (true, _) => cli::chain_list(path),
-            (false, None) => {
+            export,
+            import,
+        } => match (list, instance_id, export, import) {
+            (true, _, _, _) => cli::chain_list(path),
+            (false, None, _, _) => {
                 Cli::clap().print_help().expect("Couldn't print help!");
                 println!("\n\nTry `hc help chain` for more info");
             }
-            (false, Some(instance_id)) => {
+            (false, Some(instance_id), Some(archive_path), _) => {
+                cli::chain_export(path, instance_id, archive_path)
+                    .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
+            }
+            (false, Some(instance_id), None, Some(archive_path)) => {
+                cli::chain_import(path, instance_id, archive_path)
+                    .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
+            }
+            (false, Some(instance_id), None, None) => {
                 cli::chain_log(path, instance_id)
                     .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             }
         },
+        Cli::Storage {
+            command: StorageCommand::Migrate { path, dry_run },
+        } => {
+            cli::storage_migrate(path, dry_run)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
+        }
         Cli::HashDna { path } => {
             let dna_path = path
                 .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
@@ -296,6 +369,15 @@ fn run() -> HolochainResult<()> {
                 .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             println!("DNA Hash: {}", dna_hash);
         }
+
+        Cli::Replay {
+            recording,
+            dna_path,
+        } => {
+            let dna_path = dna_path
+                .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
+            cli::replay(&recording, &dna_path).map_err(HolochainError::Default)?
+        }
     }
 
     Ok(())
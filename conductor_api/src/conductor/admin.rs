@@ -1,8 +1,11 @@
 use crate::{
     conductor::{base::notify, Conductor},
     config::{
-        AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration,
-        InstanceReferenceConfiguration, InterfaceConfiguration, StorageConfiguration,
+        AgentConfiguration, BacktraceConfiguration, Bridge, DnaConfiguration, FuelConfiguration,
+        InstanceConfiguration, InstanceReferenceConfiguration, InterfaceConfiguration,
+        MemoryConfiguration, PersisterConfiguration, ProfilingConfiguration, QuotaConfiguration,
+        RecordingConfiguration, RetentionConfiguration, StorageConfiguration,
+        ValidationConfiguration,
     },
     dpki_instance::DpkiInstance,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
@@ -208,6 +211,15 @@ impl ConductorAdmin for Conductor {
                     ))?
                     .into(),
             },
+            persister: PersisterConfiguration::default(),
+            retention: RetentionConfiguration::default(),
+            quota: QuotaConfiguration::default(),
+            fuel: FuelConfiguration::default(),
+            memory: MemoryConfiguration::default(),
+            profiling: ProfilingConfiguration::default(),
+            backtraces: BacktraceConfiguration::default(),
+            recording: RecordingConfiguration::default(),
+            validation: ValidationConfiguration::default(),
         };
         new_config.instances.push(new_instance_config);
         new_config.check_consistency(&mut self.dna_loader)?;
@@ -244,6 +256,9 @@ impl ConductorAdmin for Conductor {
         self.instances.remove(id).map(|instance| {
             instance.write().unwrap().kill();
         });
+        for instances in self.agent_instances.values() {
+            instances.lock().unwrap().remove(id);
+        }
         let _ = self.start_signal_multiplexer();
 
         notify(format!("Removed instance \"{}\".", id));
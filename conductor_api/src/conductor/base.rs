@@ -2,33 +2,52 @@ use crate::{
     conductor::broadcaster::Broadcaster,
     config::{
         serialize_configuration, Configuration, InterfaceConfiguration, InterfaceDriver,
-        NetworkConfig, StorageConfiguration,
+        NetworkConfig, PersisterConfiguration, StorageConfiguration,
     },
     context_builder::ContextBuilder,
     dpki_instance::DpkiInstance,
     error::HolochainInstanceError,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+    storage::encrypted::StorageCipher,
     Holochain,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_common::paths::DNA_EXTENSION;
-use holochain_core::{logger::Logger, signal::Signal};
+use holochain_core::{
+    agent::chain_archive::ChainArchive,
+    context::Context,
+    logger::Logger,
+    nucleus::{
+        actions::dead_letter_validation::{
+            remove_dead_letter_validation, retry_dead_letter_validation,
+        },
+        ribosome::profile::ProfileReport,
+        state::PendingValidationKey,
+    },
+    persister::migration::{check_storage_version, MigrationStorage},
+    quota::UsageReport,
+    scheduled_jobs::pending_validations::{DeadLetterValidation, ValidatingWorkflow},
+    signal::Signal,
+};
 use holochain_core_types::{
-    agent::AgentId,
+    agent::{AgentId, Base32},
     dna::Dna,
     error::{HcResult, HolochainError},
 };
 use key_loaders::test_keystore;
 
 use holochain_json_api::json::JsonString;
-use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
+use holochain_persistence_api::{
+    cas::content::{Address, AddressableContent},
+    hash::HashString,
+};
 
 use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig};
 use jsonrpc_ws_server::jsonrpc_core::IoHandler;
 use logging::{rule::RuleFilter, FastLogger, FastLoggerBuilder};
 use std::{
     clone::Clone,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     fs::{self, File},
     io::prelude::*,
@@ -95,6 +114,9 @@ pub struct Conductor {
     pub(in crate::conductor) instances: InstanceMap,
     instance_signal_receivers: Arc<RwLock<HashMap<String, Receiver<Signal>>>>,
     agent_keys: HashMap<String, Arc<Mutex<Keystore>>>,
+    /// IDs of the instances every agent runs, which may only rotate their key
+    /// if there is just one
+    pub(in crate::conductor) agent_instances: HashMap<String, Arc<Mutex<HashSet<String>>>>,
     pub(in crate::conductor) config: Configuration,
     pub(in crate::conductor) static_servers: HashMap<String, StaticServer>,
     pub(in crate::conductor) interface_threads: HashMap<String, Sender<()>>,
@@ -213,6 +235,7 @@ impl Conductor {
             instances: HashMap::new(),
             instance_signal_receivers: Arc::new(RwLock::new(HashMap::new())),
             agent_keys: HashMap::new(),
+            agent_instances: HashMap::new(),
             interface_threads: HashMap::new(),
             static_servers: HashMap::new(),
             interface_broadcasters: Arc::new(RwLock::new(HashMap::new())),
@@ -461,6 +484,113 @@ impl Conductor {
         instance.write().unwrap().stop()
     }
 
+    /// Exports the source chain of the given instance as a portable archive.
+    pub fn export_chain(&self, id: &String) -> Result<ChainArchive, HolochainInstanceError> {
+        self.instances.get(id)?.read().unwrap().export_chain()
+    }
+
+    /// Reports the storage usage of the given instance and the quota it is limited by.
+    pub fn instance_usage(&self, id: &String) -> Result<UsageReport, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        Ok(UsageReport::new(
+            context.storage_usage.snapshot(),
+            context.storage_quota.clone(),
+        ))
+    }
+
+    /// Reports the profiles of the zome functions of the given instance called so far.
+    /// Fails if profiling is not enabled for the instance.
+    pub fn instance_profile(&self, id: &String) -> Result<ProfileReport, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        let profiler = context.profiler.as_ref().ok_or_else(|| {
+            HolochainInstanceError::InternalFailure(HolochainError::ErrorGeneric(format!(
+                "Profiling is not enabled for instance \"{}\"",
+                id
+            )))
+        })?;
+        Ok(profiler.report())
+    }
+
+    /// Lists the validations of the given instance that were given up on after
+    /// running out of attempts.
+    pub fn dead_letter_validations(
+        &self,
+        id: &String,
+    ) -> Result<Vec<DeadLetterValidation>, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        let state = context
+            .state()
+            .ok_or(HolochainInstanceError::InstanceNotInitialized)?;
+        Ok(state
+            .nucleus()
+            .dead_letter_validations
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    /// Moves a dead-lettered validation of the given instance back to its pending
+    /// validations, where it gets retried with a fresh number of attempts.
+    pub fn retry_dead_letter_validation(
+        &self,
+        id: &String,
+        address: Address,
+        workflow: ValidatingWorkflow,
+    ) -> Result<(), HolochainInstanceError> {
+        let context = self.dead_letter_context(id, &address, &workflow)?;
+        retry_dead_letter_validation(address, workflow, &context);
+        Ok(())
+    }
+
+    /// Drops a dead-lettered validation of the given instance for good.
+    pub fn drop_dead_letter_validation(
+        &self,
+        id: &String,
+        address: Address,
+        workflow: ValidatingWorkflow,
+    ) -> Result<(), HolochainInstanceError> {
+        let context = self.dead_letter_context(id, &address, &workflow)?;
+        remove_dead_letter_validation(address, workflow, &context);
+        Ok(())
+    }
+
+    /// Context of the given instance, if it has the given dead-lettered validation.
+    fn dead_letter_context(
+        &self,
+        id: &String,
+        address: &Address,
+        workflow: &ValidatingWorkflow,
+    ) -> Result<Arc<Context>, HolochainInstanceError> {
+        let context = self.instances.get(id)?.read().unwrap().context()?;
+        let key = PendingValidationKey::new(address.clone(), workflow.clone());
+        let is_dead_letter = context
+            .state()
+            .ok_or(HolochainInstanceError::InstanceNotInitialized)?
+            .nucleus()
+            .dead_letter_validations
+            .contains_key(&key);
+        if !is_dead_letter {
+            return Err(HolochainInstanceError::InternalFailure(
+                HolochainError::ErrorGeneric(format!(
+                    "No dead-lettered {} validation of {} in instance \"{}\"",
+                    workflow, address, id
+                )),
+            ));
+        }
+        Ok(context)
+    }
+
+    /// Imports a chain archive into the given instance, which has to be stopped.
+    pub fn import_chain(
+        &mut self,
+        id: &String,
+        archive: &ChainArchive,
+    ) -> Result<(), HolochainInstanceError> {
+        let instance = self.instances.get(id)?;
+        notify(format!("Importing source chain into instance \"{}\"...", id));
+        instance.write().unwrap().import_chain(archive)
+    }
+
     /// Starts all instances
     pub fn start_all_instances(&mut self) -> Result<(), HolochainInstanceError> {
         self.config
@@ -733,16 +863,43 @@ impl Conductor {
                 context_builder = context_builder.with_signals(sender);
 
                 // Storage:
+                let mut storage_cipher = None;
                 match instance_config.storage {
-                    StorageConfiguration::File { path } => {
+                    StorageConfiguration::File {
+                        path,
+                        encrypted: false,
+                    } => {
                         context_builder =
                             context_builder.with_file_storage(path).map_err(|hc_err| {
                                 format!("Error creating context: {}", hc_err.to_string())
                             })?
                     }
+                    StorageConfiguration::File {
+                        path,
+                        encrypted: true,
+                    } => {
+                        let keystore = self.get_keystore_for_agent(agent_id)?;
+                        let storage_key = keystore
+                            .lock()
+                            .unwrap()
+                            .get_storage_key()
+                            .map_err(|hc_err| {
+                                format!("Could not derive storage key: {}", hc_err.to_string())
+                            })?;
+                        let cipher = StorageCipher::new(storage_key);
+                        context_builder = context_builder
+                            .with_encrypted_file_storage(path, cipher.clone())
+                            .map_err(|hc_err| {
+                                format!("Error creating context: {}", hc_err.to_string())
+                            })?;
+                        storage_cipher = Some(cipher);
+                    }
                     StorageConfiguration::Memory => {
                         context_builder = context_builder.with_memory_storage()
                     }
+                    StorageConfiguration::IndexedMemory => {
+                        context_builder = context_builder.with_indexed_memory_storage()
+                    }
                     StorageConfiguration::Pickle { path } => {
                         context_builder =
                             context_builder
@@ -751,6 +908,82 @@ impl Conductor {
                                     format!("Error creating context: {}", hc_err.to_string())
                                 })?
                     }
+                    StorageConfiguration::Lmdb {
+                        path,
+                        initial_mmap_bytes,
+                    } => {
+                        context_builder = context_builder
+                            .with_lmdb_storage(path, initial_mmap_bytes)
+                            .map_err(|hc_err| {
+                                format!("Error creating context: {}", hc_err.to_string())
+                            })?
+                    }
+                }
+
+                // Persister:
+                if let PersisterConfiguration::Journal {
+                    path,
+                    checkpoint_interval,
+                } = instance_config.persister
+                {
+                    // The journal of an instance with encrypted storage gets encrypted as well
+                    context_builder = match storage_cipher {
+                        Some(cipher) => context_builder.with_encrypted_journal_persister(
+                            path,
+                            checkpoint_interval,
+                            cipher,
+                        ),
+                        None => context_builder.with_journal_persister(path, checkpoint_interval),
+                    }
+                    .map_err(|hc_err| format!("Error creating context: {}", hc_err.to_string()))?
+                }
+
+                // Retention:
+                context_builder =
+                    context_builder.with_retention_policy(instance_config.retention.into());
+
+                // Quota:
+                context_builder = context_builder.with_storage_quota(instance_config.quota.into());
+
+                // Fuel:
+                context_builder = context_builder.with_fuel_limits(instance_config.fuel.into());
+
+                // Memory:
+                if let Some(max_pages) = instance_config.memory.max_pages {
+                    context_builder = context_builder.with_wasm_memory_limit(max_pages);
+                }
+
+                // Profiling:
+                if instance_config.profiling.enabled {
+                    context_builder = context_builder.with_profiling();
+                }
+
+                // Backtraces:
+                if instance_config.backtraces.enabled {
+                    context_builder = context_builder.with_wasm_backtraces();
+                }
+
+                // Validation retries:
+                context_builder =
+                    context_builder.with_validation_retry_policy(instance_config.validation.into());
+
+                // Recording:
+                if let Some(path) = instance_config.recording.path {
+                    fs::create_dir_all(&path).map_err(|err| {
+                        format!("Could not create recording directory {}: {}", path, err)
+                    })?;
+                    context_builder = context_builder.with_zome_call_recording(path);
+                }
+
+                // Instance pools:
+                if let Some(pool_size) = config.wasm_instance_pool_size {
+                    context_builder = context_builder.with_wasm_instance_pool_size(pool_size);
+                }
+
+                // Timeout:
+                if let Some(timeout_ms) = config.zome_call_timeout_ms {
+                    context_builder =
+                        context_builder.with_zome_call_timeout(Duration::from_millis(timeout_ms));
                 }
 
                 let instance_name = instance_config.id.clone();
@@ -763,7 +996,9 @@ impl Conductor {
                 }
 
                 // Spawn context
-                let context = context_builder.with_instance_name(&instance_name).spawn();
+                let context = context_builder
+                    .with_instance_name(&instance_name)
+                    .try_spawn()?;
 
                 // Get DNA
                 let dna_config = config.dna_by_id(&instance_config.dna).unwrap();
@@ -820,6 +1055,17 @@ impl Conductor {
                 }
 
                 let context = Arc::new(context);
+
+                // Storage in a format we can't read must not get a new chain on top of it,
+                // outdated storage gets migrated before loading the state
+                let migration = check_storage_version(&MigrationStorage::from(&*context))
+                    .map_err(|hc_err| {
+                        format!("Could not load instance {}: {}", id, hc_err.to_string())
+                    })?;
+                if let Some(report) = migration {
+                    notify(format!("Migrated storage of instance {}:\n{}", id, report));
+                }
+
                 Holochain::load(context.clone())
                     .and_then(|hc| {
                         notify(format!(
@@ -882,20 +1128,34 @@ impl Conductor {
                     .expect("holo_remote_key needs decryption_service_uri set"),
             );
         } else {
-            api_builder = api_builder.with_agent_signature_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
+            // All callbacks share one keybundle so that they all switch to the new key
+            // when the agent rotates its key.
+            let keybundle = self.get_keybundle_for_agent(&instance_config.agent)?;
+            api_builder = api_builder.with_agent_signature_callback(keybundle.clone());
 
-            api_builder = api_builder.with_agent_encryption_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
-            api_builder = api_builder.with_agent_decryption_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
+            api_builder = api_builder.with_agent_encryption_callback(keybundle.clone());
+            api_builder = api_builder.with_agent_decryption_callback(keybundle.clone());
             let keystore = self
                 .get_keystore_for_agent(&instance_config.agent)
                 .map_err(|err| format!("{}", err))?;
-            api_builder = api_builder.with_agent_keystore_functions(keystore);
+            api_builder = api_builder.with_agent_keystore_functions(keystore.clone());
+            let keystore_file = match agent_config.test_agent {
+                Some(true) => None,
+                _ => Some(PathBuf::from(agent_config.keystore_file.clone())),
+            };
+            let agent_instances = self
+                .agent_instances
+                .entry(agent_id.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(HashSet::new())))
+                .clone();
+            agent_instances.lock().unwrap().insert(instance_id.clone());
+            api_builder = api_builder.with_agent_key_rotation_functions(
+                agent_id.clone(),
+                agent_instances,
+                keystore,
+                keybundle,
+                keystore_file,
+            );
         }
 
         // Bridges:
@@ -1096,6 +1356,21 @@ impl Conductor {
         Ok(keystore_ref.clone())
     }
 
+    /// Sets the public address of the given agent after it rotated its key
+    /// and saves the config.
+    pub fn update_agent_public_address(
+        &mut self,
+        agent_id: &String,
+        public_address: Base32,
+    ) -> Result<(), HolochainError> {
+        self.config
+            .agents
+            .iter_mut()
+            .filter(|agent_config| &agent_config.id == agent_id)
+            .for_each(|agent_config| agent_config.public_address = public_address.clone());
+        self.save_config()
+    }
+
     /// Get reference to the keybundle stored in the keystore for given agent ID.
     /// If the key was not loaded (into secure memory) yet, this will use the KeyLoader
     /// to do so.
@@ -2071,7 +2346,7 @@ pub mod tests {
             let context = instance.context().unwrap();
             make_cap_request_for_call(
                 context.clone(),
-                Address::from(context.clone().agent_id.address()),
+                Address::from(context.clone().agent_id().address()),
                 "call_bridge",
                 JsonString::empty_object(),
             )
@@ -2103,7 +2378,7 @@ pub mod tests {
             let context = instance.context().unwrap();
             make_cap_request_for_call(
                 context.clone(),
-                Address::from(context.clone().agent_id.address()),
+                Address::from(context.clone().agent_id().address()),
                 "call_bridge_error",
                 JsonString::empty_object(),
             )
@@ -2347,7 +2622,7 @@ pub mod tests {
                 let context = bridge_caller.context();
                 make_cap_request_for_call(
                     context.clone(),
-                    Address::from(context.clone().agent_id.address()),
+                    Address::from(context.clone().agent_id().address()),
                     "call_bridge",
                     JsonString::empty_object(),
                 )
@@ -2402,4 +2677,26 @@ pub mod tests {
             Err(HolochainInstanceError::InstanceNotActiveYet),
         );
     }
+
+    #[test]
+    fn test_agents_with_several_instances_cant_rotate_their_key() {
+        let mut conductor = test_conductor(10101, 10102);
+        let mut config = conductor.config.clone();
+        let prepare =
+            r#"{"jsonrpc": "2.0", "method": "agent/key_rotation/prepare", "params": {}, "id": "0"}"#;
+        let api = conductor
+            .build_conductor_api(String::from("test-instance-1"), &config)
+            .unwrap();
+        let response = api.handle_request_sync(prepare).unwrap();
+        assert!(response.contains("pub_key"), "{}", response);
+
+        let mut second_instance = config.instance_by_id("test-instance-1").unwrap();
+        second_instance.id = String::from("second-instance-of-agent-1");
+        config.instances.push(second_instance);
+        conductor
+            .build_conductor_api(String::from("second-instance-of-agent-1"), &config)
+            .unwrap();
+        let response = api.handle_request_sync(prepare).unwrap();
+        assert!(response.contains("runs 2 instances"), "{}", response);
+    }
 }
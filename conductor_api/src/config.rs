@@ -12,6 +12,10 @@ use crate::logger::LogRules;
 /// * bridges, which are
 use boolinator::*;
 use conductor::base::DnaLoader;
+use holochain_core::{
+    dht::retention::RetentionPolicy, nucleus::ribosome::fuel::FuelLimits, quota::StorageQuota,
+    scheduled_jobs::pending_validations::ValidationRetryPolicy,
+};
 use holochain_core_types::{
     agent::{AgentId, Base32},
     dna::{
@@ -36,6 +40,7 @@ use std::{
     net::Ipv4Addr,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 use toml;
 
@@ -114,6 +119,18 @@ pub struct Configuration {
     /// This config setting selects one of the available services (i.e. CLI prompt, IPC, mock)
     #[serde(default)]
     pub passphrase_service: PassphraseServiceConfig,
+
+    /// Time in milliseconds a zome function call may run if the caller does not set a
+    /// timeout of its own. Calls that take longer get aborted with a timeout error.
+    /// Optional, calls don't time out if not set.
+    #[serde(default)]
+    pub zome_call_timeout_ms: Option<u64>,
+
+    /// Number of idle WASM instances every instance keeps per zome to run zome
+    /// calls and callbacks in, instead of instantiating the zome's WASM module for every call.
+    /// Optional, defaults to 4. With 0, every call gets a fresh WASM instance.
+    #[serde(default)]
+    pub wasm_instance_pool_size: Option<usize>,
 }
 
 /// The default passphrase service is `Cmd` which will ask for a passphrase via stdout stdin.
@@ -217,6 +234,9 @@ impl Configuration {
 
         detect_dupes("instance", self.instances.iter().map(|c| &c.id))?;
         self.check_instances_storage()?;
+        self.check_instances_retention()?;
+        self.check_instances_quota()?;
+        self.check_instances_encryption()?;
 
         detect_dupes("interface", self.interfaces.iter().map(|c| &c.id))?;
 
@@ -598,8 +618,9 @@ impl Configuration {
             .instances
             .iter()
             .filter_map(|stg_config| match stg_config.storage {
-                StorageConfiguration::File { ref path }
-                | StorageConfiguration::Pickle { ref path } => Some(path.as_str()),
+                StorageConfiguration::File { ref path, .. }
+                | StorageConfiguration::Pickle { ref path }
+                | StorageConfiguration::Lmdb { ref path, .. } => Some(path.as_str()),
                 _ => None,
             })
             .collect();
@@ -620,6 +641,73 @@ impl Configuration {
             Ok(())
         }
     }
+
+    fn check_instances_retention(&self) -> Result<(), String> {
+        for instance in self.instances.iter() {
+            let policy = RetentionPolicy::from(instance.retention.clone());
+            match instance.storage {
+                StorageConfiguration::Lmdb { .. } => (),
+                _ if policy.is_enabled() => {
+                    return Err(format!(
+                        "Instance \"{}\" configures retention, which requires lmdb storage",
+                        instance.id
+                    ));
+                }
+                _ => (),
+            }
+            if let Some(arc_fraction) = instance.retention.arc_fraction {
+                if arc_fraction < 0.0 || arc_fraction > 1.0 {
+                    return Err(format!(
+                        "Retention arc_fraction of instance \"{}\" must be between 0 and 1",
+                        instance.id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_instances_quota(&self) -> Result<(), String> {
+        for instance in self.instances.iter() {
+            let quota = &instance.quota;
+            let limits = [
+                ("bytes", quota.soft_limit_bytes, quota.hard_limit_bytes),
+                ("entries", quota.soft_limit_entries, quota.hard_limit_entries),
+            ];
+            for (unit, soft, hard) in limits.iter() {
+                if let (Some(soft), Some(hard)) = (soft, hard) {
+                    if soft > hard {
+                        return Err(format!(
+                            "Soft limit on {} of instance \"{}\" must not be above its hard limit",
+                            unit, instance.id
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_instances_encryption(&self) -> Result<(), String> {
+        for instance in self.instances.iter() {
+            if let StorageConfiguration::File {
+                encrypted: true, ..
+            } = instance.storage
+            {
+                let holo_remote_key = self
+                    .agent_by_id(&instance.agent)
+                    .and_then(|agent| agent.holo_remote_key)
+                    .unwrap_or_default();
+                if holo_remote_key {
+                    return Err(format!(
+                        "Instance \"{}\" has encrypted storage, which needs an agent with a local keystore",
+                        instance.id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// An agent has a name/ID and is optionally defined by a private key that resides in a file
@@ -670,21 +758,234 @@ pub struct InstanceConfiguration {
     pub dna: String,
     pub agent: String,
     pub storage: StorageConfiguration,
+    #[serde(default)]
+    pub persister: PersisterConfiguration,
+    #[serde(default)]
+    pub retention: RetentionConfiguration,
+    #[serde(default)]
+    pub quota: QuotaConfiguration,
+    #[serde(default)]
+    pub fuel: FuelConfiguration,
+    #[serde(default)]
+    pub memory: MemoryConfiguration,
+    #[serde(default)]
+    pub profiling: ProfilingConfiguration,
+    #[serde(default)]
+    pub backtraces: BacktraceConfiguration,
+    #[serde(default)]
+    pub recording: RecordingConfiguration,
+    #[serde(default)]
+    pub validation: ValidationConfiguration,
 }
 
 /// This configures the Content Addressable Storage (CAS) that
 /// the instance uses to store source chain and DHT shard in.
-/// There are two storage implementations in cas_implementations so far:
+/// There are five storage implementations so far:
 /// * memory
+/// * indexed_memory (memory CAS with an EAV store indexed by entity, attribute and value)
 /// * file
+/// * pickle
+/// * lmdb (embedded transactional key-value database)
+///
+/// `initial_mmap_bytes` sets the initial size of LMDB's memory map, which gets doubled
+/// whenever a write does not fit into it. It defaults to 100MB.
 ///
-/// Projected are various DB adapters.
+/// Setting `encrypted` on a file storage encrypts all data it writes to disk with a key
+/// that gets derived from the agent's keystore. An existing unencrypted file storage gets
+/// encrypted when the instance starts with `encrypted` set for the first time.
+/// A `journal` persister of the instance encrypts its files with the same key.
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum StorageConfiguration {
     Memory,
-    File { path: String },
+    #[serde(rename = "indexed_memory")]
+    IndexedMemory,
+    File {
+        path: String,
+        #[serde(default)]
+        encrypted: bool,
+    },
     Pickle { path: String },
+    Lmdb {
+        path: String,
+        initial_mmap_bytes: Option<usize>,
+    },
+}
+
+/// This configures how the instance's state (top chain header, pending validations
+/// and holding list) gets persisted:
+/// * snapshot (default): full state snapshots are written to the chain CAS after every action
+/// * journal: state deltas are appended to a write-ahead journal in `path` and a full
+///   checkpoint gets written every `checkpoint_interval` records (defaults to 1000)
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PersisterConfiguration {
+    Snapshot,
+    Journal {
+        path: String,
+        checkpoint_interval: Option<usize>,
+    },
+}
+
+impl Default for PersisterConfiguration {
+    fn default() -> Self {
+        PersisterConfiguration::Snapshot
+    }
+}
+
+/// This configures which held DHT entries an instance evicts from its local shard.
+/// By default nothing gets evicted. Entries of the instance's own source chain are always kept.
+/// * max_age_secs: evict entries whose newest header is older than this
+/// * max_held_bytes: evict the oldest entries until the held content fits into this budget
+/// * arc_fraction: evict entries outside of the fraction (between 0 and 1) of the address
+///   space around the agent's own address that the instance is responsible for
+///
+/// Evicted entries are no longer gossiped and their content and metadata get removed from
+/// disk. Only the lmdb storage supports this, so retention can't be configured for instances
+/// with any other storage type.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct RetentionConfiguration {
+    pub max_age_secs: Option<u64>,
+    pub max_held_bytes: Option<u64>,
+    pub arc_fraction: Option<f64>,
+}
+
+impl From<RetentionConfiguration> for RetentionPolicy {
+    fn from(config: RetentionConfiguration) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age_secs: config.max_age_secs,
+            max_held_bytes: config.max_held_bytes,
+            arc_fraction: config.arc_fraction,
+        }
+    }
+}
+
+/// This configures limits on the storage an instance uses across its source chain,
+/// DHT shard and EAV metadata. By default nothing is limited.
+/// * soft_limit_bytes / soft_limit_entries: exceeding these only logs a warning
+/// * hard_limit_bytes / hard_limit_entries: once reached, the instance refuses to commit
+///   to its source chain or hold anything more for the DHT
+///
+/// Byte limits apply to the bytes written to all storages, entry limits to the number of
+/// contents in chain and DHT storage.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct QuotaConfiguration {
+    pub soft_limit_bytes: Option<u64>,
+    pub hard_limit_bytes: Option<u64>,
+    pub soft_limit_entries: Option<u64>,
+    pub hard_limit_entries: Option<u64>,
+}
+
+impl From<QuotaConfiguration> for StorageQuota {
+    fn from(config: QuotaConfiguration) -> StorageQuota {
+        StorageQuota {
+            soft_limit_bytes: config.soft_limit_bytes,
+            hard_limit_bytes: config.hard_limit_bytes,
+            soft_limit_entries: config.soft_limit_entries,
+            hard_limit_entries: config.hard_limit_entries,
+        }
+    }
+}
+
+/// This configures how much fuel, counted in executed WASM instructions, a single call
+/// into the instance's DNA may use before it gets aborted. By default calls are unlimited.
+/// * zome_call_limit: applies to zome function calls
+/// * validation_limit: applies to validation callbacks and the other callbacks
+///   (validation packages, init, receive)
+///
+/// Calls that run out of fuel fail with an "out of fuel" error that states the fuel used.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct FuelConfiguration {
+    pub zome_call_limit: Option<u64>,
+    pub validation_limit: Option<u64>,
+}
+
+impl From<FuelConfiguration> for FuelLimits {
+    fn from(config: FuelConfiguration) -> FuelLimits {
+        FuelLimits {
+            zome_call: config.zome_call_limit,
+            validation: config.validation_limit,
+        }
+    }
+}
+
+/// This configures how much linear memory, counted in WASM pages of 64KiB, a single call
+/// into the instance's DNA may use. By default memory is only limited by the maximum
+/// the zome declares.
+/// * max_pages: applies to zome function calls and callbacks
+///
+/// Calls that try to grow their memory beyond the limit get aborted with a
+/// "memory limit exceeded" error.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct MemoryConfiguration {
+    pub max_pages: Option<u32>,
+}
+
+/// This configures whether the zome calls of the instance get profiled.
+/// * enabled: records every Zome API function call of every zome call with the size of its
+///   arguments, its result and its duration, together with the time spent running WASM code
+///
+/// Profiles of single calls show up in their `ReturnZomeFunctionResult` trace signals.
+/// Histograms per zome function can be queried with `debug/profile`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ProfilingConfiguration {
+    pub enabled: bool,
+}
+
+/// This configures whether the zome calls of the instance report backtraces.
+/// * enabled: instruments the WASM of the instance so that calls that trap, run out of fuel,
+///   exceed their memory limit or time out report the WASM functions they were in
+///
+/// The last Zome API functions a failed call made get reported either way.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct BacktraceConfiguration {
+    pub enabled: bool,
+}
+
+/// This configures whether the zome calls of the instance get recorded.
+/// * path: directory to write a recording of every zome call to, with the arguments and
+///   results of all Zome API function calls the call made
+///
+/// Recordings can be replayed offline against the instance's DNA with `hc replay`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct RecordingConfiguration {
+    pub path: Option<String>,
+}
+
+/// This configures how the instance retries validations that are pending because
+/// their dependencies or validation packages could not be fetched yet.
+/// * workers: number of threads that retry pending validations, 4 by default
+/// * max_attempts: failed attempts after which a pending validation gets moved to the
+///   dead letters, 10 by default
+/// * initial_backoff_ms: time to wait after the first failed attempt, doubled after every
+///   further one, 1 second by default
+/// * max_backoff_ms: longest time to wait between two attempts, 5 minutes by default
+///
+/// Dead letters can be listed, retried and dropped through the admin interface.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ValidationConfiguration {
+    pub workers: Option<usize>,
+    pub max_attempts: Option<u32>,
+    pub initial_backoff_ms: Option<u64>,
+    pub max_backoff_ms: Option<u64>,
+}
+
+impl From<ValidationConfiguration> for ValidationRetryPolicy {
+    fn from(config: ValidationConfiguration) -> ValidationRetryPolicy {
+        let default = ValidationRetryPolicy::default();
+        ValidationRetryPolicy {
+            workers: config.workers.unwrap_or(default.workers),
+            max_attempts: config.max_attempts.unwrap_or(default.max_attempts),
+            initial_backoff: config
+                .initial_backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_backoff),
+            max_backoff: config
+                .max_backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_backoff),
+        }
+    }
 }
 
 /// Here, interfaces are user facing and make available zome functions to
@@ -1658,4 +1959,355 @@ pub mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_load_indexed_memory_storage() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "indexed_memory"
+        "#;
+
+        let config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.storage.clone()),
+            Some(StorageConfiguration::IndexedMemory)
+        );
+    }
+
+    #[test]
+    fn test_load_lmdb_storage() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "lmdb"
+            path = "example-config/tmp-storage"
+            initial_mmap_bytes = 1048576
+        "#;
+
+        let config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.storage.clone()),
+            Some(StorageConfiguration::Lmdb {
+                path: String::from("example-config/tmp-storage"),
+                initial_mmap_bytes: Some(1048576),
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_journal_persister() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "memory"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 2"
+
+            [instances.storage]
+            type = "file"
+            path = "example-config/tmp-storage"
+
+            [instances.persister]
+            type = "journal"
+            path = "example-config/tmp-journal"
+        "#;
+
+        let config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.persister.clone()),
+            Some(PersisterConfiguration::Snapshot)
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.persister.clone()),
+            Some(PersisterConfiguration::Journal {
+                path: String::from("example-config/tmp-journal"),
+                checkpoint_interval: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_retention() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "memory"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 2"
+
+            [instances.storage]
+            type = "lmdb"
+            path = "example-config/tmp-lmdb"
+
+            [instances.retention]
+            max_age_secs = 86400
+            arc_fraction = 1.5
+        "#;
+
+        let mut config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.retention.clone()),
+            Some(RetentionConfiguration::default())
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.retention.clone()),
+            Some(RetentionConfiguration {
+                max_age_secs: Some(86400),
+                max_held_bytes: None,
+                arc_fraction: Some(1.5),
+            })
+        );
+        assert_eq!(
+            config.check_instances_retention(),
+            Err(String::from(
+                "Retention arc_fraction of instance \"app spec instance 2\" must be between 0 and 1"
+            ))
+        );
+
+        config.instances[1].storage = StorageConfiguration::Memory;
+        assert_eq!(
+            config.check_instances_retention(),
+            Err(String::from(
+                "Instance \"app spec instance 2\" configures retention, which requires lmdb storage"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_quota() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "memory"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 2"
+
+            [instances.storage]
+            type = "memory"
+
+            [instances.fuel]
+            zome_call_limit = 5000000
+            validation_limit = 1000000
+
+            [instances.memory]
+            max_pages = 64
+
+            [instances.profiling]
+            enabled = true
+
+            [instances.backtraces]
+            enabled = true
+
+            [instances.recording]
+            path = "/tmp/recordings"
+
+            [instances.validation]
+            workers = 2
+            max_attempts = 5
+
+            [instances.quota]
+            soft_limit_bytes = 2000000
+            hard_limit_bytes = 1000000
+            hard_limit_entries = 10000
+        "#;
+
+        let config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.quota.clone()),
+            Some(QuotaConfiguration::default())
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.quota.clone()),
+            Some(QuotaConfiguration {
+                soft_limit_bytes: Some(2000000),
+                hard_limit_bytes: Some(1000000),
+                soft_limit_entries: None,
+                hard_limit_entries: Some(10000),
+            })
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.fuel.clone()),
+            Some(FuelConfiguration {
+                zome_call_limit: Some(5000000),
+                validation_limit: Some(1000000),
+            })
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.memory.clone()),
+            Some(MemoryConfiguration {
+                max_pages: Some(64)
+            })
+        );
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.profiling.enabled),
+            Some(false)
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.profiling.enabled),
+            Some(true)
+        );
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.backtraces.enabled),
+            Some(false)
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.backtraces.enabled),
+            Some(true)
+        );
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.recording.clone()),
+            Some(RecordingConfiguration::default())
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.recording.path.clone()),
+            Some(Some("/tmp/recordings".to_string()))
+        );
+        assert_eq!(
+            config
+                .instances
+                .get(1)
+                .map(|instance| ValidationRetryPolicy::from(instance.validation.clone())),
+            Some(ValidationRetryPolicy {
+                workers: 2,
+                max_attempts: 5,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            config.check_instances_quota(),
+            Err(String::from(
+                "Soft limit on bytes of instance \"app spec instance 2\" must not be above its hard limit"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_encrypted_file_storage() {
+        let toml = r#"
+        [[agents]]
+        id = "test agent 1"
+        keystore_file = "holo_tester.key"
+        name = "Holo Tester 1"
+        public_address = "HoloTester1-----------------------------------------------------------------------AAACZp4xHB"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 1"
+
+            [instances.storage]
+            type = "file"
+            path = "example-config/tmp-storage-1"
+
+        [[instances]]
+        agent = "test agent 1"
+        dna = "app spec rust"
+        id = "app spec instance 2"
+
+            [instances.storage]
+            type = "file"
+            path = "example-config/tmp-storage-2"
+            encrypted = true
+
+            [instances.persister]
+            type = "journal"
+            path = "example-config/tmp-journal"
+        "#;
+
+        let config = load_configuration::<Configuration>(&toml)
+            .expect("Config should be syntactically correct");
+
+        assert_eq!(
+            config.instances.get(0).map(|instance| instance.storage.clone()),
+            Some(StorageConfiguration::File {
+                path: String::from("example-config/tmp-storage-1"),
+                encrypted: false,
+            })
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.storage.clone()),
+            Some(StorageConfiguration::File {
+                path: String::from("example-config/tmp-storage-2"),
+                encrypted: true,
+            })
+        );
+        assert_eq!(
+            config.instances.get(1).map(|instance| instance.persister.clone()),
+            Some(PersisterConfiguration::Journal {
+                path: String::from("example-config/tmp-journal"),
+                checkpoint_interval: None,
+            })
+        );
+        assert_eq!(config.check_instances_encryption(), Ok(()));
+    }
 }
@@ -8,14 +8,35 @@ use holochain_persistence_api::{
     cas::storage::ContentAddressableStorage, eav::EntityAttributeValueStorage,
 };
 
-use holochain_core::{context::Context, persister::SimplePersister, signal::SignalSender};
-use holochain_core_types::{agent::AgentId, eav::Attribute, error::HolochainError};
+use crate::storage::{
+    encrypted::{open_encrypted_file_storage, StorageCipher, KEY_CHECK_FILE},
+    lmdb::{EavLmdbStorage, LmdbEnvironment, LmdbStorage},
+    metered::{
+        file_cas_addresses, scan_usage, AddressLister, EavMeteredStorage, MeteredEviction,
+        MeteredStorage,
+    },
+};
+use holochain_core::{
+    context::Context,
+    dht::retention::{EvictableStorage, RetentionPolicy},
+    nucleus::ribosome::{fuel::FuelLimits, pool::InstancePools, profile::Profiler},
+    persister::{JournalPersister, Persister, SimplePersister},
+    quota::{InstanceUsage, StorageKind, StorageQuota, StorageUsage},
+    scheduled_jobs::pending_validations::{ValidationRetryPolicy, ValidationWorkers},
+    signal::SignalSender,
+};
+use holochain_core_types::{
+    agent::AgentId,
+    eav::{Attribute, EavIndexedStorage},
+    error::{HcResult, HolochainError},
+};
 use holochain_net::p2p_config::P2pConfig;
 use jsonrpc_core::IoHandler;
 use std::{
     fs,
     path::{Path, PathBuf},
     sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
 /// This type helps building [context objects](struct.Context.html) that need to be
@@ -29,12 +50,23 @@ use std::{
 pub struct ContextBuilder {
     instance_name: Option<String>,
     agent_id: Option<AgentId>,
-    // Persister is currently set to a reasonable default in spawn().
-    // TODO: add with_persister() function to ContextBuilder.
-    //persister: Option<Arc<Mutex<Persister>>>,
+    persister: Option<Arc<Mutex<dyn Persister>>>,
     chain_storage: Option<Arc<RwLock<dyn ContentAddressableStorage>>>,
     dht_storage: Option<Arc<RwLock<dyn ContentAddressableStorage>>>,
     eav_storage: Option<Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+    chain_index_storage: Option<Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>>,
+    stored_addresses: Option<AddressLister>,
+    eviction_storage: Option<Arc<dyn EvictableStorage>>,
+    retention_policy: RetentionPolicy,
+    storage_quota: StorageQuota,
+    fuel_limits: FuelLimits,
+    wasm_memory_limit: Option<u32>,
+    wasm_instance_pools: InstancePools,
+    zome_call_timeout: Option<Duration>,
+    profiling: bool,
+    wasm_backtraces: bool,
+    zome_call_recordings: Option<PathBuf>,
+    validation_retry_policy: ValidationRetryPolicy,
     p2p_config: Option<P2pConfig>,
     conductor_api: Option<Arc<RwLock<IoHandler>>>,
     signal_tx: Option<SignalSender>,
@@ -46,9 +78,23 @@ impl ContextBuilder {
         ContextBuilder {
             instance_name: None,
             agent_id: None,
+            persister: None,
             chain_storage: None,
             dht_storage: None,
             eav_storage: None,
+            chain_index_storage: None,
+            stored_addresses: None,
+            eviction_storage: None,
+            retention_policy: RetentionPolicy::default(),
+            storage_quota: StorageQuota::default(),
+            fuel_limits: FuelLimits::default(),
+            wasm_memory_limit: None,
+            wasm_instance_pools: InstancePools::default(),
+            zome_call_timeout: None,
+            profiling: false,
+            wasm_backtraces: false,
+            zome_call_recordings: None,
+            validation_retry_policy: ValidationRetryPolicy::default(),
             p2p_config: None,
             conductor_api: None,
             signal_tx: None,
@@ -74,22 +120,66 @@ impl ContextBuilder {
         self
     }
 
+    /// Same as `with_memory_storage()` but uses an EAV store that keeps secondary indexes
+    /// on entity, attribute and value so that queries do not have to scan all rows.
+    pub fn with_indexed_memory_storage(mut self) -> Self {
+        let cas = Arc::new(RwLock::new(MemoryStorage::new()));
+        let eav = Arc::new(RwLock::new(EavIndexedStorage::new()));
+        self.chain_storage = Some(cas.clone());
+        self.dht_storage = Some(cas);
+        self.eav_storage = Some(eav);
+        self
+    }
+
     /// Sets all three storages, chain, DHT and EAV storage, to persistent file based implementations.
     /// Chain and DHT storages get set to the same file CAS.
-    /// Returns an error if no file storage could be spawned on the given path.
+    /// Returns an error if no file storage could be spawned on the given path
+    /// or if the storage on the given path is encrypted.
     pub fn with_file_storage<P: AsRef<Path>>(mut self, path: P) -> Result<Self, HolochainError> {
         let base_path: PathBuf = path.as_ref().into();
+        if base_path.join(KEY_CHECK_FILE).exists() {
+            return Err(HolochainError::ConfigError(format!(
+                "File storage {} is encrypted and can only be opened with encryption enabled",
+                base_path.to_string_lossy()
+            )));
+        }
         let cas_path = base_path.join("cas");
         let eav_path = base_path.join("eav");
+        let chain_index_path = base_path.join("chain_index");
         fs::create_dir_all(&cas_path)?;
         fs::create_dir_all(&eav_path)?;
+        fs::create_dir_all(&chain_index_path)?;
 
         let file_storage = Arc::new(RwLock::new(FilesystemStorage::new(&cas_path)?));
         let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
             Arc::new(RwLock::new(EavFileStorage::new(eav_path)?));
+        let chain_index_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavFileStorage::new(chain_index_path)?));
         self.chain_storage = Some(file_storage.clone());
         self.dht_storage = Some(file_storage);
         self.eav_storage = Some(eav_storage);
+        self.chain_index_storage = Some(chain_index_storage);
+        self.stored_addresses = Some(Arc::new(move || file_cas_addresses(&cas_path)));
+        Ok(self)
+    }
+
+    /// Same as `with_file_storage()` but encrypts everything that gets written to disk
+    /// with the given cipher. An existing unencrypted file storage on the given path
+    /// gets encrypted when it is opened this way for the first time.
+    /// The chain index is kept in memory so that entry types and timestamps of the chain
+    /// never get written to disk in the clear; it gets rebuilt by the first chain query.
+    /// Returns an error if the storage could not be opened or was encrypted with another key.
+    pub fn with_encrypted_file_storage<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        cipher: StorageCipher,
+    ) -> Result<Self, HolochainError> {
+        let (cas_storage, eav_storage) = open_encrypted_file_storage(path.as_ref(), &cipher)?;
+        let cas_path = path.as_ref().join("cas");
+        self.chain_storage = Some(cas_storage.clone());
+        self.dht_storage = Some(cas_storage);
+        self.eav_storage = Some(eav_storage);
+        self.stored_addresses = Some(Arc::new(move || file_cas_addresses(&cas_path)));
         Ok(self)
     }
 
@@ -100,17 +190,144 @@ impl ContextBuilder {
         let base_path: PathBuf = path.as_ref().into();
         let cas_path = base_path.join("cas");
         let eav_path = base_path.join("eav");
+        let chain_index_path = base_path.join("chain_index");
         fs::create_dir_all(&cas_path)?;
         fs::create_dir_all(&eav_path)?;
+        fs::create_dir_all(&chain_index_path)?;
 
         let file_storage = Arc::new(RwLock::new(PickleStorage::new(&cas_path)));
         let eav_storage = Arc::new(RwLock::new(EavPickleStorage::new(eav_path)));
+        let chain_index_storage = Arc::new(RwLock::new(EavPickleStorage::new(chain_index_path)));
         self.chain_storage = Some(file_storage.clone());
         self.dht_storage = Some(file_storage);
         self.eav_storage = Some(eav_storage);
+        self.chain_index_storage = Some(chain_index_storage);
         Ok(self)
     }
 
+    /// Sets all three storages, chain, DHT and EAV storage, to persistent LMDB based implementations.
+    /// All of them share the same LMDB environment in the given directory, chain and DHT
+    /// storages get set to the same CAS. The chain index gets an environment of its own
+    /// so that DHT eviction never touches it.
+    /// Returns an error if the LMDB environment could not be opened on the given path.
+    pub fn with_lmdb_storage<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        initial_mmap_bytes: Option<usize>,
+    ) -> Result<Self, HolochainError> {
+        let base_path: PathBuf = path.as_ref().into();
+        let chain_index_path = base_path.join("chain_index");
+        fs::create_dir_all(&base_path)?;
+        fs::create_dir_all(&chain_index_path)?;
+
+        let env = LmdbEnvironment::new(&base_path, initial_mmap_bytes)?;
+        let cas_storage = Arc::new(RwLock::new(LmdbStorage::new(env.clone())));
+        let eav_storage = Arc::new(RwLock::new(EavLmdbStorage::new(env.clone())));
+        let chain_index_env = LmdbEnvironment::new(&chain_index_path, initial_mmap_bytes)?;
+        let chain_index_storage = Arc::new(RwLock::new(EavLmdbStorage::new(chain_index_env)));
+        self.chain_storage = Some(cas_storage.clone());
+        self.dht_storage = Some(cas_storage);
+        self.eav_storage = Some(eav_storage);
+        self.chain_index_storage = Some(chain_index_storage);
+        let cas_env = env.clone();
+        self.stored_addresses = Some(Arc::new(move || Ok(cas_env.cas_addresses()?)));
+        self.eviction_storage = Some(Arc::new(env));
+        Ok(self)
+    }
+
+    /// Sets the persister to a `JournalPersister` that writes its journal and
+    /// checkpoints into the given directory.
+    /// Returns an error if the directory could not be created.
+    pub fn with_journal_persister<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        checkpoint_interval: Option<usize>,
+    ) -> Result<Self, HolochainError> {
+        let persister = JournalPersister::new(path, checkpoint_interval)?;
+        self.persister = Some(Arc::new(Mutex::new(persister)));
+        Ok(self)
+    }
+
+    /// Same as `with_journal_persister()` but encrypts the checkpoints and journal records
+    /// with the given cipher, which should be the one of the instance's encrypted storage.
+    pub fn with_encrypted_journal_persister<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        checkpoint_interval: Option<usize>,
+        cipher: StorageCipher,
+    ) -> Result<Self, HolochainError> {
+        let persister =
+            JournalPersister::new(path, checkpoint_interval)?.with_cipher(Arc::new(cipher));
+        self.persister = Some(Arc::new(Mutex::new(persister)));
+        Ok(self)
+    }
+
+    /// Sets the policy that decides which held DHT entries the instance evicts.
+    pub fn with_retention_policy(mut self, retention_policy: RetentionPolicy) -> Self {
+        self.retention_policy = retention_policy;
+        self
+    }
+
+    /// Sets the limits on the storage the instance may use.
+    pub fn with_storage_quota(mut self, storage_quota: StorageQuota) -> Self {
+        self.storage_quota = storage_quota;
+        self
+    }
+
+    /// Sets how much fuel the zome function and callback calls of the instance may use.
+    pub fn with_fuel_limits(mut self, fuel_limits: FuelLimits) -> Self {
+        self.fuel_limits = fuel_limits;
+        self
+    }
+
+    /// Sets the maximum number of pages of linear memory the WASM calls of the
+    /// instance may use.
+    pub fn with_wasm_memory_limit(mut self, max_pages: u32) -> Self {
+        self.wasm_memory_limit = Some(max_pages);
+        self
+    }
+
+    /// Sets how many idle WASM instances the instance keeps per zome for reuse.
+    /// With a size of 0, every call gets a fresh WASM instance.
+    pub fn with_wasm_instance_pool_size(mut self, size: usize) -> Self {
+        self.wasm_instance_pools = InstancePools::new(size);
+        self
+    }
+
+    /// Sets how long zome calls of the instance may run if the caller does not
+    /// give them a deadline.
+    pub fn with_zome_call_timeout(mut self, timeout: Duration) -> Self {
+        self.zome_call_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables profiling of the zome calls of the instance.
+    pub fn with_profiling(mut self) -> Self {
+        self.profiling = true;
+        self
+    }
+
+    /// Makes failing zome calls and callbacks of the instance report the WASM functions
+    /// they were in, see `holochain_core::nucleus::ribosome::backtrace`.
+    pub fn with_wasm_backtraces(mut self) -> Self {
+        self.wasm_backtraces = true;
+        self
+    }
+
+    /// Enables recording of the zome calls of the instance into the given directory,
+    /// see `holochain_core::nucleus::ribosome::recording`.
+    pub fn with_zome_call_recording<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.zome_call_recordings = Some(path.as_ref().into());
+        self
+    }
+
+    /// Sets how many threads retry pending validations, how long they back off after
+    /// failed attempts and after how many attempts they give up.
+    pub fn with_validation_retry_policy(mut self, policy: ValidationRetryPolicy) -> Self {
+        self.validation_retry_policy = policy;
+        self
+    }
+
     /// Sets the network config.
     pub fn with_p2p_config(mut self, p2p_config: P2pConfig) -> Self {
         self.p2p_config = Some(p2p_config);
@@ -139,24 +356,121 @@ impl ContextBuilder {
 
     /// Actually creates the context.
     /// Defaults to memory storages, an in-memory network config and a fake agent called "alice".
-    /// The persister gets set to SimplePersister based on the chain storage.
-    pub fn spawn(self) -> Context {
-        let chain_storage = self
-            .chain_storage
-            .unwrap_or(Arc::new(RwLock::new(MemoryStorage::new())));
-        let dht_storage = self
-            .dht_storage
-            .unwrap_or(Arc::new(RwLock::new(MemoryStorage::new())));
-        let eav_storage = self
-            .eav_storage
-            .unwrap_or(Arc::new(RwLock::new(EavMemoryStorage::new())));
-
-        Context::new(
+    /// The persister defaults to SimplePersister based on the chain storage.
+    /// If the storage usage of an instance with a quota can't be restored or counted, it
+    /// starts from no usage, which gets counted again the next time the context gets spawned.
+    pub fn spawn(mut self) -> Context {
+        let storages = self.take_storages();
+        let storage_usage = self.storage_usage(&storages).unwrap_or_else(|err| {
+            warn!("Could not count the storage usage of the instance: {}", err);
+            StorageUsage::unsaved(InstanceUsage::default())
+        });
+        self.build(storages, storage_usage)
+    }
+
+    /// Same as `spawn()` but fails if the storage usage of an instance with a quota can't
+    /// be restored or counted.
+    pub fn try_spawn(mut self) -> HcResult<Context> {
+        let storages = self.take_storages();
+        let storage_usage = self.storage_usage(&storages)?;
+        Ok(self.build(storages, storage_usage))
+    }
+
+    /// The chain, DHT and EAV storage, defaulting to memory storages.
+    fn take_storages(&mut self) -> Storages {
+        Storages {
+            chain: self
+                .chain_storage
+                .take()
+                .unwrap_or(Arc::new(RwLock::new(MemoryStorage::new()))),
+            dht: self
+                .dht_storage
+                .take()
+                .unwrap_or(Arc::new(RwLock::new(MemoryStorage::new()))),
+            eav: self
+                .eav_storage
+                .take()
+                .unwrap_or(Arc::new(RwLock::new(EavMemoryStorage::new()))),
+        }
+    }
+
+    /// Usage only gets tracked if the instance has a storage quota. Its usage gets restored
+    /// from the chain storage, and storages without a record of tracked usage get scanned
+    /// for the data they already hold.
+    /// Without a quota, an untracked record replaces the saved one, so that usage gets
+    /// counted again once the instance gets a quota.
+    fn storage_usage(&self, storages: &Storages) -> HcResult<StorageUsage> {
+        if !self.storage_quota.is_enabled() {
+            return Ok(StorageUsage::unsaved(InstanceUsage::default()));
+        }
+        // A usage record that can't be read gets replaced by a fresh scan
+        let saved = StorageUsage::load(&storages.chain)
+            .unwrap_or(None)
+            .filter(|usage| usage.snapshot().tracked);
+        Ok(match saved {
+            Some(usage) => usage,
+            None => {
+                let addresses = match self.stored_addresses {
+                    Some(ref stored_addresses) => stored_addresses()?,
+                    None => Vec::new(),
+                };
+                StorageUsage::unsaved(scan_usage(&storages.chain, &storages.eav, &addresses)?)
+            }
+        })
+    }
+
+    fn build(self, storages: Storages, storage_usage: StorageUsage) -> Context {
+        let Storages {
+            chain: chain_storage,
+            dht: dht_storage,
+            eav: eav_storage,
+        } = storages;
+        let persister = self
+            .persister
+            .unwrap_or(Arc::new(Mutex::new(SimplePersister::new(
+                chain_storage.clone(),
+            ))));
+        let storage_usage = Arc::new(storage_usage);
+
+        let (chain_storage, dht_storage, eav_storage, eviction_storage) =
+            if self.storage_quota.is_enabled() {
+                let eviction_storage = self.eviction_storage.map(|eviction_storage| {
+                    let metered: Arc<dyn EvictableStorage> = Arc::new(MeteredEviction::new(
+                        eviction_storage,
+                        dht_storage.clone(),
+                        eav_storage.clone(),
+                        storage_usage.clone(),
+                    ));
+                    metered
+                });
+                let chain_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+                    Arc::new(RwLock::new(MeteredStorage::new(
+                        chain_storage,
+                        storage_usage.clone(),
+                        StorageKind::Chain,
+                    )));
+                let dht_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+                    Arc::new(RwLock::new(MeteredStorage::new(
+                        dht_storage,
+                        storage_usage.clone(),
+                        StorageKind::Dht,
+                    )));
+                let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+                    Arc::new(RwLock::new(EavMeteredStorage::new(
+                        eav_storage,
+                        storage_usage.clone(),
+                    )));
+                (chain_storage, dht_storage, eav_storage, eviction_storage)
+            } else {
+                (chain_storage, dht_storage, eav_storage, self.eviction_storage)
+            };
+
+        let mut context = Context::new(
             &self
                 .instance_name
                 .unwrap_or("Anonymous-instance".to_string()),
             self.agent_id.unwrap_or(AgentId::generate_fake("alice")),
-            Arc::new(Mutex::new(SimplePersister::new(chain_storage.clone()))),
+            persister,
             chain_storage,
             dht_storage,
             eav_storage,
@@ -165,15 +479,46 @@ impl ContextBuilder {
             self.conductor_api,
             self.signal_tx,
             self.state_dump_logging,
-        )
+        );
+        if let Some(chain_index_storage) = self.chain_index_storage {
+            context.chain_index_storage = chain_index_storage;
+        }
+        context.eviction_storage = eviction_storage;
+        context.retention_policy = self.retention_policy;
+        context.storage_usage = storage_usage;
+        context.storage_quota = self.storage_quota;
+        context.fuel_limits = self.fuel_limits;
+        context.wasm_memory_limit = self.wasm_memory_limit;
+        context.wasm_instance_pools = Arc::new(self.wasm_instance_pools);
+        context.zome_call_timeout = self.zome_call_timeout;
+        if self.profiling {
+            context.profiler = Some(Arc::new(Profiler::new()));
+        }
+        context.wasm_backtraces = self.wasm_backtraces;
+        context.zome_call_recordings = self.zome_call_recordings;
+        context.validation_workers =
+            Arc::new(ValidationWorkers::new(self.validation_retry_policy));
+        context
     }
 }
 
+/// The storages of the context that gets built.
+struct Storages {
+    chain: Arc<RwLock<dyn ContentAddressableStorage>>,
+    dht: Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     extern crate tempfile;
     use self::tempfile::tempdir;
+    use crate::storage::encrypted::tests::test_cipher;
+    use holochain_core_types::{
+        eav::{test_eav, EaviQuery},
+        entry::{test_entry, test_entry_b},
+    };
     use holochain_net::p2p_config::P2pBackendKind;
     use test_utils::mock_signing::mock_conductor_api;
 
@@ -183,7 +528,7 @@ mod tests {
         let context = ContextBuilder::new()
             .with_conductor_api(mock_conductor_api(agent.clone()))
             .spawn();
-        assert_eq!(context.agent_id, agent);
+        assert_eq!(context.agent_id(), agent);
         assert_eq!(P2pBackendKind::MEMORY, context.p2p_config.backend_kind);
     }
 
@@ -194,7 +539,7 @@ mod tests {
             .with_agent(agent.clone())
             .with_conductor_api(mock_conductor_api(agent.clone()))
             .spawn();
-        assert_eq!(context.agent_id, agent);
+        assert_eq!(context.agent_id(), agent);
     }
 
     #[test]
@@ -207,12 +552,108 @@ mod tests {
         assert_eq!(context.p2p_config, net);
     }
 
+    #[test]
+    fn with_storage_quota() {
+        let quota = StorageQuota {
+            hard_limit_entries: Some(1),
+            ..Default::default()
+        };
+        let context = ContextBuilder::new()
+            .with_storage_quota(quota.clone())
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        assert_eq!(context.storage_quota, quota);
+        assert!(context.check_storage_quota().is_ok());
+        context.chain_storage.write().unwrap().add(&test_entry()).unwrap();
+        assert_eq!(context.storage_usage.snapshot().chain.count, 1);
+        assert!(context.check_storage_quota().is_err());
+    }
+
+    #[test]
+    fn existing_storage_gets_scanned_once() {
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        fs::create_dir_all(temp.path().join("cas")).unwrap();
+        FilesystemStorage::new(&temp.path().join("cas"))
+            .unwrap()
+            .add(&test_entry())
+            .unwrap();
+        let spawn = |quota: StorageQuota| {
+            ContextBuilder::new()
+                .with_file_storage(temp.path())
+                .expect("Filestorage should get instantiated with tempdir")
+                .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+                .with_storage_quota(quota)
+                .try_spawn()
+                .unwrap()
+        };
+        let quota = StorageQuota {
+            hard_limit_bytes: Some(1 << 30),
+            ..Default::default()
+        };
+
+        let context = spawn(quota.clone());
+        assert_eq!(context.storage_usage.snapshot().chain.count, 1);
+        context
+            .storage_usage
+            .save_if_changed(&context.chain_storage)
+            .unwrap();
+        context.chain_storage.write().unwrap().add(&test_entry()).unwrap();
+
+        // The saved usage gets restored and includes neither the usage record nor the entry twice
+        let context = spawn(quota.clone());
+        assert_eq!(context.storage_usage.snapshot().chain.count, 1);
+
+        // Without a quota, nothing gets tracked and the saved usage becomes untracked
+        let context = spawn(StorageQuota::default());
+        context.chain_storage.write().unwrap().add(&test_entry_b()).unwrap();
+        assert_eq!(context.storage_usage.snapshot(), InstanceUsage::default());
+        context
+            .storage_usage
+            .save_if_changed(&context.chain_storage)
+            .unwrap();
+
+        // so it gets counted again once there is a quota
+        let context = spawn(quota);
+        assert_eq!(context.storage_usage.snapshot().chain.count, 2);
+        assert!(context.storage_usage.snapshot().tracked);
+    }
+
+    #[test]
+    fn chain_index_is_kept_apart_from_dht_eav() {
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let context = ContextBuilder::new()
+            .with_lmdb_storage(temp.path(), None)
+            .expect("LMDB storage should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        context
+            .chain_index_storage
+            .write()
+            .unwrap()
+            .add_eavi(&test_eav())
+            .unwrap();
+        let query = EaviQuery::default();
+        let index_rows = context
+            .chain_index_storage
+            .read()
+            .unwrap()
+            .fetch_eavi(&query)
+            .unwrap();
+        let dht_rows = context.eav_storage.read().unwrap().fetch_eavi(&query).unwrap();
+        assert_eq!(index_rows.len(), 1);
+        assert!(dht_rows.is_empty());
+    }
+
     #[test]
     fn smoke_tests() {
         let _ = ContextBuilder::new()
             .with_memory_storage()
             .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
             .spawn();
+        let _ = ContextBuilder::new()
+            .with_indexed_memory_storage()
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
         let temp = tempdir().expect("test was supposed to create temp dir");
         let temp_path = String::from(temp.path().to_str().expect("temp dir could not be string"));
         let _ = ContextBuilder::new()
@@ -220,5 +661,30 @@ mod tests {
             .expect("Filestorage should get instantiated with tempdir")
             .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
             .spawn();
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_encrypted_file_storage(temp.path(), test_cipher())
+            .expect("Encrypted file storage should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        assert!(ContextBuilder::new().with_file_storage(temp.path()).is_err());
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_journal_persister(temp.path(), None)
+            .expect("Journal persister should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_encrypted_journal_persister(temp.path(), None, test_cipher())
+            .expect("Encrypted journal persister should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let _ = ContextBuilder::new()
+            .with_lmdb_storage(temp.path(), None)
+            .expect("LMDB storage should get instantiated with tempdir")
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
     }
 }
@@ -91,6 +91,7 @@
 
 use crate::error::{HolochainInstanceError, HolochainResult};
 use holochain_core::{
+    agent::chain_archive::{import_chain, ChainArchive},
     context::Context,
     instance::Instance,
     nucleus::{
@@ -98,7 +99,6 @@ use holochain_core::{
         ribosome::{run_dna, WasmCallData},
         ZomeFnCall,
     },
-    persister::{Persister, SimplePersister},
 };
 use holochain_core_types::{
     dna::{capabilities::CapabilityRequest, Dna},
@@ -113,7 +113,7 @@ use holochain_core::{
 };
 use holochain_persistence_api::cas::content::Address;
 use jsonrpc_core::IoHandler;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 /// contains a Holochain application instance
 pub struct Holochain {
@@ -169,8 +169,10 @@ impl Holochain {
     }
 
     pub fn load(context: Arc<Context>) -> Result<Self, HolochainError> {
-        let persister = SimplePersister::new(context.dht_storage.clone());
-        let loaded_state = persister
+        let loaded_state = context
+            .persister
+            .lock()
+            .map_err(|_| HolochainError::new("Could not get lock on persister"))?
             .load(context.clone())?
             .ok_or(HolochainError::ErrorGeneric(
                 "State could not be loaded due to NoneError".to_string(),
@@ -227,6 +229,9 @@ impl Holochain {
             log_error!(context, "Error shutting down network: {:?}", err);
         }
         self.instance.as_ref().unwrap().stop_action_loop();
+        if let Err(err) = context.storage_usage.save_if_changed(&context.chain_storage) {
+            log_error!(context, "Error saving storage usage: {:?}", err);
+        }
         self.active = false;
         Ok(())
     }
@@ -239,10 +244,29 @@ impl Holochain {
         fn_name: &str,
         params: &str,
     ) -> HolochainResult<JsonString> {
+        let zome_call = ZomeFnCall::new(&zome, cap, &fn_name, JsonString::from_json(&params));
+        self.call_zome_fn(zome_call)
+    }
+
+    /// call a function in a zome and abort it with a timeout error if it does not
+    /// return within the given time
+    pub fn call_with_timeout(
+        &mut self,
+        zome: &str,
+        cap: CapabilityRequest,
+        fn_name: &str,
+        params: &str,
+        timeout: Duration,
+    ) -> HolochainResult<JsonString> {
+        let zome_call = ZomeFnCall::new(&zome, cap, &fn_name, JsonString::from_json(&params))
+            .with_timeout(timeout);
+        self.call_zome_fn(zome_call)
+    }
+
+    fn call_zome_fn(&mut self, zome_call: ZomeFnCall) -> HolochainResult<JsonString> {
         self.check_instance()?;
         self.check_active()?;
 
-        let zome_call = ZomeFnCall::new(&zome, cap, &fn_name, JsonString::from_json(&params));
         let context = self.context()?;
         Ok(context.block_on(call_zome_function(zome_call, context.clone()))?)
     }
@@ -268,6 +292,35 @@ impl Holochain {
         Ok(())
     }
 
+    /// Exports the source chain of this instance as a portable archive.
+    pub fn export_chain(&self) -> Result<ChainArchive, HolochainInstanceError> {
+        let agent = self.state()?.agent();
+        Ok(ChainArchive::export(
+            &agent.chain_store(),
+            &agent.top_chain_header(),
+        )?)
+    }
+
+    /// Verifies the given archive and imports it into the source chain of this instance.
+    /// The instance has to be stopped. It gets rebuilt from the persisted state that
+    /// includes the imported chain.
+    pub fn import_chain(&mut self, archive: &ChainArchive) -> Result<(), HolochainInstanceError> {
+        self.check_instance()?;
+        if self.active {
+            return Err(HolochainInstanceError::InstanceAlreadyActive);
+        }
+        let context = self.context()?;
+        let state = import_chain(context.clone(), archive)?;
+
+        // dropping the old instance shuts down its network and action loop
+        self.instance = None;
+        let mut instance = Instance::from_state(state, context.clone());
+        let new_context = instance.initialize(None, context)?;
+        self.instance = Some(instance);
+        self.context = Some(new_context);
+        Ok(())
+    }
+
     pub fn get_state_dump(&self) -> Result<StateDump, HolochainInstanceError> {
         self.check_instance()?;
         Ok(StateDump::from(self.context.clone().expect(
@@ -360,7 +413,7 @@ mod tests {
     fn cap_call(context: Arc<Context>, fn_name: &str, params: &str) -> CapabilityRequest {
         make_cap_request_for_call(
             context.clone(),
-            Address::from(context.clone().agent_id.address()),
+            Address::from(context.clone().agent_id().address()),
             fn_name,
             JsonString::from_json(params),
         )
@@ -378,7 +431,7 @@ mod tests {
         let context = hc.context.as_ref().unwrap().clone();
         assert_eq!(instance.state().nucleus().dna(), Some(dna));
         assert!(!hc.active);
-        assert_eq!(context.agent_id.nick, "bob".to_string());
+        assert_eq!(context.agent_id().nick, "bob".to_string());
         let network_state = context.state().unwrap().network().clone();
         assert_eq!(network_state.agent_id.is_some(), true);
         assert_eq!(network_state.dna_address.is_some(), true);
@@ -408,13 +461,34 @@ mod tests {
         assert!(result.is_ok());
         let loaded_holo = result.unwrap();
         assert!(!loaded_holo.active);
-        assert_eq!(loaded_holo.context.agent_id.nick, "bob".to_string());
+        assert_eq!(loaded_holo.context.agent_id().nick, "bob".to_string());
         let network_state = loaded_holo.context.state().unwrap().network().clone();
         assert!(network_state.agent_id.is_some());
         assert!(network_state.dna_address.is_some());
         assert!(loaded_holo.instance.state().nucleus().has_initialized());
     }
 
+    #[test]
+    fn can_export_and_import_chain() {
+        let dna = create_arbitrary_test_dna();
+        let (context, _test_logger, _) = test_context("archie");
+        let mut hc = Holochain::new(dna.clone(), context).unwrap();
+
+        let archive = hc.export_chain().unwrap();
+        assert_eq!(archive.items.len(), 2);
+        assert_eq!(archive.author(), Some(hc.context().unwrap().agent_id().address()));
+
+        hc.import_chain(&archive).unwrap();
+        assert_eq!(hc.export_chain().unwrap(), archive);
+        assert_eq!(hc.state().unwrap().nucleus().dna(), Some(dna));
+
+        hc.start().unwrap();
+        assert_eq!(
+            hc.import_chain(&archive),
+            Err(HolochainInstanceError::InstanceAlreadyActive)
+        );
+    }
+
     #[test]
     fn fails_instantiate_if_init_fails() {
         let dna = create_test_dna_with_wat(
@@ -2,10 +2,15 @@ use crate::holo_signing_service::request_service;
 use base64;
 use conductor::broadcaster::Broadcaster;
 use crossbeam_channel::Receiver;
-use holochain_core::nucleus::actions::call_zome_function::make_cap_request_for_call;
+use holochain_core::{
+    agent::chain_archive::ChainArchive,
+    nucleus::actions::call_zome_function::make_cap_request_for_call,
+    scheduled_jobs::pending_validations::ValidatingWorkflow,
+};
 
 use holochain_core_types::{
-    agent::AgentId, dna::capabilities::CapabilityRequest, signature::Provenance,
+    agent::AgentId, dna::capabilities::CapabilityRequest, error::HolochainError,
+    signature::Provenance,
 };
 use holochain_dpki::key_bundle::KeyBundle;
 use holochain_json_api::json::JsonString;
@@ -15,11 +20,12 @@ use Holochain;
 
 use jsonrpc_core::{self, types::params::Params, IoHandler, Value};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     path::PathBuf,
     sync::{Arc, Mutex, RwLock},
     thread,
+    time::Duration,
 };
 
 use conductor::{ConductorAdmin, ConductorDebug, ConductorTestAdmin, ConductorUiAdmin, CONDUCTOR};
@@ -27,11 +33,30 @@ use config::{
     AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration, InterfaceConfiguration,
     InterfaceDriver, UiBundleConfiguration, UiInterfaceConfiguration,
 };
+use error::HolochainInstanceError;
 use holochain_dpki::utils::SeedContext;
-use keystore::{KeyType, Keystore, Secret};
+use keystore::{KeyType, Keystore, Secret, NEXT_KEYBUNDLE_ID, PRIMARY_KEYBUNDLE_ID};
 use serde_json::{self, map::Map};
 
 pub type InterfaceError = String;
+
+/// JSON-RPC error code of zome calls that got aborted because they ran past their timeout.
+/// Lies in the range JSON-RPC reserves for implementation-defined server errors.
+pub const ZOME_CALL_TIMEOUT_ERROR_CODE: i64 = -32000;
+
+/// Timeouts become server errors with the backtrace of the aborted call, if any, as data.
+fn zome_call_error(error: HolochainInstanceError) -> jsonrpc_core::Error {
+    match error {
+        HolochainInstanceError::InternalFailure(HolochainError::Timeout(backtrace)) => {
+            jsonrpc_core::Error {
+                code: jsonrpc_core::ErrorCode::ServerError(ZOME_CALL_TIMEOUT_ERROR_CODE),
+                message: HolochainError::Timeout(None).to_string(),
+                data: backtrace.map(serde_json::Value::String),
+            }
+        }
+        _ => jsonrpc_core::Error::invalid_params(error.to_string()),
+    }
+}
 pub type InstanceMap = HashMap<String, Arc<RwLock<Holochain>>>;
 
 /// An identifier for an instance that is usable by UI in making calls to the conductor
@@ -88,6 +113,7 @@ macro_rules! conductor_call {
 /// Examples for method names are:
 /// {instance_id}/{zome}/{cap}/{func} -> a zome call
 /// info/list_instances               -> Map of InstanceConfigs, keyed by ID
+/// info/validation_receipt_count     -> Number of validators that hold an entry we authored
 /// admin/...                         -> see [with_admin_dna_functions]
 ///
 /// Each interface has their own handler, and each may be configured differently.
@@ -115,6 +141,7 @@ impl ConductorApiBuilder {
     /// Finish the building and retrieve the populated handler
     pub fn spawn(mut self) -> IoHandler {
         self.setup_info_api();
+        self.setup_validation_receipt_api();
         self.setup_call_api();
         *self.io
     }
@@ -199,9 +226,26 @@ impl ConductorApiBuilder {
                 }
             };
 
-            let response = hc
-                .call(&zome_name, cap_request, &func_name, &args_string)
-                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            // Optional timeout in milliseconds, overrides the conductor's default
+            let response = match params_map.get("timeout") {
+                None => hc.call(&zome_name, cap_request, &func_name, &args_string),
+                Some(_) => {
+                    let timeout_ms = Self::get_as_int("timeout", &params_map)?;
+                    if timeout_ms < 0 {
+                        return Err(jsonrpc_core::Error::invalid_params(
+                            "`timeout` must not be negative",
+                        ));
+                    }
+                    hc.call_with_timeout(
+                        &zome_name,
+                        cap_request,
+                        &func_name,
+                        &args_string,
+                        Duration::from_millis(timeout_ms as u64),
+                    )
+                }
+            }
+            .map_err(zome_call_error)?;
             Ok(Value::String(response.to_string()))
         });
     }
@@ -230,6 +274,45 @@ impl ConductorApiBuilder {
         });
     }
 
+    /// Adds a "info/validation_receipt_count" method that returns the number of distinct
+    /// validators that sent the given instance a valid receipt for the entry at `address`.
+    ///
+    /// Params:
+    /// * `instance_id`: public id of the instance that authored the entry
+    /// * `address`: address of the entry
+    fn setup_validation_receipt_api(&mut self) {
+        let instances = self.instances.clone();
+        let instance_ids_map = self.instance_ids_map.clone();
+
+        self.io.add_method("info/validation_receipt_count", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let public_id_str = Self::get_as_string("instance_id", &params_map)?;
+            let address = Self::get_as_string("address", &params_map)?;
+            let id = instance_ids_map
+                .get(&PublicInstanceIdentifier::from(public_id_str))
+                .ok_or(jsonrpc_core::Error::invalid_params(
+                    "instance identifier invalid",
+                ))?;
+            let instance = instances
+                .get(id)
+                .ok_or(jsonrpc_core::Error::invalid_params("unknown instance"))?;
+            let context = instance
+                .read()
+                .unwrap()
+                .context()
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?;
+            let count = context
+                .receipt_store()
+                .count(&Address::from(address))
+                .map_err(|e| {
+                    let mut error = jsonrpc_core::Error::internal_error();
+                    error.message = e.to_string();
+                    error
+                })?;
+            Ok(json!(count))
+        });
+    }
+
     /// Add a [InstanceConfig](struct.InstanceConfig.html) for a custom named instance
     pub fn with_named_instance_config(
         mut self,
@@ -334,6 +417,20 @@ impl ConductorApiBuilder {
             )))?)
     }
 
+    /// Reads the `address` and `workflow` params that identify a dead-lettered validation.
+    fn get_dead_letter_key(
+        params_map: &Map<String, Value>,
+    ) -> Result<(Address, ValidatingWorkflow), jsonrpc_core::Error> {
+        let address = Address::from(Self::get_as_string("address", params_map)?);
+        let workflow = ValidatingWorkflow::try_from(Self::get_as_string("workflow", params_map)?)
+            .map_err(|_| {
+                jsonrpc_core::Error::invalid_params(
+                    "`workflow` has to be one of HoldEntry, HoldLink, RemoveLink, UpdateEntry or RemoveEntry",
+                )
+            })?;
+        Ok((address, workflow))
+    }
+
     /// This adds functions to remotely change any aspect of the conductor config.
     /// After any change the conductor's config file gets saved.
     /// It is guaranteed that the config is either valid after the change or the change
@@ -390,6 +487,47 @@ impl ConductorApiBuilder {
     ///  * `admin/instance/running`
     ///     Returns an array of all instances that are running.
     ///
+    ///  * `admin/instance/export_chain`
+    ///     Returns the source chain of an instance as a versioned archive that lists every
+    ///     header with its entry in chain order.
+    ///     Params:
+    ///     * `id`: [string] Which instance's chain to export?
+    ///
+    ///  * `admin/instance/import_chain`
+    ///     Verifies a chain archive (header links, entry addresses and signatures) and imports
+    ///     it into a stopped instance. Broken archives and archives that would fork the
+    ///     instance's existing chain are refused.
+    ///     Params:
+    ///     * `id`: [string] Which instance to import the chain into?
+    ///     * `archive`: [object] The archive as returned by `admin/instance/export_chain`
+    ///
+    ///  * `admin/instance/usage`
+    ///     Returns the bytes and number of contents an instance stores in its chain, DHT
+    ///     and EAV storage, their totals, and the instance's configured storage quota.
+    ///     Params:
+    ///     * `id`: [string] Which instance's usage to report?
+    ///
+    ///  * `admin/instance/dead_letters`
+    ///     Returns the pending validations of an instance that ran out of attempts,
+    ///     with the number of attempts they got.
+    ///     Params:
+    ///     * `id`: [string] Which instance's dead letters to list?
+    ///
+    ///  * `admin/instance/retry_dead_letter`
+    ///     Moves a dead-lettered validation back to the instance's pending validations,
+    ///     where it gets retried with a fresh number of attempts.
+    ///     Params:
+    ///     * `id`: [string] Which instance's dead letter to retry?
+    ///     * `address`: [string] Address of the entry whose validation to retry
+    ///     * `workflow`: [string] Which validation of the entry to retry, e.g. `HoldEntry`
+    ///
+    ///  * `admin/instance/drop_dead_letter`
+    ///     Drops a dead-lettered validation for good.
+    ///     Params:
+    ///     * `id`: [string] Which instance's dead letter to drop?
+    ///     * `address`: [string] Address of the entry whose validation to drop
+    ///     * `workflow`: [string] Which validation of the entry to drop, e.g. `HoldEntry`
+    ///
     ///  * `admin/interface/add`
     ///     Adds a new DNA / zome / conductor interface (that provides access to zome functions
     ///     of selected instances and conductor functions, depending on the interfaces config).
@@ -588,6 +726,78 @@ impl ConductorApiBuilder {
                 ))
             });
 
+        self.io
+            .add_method("admin/instance/export_chain", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let archive = conductor_call!(|c| c.export_chain(&id))?;
+                serde_json::to_value(archive).map_err(|_| jsonrpc_core::Error::internal_error())
+            });
+
+        self.io
+            .add_method("admin/instance/import_chain", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let archive: ChainArchive = serde_json::from_value(
+                    params_map
+                        .get("archive")
+                        .ok_or(jsonrpc_core::Error::invalid_params(
+                            "`archive` param not provided",
+                        ))?
+                        .clone(),
+                )
+                .map_err(|e| {
+                    jsonrpc_core::Error::invalid_params(format!(
+                        "`archive` is not a valid chain archive: {}",
+                        e
+                    ))
+                })?;
+                conductor_call!(|c| c.import_chain(&id, &archive))?;
+                Ok(json!({"success": true}))
+            });
+
+        self.io.add_method("admin/instance/usage", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let report = conductor_call!(|c| c.instance_usage(&id))?;
+            serde_json::to_value(report).map_err(|_| jsonrpc_core::Error::internal_error())
+        });
+
+        self.io
+            .add_method("admin/instance/dead_letters", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let dead_letters = conductor_call!(|c| c.dead_letter_validations(&id))?;
+                serde_json::to_value(dead_letters)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())
+            });
+
+        self.io
+            .add_method("admin/instance/retry_dead_letter", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let (address, workflow) = Self::get_dead_letter_key(&params_map)?;
+                conductor_call!(|c| c.retry_dead_letter_validation(
+                    &id,
+                    address.clone(),
+                    workflow.clone()
+                ))?;
+                Ok(json!({"success": true}))
+            });
+
+        self.io
+            .add_method("admin/instance/drop_dead_letter", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let (address, workflow) = Self::get_dead_letter_key(&params_map)?;
+                conductor_call!(|c| c.drop_dead_letter_validation(
+                    &id,
+                    address.clone(),
+                    workflow.clone()
+                ))?;
+                Ok(json!({"success": true}))
+            });
+
         self.io.add_method("admin/interface/add", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
 
@@ -739,6 +949,15 @@ impl ConductorApiBuilder {
     ///   - `address` Address (hash) of the content that is requests
     ///   Returns an object of the form: {type:"<entry type>", content: "<content>"}
     ///
+    /// - `debug/profile`
+    ///   Returns the profiles of all zome functions of an instance called so far, by zome
+    ///   and function name. Every profile has the number of calls and failed calls,
+    ///   histograms of the call and WASM time, and the number, failures, argument bytes
+    ///   and a time histogram of every Zome API function the zome function called.
+    ///   Fails if profiling is not enabled for the instance.
+    ///   Params:
+    ///   - `instance_id` ID of the instance of which the profiles are requested
+    ///
     pub fn with_debug_functions(mut self) -> Self {
         self.io
             .add_method("debug/running_instances", move |_params| {
@@ -771,6 +990,15 @@ impl ConductorApiBuilder {
             }))
         });
 
+        self.io.add_method("debug/profile", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+
+            let report = conductor_call!(|c| c.instance_profile(&instance_id))?;
+
+            Ok(serde_json::to_value(report).map_err(|_| jsonrpc_core::Error::internal_error())?)
+        });
+
         self
     }
 
@@ -1200,6 +1428,99 @@ impl ConductorApiBuilder {
 
         self
     }
+
+    /// Adds the methods core uses to rotate the agent's key (see `hdk::update_agent`):
+    /// * `agent/key_rotation/prepare` creates the next keybundle in the keystore
+    ///   and returns its public signing key. Agents that run more than one instance, as
+    ///   listed in `agent_instances`, can't rotate their key since their other instances
+    ///   would go on signing with the old one.
+    /// * `agent/key_rotation/sign` signs the payload with the next keybundle.
+    /// * `agent/key_rotation/complete`, which core calls once the new agent entry is
+    ///   committed, makes the next keybundle the primary one and switches the given
+    ///   keybundle, which the agent's signing, encryption and decryption callbacks share,
+    ///   over to it. The keystore gets saved to `keystore_file` (if any) and the agent's
+    ///   public address in the conductor config gets updated.
+    pub fn with_agent_key_rotation_functions(
+        mut self,
+        agent_id: String,
+        agent_instances: Arc<Mutex<HashSet<String>>>,
+        keystore: Arc<Mutex<Keystore>>,
+        keybundle: Arc<Mutex<KeyBundle>>,
+        keystore_file: Option<PathBuf>,
+    ) -> Self {
+        let k = keystore.clone();
+        let id = agent_id.clone();
+        self.io
+            .add_method("agent/key_rotation/prepare", move |_params| {
+                let instance_count = agent_instances.lock().unwrap().len();
+                if instance_count > 1 {
+                    return Err(jsonrpc_core::Error::invalid_params(format!(
+                        "Agent {} runs {} instances and can't rotate its key",
+                        id, instance_count
+                    )));
+                }
+                let pub_key = k
+                    .lock()
+                    .unwrap()
+                    .add_next_keybundle()
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?;
+                Ok(json!({ "pub_key": pub_key }))
+            });
+
+        let k = keystore.clone();
+        self.io
+            .add_method("agent/key_rotation/sign", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let payload = Self::get_as_string("payload", &params_map)?;
+                let signature = k
+                    .lock()
+                    .unwrap()
+                    .sign(&format!("{}:sign_key", NEXT_KEYBUNDLE_ID), payload.clone())
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?;
+                Ok(json!({ "signature": String::from(signature) }))
+            });
+
+        self.io
+            .add_method("agent/key_rotation/complete", move |_params| {
+                let mut keystore = keystore.lock().unwrap();
+                let new_address = keystore
+                    .promote_next_keybundle(keystore_file.clone())
+                    .map_err(|err| {
+                        jsonrpc_core::Error::invalid_params(format!(
+                            "Could not rotate agent key: {}",
+                            err
+                        ))
+                    })?;
+                *keybundle.lock().unwrap() = keystore
+                    .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?;
+
+                // The conductor might be locked by whoever runs the zome call that is
+                // rotating the key, so the config gets updated in the background.
+                let agent_id = agent_id.clone();
+                let public_address = new_address.clone();
+                thread::spawn(move || match *CONDUCTOR.lock().unwrap() {
+                    Some(ref mut conductor) => {
+                        if let Err(err) = conductor
+                            .update_agent_public_address(&agent_id, public_address.clone())
+                        {
+                            log_error!(target: "holochain::conductor",
+                                "conductor: could not save key {} of agent {} to config: {}",
+                                public_address, agent_id, err
+                            );
+                        }
+                    }
+                    None => log_error!(target: "holochain::conductor",
+                        "conductor: no conductor mounted to save rotated key {} of agent {} to",
+                        public_address, agent_id
+                    ),
+                });
+
+                Ok(json!({ "pub_key": new_address }))
+            });
+
+        self
+    }
 }
 
 /// A Broadcaster is something that knows how to send a Signal back to a client.
@@ -1274,6 +1595,22 @@ pub mod tests {
         assert!(!result.contains(r#""test-instance-1//test""#));
     }
 
+    #[test]
+    fn test_zome_call_timeout_is_a_server_error() {
+        let error = zome_call_error(HolochainInstanceError::InternalFailure(
+            HolochainError::Timeout(Some("WASM backtrace:".to_string())),
+        ));
+        assert_eq!(
+            error.code,
+            jsonrpc_core::ErrorCode::ServerError(ZOME_CALL_TIMEOUT_ERROR_CODE)
+        );
+        assert_eq!(error.message, "timeout");
+        assert_eq!(error.data, Some(json!("WASM backtrace:")));
+
+        let error = zome_call_error(HolochainInstanceError::NoSuchInstance);
+        assert_eq!(error.code, jsonrpc_core::ErrorCode::InvalidParams);
+    }
+
     /// The below test cannot be extented to test the other RPC methods due to the singleton design of the conductor
     /// It may be worth removing this test but I have included it as an example of testing the responses for the
     /// other rpc methods if this becomes possible in the future
@@ -1297,6 +1634,48 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_rpc_validation_receipt_count_method() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "info/validation_receipt_count",
+                Some(json!({"instance_id" : "test-instance-1"})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"`address` param not provided"},"id":"0"}"#
+        );
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "info/validation_receipt_count",
+                Some(json!({"instance_id" : "bad instance id", "address" : "QmEntry"})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"instance identifier invalid"},"id":"0"}"#
+        );
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "info/validation_receipt_count",
+                Some(json!({"instance_id" : "test-instance-1", "address" : "QmEntry"})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Holochain Instance Error: Holochain instance is not active yet."},"id":"0"}"#
+        );
+    }
+
     #[test]
     fn test_rpc_call_method() {
         let (config, instances) = example_config_and_instances();
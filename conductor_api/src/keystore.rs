@@ -23,8 +23,8 @@ use lib3h_sodium::{
 use conductor::passphrase_manager::PassphraseManager;
 use holochain_dpki::{password_encryption::PwHashConfig, seed::SeedType};
 use std::{
-    collections::{BTreeMap, HashMap},
-    fs::File,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File},
     io::prelude::*,
     path::PathBuf,
     sync::{Arc, Mutex},
@@ -38,6 +38,10 @@ const KEYBUNDLE_SIGNKEY_SUFFIX: &str = ":sign_key";
 const KEYBUNDLE_ENCKEY_SUFFIX: &str = ":enc_key";
 pub const PRIMARY_KEYBUNDLE_ID: &str = "primary_keybundle";
 pub const STANDALONE_ROOT_SEED: &str = "root_seed";
+pub const NEXT_KEYBUNDLE_ID: &str = "next_keybundle";
+const NEXT_KEYBUNDLE_SEED: &str = "next_keybundle:seed";
+const RETIRED_KEYBUNDLE_PREFIX: &str = "retired_keybundle";
+const STORAGE_KEY_CONTEXT: [u8; 8] = *b"HCSTORKY";
 
 pub enum Secret {
     SigningKey(SigningKeyPair),
@@ -279,10 +283,19 @@ impl Keystore {
     }
 
     /// Serialize the keystore to a file.
+    /// The keystore gets written to a temporary file next to `path` that then replaces
+    /// `path` in one rename, so a crash can't leave a half written keystore behind.
     pub fn save(&self, path: PathBuf) -> HcResult<()> {
         let json_string = serde_json::to_string(self)?;
-        let mut file = File::create(path)?;
-        file.write_all(&json_string.as_bytes())?;
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&json_string.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
         Ok(())
     }
 
@@ -309,6 +322,60 @@ impl Keystore {
         Ok(())
     }
 
+    /// removes a secret from the keystore
+    pub fn remove(&mut self, id_str: &str) -> HcResult<()> {
+        let id = id_str.to_string();
+        self.secrets
+            .remove(&id)
+            .ok_or(HolochainError::ErrorGeneric(
+                "unknown source identifier".to_string(),
+            ))?;
+        self.cache.remove(&id);
+        Ok(())
+    }
+
+    /// moves a secret to a new identifier
+    fn rename(&mut self, src_id_str: &str, dst_id_str: &str) -> HcResult<()> {
+        let dst_id = self.check_dst_identifier(dst_id_str)?;
+        let src_id = src_id_str.to_string();
+        let blob = self
+            .secrets
+            .remove(&src_id)
+            .ok_or(HolochainError::ErrorGeneric(
+                "unknown source identifier".to_string(),
+            ))?;
+        self.secrets.insert(dst_id.clone(), blob);
+        if let Some(secret) = self.cache.remove(&src_id) {
+            self.cache.insert(dst_id, secret);
+        }
+        Ok(())
+    }
+
+    /// moves secrets to new identifiers, one after the other.
+    /// Either all of them get moved or, if any source is missing or any destination
+    /// is taken by the time it gets moved to, none.
+    fn rename_all(&mut self, renames: &[(String, String)]) -> HcResult<()> {
+        let mut ids: BTreeSet<&String> = self.secrets.keys().collect();
+        for (src_id, dst_id) in renames {
+            if !ids.remove(src_id) {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "unknown source identifier {}",
+                    src_id
+                )));
+            }
+            if !ids.insert(dst_id) {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "identifier {} already exists",
+                    dst_id
+                )));
+            }
+        }
+        for (src_id, dst_id) in renames {
+            self.rename(src_id, dst_id)?;
+        }
+        Ok(())
+    }
+
     fn check_dst_identifier(&self, dst_id_str: &str) -> HcResult<String> {
         let dst_id = dst_id_str.to_string();
         if self.secrets.contains_key(&dst_id) {
@@ -345,6 +412,25 @@ impl Keystore {
         Ok((src_secret, dst_id))
     }
 
+    /// derives the symmetric key that encrypted instance storage uses from the root seed.
+    /// The key never gets stored: it is derived again, which may ask for the passphrase,
+    /// every time an instance with encrypted storage gets started.
+    pub fn get_storage_key(&mut self) -> HcResult<SecBuf> {
+        let secret = self.get(STANDALONE_ROOT_SEED)?;
+        let mut secret = secret.lock()?;
+        match *secret {
+            Secret::Seed(ref mut root_seed) => generate_derived_seed_buf(
+                root_seed,
+                &SeedContext::new(STORAGE_KEY_CONTEXT),
+                1,
+                SEED_SIZE,
+            ),
+            _ => Err(HolochainError::ErrorGeneric(
+                "root secret is not a seed".to_string(),
+            )),
+        }
+    }
+
     /// adds a derived seed into the keystore
     pub fn add_seed_from_seed(
         &mut self,
@@ -507,6 +593,57 @@ impl Keystore {
         Ok(KeyBundle::new(sign_key, enc_key)?)
     }
 
+    /// adds the keybundle an agent rotates its keys to under NEXT_KEYBUNDLE_ID, replacing
+    /// any keybundle left there by a rotation that never got completed.
+    /// The keys get generated from a fresh random seed and not from the root seed, so
+    /// rotating away from compromised keys doesn't leave the new keys derivable from them.
+    /// returns the public signing key, i.e. the agent's address after the rotation
+    pub fn add_next_keybundle(&mut self) -> HcResult<Base32> {
+        for suffix in &[KEYBUNDLE_SIGNKEY_SUFFIX, KEYBUNDLE_ENCKEY_SUFFIX] {
+            let _ = self.remove(&[NEXT_KEYBUNDLE_ID, suffix].join(""));
+        }
+        let _ = self.remove(NEXT_KEYBUNDLE_SEED);
+        self.add_random_seed(NEXT_KEYBUNDLE_SEED, SEED_SIZE)?;
+        let result = self.add_keybundle_from_seed(NEXT_KEYBUNDLE_SEED, NEXT_KEYBUNDLE_ID);
+        self.remove(NEXT_KEYBUNDLE_SEED)?;
+        result.map(|(sign_pub_key, _)| sign_pub_key)
+    }
+
+    /// makes the keybundle under NEXT_KEYBUNDLE_ID the primary keybundle.
+    /// The previous primary keybundle is kept as "retired_keybundle:<address>".
+    /// If a path is given, the keystore gets saved there. Either both the keystore and
+    /// the file switch to the new keybundle or, if anything fails, neither does.
+    /// returns the public signing key of the new primary keybundle
+    pub fn promote_next_keybundle(&mut self, path: Option<PathBuf>) -> HcResult<Base32> {
+        let new_address = self.get_keybundle(NEXT_KEYBUNDLE_ID)?.get_id();
+        let old_address = self.get_keybundle(PRIMARY_KEYBUNDLE_ID)?.get_id();
+        let retired_id = [RETIRED_KEYBUNDLE_PREFIX, ":", &old_address.to_string()].join("");
+        let mut renames = Vec::new();
+        for suffix in &[KEYBUNDLE_SIGNKEY_SUFFIX, KEYBUNDLE_ENCKEY_SUFFIX] {
+            renames.push((
+                [PRIMARY_KEYBUNDLE_ID, suffix].join(""),
+                [retired_id.as_str(), suffix].join(""),
+            ));
+            renames.push((
+                [NEXT_KEYBUNDLE_ID, suffix].join(""),
+                [PRIMARY_KEYBUNDLE_ID, suffix].join(""),
+            ));
+        }
+        self.rename_all(&renames)?;
+        if let Some(path) = path {
+            if let Err(err) = self.save(path) {
+                let undo: Vec<(String, String)> = renames
+                    .into_iter()
+                    .rev()
+                    .map(|(src_id, dst_id)| (dst_id, src_id))
+                    .collect();
+                self.rename_all(&undo)?;
+                return Err(err);
+            }
+        }
+        Ok(new_address)
+    }
+
     /// signs some data using a keypair in the keystore
     /// returns the signature
     pub fn sign(&mut self, src_id_str: &str, data: String) -> HcResult<Signature> {
@@ -546,6 +683,8 @@ pub mod tests {
     use conductor::passphrase_manager::PassphraseServiceMock;
     use holochain_dpki::utils;
     use holochain_persistence_api::cas::content::Address;
+    extern crate tempfile;
+    use self::tempfile::tempdir;
 
     fn mock_passphrase_manager(passphrase: String) -> Arc<PassphraseManager> {
         Arc::new(PassphraseManager::new(Arc::new(Mutex::new(
@@ -686,6 +825,18 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_keystore_get_storage_key() {
+        let mut keystore = new_test_keystore(random_test_passphrase());
+        assert!(keystore.get_storage_key().is_err());
+
+        let _ = keystore.add_random_seed(STANDALONE_ROOT_SEED, SEED_SIZE);
+        let mut key1 = keystore.get_storage_key().unwrap();
+        let mut key2 = keystore.get_storage_key().unwrap();
+        assert_eq!(key1.len(), SEED_SIZE);
+        assert_eq!(key1.compare(&mut key2), 0);
+    }
+
     #[test]
     fn test_keystore_add_signing_key_from_seed() {
         let mut keystore = new_test_keystore(random_test_passphrase());
@@ -795,6 +946,72 @@ pub mod tests {
         assert!(key_bundle.enc_keys.is_same(&mut key_bundle_copy.enc_keys));
     }
 
+    #[test]
+    fn test_keystore_rotate_keybundle() {
+        let random_passphrase = random_test_passphrase();
+        let mut keystore = new_test_keystore(random_passphrase.clone());
+        let _ = keystore.add_random_seed(STANDALONE_ROOT_SEED, SEED_SIZE);
+        let (old_address, _) = keystore
+            .add_keybundle_from_seed(STANDALONE_ROOT_SEED, PRIMARY_KEYBUNDLE_ID)
+            .unwrap();
+
+        assert!(keystore.promote_next_keybundle(None).is_err());
+
+        // preparing twice replaces the first next keybundle
+        let _ = keystore.add_next_keybundle().unwrap();
+        let new_address = keystore.add_next_keybundle().unwrap();
+        assert_ne!(new_address, old_address);
+        assert_eq!(
+            keystore.get_keybundle(NEXT_KEYBUNDLE_ID).unwrap().get_id(),
+            new_address
+        );
+
+        // a keystore that can't be saved keeps its primary keybundle
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let missing_dir_path = temp.path().join("missing").join("keystore");
+        assert!(keystore
+            .promote_next_keybundle(Some(missing_dir_path))
+            .is_err());
+        assert_eq!(
+            keystore
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .unwrap()
+                .get_id(),
+            old_address
+        );
+
+        let path = temp.path().join("keystore");
+        assert_eq!(
+            keystore.promote_next_keybundle(Some(path.clone())),
+            Ok(new_address.clone())
+        );
+        let mut loaded_keystore = Keystore::new_from_file(
+            path,
+            mock_passphrase_manager(random_passphrase),
+            test_hash_config(),
+        )
+        .unwrap();
+        assert!(loaded_keystore.get_keybundle(NEXT_KEYBUNDLE_ID).is_err());
+        assert_eq!(loaded_keystore.list(), keystore.list());
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+        assert_eq!(
+            keystore
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .unwrap()
+                .get_id(),
+            new_address
+        );
+        assert!(keystore.get_keybundle(NEXT_KEYBUNDLE_ID).is_err());
+        assert_eq!(
+            keystore
+                .get_keybundle(&format!("retired_keybundle:{}", old_address))
+                .unwrap()
+                .get_id(),
+            old_address
+        );
+        assert!(!keystore.list().contains(&NEXT_KEYBUNDLE_SEED.to_string()));
+    }
+
     #[test]
     /// Tests if the keystore encrypted with holochain_common::DEFAULT_PASSPHRASE can be decrypted,
     /// no matter what passphrase we get from the passphrase manager
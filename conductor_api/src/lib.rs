@@ -103,6 +103,7 @@ extern crate holochain_persistence_file;
 extern crate holochain_persistence_mem;
 extern crate holochain_persistence_pickle;
 extern crate lib3h_sodium;
+extern crate lmdb;
 #[macro_use]
 extern crate log;
 extern crate logging;
@@ -139,6 +140,7 @@ extern crate base64;
 extern crate lib3h;
 #[macro_use]
 extern crate nickel;
+extern crate uuid;
 
 pub mod conductor;
 pub mod config;
@@ -155,5 +157,6 @@ pub mod logger;
 pub mod signal_wrapper;
 pub mod static_file_server;
 pub mod static_server_impls;
+pub mod storage;
 
 pub use crate::holochain::Holochain;
@@ -0,0 +1,785 @@
+//! Encryption at rest for content addressable and EAV storage.
+//!
+//! `EncryptedStorage` and `EavEncryptedStorage` wrap any other storage implementation and
+//! encrypt what they write with a symmetric key that gets derived from the agent's root seed
+//! (see `Keystore::get_storage_key()`).
+//!
+//! Content addresses stay in the clear so that the wrapped stores can still key their data by
+//! them:
+//! * the CAS stores every content under its original address, but only its ciphertext
+//! * the EAV store keeps value and index of every row, and encrypts the entity as well as the
+//!   names and tags that link and chain index attributes carry. These get encrypted
+//!   deterministically, so equal plaintexts give equal ciphertexts. The wrapped store can then
+//!   run a query's entity and attribute filters itself, decrypting each distinct ciphertext
+//!   once, instead of handing every row up for decryption.
+//!
+//! `open_encrypted_file_storage()` opens an encrypted file storage directory and migrates an
+//! existing unencrypted one on its first use.
+
+use crate::storage::metered::file_cas_addresses;
+use holochain_core::persister::JournalCipher;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EavFilter, EntityAttributeValueStorage, IndexFilter},
+    error::{PersistenceError, PersistenceResult},
+};
+use holochain_persistence_file::{cas::file::FilesystemStorage, eav::file::EavFileStorage};
+use lib3h_sodium::{aead, hash::sha256, secbuf::SecBuf};
+use serde_json;
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str,
+    sync::{Arc, Mutex, RwLock},
+};
+use uuid::Uuid;
+
+/// Name of the file that marks a storage directory as encrypted.
+/// It holds `KEY_CHECK_PLAINTEXT` encrypted with the storage key, which lets us
+/// detect a wrong key before reading any data.
+pub const KEY_CHECK_FILE: &str = "encryption_check";
+const KEY_CHECK_PLAINTEXT: &[u8] = b"holochain encrypted storage";
+
+/// Context of the key that deterministic nonces get derived with.
+const NONCE_KEY_CONTEXT: &[u8] = b"holochain storage nonce key";
+const SHA256_BYTES: usize = 32;
+const HMAC_BLOCK_BYTES: usize = 64;
+
+fn to_persistence_error(error: HolochainError) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("Encrypted storage error: {}", error))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> HcResult<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Invalid hex string: {}",
+            hex
+        )));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| {
+                HolochainError::ErrorGeneric(format!("Invalid hex string: {}", hex))
+            })
+        })
+        .collect()
+}
+
+fn sha256_bytes(data: &[u8]) -> HcResult<Vec<u8>> {
+    let mut input = SecBuf::with_insecure(data.len());
+    input.from_array(data)?;
+    let mut hash = SecBuf::with_insecure(SHA256_BYTES);
+    sha256(&mut input, &mut hash)?;
+    let hash = (&**hash.read_lock()).to_vec();
+    Ok(hash)
+}
+
+/// HMAC-SHA256 as in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> HcResult<Vec<u8>> {
+    let mut block_key = if key.len() > HMAC_BLOCK_BYTES {
+        sha256_bytes(key)?
+    } else {
+        key.to_vec()
+    };
+    block_key.resize(HMAC_BLOCK_BYTES, 0);
+    let mut inner: Vec<u8> = block_key.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block_key.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256_bytes(&inner)?);
+    sha256_bytes(&outer)
+}
+
+/// Symmetric authenticated encryption with a storage key.
+/// Every encryption uses a random nonce, which gets prepended to the ciphertext,
+/// unless it is deterministic (see `encrypt_deterministic()`).
+#[derive(Clone)]
+pub struct StorageCipher {
+    key: Arc<Mutex<SecBuf>>,
+}
+
+impl fmt::Debug for StorageCipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StorageCipher")
+    }
+}
+
+impl StorageCipher {
+    pub fn new(key: SecBuf) -> Self {
+        StorageCipher {
+            key: Arc::new(Mutex::new(key)),
+        }
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce.randomize();
+        self.encrypt_with_nonce(data, nonce)
+    }
+
+    /// Encrypts the given data with a nonce that is the HMAC of the data under a key derived
+    /// from the storage key, so equal plaintexts give equal ciphertexts. This reveals which
+    /// stored values are equal but nothing else about them. The result decrypts with
+    /// `decrypt()` like any other ciphertext.
+    pub fn encrypt_deterministic(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        let nonce_key = hmac_sha256(&**self.key.lock()?.read_lock(), NONCE_KEY_CONTEXT)?;
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce.from_array(&hmac_sha256(&nonce_key, data)?[..aead::NONCEBYTES])?;
+        self.encrypt_with_nonce(data, nonce)
+    }
+
+    fn encrypt_with_nonce(&self, data: &[u8], mut nonce: SecBuf) -> HcResult<Vec<u8>> {
+        let mut message = SecBuf::with_insecure(data.len());
+        message.from_array(data)?;
+        let mut cipher = SecBuf::with_insecure(data.len() + aead::ABYTES);
+        aead::enc(
+            &mut message,
+            &mut *self.key.lock()?,
+            None,
+            &mut nonce,
+            &mut cipher,
+        )?;
+
+        let mut encrypted = (&**nonce.read_lock()).to_vec();
+        encrypted.extend_from_slice(&**cipher.read_lock());
+        Ok(encrypted)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        if data.len() <= aead::NONCEBYTES + aead::ABYTES {
+            return Err(HolochainError::ErrorGeneric(
+                "Encrypted data is too short".to_string(),
+            ));
+        }
+        let (nonce_bytes, cipher_bytes) = data.split_at(aead::NONCEBYTES);
+        let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+        nonce.from_array(nonce_bytes)?;
+        let mut cipher = SecBuf::with_insecure(cipher_bytes.len());
+        cipher.from_array(cipher_bytes)?;
+        let mut message = SecBuf::with_insecure(cipher_bytes.len() - aead::ABYTES);
+        aead::dec(
+            &mut message,
+            &mut *self.key.lock()?,
+            None,
+            &mut nonce,
+            &mut cipher,
+        )
+        .map_err(|_| {
+            HolochainError::ErrorGeneric(
+                "Could not decrypt stored data. Was it encrypted with another agent's key?"
+                    .to_string(),
+            )
+        })?;
+        let decrypted = (&**message.read_lock()).to_vec();
+        Ok(decrypted)
+    }
+
+    /// Deterministically encrypts a string into a hex string. Empty strings stay empty.
+    fn encrypt_string(&self, plaintext: &str) -> HcResult<String> {
+        if plaintext.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(to_hex(&self.encrypt_deterministic(plaintext.as_bytes())?))
+    }
+
+    fn decrypt_string(&self, hex: &str) -> HcResult<String> {
+        if hex.is_empty() {
+            return Ok(String::new());
+        }
+        String::from_utf8(self.decrypt(&from_hex(hex)?)?).map_err(|_| {
+            HolochainError::ErrorGeneric("Decrypted string is not valid UTF-8".to_string())
+        })
+    }
+
+    /// Writes the key check file into the given directory.
+    pub fn write_key_check(&self, dir: &Path) -> HcResult<()> {
+        fs::write(
+            dir.join(KEY_CHECK_FILE),
+            to_hex(&self.encrypt(KEY_CHECK_PLAINTEXT)?),
+        )?;
+        Ok(())
+    }
+
+    /// Makes sure the key check file in the given directory was written with this key.
+    pub fn verify_key_check(&self, dir: &Path) -> HcResult<()> {
+        let check = fs::read_to_string(dir.join(KEY_CHECK_FILE))?;
+        if self.decrypt(&from_hex(check.trim())?)? == KEY_CHECK_PLAINTEXT {
+            Ok(())
+        } else {
+            Err(HolochainError::ErrorGeneric(format!(
+                "Encryption check of storage {} failed",
+                dir.to_string_lossy()
+            )))
+        }
+    }
+}
+
+impl JournalCipher for StorageCipher {
+    fn encrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        StorageCipher::encrypt(self, data)
+    }
+
+    fn decrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        StorageCipher::decrypt(self, data)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedContent {
+    encrypted: String,
+}
+
+/// A content together with the address it gets stored under.
+/// Used to store ciphertexts under the address of their plaintext.
+struct StoredContent {
+    address: Address,
+    content: Content,
+}
+
+impl AddressableContent for StoredContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        self.content.clone()
+    }
+
+    fn try_from_content(_content: &Content) -> JsonResult<Self> {
+        Err(JsonError::ErrorGeneric(
+            "The address of stored content can not be derived from it".to_string(),
+        ))
+    }
+}
+
+/// Content addressable storage that encrypts all contents before passing them on
+/// to the wrapped storage.
+#[derive(Clone, Debug)]
+pub struct EncryptedStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    cipher: StorageCipher,
+    id: Uuid,
+}
+
+impl PartialEq for EncryptedStorage {
+    fn eq(&self, other: &EncryptedStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+impl EncryptedStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+        cipher: StorageCipher,
+    ) -> EncryptedStorage {
+        EncryptedStorage {
+            inner,
+            cipher,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    fn encrypt_content(&self, content: &dyn AddressableContent) -> HcResult<StoredContent> {
+        let encrypted = EncryptedContent {
+            encrypted: base64::encode(
+                &self
+                    .cipher
+                    .encrypt(String::from(content.content()).as_bytes())?,
+            ),
+        };
+        Ok(StoredContent {
+            address: content.address(),
+            content: JsonString::from_json(&serde_json::to_string(&encrypted)?),
+        })
+    }
+
+    fn decrypt_content(&self, stored: &Content) -> HcResult<Content> {
+        let encrypted: EncryptedContent = serde_json::from_str(&String::from(stored.clone()))?;
+        let plaintext = self.cipher.decrypt(&base64::decode(&encrypted.encrypted)?)?;
+        Ok(JsonString::from_json(str::from_utf8(&plaintext)?))
+    }
+}
+
+impl ContentAddressableStorage for EncryptedStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        let ciphertext = self
+            .encrypt_content(content)
+            .map_err(to_persistence_error)?;
+        self.inner.write()?.add(&ciphertext)
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read()?.contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        match self.inner.read()?.fetch(address)? {
+            Some(stored) => Ok(Some(
+                self.decrypt_content(&stored)
+                    .map_err(to_persistence_error)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// EAV storage that encrypts the entity and the strings carried by the attributes of all rows
+/// before passing them on to the wrapped storage.
+#[derive(Clone, Debug)]
+pub struct EavEncryptedStorage {
+    inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    cipher: StorageCipher,
+    id: Uuid,
+}
+
+impl PartialEq for EavEncryptedStorage {
+    fn eq(&self, other: &EavEncryptedStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+impl EavEncryptedStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+        cipher: StorageCipher,
+    ) -> EavEncryptedStorage {
+        EavEncryptedStorage {
+            inner,
+            cipher,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    fn map_attribute<F>(attribute: Attribute, f: F) -> HcResult<Attribute>
+    where
+        F: Fn(&str) -> HcResult<String>,
+    {
+        Ok(match attribute {
+            Attribute::LinkTag(name, tag) => Attribute::LinkTag(f(&name)?, f(&tag)?),
+            Attribute::RemovedLink(name, tag) => Attribute::RemovedLink(f(&name)?, f(&tag)?),
+            Attribute::ChainIndex(root, entry_type) => {
+                Attribute::ChainIndex(f(&root)?, f(&entry_type)?)
+            }
+            attribute => attribute,
+        })
+    }
+
+    fn map_eavi<F>(eavi: &EntityAttributeValueIndex, f: F) -> HcResult<EntityAttributeValueIndex>
+    where
+        F: Fn(&str) -> HcResult<String>,
+    {
+        Ok(EntityAttributeValueIndex::new_with_index(
+            &Address::from(f(&String::from(eavi.entity()))?),
+            &Self::map_attribute(eavi.attribute(), &f)?,
+            &eavi.value(),
+            eavi.index(),
+        )?)
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavEncryptedStorage {
+    fn add_eavi(
+        &mut self,
+        eav: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let cipher = self.cipher.clone();
+        let encrypted = Self::map_eavi(eav, |s| cipher.encrypt_string(s))
+            .map_err(to_persistence_error)?;
+        let stored = self.inner.write()?.add_eavi(&encrypted)?;
+        // The wrapped store might have moved the row to another index
+        Ok(stored.map(|stored| {
+            let mut added = eav.clone();
+            added.set_index(stored.index());
+            added
+        }))
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        // The wrapped store runs the entity, attribute and value filters on the stored
+        // ciphertexts by decrypting them first. Rows it can't decrypt never match.
+        // Only the index range gets pushed down as is: the latest row per attribute has to be
+        // picked among the decrypted rows, together with the tombstone filter.
+        let cipher = &self.cipher;
+        let index = match query.index() {
+            IndexFilter::Range(start, end) => IndexFilter::Range(start.clone(), end.clone()),
+            _ => IndexFilter::Range(None, None),
+        };
+        let rows = self.inner.read()?.fetch_eavi(&EaviQuery::new(
+            EavFilter::predicate(|entity: Address| {
+                cipher
+                    .decrypt_string(&String::from(entity))
+                    .map(|entity| query.entity().check(Address::from(entity)))
+                    .unwrap_or(false)
+            }),
+            EavFilter::predicate(|attribute: Attribute| {
+                Self::map_attribute(attribute, |s| cipher.decrypt_string(s))
+                    .map(|attribute| query.attribute().check(attribute))
+                    .unwrap_or(false)
+            }),
+            EavFilter::predicate(|value: Address| query.value().check(value)),
+            index,
+            None,
+        ))?;
+        let decrypted = rows
+            .iter()
+            .map(|eavi| Self::map_eavi(eavi, |s| self.cipher.decrypt_string(s)))
+            .collect::<HcResult<Vec<_>>>()
+            .map_err(to_persistence_error)?;
+        Ok(query.run(decrypted.into_iter()))
+    }
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn is_empty_dir(path: &Path) -> HcResult<bool> {
+    Ok(!path.exists() || fs::read_dir(path)?.next().is_none())
+}
+
+type StoragePair = (
+    Arc<RwLock<dyn ContentAddressableStorage>>,
+    Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+);
+
+fn file_storage(base_path: &Path) -> HcResult<StoragePair> {
+    let cas_path = base_path.join("cas");
+    let eav_path = base_path.join("eav");
+    fs::create_dir_all(&cas_path)?;
+    fs::create_dir_all(&eav_path)?;
+    let cas = Arc::new(RwLock::new(FilesystemStorage::new(&cas_path)?));
+    let eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavFileStorage::new(eav_path)?));
+    Ok((cas, eav))
+}
+
+fn encrypted_file_storage(base_path: &Path, cipher: &StorageCipher) -> HcResult<StoragePair> {
+    let (cas, eav) = file_storage(base_path)?;
+    Ok((
+        Arc::new(RwLock::new(EncryptedStorage::new(cas, cipher.clone()))),
+        Arc::new(RwLock::new(EavEncryptedStorage::new(eav, cipher.clone()))),
+    ))
+}
+
+/// Copies all contents and EAV rows of the unencrypted file storage in `from`
+/// into a new encrypted file storage in `to`.
+fn encrypt_file_storage(from: &Path, to: &Path, cipher: &StorageCipher) -> HcResult<()> {
+    let (plain_cas, plain_eav) = file_storage(from)?;
+    let (encrypted_cas, encrypted_eav) = encrypted_file_storage(to, cipher)?;
+
+    for address in file_cas_addresses(&from.join("cas"))? {
+        if let Some(content) = plain_cas.read()?.fetch(&address)? {
+            encrypted_cas.write()?.add(&StoredContent { address, content })?;
+        }
+    }
+    let rows = plain_eav.read()?.fetch_eavi(&EaviQuery::new(
+        None.into(),
+        None.into(),
+        None.into(),
+        IndexFilter::Range(None, None),
+        None,
+    ))?;
+    for eavi in rows.iter() {
+        encrypted_eav.write()?.add_eavi(eavi)?;
+    }
+    cipher.write_key_check(to)
+}
+
+/// Opens the encrypted file storage in the given directory.
+///
+/// If the directory holds an unencrypted file storage, all of it gets encrypted into
+/// `<path>.encrypting` first, which then replaces the unencrypted directory.
+/// A migration that got interrupted either gets completed or started over.
+pub fn open_encrypted_file_storage(
+    base_path: &Path,
+    cipher: &StorageCipher,
+) -> HcResult<StoragePair> {
+    let encrypting_path = sibling_path(base_path, ".encrypting");
+    let plaintext_path = sibling_path(base_path, ".plaintext");
+
+    if encrypting_path.exists() {
+        if base_path.exists() {
+            // Interrupted while encrypting, start over
+            fs::remove_dir_all(&encrypting_path)?;
+        } else {
+            // Interrupted while swapping directories
+            fs::rename(&encrypting_path, base_path)?;
+        }
+    }
+
+    if base_path.join(KEY_CHECK_FILE).exists() {
+        cipher.verify_key_check(base_path)?;
+    } else if !is_empty_dir(&base_path.join("cas"))? || !is_empty_dir(&base_path.join("eav"))? {
+        encrypt_file_storage(base_path, &encrypting_path, cipher)?;
+        fs::rename(base_path, &plaintext_path)?;
+        fs::rename(&encrypting_path, base_path)?;
+    } else {
+        fs::create_dir_all(base_path)?;
+        cipher.write_key_check(base_path)?;
+    }
+
+    if plaintext_path.exists() {
+        fs::remove_dir_all(&plaintext_path)?;
+    }
+
+    encrypted_file_storage(base_path, cipher)
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempfile;
+    use self::tempfile::tempdir;
+    use super::*;
+    use holochain_dpki::{utils::generate_random_buf, SEED_SIZE};
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::{
+        cas::{
+            content::{ExampleAddressableContent, OtherExampleAddressableContent},
+            storage::{EavTestSuite, StorageTestSuite},
+        },
+        eav::EavFilter,
+    };
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+
+    pub fn test_cipher() -> StorageCipher {
+        StorageCipher::new(generate_random_buf(SEED_SIZE))
+    }
+
+    fn test_cas(cipher: StorageCipher) -> EncryptedStorage {
+        EncryptedStorage::new(Arc::new(RwLock::new(MemoryStorage::new())), cipher)
+    }
+
+    fn test_eav(cipher: StorageCipher) -> EavEncryptedStorage {
+        EavEncryptedStorage::new(Arc::new(RwLock::new(EavMemoryStorage::new())), cipher)
+    }
+
+    fn all_rows() -> EaviQuery<'static> {
+        EaviQuery::new(
+            None.into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        )
+    }
+
+    #[test]
+    fn cipher_round_trip() {
+        let cipher = test_cipher();
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+        assert!(!encrypted.windows(6).any(|window| window == b"secret"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret".to_vec());
+        assert!(test_cipher().decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn encrypted_content_round_trip_test() {
+        let test_suite = StorageTestSuite::new(test_cas(test_cipher()));
+        test_suite.round_trip_test::<ExampleAddressableContent, OtherExampleAddressableContent>(
+            RawString::from("foo").into(),
+            RawString::from(true).into(),
+        );
+    }
+
+    #[test]
+    fn encrypted_content_is_not_stored_in_plaintext() {
+        let inner = Arc::new(RwLock::new(MemoryStorage::new()));
+        let mut cas = EncryptedStorage::new(inner.clone(), test_cipher());
+        let content =
+            ExampleAddressableContent::try_from_content(&RawString::from("private").into())
+                .unwrap();
+        cas.add(&content).unwrap();
+
+        let stored = inner.read().unwrap().fetch(&content.address()).unwrap();
+        assert!(!String::from(stored.unwrap()).contains("private"));
+        assert_eq!(
+            cas.fetch(&content.address()).unwrap(),
+            Some(content.content())
+        );
+    }
+
+    #[test]
+    fn encrypted_eav_round_trip() {
+        let entity =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("foo")))
+                .unwrap();
+        let attribute = Attribute::LinkTag("abc".to_string(), "favourite-color".to_string());
+        let value =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("blue")))
+                .unwrap();
+
+        EavTestSuite::test_round_trip(test_eav(test_cipher()), entity, attribute, value)
+    }
+
+    #[test]
+    fn encrypted_eav_range() {
+        EavTestSuite::test_range::<ExampleAddressableContent, Attribute, EavEncryptedStorage>(
+            test_eav(test_cipher()),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn deterministic_encryption_is_stable_per_key() {
+        let cipher = test_cipher();
+        let encrypted = cipher.encrypt_deterministic(b"secret").unwrap();
+        assert_eq!(cipher.encrypt_deterministic(b"secret").unwrap(), encrypted);
+        assert_ne!(cipher.encrypt_deterministic(b"other").unwrap(), encrypted);
+        assert_ne!(test_cipher().encrypt_deterministic(b"secret").unwrap(), encrypted);
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret".to_vec());
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        // Test case 2 of RFC 4231
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?").unwrap()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn encrypted_eav_filters_on_encrypted_entities() {
+        let inner = Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut eav = EavEncryptedStorage::new(inner.clone(), test_cipher());
+        let foo = ExampleAddressableContent::try_from_content(&RawString::from("foo").into())
+            .unwrap()
+            .address();
+        let bar = ExampleAddressableContent::try_from_content(&RawString::from("bar").into())
+            .unwrap()
+            .address();
+        let attribute = Attribute::LinkTag("friends".to_string(), "bob".to_string());
+        let foo_row = EntityAttributeValueIndex::new(&foo, &attribute, &bar).unwrap();
+        let bar_row = EntityAttributeValueIndex::new(&bar, &attribute, &foo).unwrap();
+        eav.add_eavi(&foo_row).unwrap();
+        eav.add_eavi(&bar_row).unwrap();
+
+        // Both rows share the same encrypted attribute, but none of them shows its entity
+        let stored = inner.read().unwrap().fetch_eavi(&all_rows()).unwrap();
+        assert_eq!(
+            stored
+                .iter()
+                .map(|eavi| eavi.attribute())
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            1
+        );
+        assert!(stored
+            .iter()
+            .all(|eavi| eavi.entity() != foo && eavi.entity() != bar));
+
+        let found = eav
+            .fetch_eavi(&EaviQuery::new(
+                EavFilter::single(foo.clone()),
+                EavFilter::single(attribute),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![foo_row]);
+    }
+
+    #[test]
+    fn encrypted_eav_hides_link_tags() {
+        let inner = Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut eav = EavEncryptedStorage::new(inner.clone(), test_cipher());
+        let entity = ExampleAddressableContent::try_from_content(&RawString::from("foo").into())
+            .unwrap();
+        let attribute = Attribute::LinkTag("friends".to_string(), "bob".to_string());
+        let eavi =
+            EntityAttributeValueIndex::new(&entity.address(), &attribute, &entity.address())
+                .unwrap();
+        eav.add_eavi(&eavi).unwrap();
+
+        let stored = inner.read().unwrap().fetch_eavi(&all_rows()).unwrap();
+        let stored_attribute = stored.iter().next().unwrap().attribute().to_string();
+        assert!(!stored_attribute.contains("friends") && !stored_attribute.contains("bob"));
+
+        let found = eav
+            .fetch_eavi(&EaviQuery::new(
+                None.into(),
+                EavFilter::single(attribute),
+                None.into(),
+                IndexFilter::LatestByAttribute,
+                None,
+            ))
+            .unwrap();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![eavi]);
+    }
+
+    #[test]
+    fn unencrypted_file_storage_gets_migrated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("storage");
+        let content =
+            ExampleAddressableContent::try_from_content(&RawString::from("private").into())
+                .unwrap();
+        let eavi = EntityAttributeValueIndex::new(
+            &content.address(),
+            &Attribute::LinkTag("friends".to_string(), "bob".to_string()),
+            &content.address(),
+        )
+        .unwrap();
+        {
+            let (cas, eav) = file_storage(&path).unwrap();
+            cas.write().unwrap().add(&content).unwrap();
+            eav.write().unwrap().add_eavi(&eavi).unwrap();
+        }
+
+        let cipher = test_cipher();
+        let (cas, eav) = open_encrypted_file_storage(&path, &cipher).unwrap();
+        assert!(path.join(KEY_CHECK_FILE).exists());
+        assert!(!sibling_path(&path, ".encrypting").exists());
+        assert!(!sibling_path(&path, ".plaintext").exists());
+        assert_eq!(
+            cas.read().unwrap().fetch(&content.address()).unwrap(),
+            Some(content.content())
+        );
+        assert_eq!(
+            eav.read()
+                .unwrap()
+                .fetch_eavi(&all_rows())
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![eavi]
+        );
+        for file in fs::read_dir(path.join("cas")).unwrap() {
+            let stored = fs::read_to_string(file.unwrap().path()).unwrap();
+            assert!(!stored.contains("private"));
+        }
+
+        // Reopening works with the same key only
+        assert!(open_encrypted_file_storage(&path, &cipher).is_ok());
+        assert!(open_encrypted_file_storage(&path, &test_cipher()).is_err());
+    }
+}
@@ -0,0 +1,923 @@
+//! Content addressable and EAV storage backed by an embedded LMDB database.
+//!
+//! Both stores of an instance live in the same LMDB environment (one directory,
+//! one memory map) in separate named databases. Every write happens inside a single
+//! LMDB read-write transaction, so an EAVI and its index entries, or a batch of
+//! contents added through `LmdbStorage::add_all()`, get persisted atomically.
+//! A write that fails because the memory map is full gets retried after doubling
+//! the map size.
+
+use holochain_core::dht::retention::EvictableStorage;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::HcResult,
+};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EntityAttributeValueStorage, IndexFilter},
+    error::{PersistenceError, PersistenceResult},
+};
+use lmdb::{
+    Cursor, Database, DatabaseFlags, Environment, Error as LmdbError, RoTransaction, RwTransaction,
+    Transaction, WriteFlags,
+};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fmt,
+    path::Path,
+    str,
+    sync::{Arc, RwLock},
+};
+use uuid::Uuid;
+
+/// Size of the memory map if none is configured.
+/// The map gets doubled whenever a write does not fit into it anymore.
+pub const DEFAULT_INITIAL_MMAP_BYTES: usize = 100 * 1024 * 1024;
+
+const CAS_DB: &str = "cas";
+const EAV_ROWS_DB: &str = "eav_rows";
+const EAV_ENTITY_DB: &str = "eav_entity";
+const EAV_ATTRIBUTE_DB: &str = "eav_attribute";
+const EAV_VALUE_DB: &str = "eav_value";
+const EAV_COUNTS_DB: &str = "eav_counts";
+const MAX_DBS: u32 = 6;
+
+/// Key of the number of EAV rows in the counts database. The number of distinct
+/// prefixes of each index database is stored under the name of that database.
+const ROWS_COUNT_KEY: &str = "rows";
+
+fn to_persistence_error(error: LmdbError) -> PersistenceError {
+    PersistenceError::ErrorGeneric(format!("LMDB storage error: {}", error))
+}
+
+fn is_map_full(error: &PersistenceError) -> bool {
+    match (error, to_persistence_error(LmdbError::MapFull)) {
+        (PersistenceError::ErrorGeneric(message), PersistenceError::ErrorGeneric(map_full)) => {
+            *message == map_full
+        }
+        _ => false,
+    }
+}
+
+fn to_serialization_error<E: fmt::Display>(error: E) -> PersistenceError {
+    PersistenceError::SerializationError(format!("LMDB storage error: {}", error))
+}
+
+/// Handle to an opened LMDB environment and the databases the
+/// CAS and EAV stores use. Clones share the same environment.
+#[derive(Clone)]
+pub struct LmdbEnvironment {
+    env: Arc<Environment>,
+    /// LMDB only allows resizing the memory map while no transaction is active, so every
+    /// transaction holds a read lock and growing the map takes the write lock.
+    resize_lock: Arc<RwLock<()>>,
+    cas: Database,
+    eav_rows: Database,
+    eav_by_entity: Database,
+    eav_by_attribute: Database,
+    eav_by_value: Database,
+    eav_counts: Database,
+}
+
+impl fmt::Debug for LmdbEnvironment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LmdbEnvironment")
+    }
+}
+
+impl LmdbEnvironment {
+    /// Opens (or creates) the LMDB environment in the given directory.
+    /// The directory has to exist already.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        initial_mmap_bytes: Option<usize>,
+    ) -> PersistenceResult<LmdbEnvironment> {
+        let env = Environment::new()
+            .set_max_dbs(MAX_DBS)
+            .set_map_size(initial_mmap_bytes.unwrap_or(DEFAULT_INITIAL_MMAP_BYTES))
+            .open(path.as_ref())
+            .map_err(to_persistence_error)?;
+        let create_db = |name, flags| {
+            env.create_db(Some(name), flags)
+                .map_err(to_persistence_error)
+        };
+        let cas = create_db(CAS_DB, DatabaseFlags::empty())?;
+        let eav_rows = create_db(EAV_ROWS_DB, DatabaseFlags::empty())?;
+        let eav_by_entity = create_db(EAV_ENTITY_DB, DatabaseFlags::empty())?;
+        let eav_by_attribute = create_db(EAV_ATTRIBUTE_DB, DatabaseFlags::empty())?;
+        let eav_by_value = create_db(EAV_VALUE_DB, DatabaseFlags::empty())?;
+        let eav_counts = create_db(EAV_COUNTS_DB, DatabaseFlags::empty())?;
+        Ok(LmdbEnvironment {
+            env: Arc::new(env),
+            resize_lock: Arc::new(RwLock::new(())),
+            cas,
+            eav_rows,
+            eav_by_entity,
+            eav_by_attribute,
+            eav_by_value,
+            eav_counts,
+        })
+    }
+
+    /// Addresses of all contents in the CAS database.
+    pub fn cas_addresses(&self) -> PersistenceResult<Vec<Address>> {
+        self.read(|txn| {
+            let mut cursor = txn.open_ro_cursor(self.cas).map_err(to_persistence_error)?;
+            let mut addresses = Vec::new();
+            for item in cursor.iter_start() {
+                let (key, _) = item.map_err(to_persistence_error)?;
+                let address = str::from_utf8(key).map_err(to_serialization_error)?;
+                addresses.push(Address::from(address.to_string()));
+            }
+            Ok(addresses)
+        })
+    }
+
+    /// Runs the given closure inside one read-only transaction.
+    pub fn read<F, T>(&self, f: F) -> PersistenceResult<T>
+    where
+        F: FnOnce(&RoTransaction) -> PersistenceResult<T>,
+    {
+        let _resize_guard = self.resize_lock.read()?;
+        let txn = self.env.begin_ro_txn().map_err(to_persistence_error)?;
+        f(&txn)
+    }
+
+    /// Runs the given closure inside one read-write transaction.
+    /// The transaction only gets committed if the closure returns `Ok`,
+    /// otherwise none of its writes become visible.
+    /// If the writes do not fit into the memory map, the map gets doubled and the
+    /// closure runs again in a new transaction.
+    pub fn write<F, T>(&self, f: F) -> PersistenceResult<T>
+    where
+        F: Fn(&mut RwTransaction) -> PersistenceResult<T>,
+    {
+        loop {
+            match self.try_write(&f) {
+                Err(ref err) if is_map_full(err) => self.grow_map()?,
+                result => return result,
+            }
+        }
+    }
+
+    fn try_write<F, T>(&self, f: &F) -> PersistenceResult<T>
+    where
+        F: Fn(&mut RwTransaction) -> PersistenceResult<T>,
+    {
+        let _resize_guard = self.resize_lock.read()?;
+        let mut txn = self.env.begin_rw_txn().map_err(to_persistence_error)?;
+        let result = f(&mut txn)?;
+        txn.commit().map_err(to_persistence_error)?;
+        Ok(result)
+    }
+
+    /// Doubles the size of the memory map.
+    /// Fails with the error of LMDB if the map can not grow any further.
+    fn grow_map(&self) -> PersistenceResult<()> {
+        let _resize_guard = self.resize_lock.write()?;
+        let map_size = self.env.info().map_err(to_persistence_error)?.map_size();
+        self.env
+            .set_map_size(map_size * 2)
+            .map_err(|err| {
+                PersistenceError::ErrorGeneric(format!(
+                    "LMDB storage error: could not grow the memory map beyond {} bytes: {}",
+                    map_size, err
+                ))
+            })
+    }
+}
+
+fn address_key(address: &Address) -> Vec<u8> {
+    String::from(address.clone()).into_bytes()
+}
+
+/// Big endian with flipped sign bit so that LMDB's byte-wise key order
+/// is the same as the numerical order of the EAVI index.
+fn index_key(index: i64) -> [u8; 8] {
+    ((index as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+/// Separates the entity, attribute or value from the row index in the keys of the
+/// EAV index databases. Addresses and attribute names never contain it.
+const PREFIX_SEPARATOR: u8 = 0;
+
+/// Key of an EAV index database entry: the entity, attribute or value, the separator and
+/// the row index. All rows of one prefix are adjacent and sorted by index, so a range
+/// cursor can seek to them, and skip them, without touching the other rows.
+fn prefixed_key(prefix: &[u8], row: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(prefix.len() + 1 + row.len());
+    key.extend_from_slice(prefix);
+    key.push(PREFIX_SEPARATOR);
+    key.extend_from_slice(row);
+    key
+}
+
+/// Splits a key of an EAV index database into its prefix and row index.
+fn split_prefixed_key(key: &[u8]) -> PersistenceResult<(&[u8], &[u8])> {
+    if key.len() < 9 || key[key.len() - 9] != PREFIX_SEPARATOR {
+        return Err(PersistenceError::SerializationError(String::from(
+            "LMDB storage error: malformed EAV index key",
+        )));
+    }
+    let (prefix, rest) = key.split_at(key.len() - 9);
+    Ok((prefix, &rest[1..]))
+}
+
+/// The smallest key that sorts after all keys with the given prefix.
+fn next_prefix_key(prefix: &[u8]) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.push(PREFIX_SEPARATOR + 1);
+    key
+}
+
+/// Deletes the given key, ignoring keys that do not exist.
+fn delete_key(txn: &mut RwTransaction, db: Database, key: &[u8]) -> PersistenceResult<()> {
+    match txn.del(db, &key, None) {
+        Ok(()) | Err(LmdbError::NotFound) => Ok(()),
+        Err(err) => Err(to_persistence_error(err)),
+    }
+}
+
+fn contains_key<T: Transaction>(txn: &T, db: Database, key: &[u8]) -> PersistenceResult<bool> {
+    match txn.get(db, &key) {
+        Ok(_) => Ok(true),
+        Err(LmdbError::NotFound) => Ok(false),
+        Err(err) => Err(to_persistence_error(err)),
+    }
+}
+
+/// Whether any key of the given index database starts with the given prefix.
+fn has_prefix<T: Transaction>(txn: &T, db: Database, prefix: &[u8]) -> PersistenceResult<bool> {
+    let mut cursor = txn.open_ro_cursor(db).map_err(to_persistence_error)?;
+    match cursor.iter_from(prefixed_key(prefix, &[])).next() {
+        Some(item) => {
+            let (key, _) = item.map_err(to_persistence_error)?;
+            Ok(split_prefixed_key(key)?.0 == prefix)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Reads a count of the counts database. Counts that were never written are 0.
+fn read_count<T: Transaction>(txn: &T, db: Database, name: &str) -> PersistenceResult<usize> {
+    match txn.get(db, &name) {
+        Ok(bytes) if bytes.len() == 8 => {
+            let mut count = [0; 8];
+            count.copy_from_slice(bytes);
+            Ok(u64::from_be_bytes(count) as usize)
+        }
+        Ok(_) => Err(PersistenceError::SerializationError(format!(
+            "LMDB storage error: malformed count {}",
+            name
+        ))),
+        Err(LmdbError::NotFound) => Ok(0),
+        Err(err) => Err(to_persistence_error(err)),
+    }
+}
+
+fn add_to_count(
+    txn: &mut RwTransaction,
+    db: Database,
+    name: &str,
+    delta: i64,
+) -> PersistenceResult<()> {
+    let count = (read_count(&*txn, db, name)? as i64 + delta).max(0) as u64;
+    txn.put(db, &name, &count.to_be_bytes(), WriteFlags::empty())
+        .map_err(to_persistence_error)
+}
+
+/// Persistent content addressable storage in an LMDB database.
+#[derive(Clone, Debug)]
+pub struct LmdbStorage {
+    env: LmdbEnvironment,
+    id: Uuid,
+}
+
+impl PartialEq for LmdbStorage {
+    fn eq(&self, other: &LmdbStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+impl LmdbStorage {
+    pub fn new(env: LmdbEnvironment) -> LmdbStorage {
+        LmdbStorage {
+            env,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    /// Adds all given contents in one transaction: either all of them get stored or none.
+    pub fn add_all(&mut self, contents: &[&dyn AddressableContent]) -> PersistenceResult<()> {
+        let db = self.env.cas;
+        self.env.write(|txn| {
+            for content in contents {
+                txn.put(
+                    db,
+                    &address_key(&content.address()),
+                    &String::from(content.content()),
+                    WriteFlags::empty(),
+                )
+                .map_err(to_persistence_error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ContentAddressableStorage for LmdbStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.add_all(&[content])
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.env
+            .read(|txn| contains_key(txn, self.env.cas, &address_key(address)))
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        self.env
+            .read(|txn| match txn.get(self.env.cas, &address_key(address)) {
+                Ok(bytes) => {
+                    let json = str::from_utf8(bytes).map_err(to_serialization_error)?;
+                    Ok(Some(JsonString::from_json(json)))
+                }
+                Err(LmdbError::NotFound) => Ok(None),
+                Err(err) => Err(to_persistence_error(err)),
+            })
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// Persistent EAV storage in LMDB.
+///
+/// Rows are stored by their index. Three index databases have one key per row,
+/// prefixed with the row's entity, attribute or value (see `prefixed_key`), and the
+/// counts database keeps the number of rows and of distinct prefixes per index database.
+/// `fetch_eavi()` uses the index databases in the order of their number of distinct
+/// prefixes. It seeks from one distinct prefix to the next, so each filter only gets
+/// checked once per distinct entity, attribute or value, and only reads the row indexes
+/// of matching prefixes within the query's index range. Once fewer candidate rows are left
+/// than the next index database has prefixes, the remaining filters get checked on the
+/// candidate rows instead, so filters that match everything (i.e. `None`) do not get
+/// walked unless that is the cheapest way to narrow down the rows.
+#[derive(Clone, Debug)]
+pub struct EavLmdbStorage {
+    env: LmdbEnvironment,
+    id: Uuid,
+}
+
+impl PartialEq for EavLmdbStorage {
+    fn eq(&self, other: &EavLmdbStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Check of a prefix of an index database against the query's filter for it.
+type PrefixCheck<'a> = Box<dyn Fn(&[u8]) -> PersistenceResult<bool> + 'a>;
+
+impl EavLmdbStorage {
+    pub fn new(env: LmdbEnvironment) -> EavLmdbStorage {
+        EavLmdbStorage {
+            env,
+            id: Uuid::new_v4(),
+        }
+    }
+
+    /// Returns the row keys within `range` of all prefixes of the given index database
+    /// that pass the given check and are contained in `candidates`, if given,
+    /// or None if every prefix passes it.
+    /// The cursor seeks from one prefix to the next, so the check runs once per distinct
+    /// prefix and the entries of non-matching prefixes never get read.
+    fn matching_rows<T: Transaction>(
+        txn: &T,
+        db: Database,
+        range: &(Vec<u8>, Vec<u8>),
+        check: &PrefixCheck,
+        candidates: Option<&BTreeSet<Vec<u8>>>,
+    ) -> PersistenceResult<Option<BTreeSet<Vec<u8>>>> {
+        let mut cursor = txn.open_ro_cursor(db).map_err(to_persistence_error)?;
+        let mut matching_prefixes = Vec::new();
+        let mut all_match = true;
+        let mut next = cursor.iter_start().next();
+        while let Some(item) = next {
+            let (key, _) = item.map_err(to_persistence_error)?;
+            let (prefix, _) = split_prefixed_key(key)?;
+            let prefix = prefix.to_vec();
+            if check(&prefix)? {
+                matching_prefixes.push(prefix.clone());
+            } else {
+                all_match = false;
+            }
+            next = cursor.iter_from(next_prefix_key(&prefix)).next();
+        }
+        if all_match {
+            return Ok(None);
+        }
+
+        let (start, end) = range;
+        let mut rows = BTreeSet::new();
+        for prefix in matching_prefixes {
+            for item in cursor.iter_from(prefixed_key(&prefix, start)) {
+                let (key, _) = item.map_err(to_persistence_error)?;
+                let (key_prefix, row) = split_prefixed_key(key)?;
+                if key_prefix != prefix.as_slice() || row > end.as_slice() {
+                    break;
+                }
+                if candidates.map(|rows| rows.contains(row)).unwrap_or(true) {
+                    rows.insert(row.to_vec());
+                }
+            }
+        }
+        Ok(Some(rows))
+    }
+
+    /// All row keys within `range`, read with a range cursor over the rows database.
+    fn all_rows<T: Transaction>(
+        txn: &T,
+        db: Database,
+        range: &(Vec<u8>, Vec<u8>),
+    ) -> PersistenceResult<BTreeSet<Vec<u8>>> {
+        let (start, end) = range;
+        let mut cursor = txn.open_ro_cursor(db).map_err(to_persistence_error)?;
+        let mut rows = BTreeSet::new();
+        for item in cursor.iter_from(start) {
+            let (row, _) = item.map_err(to_persistence_error)?;
+            if row > end.as_slice() {
+                break;
+            }
+            rows.insert(row.to_vec());
+        }
+        Ok(rows)
+    }
+
+    fn read_row<T: Transaction>(
+        txn: &T,
+        db: Database,
+        key: &[u8],
+    ) -> PersistenceResult<EntityAttributeValueIndex> {
+        let bytes = txn.get(db, &key).map_err(to_persistence_error)?;
+        let json = str::from_utf8(bytes).map_err(to_serialization_error)?;
+        EntityAttributeValueIndex::try_from_content(&JsonString::from_json(json))
+            .map_err(to_serialization_error)
+    }
+}
+
+/// Bounds of the row keys a query can match, taken from its index filter.
+fn index_range(query: &EaviQuery) -> (Vec<u8>, Vec<u8>) {
+    let (start, end) = match query.index() {
+        IndexFilter::Range(start, end) => (start.clone(), end.clone()),
+        _ => (None, None),
+    };
+    (
+        index_key(start.unwrap_or_else(i64::min_value)).to_vec(),
+        index_key(end.unwrap_or_else(i64::max_value)).to_vec(),
+    )
+}
+
+fn parse_address(bytes: &[u8]) -> PersistenceResult<Address> {
+    Ok(Address::from(
+        str::from_utf8(bytes).map_err(to_serialization_error)?,
+    ))
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavLmdbStorage {
+    fn add_eavi(
+        &mut self,
+        eav: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let env = self.env.clone();
+        self.env.write(|txn| {
+            let mut new_eav = eav.clone();
+            while contains_key(&*txn, env.eav_rows, &index_key(new_eav.index()))? {
+                let index = new_eav.index() + 1;
+                new_eav.set_index(index);
+            }
+            let key = index_key(new_eav.index());
+            txn.put(
+                env.eav_rows,
+                &key,
+                &String::from(new_eav.content()),
+                WriteFlags::empty(),
+            )
+            .map_err(to_persistence_error)?;
+            add_to_count(txn, env.eav_counts, ROWS_COUNT_KEY, 1)?;
+            let empty: &[u8] = &[];
+            for (db, name, prefix) in env.index_prefixes(&new_eav) {
+                if !has_prefix(&*txn, db, &prefix)? {
+                    add_to_count(txn, env.eav_counts, name, 1)?;
+                }
+                txn.put(db, &prefixed_key(&prefix, &key), &empty, WriteFlags::empty())
+                    .map_err(to_persistence_error)?;
+            }
+            Ok(Some(new_eav))
+        })
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        self.env.read(|txn| {
+            let range = index_range(query);
+            let counts = self.env.eav_counts;
+            let entity_check: PrefixCheck =
+                Box::new(|key| Ok(query.entity().check(parse_address(key)?)));
+            let attribute_check: PrefixCheck = Box::new(|key| {
+                let attribute = str::from_utf8(key).map_err(to_serialization_error)?;
+                let attribute = Attribute::try_from(attribute).map_err(|_| {
+                    PersistenceError::SerializationError(format!(
+                        "Could not parse stored attribute {}",
+                        attribute
+                    ))
+                })?;
+                Ok(query.attribute().check(attribute))
+            });
+            let value_check: PrefixCheck =
+                Box::new(|key| Ok(query.value().check(parse_address(key)?)));
+            let mut indexes = vec![
+                (
+                    read_count(txn, counts, EAV_ENTITY_DB)?,
+                    self.env.eav_by_entity,
+                    entity_check,
+                ),
+                (
+                    read_count(txn, counts, EAV_ATTRIBUTE_DB)?,
+                    self.env.eav_by_attribute,
+                    attribute_check,
+                ),
+                (
+                    read_count(txn, counts, EAV_VALUE_DB)?,
+                    self.env.eav_by_value,
+                    value_check,
+                ),
+            ];
+            indexes.sort_by_key(|(prefixes, _, _)| *prefixes);
+
+            let row_count = read_count(txn, counts, ROWS_COUNT_KEY)?;
+            let mut candidates: Option<BTreeSet<Vec<u8>>> = None;
+            for (prefixes, db, check) in indexes {
+                let candidate_count = candidates
+                    .as_ref()
+                    .map(|rows| rows.len())
+                    .unwrap_or(row_count);
+                if candidate_count <= prefixes {
+                    break;
+                }
+                if let Some(rows) =
+                    Self::matching_rows(txn, db, &range, &check, candidates.as_ref())?
+                {
+                    candidates = Some(rows);
+                }
+            }
+            let rows = match candidates {
+                Some(rows) => rows,
+                None => Self::all_rows(txn, self.env.eav_rows, &range)?,
+            };
+
+            let eavis = rows
+                .iter()
+                .map(|row| Self::read_row(txn, self.env.eav_rows, row))
+                .collect::<PersistenceResult<Vec<_>>>()?;
+            Ok(query.run(eavis.into_iter()))
+        })
+    }
+}
+
+impl LmdbEnvironment {
+    /// The index databases, each with the key its prefix count is stored under
+    /// and the prefix of the given EAVI in it.
+    fn index_prefixes(
+        &self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> Vec<(Database, &'static str, Vec<u8>)> {
+        vec![
+            (self.eav_by_entity, EAV_ENTITY_DB, address_key(&eavi.entity())),
+            (
+                self.eav_by_attribute,
+                EAV_ATTRIBUTE_DB,
+                eavi.attribute().to_string().into_bytes(),
+            ),
+            (self.eav_by_value, EAV_VALUE_DB, address_key(&eavi.value())),
+        ]
+    }
+
+    /// Deletes the given EAV row with its index entries and updates the counts.
+    fn delete_row(&self, txn: &mut RwTransaction, row: &[u8]) -> PersistenceResult<()> {
+        let eavi = EavLmdbStorage::read_row(&*txn, self.eav_rows, row)?;
+        delete_key(txn, self.eav_rows, row)?;
+        add_to_count(txn, self.eav_counts, ROWS_COUNT_KEY, -1)?;
+        for (db, name, prefix) in self.index_prefixes(&eavi) {
+            delete_key(txn, db, &prefixed_key(&prefix, row))?;
+            if !has_prefix(&*txn, db, &prefix)? {
+                add_to_count(txn, self.eav_counts, name, -1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All rows of the given prefix in an index database.
+fn rows_with_prefix<T: Transaction>(
+    txn: &T,
+    db: Database,
+    prefix: &[u8],
+) -> PersistenceResult<Vec<Vec<u8>>> {
+    let mut cursor = txn.open_ro_cursor(db).map_err(to_persistence_error)?;
+    let mut rows = Vec::new();
+    for item in cursor.iter_from(prefixed_key(prefix, &[])) {
+        let (key, _) = item.map_err(to_persistence_error)?;
+        let (key_prefix, row) = split_prefixed_key(key)?;
+        if key_prefix != prefix {
+            break;
+        }
+        rows.push(row.to_vec());
+    }
+    Ok(rows)
+}
+
+/// Evicted DHT data gets removed from the CAS and EAV databases in one transaction.
+/// That is every EAV row with an evicted address as its entity or value (e.g. the
+/// `AuthoredEntry` row of an evicted entry or links to it), including the index entries
+/// of all removed rows.
+impl EvictableStorage for LmdbEnvironment {
+    fn evict(&self, addresses: &[Address]) -> HcResult<()> {
+        self.write(|txn| {
+            for address in addresses {
+                let key = address_key(address);
+                delete_key(txn, self.cas, &key)?;
+
+                // A row can have the address as both entity and value
+                let mut rows = BTreeSet::new();
+                rows.extend(rows_with_prefix(&*txn, self.eav_by_entity, &key)?);
+                rows.extend(rows_with_prefix(&*txn, self.eav_by_value, &key)?);
+                for row in rows {
+                    self.delete_row(txn, &row)?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    extern crate tempfile;
+    use self::tempfile::{tempdir, TempDir};
+    use super::*;
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::{
+        cas::{
+            content::{ExampleAddressableContent, OtherExampleAddressableContent},
+            storage::{EavTestSuite, StorageTestSuite},
+        },
+        eav::IndexFilter,
+    };
+
+    pub fn test_lmdb_env() -> (LmdbEnvironment, TempDir) {
+        let dir = tempdir().expect("Could not create a tempdir for LMDB testing");
+        (LmdbEnvironment::new(dir.path(), None).unwrap(), dir)
+    }
+
+    #[test]
+    fn lmdb_content_round_trip_test() {
+        let (env, _dir) = test_lmdb_env();
+        let test_suite = StorageTestSuite::new(LmdbStorage::new(env));
+        test_suite.round_trip_test::<ExampleAddressableContent, OtherExampleAddressableContent>(
+            RawString::from("foo").into(),
+            RawString::from(true).into(),
+        );
+    }
+
+    #[test]
+    fn lmdb_add_all_is_visible_at_once() {
+        let (env, _dir) = test_lmdb_env();
+        let mut cas = LmdbStorage::new(env);
+        let foo = ExampleAddressableContent::try_from_content(&RawString::from("foo").into())
+            .unwrap();
+        let bar = ExampleAddressableContent::try_from_content(&RawString::from("bar").into())
+            .unwrap();
+        cas.add_all(&[&foo, &bar]).unwrap();
+        assert_eq!(cas.fetch(&foo.address()).unwrap(), Some(foo.content()));
+        assert_eq!(cas.fetch(&bar.address()).unwrap(), Some(bar.content()));
+        let addresses: BTreeSet<Address> = cas.env.cas_addresses().unwrap().into_iter().collect();
+        assert_eq!(addresses, vec![foo.address(), bar.address()].into_iter().collect());
+    }
+
+    #[test]
+    fn lmdb_evict_removes_content_and_metadata() {
+        let (env, _dir) = test_lmdb_env();
+        let mut cas = LmdbStorage::new(env.clone());
+        let mut eav = EavLmdbStorage::new(env.clone());
+        let foo = ExampleAddressableContent::try_from_content(&RawString::from("foo").into())
+            .unwrap();
+        let bar = ExampleAddressableContent::try_from_content(&RawString::from("bar").into())
+            .unwrap();
+        cas.add_all(&[&foo, &bar]).unwrap();
+        for (entity, value) in vec![(&foo, &bar), (&bar, &foo), (&bar, &bar)] {
+            eav.add_eavi(
+                &EntityAttributeValueIndex::new(
+                    &entity.address(),
+                    &Attribute::CrudStatus,
+                    &value.address(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        }
+
+        env.evict(&[foo.address()]).unwrap();
+
+        assert_eq!(cas.fetch(&foo.address()).unwrap(), None);
+        assert_eq!(cas.fetch(&bar.address()).unwrap(), Some(bar.content()));
+        let rows = eav
+            .fetch_eavi(&EaviQuery::new(
+                None.into(),
+                None.into(),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))
+            .unwrap();
+        // rows with foo as their entity or value are gone
+        assert_eq!(
+            rows.iter()
+                .map(|eavi| (eavi.entity(), eavi.value()))
+                .collect::<Vec<_>>(),
+            vec![(bar.address(), bar.address())]
+        );
+    }
+
+    #[test]
+    fn lmdb_grows_the_memory_map_when_it_is_full() {
+        let dir = tempdir().unwrap();
+        let initial_mmap_bytes = 64 * 1024;
+        let env = LmdbEnvironment::new(dir.path(), Some(initial_mmap_bytes)).unwrap();
+        let mut cas = LmdbStorage::new(env.clone());
+        let contents: Vec<ExampleAddressableContent> = (0..200)
+            .map(|i| {
+                let content = format!("{}{}", "x".repeat(1024), i);
+                ExampleAddressableContent::try_from_content(
+                    &RawString::from(content.as_str()).into(),
+                )
+                .unwrap()
+            })
+            .collect();
+        for content in contents.iter() {
+            cas.add(content).unwrap();
+        }
+        for content in contents.iter() {
+            assert_eq!(cas.fetch(&content.address()).unwrap(), Some(content.content()));
+        }
+        assert!(env.env.info().unwrap().map_size() > initial_mmap_bytes);
+    }
+
+    #[test]
+    fn lmdb_counts_rows_and_distinct_prefixes() {
+        let (env, _dir) = test_lmdb_env();
+        let mut eav = EavLmdbStorage::new(env.clone());
+        let foo = Address::from("foo");
+        let bar = Address::from("bar");
+        for (entity, value) in vec![(&foo, &bar), (&foo, &foo), (&bar, &foo)] {
+            eav.add_eavi(
+                &EntityAttributeValueIndex::new(entity, &Attribute::CrudStatus, value).unwrap(),
+            )
+            .unwrap();
+        }
+        let counts = || {
+            env.read(|txn| {
+                Ok(vec![ROWS_COUNT_KEY, EAV_ENTITY_DB, EAV_ATTRIBUTE_DB, EAV_VALUE_DB]
+                    .into_iter()
+                    .map(|name| read_count(txn, env.eav_counts, name).unwrap())
+                    .collect::<Vec<_>>())
+            })
+            .unwrap()
+        };
+        assert_eq!(counts(), vec![3, 2, 1, 2]);
+
+        env.evict(&[bar.clone()]).unwrap();
+        assert_eq!(counts(), vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn lmdb_eav_round_trip() {
+        let (env, _dir) = test_lmdb_env();
+        let entity =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("foo")))
+                .unwrap();
+        let attribute = Attribute::LinkTag("abc".to_string(), "favourite-color".to_string());
+        let value =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("blue")))
+                .unwrap();
+
+        EavTestSuite::test_round_trip(EavLmdbStorage::new(env), entity, attribute, value)
+    }
+
+    #[test]
+    fn lmdb_eav_one_to_many() {
+        let (env, _dir) = test_lmdb_env();
+        EavTestSuite::test_one_to_many::<ExampleAddressableContent, Attribute, EavLmdbStorage>(
+            EavLmdbStorage::new(env),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn lmdb_eav_many_to_one() {
+        let (env, _dir) = test_lmdb_env();
+        EavTestSuite::test_many_to_one::<ExampleAddressableContent, Attribute, EavLmdbStorage>(
+            EavLmdbStorage::new(env),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn lmdb_eav_range() {
+        let (env, _dir) = test_lmdb_env();
+        EavTestSuite::test_range::<ExampleAddressableContent, Attribute, EavLmdbStorage>(
+            EavLmdbStorage::new(env),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn lmdb_eav_prefixes() {
+        let (env, _dir) = test_lmdb_env();
+        EavTestSuite::test_multiple_attributes::<
+            ExampleAddressableContent,
+            Attribute,
+            EavLmdbStorage,
+        >(
+            EavLmdbStorage::new(env),
+            vec!["a_", "b_", "c_", "d_"]
+                .into_iter()
+                .map(|p| Attribute::LinkTag(p.to_string() + "one_to_many", "".into()))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn lmdb_fetch_only_reads_matching_prefixes_within_the_index_range() {
+        let (env, _dir) = test_lmdb_env();
+        let mut eav = EavLmdbStorage::new(env);
+        let foo = Address::from("foo");
+        let bar = Address::from("bar");
+        let mut added = Vec::new();
+        for index in 0..4 {
+            for entity in vec![&foo, &bar] {
+                let eavi = EntityAttributeValueIndex::new_with_index(
+                    entity,
+                    &Attribute::CrudStatus,
+                    &Address::from(format!("value-{}", index)),
+                    index,
+                )
+                .unwrap();
+                added.push(eav.add_eavi(&eavi).unwrap().unwrap());
+            }
+        }
+
+        let rows = eav
+            .fetch_eavi(&EaviQuery::new(
+                Some(foo.clone()).into(),
+                None.into(),
+                None.into(),
+                IndexFilter::Range(Some(1), Some(5)),
+                None,
+            ))
+            .unwrap();
+        let expected: BTreeSet<_> = added
+            .into_iter()
+            .filter(|eavi| eavi.entity() == foo && eavi.index() >= 1 && eavi.index() <= 5)
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn lmdb_prefixed_keys_split_into_prefix_and_row() {
+        let key = prefixed_key(b"foo", &index_key(3));
+        assert_eq!(
+            split_prefixed_key(&key).unwrap(),
+            (&b"foo"[..], &index_key(3)[..])
+        );
+        assert!(key < next_prefix_key(b"foo"));
+        assert!(prefixed_key(b"foo", &index_key(i64::max_value())) < next_prefix_key(b"foo"));
+        assert!(split_prefixed_key(b"foo").is_err());
+    }
+
+    #[test]
+    fn lmdb_index_keys_sort_numerically() {
+        assert!(index_key(-1) < index_key(0));
+        assert!(index_key(0) < index_key(1));
+        assert!(index_key(i64::min_value()) < index_key(i64::max_value()));
+    }
+}
@@ -0,0 +1,371 @@
+//! Storage accounting for instance quotas.
+//!
+//! `MeteredStorage`, `EavMeteredStorage` and `MeteredEviction` wrap the storages of an
+//! instance with a quota and record every content and EAV row that gets added or evicted
+//! in the instance's `StorageUsage` (see `holochain_core::quota`).
+//! Contents that are already stored do not get counted again, and neither does the
+//! instance's bookkeeping (usage record, storage version and state snapshots). EAV storage
+//! is append-only, so every row it stores counts.
+//! Storages that already hold data but have no usage record get scanned once with
+//! `scan_usage()` when they are opened.
+
+use holochain_core::{
+    dht::retention::EvictableStorage,
+    quota::{is_bookkeeping_address, is_bookkeeping_row, InstanceUsage, StorageKind, StorageUsage},
+};
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::HcResult,
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EntityAttributeValueStorage, IndexFilter},
+    error::PersistenceResult,
+};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+use uuid::Uuid;
+
+fn content_bytes(content: &Content) -> u64 {
+    String::from(content.clone()).len() as u64
+}
+
+fn eavi_bytes(eavi: &EntityAttributeValueIndex) -> u64 {
+    (String::from(eavi.entity()).len()
+        + eavi.attribute().to_string().len()
+        + String::from(eavi.value()).len()) as u64
+}
+
+/// The rows that count towards usage, i.e. all but bookkeeping.
+fn metered_rows<'a>(
+    rows: &'a BTreeSet<EntityAttributeValueIndex>,
+) -> impl Iterator<Item = &'a EntityAttributeValueIndex> {
+    rows.iter().filter(|row| !is_bookkeeping_row(row))
+}
+
+/// Lists the addresses of all contents of a CAS, for storages that can enumerate them.
+pub type AddressLister = Arc<dyn Fn() -> HcResult<Vec<Address>> + Send + Sync>;
+
+/// Addresses of all contents of a file CAS in the given directory.
+/// File CAS names its files after the addresses of their contents.
+pub fn file_cas_addresses(path: &Path) -> HcResult<Vec<Address>> {
+    let mut addresses = Vec::new();
+    for file in fs::read_dir(path)? {
+        if let Some(stem) = file?.path().file_stem() {
+            addresses.push(Address::from(stem.to_string_lossy().to_string()));
+        }
+    }
+    Ok(addresses)
+}
+
+/// Counts what the given storages already hold: the contents with the given addresses
+/// and all EAV rows, leaving out bookkeeping.
+/// Chain and DHT content can't be told apart afterwards, so all contents count as chain.
+pub fn scan_usage(
+    cas: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav: &Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    addresses: &[Address],
+) -> HcResult<InstanceUsage> {
+    let mut usage = InstanceUsage {
+        tracked: true,
+        ..Default::default()
+    };
+    let cas = cas.read()?;
+    for address in addresses
+        .iter()
+        .filter(|address| !is_bookkeeping_address(address))
+    {
+        if let Some(content) = cas.fetch(address)? {
+            usage.record(StorageKind::Chain, content_bytes(&content));
+        }
+    }
+    let rows = eav.read()?.fetch_eavi(&EaviQuery::new(
+        None.into(),
+        None.into(),
+        None.into(),
+        IndexFilter::Range(None, None),
+        None,
+    ))?;
+    for row in metered_rows(&rows) {
+        usage.record(StorageKind::Eav, eavi_bytes(row));
+    }
+    Ok(usage)
+}
+
+/// Content addressable storage that records the size of all new contents it passes on
+/// to the wrapped storage.
+#[derive(Clone, Debug)]
+pub struct MeteredStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    usage: Arc<StorageUsage>,
+    kind: StorageKind,
+    id: Uuid,
+}
+
+impl PartialEq for MeteredStorage {
+    fn eq(&self, other: &MeteredStorage) -> bool {
+        self.get_id() == other.get_id()
+    }
+}
+
+impl MeteredStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+        usage: Arc<StorageUsage>,
+        kind: StorageKind,
+    ) -> MeteredStorage {
+        // Metering does not change what is stored, so this is the wrapped storage to callers.
+        // Reading the id does not depend on state a panicking writer could have broken.
+        let id = inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_id();
+        MeteredStorage {
+            inner,
+            usage,
+            kind,
+            id,
+        }
+    }
+}
+
+impl ContentAddressableStorage for MeteredStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        let address = content.address();
+        let mut inner = self.inner.write()?;
+        let is_new = !is_bookkeeping_address(&address) && !inner.contains(&address)?;
+        inner.add(content)?;
+        if is_new {
+            self.usage.record(self.kind, content_bytes(&content.content()));
+        }
+        Ok(())
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read()?.contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        self.inner.read()?.fetch(address)
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// EAV storage that records the size of every row the wrapped storage reports as stored.
+#[derive(Clone, Debug)]
+pub struct EavMeteredStorage {
+    inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    usage: Arc<StorageUsage>,
+    id: Uuid,
+}
+
+impl PartialEq for EavMeteredStorage {
+    fn eq(&self, other: &EavMeteredStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+impl EavMeteredStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+        usage: Arc<StorageUsage>,
+    ) -> EavMeteredStorage {
+        EavMeteredStorage {
+            inner,
+            usage,
+            id: Uuid::new_v4(),
+        }
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EavMeteredStorage {
+    fn add_eavi(
+        &mut self,
+        eav: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let stored = self.inner.write()?.add_eavi(eav)?;
+        if let Some(ref row) = stored {
+            if !is_bookkeeping_row(row) {
+                self.usage.record(StorageKind::Eav, eavi_bytes(row));
+            }
+        }
+        Ok(stored)
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        self.inner.read()?.fetch_eavi(query)
+    }
+}
+
+/// Evicts through the wrapped `EvictableStorage` and releases the evicted contents
+/// and EAV rows from the instance's usage.
+#[derive(Debug)]
+pub struct MeteredEviction {
+    inner: Arc<dyn EvictableStorage>,
+    cas: Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    usage: Arc<StorageUsage>,
+}
+
+impl MeteredEviction {
+    pub fn new(
+        inner: Arc<dyn EvictableStorage>,
+        cas: Arc<RwLock<dyn ContentAddressableStorage>>,
+        eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+        usage: Arc<StorageUsage>,
+    ) -> MeteredEviction {
+        MeteredEviction {
+            inner,
+            cas,
+            eav,
+            usage,
+        }
+    }
+}
+
+impl EvictableStorage for MeteredEviction {
+    fn evict(&self, addresses: &[Address]) -> HcResult<()> {
+        let mut released = Vec::new();
+        // Evicting an address removes the rows that have it as their entity or value
+        let mut rows = BTreeSet::new();
+        for address in addresses {
+            if let Some(content) = self.cas.read()?.fetch(address)? {
+                released.push((StorageKind::Dht, content_bytes(&content)));
+            }
+            let eav = self.eav.read()?;
+            rows.extend(eav.fetch_eavi(&EaviQuery::new(
+                Some(address.clone()).into(),
+                None.into(),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))?);
+            rows.extend(eav.fetch_eavi(&EaviQuery::new(
+                None.into(),
+                None.into(),
+                Some(address.clone()).into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))?);
+        }
+        released.extend(metered_rows(&rows).map(|row| (StorageKind::Eav, eavi_bytes(row))));
+        self.inner.evict(addresses)?;
+        for (kind, bytes) in released {
+            self.usage.release(kind, bytes);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core::persister::migration::{StorageVersion, CURRENT_STORAGE_VERSION};
+    use holochain_core_types::entry::{test_entry, test_entry_b};
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+
+    fn version() -> StorageVersion {
+        StorageVersion {
+            version: CURRENT_STORAGE_VERSION,
+        }
+    }
+
+    #[test]
+    fn metered_storage_counts_new_content_once() {
+        let usage = Arc::new(StorageUsage::default());
+        let inner = Arc::new(RwLock::new(MemoryStorage::new()));
+        let mut chain =
+            MeteredStorage::new(inner.clone(), usage.clone(), StorageKind::Chain);
+        let mut dht = MeteredStorage::new(inner, usage.clone(), StorageKind::Dht);
+
+        chain.add(&test_entry()).unwrap();
+        chain.add(&test_entry()).unwrap();
+        dht.add(&test_entry()).unwrap();
+        dht.add(&test_entry_b()).unwrap();
+
+        let snapshot = usage.snapshot();
+        assert_eq!(snapshot.chain.count, 1);
+        assert_eq!(snapshot.chain.bytes, content_bytes(&test_entry().content()));
+        assert_eq!(snapshot.dht.count, 1);
+        assert_eq!(snapshot.dht.bytes, content_bytes(&test_entry_b().content()));
+        assert_eq!(
+            dht.fetch(&test_entry().address()).unwrap(),
+            Some(test_entry().content())
+        );
+        assert_eq!(chain, dht);
+    }
+
+    #[test]
+    fn metered_storage_does_not_count_bookkeeping() {
+        let usage = Arc::new(StorageUsage::default());
+        let mut cas = MeteredStorage::new(
+            Arc::new(RwLock::new(MemoryStorage::new())),
+            usage.clone(),
+            StorageKind::Chain,
+        );
+        cas.add(&usage.snapshot()).unwrap();
+        cas.add(&version()).unwrap();
+        assert_eq!(usage.snapshot(), InstanceUsage::default());
+    }
+
+    #[test]
+    fn metered_eav_storage_counts_rows() {
+        let usage = Arc::new(StorageUsage::default());
+        let mut eav = EavMeteredStorage::new(
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+            usage.clone(),
+        );
+        let eavi = EntityAttributeValueIndex::new(
+            &test_entry().address(),
+            &Attribute::CrudLink,
+            &test_entry_b().address(),
+        )
+        .unwrap();
+        eav.add_eavi(&eavi).unwrap();
+        // EAV storage is append-only, so adding the same row again stores another one
+        eav.add_eavi(&eavi).unwrap();
+
+        let snapshot = usage.snapshot();
+        assert_eq!(snapshot.eav.count, 2);
+        assert_eq!(snapshot.eav.bytes, 2 * eavi_bytes(&eavi));
+        assert_eq!(snapshot.total_entries(), 0);
+    }
+
+    #[test]
+    fn scan_usage_counts_stored_data() {
+        let cas: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        let eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        cas.write().unwrap().add(&test_entry()).unwrap();
+        cas.write().unwrap().add(&version()).unwrap();
+        let eavi = EntityAttributeValueIndex::new(
+            &test_entry().address(),
+            &Attribute::CrudLink,
+            &test_entry_b().address(),
+        )
+        .unwrap();
+        eav.write().unwrap().add_eavi(&eavi).unwrap();
+
+        let addresses = vec![test_entry().address(), version().address()];
+        let usage = scan_usage(&cas, &eav, &addresses).unwrap();
+        assert_eq!(usage.chain.count, 1);
+        assert_eq!(usage.chain.bytes, content_bytes(&test_entry().content()));
+        assert_eq!(usage.eav.count, 1);
+        assert_eq!(usage.eav.bytes, eavi_bytes(&eavi));
+        assert!(usage.tracked);
+    }
+}
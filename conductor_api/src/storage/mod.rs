@@ -0,0 +1,5 @@
+//! Storage implementations that ship with the conductor, in addition to the
+//! memory, file and pickle backends of the holochain_persistence crates.
+pub mod encrypted;
+pub mod lmdb;
+pub mod metered;
@@ -1,11 +1,15 @@
 use crate::{
-    agent::state::AgentState,
+    agent::{
+        bundle::{BundleId, CommitBundle, StagedEntry},
+        state::AgentState,
+    },
     network::{
         direct_message::DirectMessage,
         entry_aspect::EntryAspect,
         entry_with_header::EntryWithHeader,
         query::{GetLinksNetworkQuery, NetworkQueryResult},
         state::NetworkState,
+        warrant::Warrant,
     },
     nucleus::{
         actions::{call_zome_function::ExecuteZomeFnResponse, initialize::Initialization},
@@ -13,7 +17,9 @@ use crate::{
         validation::ValidationResult,
         ZomeFnCall,
     },
-    scheduled_jobs::pending_validations::{PendingValidation, ValidatingWorkflow},
+    scheduled_jobs::pending_validations::{
+        DeadLetterValidation, PendingValidation, ValidatingWorkflow,
+    },
     state::State,
 };
 
@@ -107,6 +113,23 @@ pub enum Action {
     /// Does not validate, assumes entry is valid.
     Commit((Entry, Option<Address>, Vec<Provenance>)),
 
+    /// Takes the given header off the top of the source chain again, if it still is on top.
+    RollBackCommit(ChainHeader),
+
+    /// Opens a commit bundle for the zome call with the given id.
+    StartBundle((BundleId, CommitBundle)),
+
+    /// Stages an entry in the open bundle of the given zome call instead of committing it.
+    StageEntry((BundleId, StagedEntry)),
+
+    /// Writes all entries staged in the given bundle to the source chain and closes it.
+    /// Either all entries get written or none.
+    /// Does not validate, assumes all entries are valid.
+    CommitBundle(BundleId),
+
+    /// Closes the given bundle and drops the entries staged in it.
+    DiscardBundle(BundleId),
+
     // -------------
     // DHT actions:
     // -------------
@@ -124,6 +147,14 @@ pub enum Action {
     //Removes a link for the local DHT
     RemoveLink(Entry),
 
+    /// Evicts a held entry from the local DHT shard according to the retention policy.
+    /// Removes it from the holding list and, if the storage supports it, from CAS and EAV.
+    EvictEntry(Address),
+
+    /// Adds a verified warrant to the local DHT shard and marks the warranted
+    /// author's entries as rejected.
+    HoldWarrant(Warrant),
+
     // ----------------
     // Network actions:
     // ----------------
@@ -139,6 +170,9 @@ pub enum Action {
     /// (only publish for AppEntryType, publish and publish_meta for links etc)
     Publish(Address),
 
+    /// Makes the network publish the given warrant at the address of the agent it warrants.
+    PublishWarrant(Warrant),
+
     ///Performs a Network Query Action based on the key and payload, used for links and Entries
     Query((QueryKey, QueryPayload)),
 
@@ -234,6 +268,20 @@ pub enum Action {
     /// Clear an entry from the pending validation list
     RemovePendingValidation((Address, ValidatingWorkflow)),
 
+    /// Retrying a pending validation failed again.
+    /// This counts the failed attempt in nucleus state's pending list.
+    CountPendingValidationAttempt((Address, ValidatingWorkflow)),
+
+    /// A pending validation ran out of attempts.
+    /// This moves it from the pending list to the dead letters.
+    AddDeadLetterValidation(DeadLetterValidation),
+
+    /// Moves a dead-lettered validation back to the pending list
+    RetryDeadLetterValidation((Address, ValidatingWorkflow)),
+
+    /// Drop a dead-lettered validation
+    RemoveDeadLetterValidation((Address, ValidatingWorkflow)),
+
     /// No-op, used to check if an action channel is still open
     Ping,
 }
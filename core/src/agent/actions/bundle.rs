@@ -0,0 +1,85 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::{
+        bundle::{BundleId, CommitBundle, StagedEntry},
+        state::ActionResponse,
+    },
+    context::Context,
+    instance::{dispatch_action, dispatch_action_and_wait},
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::cas::content::Address;
+use std::{pin::Pin, sync::Arc};
+
+/// Opens a commit bundle for the zome call with the given id.
+/// Returns once the bundle is in the agent state.
+pub fn start_bundle(id: BundleId, bundle: CommitBundle, context: &Arc<Context>) {
+    dispatch_action_and_wait(
+        context.clone(),
+        ActionWrapper::new(Action::StartBundle((id, bundle))),
+    );
+}
+
+/// Stages an entry in the open bundle of the zome call with the given id.
+/// Returns once the entry is in the agent state.
+pub fn stage_entry(id: BundleId, staged: StagedEntry, context: &Arc<Context>) {
+    dispatch_action_and_wait(
+        context.clone(),
+        ActionWrapper::new(Action::StageEntry((id, staged))),
+    );
+}
+
+/// Closes the bundle of the zome call with the given id and drops all entries staged in it.
+pub fn discard_bundle(id: BundleId, context: &Arc<Context>) {
+    dispatch_action_and_wait(
+        context.clone(),
+        ActionWrapper::new(Action::DiscardBundle(id)),
+    );
+}
+
+/// CommitBundle Action Creator
+/// Writes all entries staged in the given bundle to the source chain and closes the bundle.
+///
+/// Returns a future that resolves to the addresses of the committed entries.
+pub async fn commit_bundle(
+    id: BundleId,
+    context: &Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let action_wrapper = ActionWrapper::new(Action::CommitBundle(id));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    await!(CommitBundleFuture {
+        context: context.clone(),
+        action: action_wrapper,
+    })
+}
+
+/// CommitBundleFuture resolves to the result of the CommitBundle action
+/// Tracks the state for a response to its ActionWrapper
+pub struct CommitBundleFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+}
+
+impl Future for CommitBundleFuture {
+    type Output = Result<Vec<Address>, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("CommitBundleFuture") {
+            return Poll::Ready(Err(err));
+        }
+        cx.waker().clone().wake();
+        match self
+            .context
+            .state()
+            .unwrap()
+            .agent()
+            .actions()
+            .get(&self.action)
+        {
+            Some(ActionResponse::CommitBundle(result)) => Poll::Ready(result.clone()),
+            Some(_) => unreachable!(),
+            None => Poll::Pending,
+        }
+    }
+}
@@ -2,10 +2,12 @@ use crate::{
     action::{Action, ActionWrapper},
     agent::state::ActionResponse,
     context::Context,
-    instance::dispatch_action,
+    instance::{dispatch_action, dispatch_action_and_wait},
 };
 use futures::{future::Future, task::Poll};
-use holochain_core_types::{entry::Entry, error::HolochainError};
+use holochain_core_types::{
+    chain_header::ChainHeader, entry::Entry, error::HolochainError, signature::Provenance,
+};
 use holochain_persistence_api::cas::content::Address;
 use std::{pin::Pin, sync::Arc};
 
@@ -18,11 +20,27 @@ pub async fn commit_entry(
     entry: Entry,
     maybe_link_update_delete: Option<Address>,
     context: &Arc<Context>,
+) -> Result<Address, HolochainError> {
+    await!(commit_entry_with_provenances(
+        entry,
+        maybe_link_update_delete,
+        Vec::new(),
+        context
+    ))
+}
+
+/// Like `commit_entry()`, but the header of the entry also carries the given provenances,
+/// in front of the one of the agent.
+pub async fn commit_entry_with_provenances(
+    entry: Entry,
+    maybe_link_update_delete: Option<Address>,
+    provenances: Vec<Provenance>,
+    context: &Arc<Context>,
 ) -> Result<Address, HolochainError> {
     let action_wrapper = ActionWrapper::new(Action::Commit((
         entry.clone(),
         maybe_link_update_delete,
-        vec![],
+        provenances,
     )));
     dispatch_action(context.action_channel(), action_wrapper.clone());
     await!(CommitFuture {
@@ -31,6 +49,16 @@ pub async fn commit_entry(
     })
 }
 
+/// Takes the given header off the top of the source chain again.
+/// Returns once the action got reduced, which leaves the chain as it was if another header
+/// got committed on top of the given one already.
+pub fn roll_back_commit(chain_header: ChainHeader, context: &Arc<Context>) {
+    dispatch_action_and_wait(
+        context.clone(),
+        ActionWrapper::new(Action::RollBackCommit(chain_header)),
+    );
+}
+
 /// CommitFuture resolves to ActionResponse
 /// Tracks the state for a response to its ActionWrapper
 pub struct CommitFuture {
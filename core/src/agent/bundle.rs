@@ -0,0 +1,97 @@
+//! Commit bundles, i.e. entries that get committed to the source chain all together or not at all.
+//!
+//! A zome function opens a bundle with `hdk::start_bundle()`. Until it closes the bundle
+//! with `hdk::close_bundle()`, everything it commits or links gets staged in the bundle
+//! in the agent state instead of being written to the chain. Closing with `Commit` validates
+//! all staged entries and only if all of them are valid, writes them to the chain in one
+//! reduction and publishes them (see `workflows::author_bundle`). Closing with `Discard`,
+//! closing after the bundle's timeout or returning from the zome function without closing
+//! drops the staged entries.
+
+use holochain_core_types::{entry::Entry, signature::Provenance};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::time::{Duration, Instant};
+
+/// Bundles are opened by zome calls and identified by the id of the call.
+pub type BundleId = snowflake::ProcessUniqueId;
+
+/// An entry waiting in a bundle, with everything needed to commit it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StagedEntry {
+    pub entry: Entry,
+    pub maybe_link_update_delete: Option<Address>,
+    pub provenances: Vec<Provenance>,
+}
+
+/// The entries staged by a zome call since it started a bundle.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CommitBundle {
+    user_param: JsonString,
+    #[serde(skip)]
+    deadline: Option<Instant>,
+    entries: Vec<StagedEntry>,
+}
+
+impl CommitBundle {
+    /// Creates an empty bundle that can't be committed anymore after `timeout`.
+    pub fn new(timeout: Option<Duration>, user_param: JsonString) -> Self {
+        CommitBundle {
+            user_param,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn user_param(&self) -> &JsonString {
+        &self.user_param
+    }
+
+    pub fn entries(&self) -> &Vec<StagedEntry> {
+        &self.entries
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false)
+    }
+
+    pub fn stage(&mut self, staged: StagedEntry) {
+        self.entries.push(staged);
+    }
+
+    /// Returns the staged entry with the given address, if any.
+    pub fn get(&self, address: &Address) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .map(|staged| &staged.entry)
+            .find(|entry| entry.address() == *address)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::entry::{test_entry, test_entry_b};
+
+    #[test]
+    fn bundle_finds_staged_entries() {
+        let mut bundle = CommitBundle::new(None, JsonString::null());
+        bundle.stage(StagedEntry {
+            entry: test_entry(),
+            maybe_link_update_delete: None,
+            provenances: vec![],
+        });
+        assert_eq!(bundle.entries().len(), 1);
+        assert_eq!(bundle.get(&test_entry().address()), Some(&test_entry()));
+        assert_eq!(bundle.get(&test_entry_b().address()), None);
+        assert!(!bundle.is_expired());
+    }
+
+    #[test]
+    fn bundle_expires_after_timeout() {
+        let bundle = CommitBundle::new(Some(Duration::from_millis(0)), JsonString::null());
+        assert!(bundle.is_expired());
+    }
+}
@@ -0,0 +1,504 @@
+//! A portable, versioned archive of a source chain.
+//!
+//! The archive lists every chain header together with its entry in chain order, oldest first.
+//! Provenances travel inside the headers. Before an archive gets imported it is verified as a
+//! whole: every header has to link to its predecessor, every entry has to hash to the address
+//! its header points to and every provenance has to carry a valid signature of that address.
+//! Headers are signed by the chain's author, and after an agent entry that rotates the
+//! author's key, by the new key.
+
+use crate::{
+    agent::{chain_store::ChainStore, state::AgentStateSnapshot},
+    context::Context,
+    dht::dht_store::DhtStoreSnapshot,
+    nucleus::state::NucleusStateSnapshot,
+    state::{State, StateWrapper},
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::{entry_type::EntryType, Entry},
+    error::{HcResult, HolochainError},
+};
+use holochain_dpki::utils::Verify;
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::{
+    content::{Address, AddressableContent},
+    storage::ContentAddressableStorage,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Version of the archive format written by `ChainArchive::export`.
+pub const CHAIN_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct ChainArchiveItem {
+    pub header: ChainHeader,
+    pub entry: Entry,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct ChainArchive {
+    pub version: u32,
+    pub items: Vec<ChainArchiveItem>,
+}
+
+impl ChainArchive {
+    /// Exports the chain that ends in the given top header.
+    /// Fails if a header or entry of the chain is missing from the chain's storage.
+    pub fn export(
+        chain_store: &ChainStore,
+        top_chain_header: &Option<ChainHeader>,
+    ) -> HcResult<ChainArchive> {
+        let storage_lock = chain_store.content_storage();
+        let storage = storage_lock.read()?;
+        let mut items = Vec::new();
+        let mut current = top_chain_header.clone();
+        while let Some(header) = current {
+            let entry: Entry = fetch(&*storage, header.entry_address())?;
+            current = match header.link() {
+                Some(previous) => Some(fetch(&*storage, &previous)?),
+                None => None,
+            };
+            items.push(ChainArchiveItem { header, entry });
+        }
+        items.reverse();
+        Ok(ChainArchive {
+            version: CHAIN_ARCHIVE_VERSION,
+            items,
+        })
+    }
+
+    pub fn top_chain_header(&self) -> Option<&ChainHeader> {
+        self.items.last().map(|item| &item.header)
+    }
+
+    /// The agent that started the chain, i.e. the last provenance of its first header.
+    pub fn author(&self) -> Option<Address> {
+        self.items
+            .first()
+            .and_then(|item| item.header.provenances().last())
+            .map(|provenance| provenance.source())
+    }
+
+    /// The agent key the chain ends with, which is the author's key after all key
+    /// rotations on the chain.
+    pub fn latest_author(&self) -> Option<Address> {
+        self.items
+            .iter()
+            .fold(self.author(), |author, item| rotated_agent(item).or(author))
+    }
+
+    /// Checks that the archive is of a known version and holds one unbroken chain of
+    /// correctly signed headers of a single author. Each header has to be signed by the
+    /// author's key that was current when it got committed: an agent entry that updates
+    /// the agent entry in effect rotates the key, so it has to carry a signature of the
+    /// new key and all headers after it have to be signed by the new key.
+    pub fn verify(&self) -> HcResult<()> {
+        if self.version != CHAIN_ARCHIVE_VERSION {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Unsupported chain archive version {} (expected {})",
+                self.version, CHAIN_ARCHIVE_VERSION
+            )));
+        }
+        let mut signer = self.author();
+        let mut agent_entry: Option<Address> = None;
+        let mut previous: Option<Address> = None;
+        let mut previous_of_type: HashMap<EntryType, Address> = HashMap::new();
+        for (position, item) in self.items.iter().enumerate() {
+            let header = &item.header;
+            if header.link() != previous {
+                return Err(invalid_item(position, "header does not link to its predecessor"));
+            }
+            if header.link_same_type() != previous_of_type.get(header.entry_type()).cloned() {
+                return Err(invalid_item(
+                    position,
+                    "header does not link to the previous header of its type",
+                ));
+            }
+            if item.entry.address() != *header.entry_address()
+                || item.entry.entry_type() != *header.entry_type()
+            {
+                return Err(invalid_item(position, "entry does not match its header"));
+            }
+            if header.provenances().is_empty() {
+                return Err(invalid_item(position, "header has no provenance"));
+            }
+            for provenance in header.provenances() {
+                if !provenance
+                    .verify(header.entry_address().to_string())
+                    .unwrap_or(false)
+                {
+                    return Err(invalid_item(
+                        position,
+                        &format!("signature of {} does not verify", provenance.source()),
+                    ));
+                }
+            }
+            if header.provenances().last().map(|p| p.source()) != signer {
+                return Err(invalid_item(position, "header is signed by a different agent"));
+            }
+            if let Some(new_agent) = rotated_agent(item) {
+                if agent_entry.is_some() && header.link_update_delete() != agent_entry {
+                    return Err(invalid_item(
+                        position,
+                        "agent update does not replace the agent entry in effect",
+                    ));
+                }
+                if !header.provenances().iter().any(|p| p.source() == new_agent) {
+                    return Err(invalid_item(position, "agent update is not signed by the new key"));
+                }
+                signer = Some(new_agent);
+            }
+            if let Entry::AgentId(_) = item.entry {
+                agent_entry = Some(item.entry.address());
+            }
+            previous_of_type.insert(header.entry_type().clone(), header.address());
+            previous = Some(header.address());
+        }
+        Ok(())
+    }
+
+    /// Verifies the archive, checks that it continues the chain that currently ends in
+    /// `top_chain_header` and writes all of its headers and entries into the chain store.
+    /// Returns the top header of the imported chain.
+    ///
+    /// A chain that only holds the genesis entries (DNA and agent) may be replaced by an
+    /// archive that starts with the same entries, so a freshly created instance can take
+    /// over a chain. Any other chain that is not contained in the archive gets refused
+    /// since importing would fork it.
+    pub fn import_into(
+        &self,
+        chain_store: &ChainStore,
+        top_chain_header: &Option<ChainHeader>,
+    ) -> HcResult<Option<ChainHeader>> {
+        self.verify()?;
+        self.check_continues(chain_store, top_chain_header)?;
+
+        let storage_lock = chain_store.content_storage();
+        let mut storage = storage_lock.write()?;
+        for item in self.items.iter() {
+            storage.add(&item.entry)?;
+            storage.add(&item.header)?;
+        }
+        Ok(self.top_chain_header().cloned())
+    }
+
+    fn check_continues(
+        &self,
+        chain_store: &ChainStore,
+        top_chain_header: &Option<ChainHeader>,
+    ) -> HcResult<()> {
+        let top = match top_chain_header {
+            Some(top) => top,
+            None => return Ok(()),
+        };
+        if self.items.iter().any(|item| item.header == *top) {
+            return Ok(());
+        }
+        let archived_entries: Vec<&Address> = self
+            .items
+            .iter()
+            .map(|item| item.header.entry_address())
+            .collect();
+        let replaceable_genesis = chain_store.iter(top_chain_header).all(|header| {
+            (*header.entry_type() == EntryType::Dna || *header.entry_type() == EntryType::AgentId)
+                && archived_entries.contains(&header.entry_address())
+        });
+        if replaceable_genesis {
+            Ok(())
+        } else {
+            Err(HolochainError::ErrorGeneric(format!(
+                "Chain archive does not contain the current top header {}, importing it would fork the chain",
+                top.address()
+            )))
+        }
+    }
+}
+
+/// Imports the archive into the source chain of the given context's agent and saves the
+/// resulting state through the context's persister.
+/// Returns the new state, which still has to be installed into a (stopped) instance.
+pub fn import_chain(context: Arc<Context>, archive: &ChainArchive) -> HcResult<State> {
+    if let Some(author) = archive.latest_author() {
+        if author != context.agent_id().address() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Chain archive ends with agent {} and cannot be imported for agent {}",
+                author,
+                context.agent_id().address()
+            )));
+        }
+    }
+
+    let (agent_state, nucleus_snapshot, dht_store_snapshot) = {
+        let state = context
+            .state()
+            .ok_or_else(|| HolochainError::new("Context has no state to import a chain into"))?;
+        (
+            state.agent(),
+            NucleusStateSnapshot::from(&*state),
+            DhtStoreSnapshot::from(&*state),
+        )
+    };
+    let top_chain_header =
+        archive.import_into(&agent_state.chain_store(), &agent_state.top_chain_header())?;
+
+    let state = State::try_from_snapshots(
+        context.clone(),
+        AgentStateSnapshot::new(top_chain_header),
+        nucleus_snapshot,
+        dht_store_snapshot,
+    )?;
+    context
+        .persister
+        .lock()
+        .map_err(|_| HolochainError::new("Could not get lock on persister"))?
+        .save(&StateWrapper::from(state.clone()))?;
+    Ok(state)
+}
+
+/// The new agent if the item is an agent entry that updates the previous one,
+/// i.e. rotates the agent's key.
+fn rotated_agent(item: &ChainArchiveItem) -> Option<Address> {
+    match (&item.entry, item.header.link_update_delete()) {
+        (Entry::AgentId(agent), Some(_)) => Some(agent.address()),
+        _ => None,
+    }
+}
+
+fn fetch<T: AddressableContent>(
+    storage: &dyn ContentAddressableStorage,
+    address: &Address,
+) -> HcResult<T> {
+    let content = storage.fetch(address)?.ok_or_else(|| {
+        HolochainError::ErrorGeneric(format!(
+            "{} is referenced by the chain but missing from the chain storage",
+            address
+        ))
+    })?;
+    Ok(T::try_from_content(&content)?)
+}
+
+fn invalid_item(position: usize, reason: &str) -> HolochainError {
+    HolochainError::ErrorGeneric(format!("Invalid chain archive, item {}: {}", position, reason))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::agent::chain_store::tests::test_chain_store;
+    use holochain_core_types::{
+        agent::AgentId,
+        entry::{test_entry, test_entry_b},
+        signature::{Provenance, Signature},
+        time::{test_iso_8601, Iso8601},
+    };
+    use std::convert::TryFrom;
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    fn signed_header(agent: &AgentId, entry: &Entry, previous: &[&ChainHeader]) -> ChainHeader {
+        let signature = Signature::from(mock_signer(entry.address().to_string(), agent));
+        ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &[Provenance::new(agent.address(), signature)],
+            &previous.last().map(|header| header.address()),
+            &previous
+                .iter()
+                .rev()
+                .find(|header| *header.entry_type() == entry.entry_type())
+                .map(|header| header.address()),
+            &None,
+            &test_iso_8601(),
+        )
+    }
+
+    /// A chain of the agent's own entry followed by two app entries, committed to the store.
+    fn test_chain(chain_store: &ChainStore, agent: &AgentId) -> Vec<ChainHeader> {
+        let entries = vec![Entry::AgentId(agent.clone()), test_entry(), test_entry_b()];
+        let mut headers: Vec<ChainHeader> = Vec::new();
+        for entry in entries.iter() {
+            let header = {
+                let previous: Vec<&ChainHeader> = headers.iter().collect();
+                signed_header(agent, entry, &previous)
+            };
+            let storage = chain_store.content_storage();
+            let mut storage = storage.write().unwrap();
+            storage.add(entry).unwrap();
+            storage.add(&header).unwrap();
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let agent = registered_test_agent("archive-alice");
+        let source = test_chain_store();
+        let headers = test_chain(&source, &agent);
+        let top = headers.last().cloned();
+
+        let archive = ChainArchive::export(&source, &top).unwrap();
+        assert_eq!(archive.items.len(), 3);
+        assert_eq!(archive.items[0].entry, Entry::AgentId(agent.clone()));
+        assert_eq!(archive.top_chain_header(), top.as_ref());
+        assert_eq!(archive.author(), Some(agent.address()));
+        archive.verify().unwrap();
+
+        let archive = ChainArchive::try_from(JsonString::from(archive.clone())).unwrap();
+        let target = test_chain_store();
+        assert_eq!(archive.import_into(&target, &None).unwrap(), top);
+        assert_eq!(
+            target.iter(&top).collect::<Vec<_>>(),
+            source.iter(&top).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn verify_refuses_tampered_archives() {
+        let agent = registered_test_agent("archive-bob");
+        let chain_store = test_chain_store();
+        let headers = test_chain(&chain_store, &agent);
+        let archive = ChainArchive::export(&chain_store, &headers.last().cloned()).unwrap();
+
+        let mut swapped_entry = archive.clone();
+        swapped_entry.items[2].entry = test_entry();
+        assert!(swapped_entry.verify().is_err());
+
+        let mut missing_item = archive.clone();
+        missing_item.items.remove(1);
+        assert!(missing_item.verify().is_err());
+
+        let mut forged = archive.clone();
+        let header = &forged.items[2].header;
+        forged.items[2].header = ChainHeader::new(
+            header.entry_type(),
+            header.entry_address(),
+            &[Provenance::new(agent.address(), Signature::from("forged"))],
+            &header.link(),
+            &header.link_same_type(),
+            &None,
+            &test_iso_8601(),
+        );
+        assert!(forged.verify().is_err());
+
+        let mut future = archive.clone();
+        future.version = CHAIN_ARCHIVE_VERSION + 1;
+        assert!(future.verify().is_err());
+    }
+
+    #[test]
+    fn import_refuses_to_fork_an_existing_chain() {
+        let agent = registered_test_agent("archive-carol");
+        let source = test_chain_store();
+        let headers = test_chain(&source, &agent);
+        let archive = ChainArchive::export(&source, &headers.last().cloned()).unwrap();
+
+        // the archive continues a chain that ends in one of its headers
+        let partial = test_chain_store();
+        test_chain(&partial, &agent);
+        assert!(archive
+            .import_into(&partial, &Some(headers[1].clone()))
+            .is_ok());
+
+        // a chain with a diverging app entry would get forked
+        let diverged = test_chain_store();
+        let agent_entry = Entry::AgentId(agent.clone());
+        let genesis_header = ChainHeader::new(
+            &agent_entry.entry_type(),
+            &agent_entry.address(),
+            headers[0].provenances(),
+            &None,
+            &None,
+            &None,
+            &Iso8601::from(0u64),
+        );
+        let other_header = signed_header(&agent, &test_entry_b(), &[&genesis_header]);
+        {
+            let storage = diverged.content_storage();
+            let mut storage = storage.write().unwrap();
+            storage.add(&genesis_header).unwrap();
+            storage.add(&other_header).unwrap();
+        }
+        assert!(archive
+            .import_into(&diverged, &Some(other_header))
+            .is_err());
+
+        // a genesis-only chain of the same agent can be replaced
+        assert!(archive
+            .import_into(&diverged, &Some(genesis_header))
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_follows_key_rotations() {
+        let old_agent = registered_test_agent("archive-dave");
+        let new_agent = registered_test_agent("archive-dave-rotated");
+        let chain_store = test_chain_store();
+        let mut headers = test_chain(&chain_store, &old_agent);
+
+        let rotation = Entry::AgentId(new_agent.clone());
+        let signature_of = |agent: &AgentId| {
+            Provenance::new(
+                agent.address(),
+                Signature::from(mock_signer(rotation.address().to_string(), agent)),
+            )
+        };
+        let rotation_header = ChainHeader::new(
+            &rotation.entry_type(),
+            &rotation.address(),
+            &[signature_of(&new_agent), signature_of(&old_agent)],
+            &headers.last().map(|header| header.address()),
+            &Some(headers[0].address()),
+            &Some(headers[0].entry_address().clone()),
+            &test_iso_8601(),
+        );
+        headers.push(rotation_header.clone());
+        let after_rotation = {
+            let previous: Vec<&ChainHeader> = headers.iter().collect();
+            signed_header(&new_agent, &test_entry(), &previous)
+        };
+        {
+            let storage = chain_store.content_storage();
+            let mut storage = storage.write().unwrap();
+            storage.add(&rotation).unwrap();
+            storage.add(&rotation_header).unwrap();
+            storage.add(&after_rotation).unwrap();
+        }
+
+        let archive = ChainArchive::export(&chain_store, &Some(after_rotation.clone())).unwrap();
+        archive.verify().unwrap();
+        assert_eq!(archive.author(), Some(old_agent.address()));
+        assert_eq!(archive.latest_author(), Some(new_agent.address()));
+
+        // headers after the rotation have to be signed with the new key
+        let mut old_key_after_rotation = archive.clone();
+        let previous: Vec<&ChainHeader> = headers.iter().collect();
+        old_key_after_rotation.items[4].header =
+            signed_header(&old_agent, &test_entry(), &previous);
+        assert!(old_key_after_rotation
+            .verify()
+            .unwrap_err()
+            .to_string()
+            .contains("signed by a different agent"));
+
+        // and the rotation itself has to be signed with the new key
+        let mut unsigned_rotation = archive.clone();
+        unsigned_rotation.items[3].header = ChainHeader::new(
+            &rotation.entry_type(),
+            &rotation.address(),
+            &[signature_of(&old_agent)],
+            &rotation_header.link(),
+            &rotation_header.link_same_type(),
+            &rotation_header.link_update_delete(),
+            &test_iso_8601(),
+        );
+        assert!(unsigned_rotation
+            .verify()
+            .unwrap_err()
+            .to_string()
+            .contains("not signed by the new key"));
+    }
+}
@@ -0,0 +1,238 @@
+//! A persistent index over the local source chain, kept in the instance's EAV storage.
+//!
+//! Every chain header gets exactly one EAVI row:
+//!
+//! * entity: the address of the header
+//! * attribute: `Attribute::ChainIndex(root, entry_type)`, where `root` is the address of the
+//!   first header of the chain the header belongs to
+//! * value: the address of the header's entry
+//! * index: `timestamp_secs * SEQUENCE_SPAN + sequence`, where `sequence` is the position of the
+//!   header in its chain
+//!
+//! This lets queries select headers by entry type (through the attribute) and by time range
+//! (through an index range) without walking the chain, and order them by their position in the
+//! chain. EAV stores move colliding indexes forward, so a stored sequence number may be larger
+//! than the header's actual position. Headers are always indexed oldest first though, so the
+//! order of the stored sequence numbers matches the chain order.
+use chrono::{DateTime, FixedOffset};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+    time::Iso8601,
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EavFilter, EntityAttributeValueStorage, IndexFilter},
+};
+use holochain_wasm_utils::api_serialization::QueryOrdering;
+use std::sync::{Arc, RwLock};
+
+/// Number of sequence numbers available within one second of header timestamps.
+pub const SEQUENCE_SPAN: i64 = 1_000_000_000;
+
+/// A header as it is recorded in the chain index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedHeader {
+    pub header_address: Address,
+    pub root: String,
+    pub entry_type: String,
+    pub sequence: i64,
+}
+
+impl IndexedHeader {
+    fn from_eavi(eavi: &EntityAttributeValueIndex) -> Option<IndexedHeader> {
+        match eavi.attribute() {
+            Attribute::ChainIndex(root, entry_type) => Some(IndexedHeader {
+                header_address: eavi.entity(),
+                root,
+                entry_type,
+                sequence: eavi.index() % SEQUENCE_SPAN,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn timestamp_secs(timestamp: &Iso8601) -> i64 {
+    DateTime::<FixedOffset>::from(timestamp).timestamp().max(0)
+}
+
+/// Whether the given header timestamp lies within `since` and `until`, using the same whole
+/// second granularity as the index.
+pub fn within_time_range(
+    timestamp: &Iso8601,
+    since: Option<&Iso8601>,
+    until: Option<&Iso8601>,
+) -> bool {
+    let secs = timestamp_secs(timestamp);
+    since.map(|since| timestamp_secs(since) <= secs).unwrap_or(true)
+        && until.map(|until| secs <= timestamp_secs(until)).unwrap_or(true)
+}
+
+#[derive(Clone, Debug)]
+pub struct ChainIndex {
+    content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+}
+
+impl ChainIndex {
+    pub fn new(
+        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+        eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    ) -> Self {
+        ChainIndex {
+            content_storage,
+            eav_storage,
+        }
+    }
+
+    /// Returns the index row of the header with the given address, if it has been indexed.
+    pub fn get(&self, header_address: &Address) -> HcResult<Option<IndexedHeader>> {
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            Some(header_address.clone()).into(),
+            EavFilter::predicate(|attribute: Attribute| match attribute {
+                Attribute::ChainIndex(_, _) => true,
+                _ => false,
+            }),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        Ok(rows.iter().filter_map(IndexedHeader::from_eavi).next())
+    }
+
+    /// Indexes the given header.
+    /// Predecessors that are not indexed yet, e.g. because the chain was written before the
+    /// index existed or got imported, are indexed along with it.
+    pub fn add(&self, header: &ChainHeader) -> HcResult<IndexedHeader> {
+        if let Some(indexed) = self.get(&header.address())? {
+            return Ok(indexed);
+        }
+
+        // Walk back until we reach an indexed header or the start of the chain
+        let mut pending = vec![header.clone()];
+        let mut previous = None;
+        let mut link = header.link();
+        while let Some(address) = link {
+            if let Some(indexed) = self.get(&address)? {
+                previous = Some(indexed);
+                break;
+            }
+            let content = self.content_storage.read()?.fetch(&address)?.ok_or_else(|| {
+                HolochainError::ErrorGeneric(format!(
+                    "Chain header {} is missing from the chain store",
+                    address
+                ))
+            })?;
+            let linked = ChainHeader::try_from_content(&content)?;
+            link = linked.link();
+            pending.push(linked);
+        }
+
+        let (root, mut sequence) = match previous {
+            Some(indexed) => (indexed.root, indexed.sequence + 1),
+            None => (
+                pending
+                    .last()
+                    .expect("pending always holds the given header")
+                    .address()
+                    .to_string(),
+                0,
+            ),
+        };
+
+        let mut eav_storage = self.eav_storage.write()?;
+        let mut indexed = None;
+        for header in pending.iter().rev() {
+            let eavi = EntityAttributeValueIndex::new_with_index(
+                &header.address(),
+                &Attribute::ChainIndex(root.clone(), header.entry_type().to_string()),
+                header.entry_address(),
+                timestamp_secs(header.timestamp()) * SEQUENCE_SPAN + sequence,
+            )?;
+            let stored = eav_storage.add_eavi(&eavi)?.unwrap_or(eavi);
+            let row = IndexedHeader::from_eavi(&stored)
+                .expect("stored row carries the chain index attribute");
+            sequence = row.sequence + 1;
+            indexed = Some(row);
+        }
+        Ok(indexed.expect("pending always holds the given header"))
+    }
+
+    /// Returns the addresses of all headers up to and including `top` whose entry type
+    /// matches `entry_type_matches` and whose timestamp lies within `since` and `until`.
+    /// Both bounds are inclusive and `until` covers the whole second it names.
+    pub fn query<F>(
+        &self,
+        top: &ChainHeader,
+        entry_type_matches: F,
+        since: Option<&Iso8601>,
+        until: Option<&Iso8601>,
+        ordering: QueryOrdering,
+    ) -> HcResult<Vec<Address>>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        let top = self.add(top)?;
+        let root = top.root.clone();
+        let lower = since.map(|since| timestamp_secs(since) * SEQUENCE_SPAN);
+        let upper = until.map(|until| (timestamp_secs(until) + 1) * SEQUENCE_SPAN - 1);
+
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            None.into(),
+            EavFilter::predicate(move |attribute: Attribute| match attribute {
+                Attribute::ChainIndex(ref chain_root, ref entry_type) => {
+                    *chain_root == root && entry_type_matches(entry_type)
+                }
+                _ => false,
+            }),
+            None.into(),
+            IndexFilter::Range(lower, upper),
+            None,
+        ))?;
+
+        let mut headers: Vec<IndexedHeader> = rows
+            .iter()
+            .filter_map(IndexedHeader::from_eavi)
+            .filter(|indexed| indexed.sequence <= top.sequence)
+            .collect();
+        headers.sort_by_key(|indexed| indexed.sequence);
+        if ordering == QueryOrdering::Reverse {
+            headers.reverse();
+        }
+        Ok(headers
+            .into_iter()
+            .map(|indexed| indexed.header_address)
+            .collect())
+    }
+
+    /// Returns the address of a header up to and including `top` whose entry has the given
+    /// address, if there is one.
+    pub fn find_entry(
+        &self,
+        top: &ChainHeader,
+        entry_address: &Address,
+    ) -> HcResult<Option<Address>> {
+        let top = self.add(top)?;
+        let root = top.root.clone();
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            None.into(),
+            EavFilter::predicate(move |attribute: Attribute| match attribute {
+                Attribute::ChainIndex(ref chain_root, _) => *chain_root == root,
+                _ => false,
+            }),
+            Some(entry_address.clone()).into(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        Ok(rows
+            .iter()
+            .filter_map(IndexedHeader::from_eavi)
+            .find(|indexed| indexed.sequence <= top.sequence)
+            .map(|indexed| indexed.header_address))
+    }
+}
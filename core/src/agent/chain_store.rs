@@ -1,13 +1,22 @@
-use globset::{GlobBuilder, GlobSetBuilder};
+use crate::agent::chain_index::{within_time_range, ChainIndex};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use holochain_core_types::{
     chain_header::ChainHeader,
+    eav::Attribute,
     entry::entry_type::EntryType,
-    error::RibosomeErrorCode::{self, *},
+    error::{
+        HcResult,
+        RibosomeErrorCode::{self, *},
+    },
 };
-use holochain_persistence_api::cas::{
-    content::{Address, AddressableContent},
-    storage::ContentAddressableStorage,
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    },
+    eav::EntityAttributeValueStorage,
 };
+use holochain_wasm_utils::api_serialization::{QueryFilter, QueryOrdering};
 
 use std::{
     str::FromStr,
@@ -18,6 +27,8 @@ use std::{
 pub struct ChainStore {
     // Storages holding local shard data
     content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    // Index of the chain's headers by entry type and timestamp, if the chain has one
+    chain_index: Option<ChainIndex>,
 }
 
 impl PartialEq for ChainStore {
@@ -35,6 +46,8 @@ pub struct ChainStoreQueryOptions {
     pub start: usize,
     pub limit: usize,
     pub headers: bool,
+    pub ordering: QueryOrdering,
+    pub filter_by: QueryFilter,
 }
 
 #[derive(Debug)]
@@ -43,15 +56,117 @@ pub enum ChainStoreQueryResult {
     Headers(Vec<ChainHeader>),
 }
 
+/// The entry types selected by the entry type names given to a query
+#[derive(Clone)]
+enum EntryTypeMatcher {
+    All,
+    One(EntryType),
+    Glob(GlobSet),
+}
+
+impl EntryTypeMatcher {
+    // Get entry_type name(s), if any.  If empty/blank, matches the complete source chain.  A
+    // single matching entry type name with no glob pattern matching will use the single
+    // entry_type optimization.  Otherwise, we'll construct a GlobSet match and scan the list to
+    // create a pattern-match engine to select the EntryTypes we want.
+    fn new(entry_type_names: &[&str]) -> Result<EntryTypeMatcher, RibosomeErrorCode> {
+        fn is_glob(c: &char) -> bool {
+            "./*[]{}".chars().any(|y| y == *c)
+        }
+        fn is_glob_str(s: &str) -> bool {
+            s.chars().any(|c| is_glob(&c))
+        }
+
+        match entry_type_names {
+            [] | [""] | ["**"] => Ok(EntryTypeMatcher::All),
+            [one] if !is_glob_str(one) => EntryType::from_str(&one)
+                .map(EntryTypeMatcher::One)
+                .map_err(|_| UnknownEntryType),
+            rest => {
+                // 1 or more EntryTypes, may or may not include glob wildcards.  Create a
+                // GlobSetBuilder and add all the EntryType name patterns to it; this will
+                // recognize all matching EntryTypes using a single regex machine invocation.  In
+                // order to support .../... EntryType namespaces, force the '/' path separator to
+                // match literally.
+                let mut builder = GlobSetBuilder::new();
+                for name in rest {
+                    builder.add(
+                        GlobBuilder::new(name)
+                            .literal_separator(true)
+                            .build()
+                            .map_err(|_| UnknownEntryType)?,
+                    );
+                }
+                Ok(EntryTypeMatcher::Glob(
+                    builder.build().map_err(|_| UnknownEntryType)?,
+                ))
+            }
+        }
+    }
+
+    fn matches(&self, entry_type: &str) -> bool {
+        match self {
+            EntryTypeMatcher::All => true,
+            EntryTypeMatcher::One(one) => one.to_string() == entry_type,
+            EntryTypeMatcher::Glob(globset) => globset.is_match(entry_type),
+        }
+    }
+}
+
 impl ChainStore {
     pub fn new(content_storage: Arc<RwLock<dyn ContentAddressableStorage>>) -> Self {
-        ChainStore { content_storage }
+        ChainStore {
+            content_storage,
+            chain_index: None,
+        }
+    }
+
+    /// Creates a chain store that keeps an index of its headers in the given EAV storage,
+    /// which lets queries by entry type and time range skip walking the chain.
+    pub fn new_with_index(
+        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+        eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    ) -> Self {
+        ChainStore {
+            content_storage: content_storage.clone(),
+            chain_index: Some(ChainIndex::new(content_storage, eav_storage)),
+        }
     }
 
     pub fn content_storage(&self) -> Arc<RwLock<dyn ContentAddressableStorage>> {
         self.content_storage.clone()
     }
 
+    /// Adds the given header, which must already be stored, to the chain index.
+    /// Does nothing for chain stores without an index.
+    pub fn index_header(&self, chain_header: &ChainHeader) -> HcResult<()> {
+        match self.chain_index {
+            Some(ref chain_index) => chain_index.add(chain_header).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the chain that ends with the given header holds the entry with the given address.
+    /// Uses the chain index if there is one and walks the chain otherwise.
+    pub fn contains_entry(
+        &self,
+        start_chain_header: &Option<ChainHeader>,
+        entry_address: &Address,
+    ) -> bool {
+        if let (Some(chain_index), Some(top)) = (&self.chain_index, start_chain_header) {
+            match chain_index.find_entry(top, entry_address) {
+                Ok(found) => return found.is_some(),
+                Err(error) => log_warn!(
+                    target: "holochain::chain_store",
+                    "Chain index lookup failed, walking the chain instead: {}",
+                    error
+                ),
+            }
+        }
+        self.iter(start_chain_header)
+            .any(|header| header.entry_address() == entry_address)
+    }
+
     pub fn iter(&self, start_chain_header: &Option<ChainHeader>) -> ChainStoreIterator {
         ChainStoreIterator::new(self.content_storage.clone(), start_chain_header.clone())
     }
@@ -71,23 +186,15 @@ impl ChainStore {
         )
     }
 
-    // Supply a None for options to get defaults (all elements, no ChainHeaders just Addresses)
+    // Supply a None for options to get defaults (all elements, latest first, no ChainHeaders just
+    // Addresses)
     pub fn query(
         &self,
         start_chain_header: &Option<ChainHeader>,
         entry_type_names: &[&str],
         options: ChainStoreQueryOptions,
     ) -> Result<ChainStoreQueryResult, RibosomeErrorCode> {
-        // Get entry_type name(s), if any.  If empty/blank, returns the complete source chain.  A
-        // single matching entry type name with no glob pattern matching will use the single
-        // entry_type optimization.  Otherwise, we'll construct a GlobSet match and scan the list to
-        // create a pattern-match engine to select the EntryTypes we want.
-        fn is_glob(c: &char) -> bool {
-            "./*[]{}".chars().any(|y| y == *c)
-        }
-        fn is_glob_str(s: &str) -> bool {
-            s.chars().any(|c| is_glob(&c))
-        }
+        let matcher = EntryTypeMatcher::new(entry_type_names)?;
 
         // Unpack options; start == 0 --> start at beginning, limit == 0 --> take all remaining
         let start = options.start;
@@ -96,97 +203,103 @@ impl ChainStore {
         } else {
             options.limit
         };
-        let headers = options.headers;
-
-        let vector = match entry_type_names {
-            // Vec<Address> or Vec<ChainHeader>
-            [] | [""] | ["**"] => {
-                // No filtering desired; uses bare .iter()
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter(start_chain_header)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.to_owned())
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter(start_chain_header)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+
+        // Chains without an index, or whose index can't be read, get walked instead.
+        let headers = match self.query_index(start_chain_header, &matcher, &options) {
+            Some(Ok(addresses)) => addresses
+                .iter()
+                .skip(start)
+                .take(limit)
+                .map(|address| self.fetch_header(address))
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(Err(error)) => {
+                log_warn!(
+                    target: "holochain::chain_store",
+                    "Chain index query failed, walking the chain instead: {}",
+                    error
+                );
+                self.query_chain(start_chain_header, &matcher, &options, start, limit)
             }
-            [one] if !is_glob_str(one) => {
-                // Single EntryType without "glob" pattern; uses .iter_type()
-                let entry_type = match EntryType::from_str(&one) {
-                    Ok(inner) => inner,
-                    Err(..) => return Err(UnknownEntryType),
-                };
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter_type(start_chain_header, &entry_type)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.to_owned())
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter_type(start_chain_header, &entry_type)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+            None => self.query_chain(start_chain_header, &matcher, &options, start, limit),
+        };
+
+        Ok(if options.headers {
+            ChainStoreQueryResult::Headers(headers)
+        } else {
+            ChainStoreQueryResult::Addresses(
+                headers
+                    .iter()
+                    .map(|header| header.entry_address().to_owned())
+                    .collect(),
+            )
+        })
+    }
+
+    fn query_index(
+        &self,
+        start_chain_header: &Option<ChainHeader>,
+        matcher: &EntryTypeMatcher,
+        options: &ChainStoreQueryOptions,
+    ) -> Option<HcResult<Vec<Address>>> {
+        let chain_index = self.chain_index.as_ref()?;
+        let top = start_chain_header.as_ref()?;
+        let matcher = matcher.clone();
+        Some(chain_index.query(
+            top,
+            move |entry_type| matcher.matches(entry_type),
+            options.filter_by.since.as_ref(),
+            options.filter_by.until.as_ref(),
+            options.ordering,
+        ))
+    }
+
+    fn query_chain(
+        &self,
+        start_chain_header: &Option<ChainHeader>,
+        matcher: &EntryTypeMatcher,
+        options: &ChainStoreQueryOptions,
+        start: usize,
+        limit: usize,
+    ) -> Vec<ChainHeader> {
+        let filter = options.filter_by.clone();
+        let headers: Box<dyn Iterator<Item = ChainHeader>> = match matcher {
+            // Single EntryType without "glob" pattern; uses .iter_type()
+            EntryTypeMatcher::One(entry_type) => {
+                Box::new(self.iter_type(start_chain_header, entry_type))
             }
-            rest => {
-                // 1 or more EntryTypes, may or may not include glob wildcards.  Create a
-                // GlobSetBuilder and add all the EntryType name patterns to it; this will recognize
-                // all matching EntryTypes using a single regex machine invocation.  In order to
-                // support .../... EntryType namespaces, force the '/' path separator to match
-                // literally.
-                let mut builder = GlobSetBuilder::new();
-                for name in rest {
-                    builder.add(
-                        GlobBuilder::new(name)
-                            .literal_separator(true)
-                            .build()
-                            .map_err(|_| UnknownEntryType)?,
-                    );
-                }
-                let globset = builder.build().map_err(|_| UnknownEntryType)?;
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter(start_chain_header)
-                            .filter(|header| {
-                                globset.matches(header.entry_type().to_string()).len() > 0
-                            })
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.to_owned())
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter(start_chain_header)
-                            .filter(|header| {
-                                globset.matches(header.entry_type().to_string()).len() > 0
-                            })
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+            _ => {
+                let matcher = matcher.clone();
+                Box::new(
+                    self.iter(start_chain_header)
+                        .filter(move |header| matcher.matches(&header.entry_type().to_string())),
+                )
             }
         };
+        let headers = headers.filter(|header| {
+            within_time_range(
+                header.timestamp(),
+                filter.since.as_ref(),
+                filter.until.as_ref(),
+            )
+        });
+        match options.ordering {
+            QueryOrdering::Reverse => headers.skip(start).take(limit).collect(),
+            QueryOrdering::Forward => {
+                let mut headers: Vec<ChainHeader> = headers.collect();
+                headers.reverse();
+                headers.into_iter().skip(start).take(limit).collect()
+            }
+        }
+    }
 
-        Ok(vector)
+    fn fetch_header(&self, address: &Address) -> Result<ChainHeader, RibosomeErrorCode> {
+        self.content_storage
+            .read()
+            .map_err(|_| Unspecified)?
+            .fetch(address)
+            .map_err(|_| Unspecified)?
+            .ok_or(Unspecified)
+            .and_then(|content| ChainHeader::try_from_content(&content).map_err(|_| Unspecified))
     }
 }
 
@@ -307,11 +420,14 @@ pub mod tests {
             entry_type::{test_entry_type_b, AppEntryType},
             test_entry, test_entry_b, test_entry_c, Entry,
         },
-        time::test_iso_8601,
+        time::{test_iso_8601, Iso8601},
     };
     use holochain_json_api::json::{JsonString, RawString};
-    use holochain_persistence_api::cas::content::AddressableContent;
+    use holochain_persistence_api::cas::content::{Address, AddressableContent};
     use holochain_persistence_file::cas::file::FilesystemStorage;
+    use holochain_persistence_mem::eav::memory::EavMemoryStorage;
+    use holochain_wasm_utils::api_serialization::{QueryFilter, QueryOrdering};
+    use std::sync::{Arc, RwLock};
     use tempfile;
 
     pub fn test_chain_store() -> ChainStore {
@@ -533,6 +649,7 @@ pub mod tests {
                     start: 0,
                     limit: 1,
                     headers: false,
+                    ..Default::default()
                 },
             )
             .unwrap()
@@ -791,6 +908,154 @@ pub mod tests {
         }
     }
 
+    fn query_addresses(
+        chain_store: &ChainStore,
+        top: &ChainHeader,
+        entry_type_names: &[&str],
+        options: ChainStoreQueryOptions,
+    ) -> Vec<Address> {
+        match chain_store
+            .query(&Some(top.clone()), entry_type_names, options)
+            .unwrap()
+        {
+            ChainStoreQueryResult::Addresses(addresses) => addresses,
+            other => panic!("Unexpected query value {:?}", other),
+        }
+    }
+
+    #[test]
+    /// show that indexed queries match walking the chain, in both orders and within time ranges
+    fn indexed_query_test() {
+        let cas = Arc::new(RwLock::new(
+            FilesystemStorage::new(tempdir().unwrap().path().to_str().unwrap())
+                .expect("could not create chain store"),
+        ));
+        let indexed = ChainStore::new_with_index(
+            cas.clone(),
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+        );
+        let walked = ChainStore::new(cas.clone());
+
+        let mut headers: Vec<ChainHeader> = Vec::new();
+        for (i, entry) in vec![test_entry(), test_entry_b(), test_entry_c(), test_entry()]
+            .into_iter()
+            .enumerate()
+        {
+            let link_same_type = headers
+                .iter()
+                .rev()
+                .find(|header| *header.entry_type() == entry.entry_type())
+                .map(|header| header.address());
+            let header = ChainHeader::new(
+                &entry.entry_type(),
+                &entry.address(),
+                &test_provenances("sig"),
+                &headers.last().map(|header| header.address()),
+                &link_same_type,
+                &None,
+                &Iso8601::from(1000 * (i as i64 + 1)),
+            );
+            cas.write().unwrap().add(&header).unwrap();
+            headers.push(header);
+        }
+        let addresses = |indexes: Vec<usize>| -> Vec<Address> {
+            indexes
+                .into_iter()
+                .map(|i| headers[i].entry_address().clone())
+                .collect()
+        };
+        let top = headers[3].clone();
+        let forward = || ChainStoreQueryOptions {
+            ordering: QueryOrdering::Forward,
+            ..Default::default()
+        };
+        let in_range = |since: i64, until: i64| ChainStoreQueryOptions {
+            filter_by: QueryFilter {
+                since: Some(Iso8601::from(since)),
+                until: Some(Iso8601::from(until)),
+            },
+            ..Default::default()
+        };
+
+        // None of the headers got indexed on commit, so the first query indexes them all
+        for chain_store in vec![&indexed, &walked] {
+            assert_eq!(
+                query_addresses(chain_store, &top, &[], ChainStoreQueryOptions::default()),
+                addresses(vec![3, 2, 1, 0])
+            );
+            assert_eq!(
+                query_addresses(chain_store, &top, &[], forward()),
+                addresses(vec![0, 1, 2, 3])
+            );
+            assert_eq!(
+                query_addresses(
+                    chain_store,
+                    &top,
+                    &[test_entry_type_b().to_string().as_ref()],
+                    forward()
+                ),
+                addresses(vec![1, 2])
+            );
+            assert_eq!(
+                query_addresses(chain_store, &top, &[], in_range(2000, 3000)),
+                addresses(vec![2, 1])
+            );
+            assert_eq!(
+                query_addresses(chain_store, &top, &["testEntryType*"], in_range(2500, 5000)),
+                addresses(vec![3, 2])
+            );
+            assert_eq!(
+                query_addresses(
+                    chain_store,
+                    &top,
+                    &[],
+                    ChainStoreQueryOptions {
+                        start: 1,
+                        limit: 2,
+                        ..forward()
+                    }
+                ),
+                addresses(vec![1, 2])
+            );
+            // Headers after the start header are left out
+            assert_eq!(
+                query_addresses(chain_store, &headers[1], &[], forward()),
+                addresses(vec![0, 1])
+            );
+            assert!(chain_store.contains_entry(&Some(top.clone()), headers[2].entry_address()));
+            assert!(!chain_store
+                .contains_entry(&Some(headers[1].clone()), headers[2].entry_address()));
+            assert!(!chain_store.contains_entry(&Some(top.clone()), &Address::from("QmOther")));
+        }
+
+        // Headers committed later get added to the existing index
+        let entry = test_entry_b();
+        let chain_header_e = ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &test_provenances("sig"),
+            &Some(top.address()),
+            &Some(headers[2].address()),
+            &None,
+            &Iso8601::from(5000),
+        );
+        cas.write().unwrap().add(&chain_header_e).unwrap();
+        indexed.index_header(&chain_header_e).unwrap();
+        assert_eq!(
+            query_addresses(
+                &indexed,
+                &chain_header_e,
+                &[test_entry_type_b().to_string().as_ref()],
+                ChainStoreQueryOptions::default()
+            ),
+            vec![
+                chain_header_e.entry_address().clone(),
+                headers[2].entry_address().clone(),
+                headers[1].entry_address().clone(),
+            ]
+        );
+    }
+
     use globset::{Glob, GlobBuilder, GlobSetBuilder};
 
     #[test]
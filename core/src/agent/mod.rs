@@ -1,7 +1,11 @@
 /// Agent is the module that handles the user’s identity and source chain for every Phenotype.
 ///
 pub mod actions;
+pub mod bundle;
+pub mod chain_archive;
+pub mod chain_index;
 pub mod chain_store;
+pub mod receipt_store;
 pub mod state;
 
 use crate::state::StateWrapper;
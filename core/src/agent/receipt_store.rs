@@ -0,0 +1,218 @@
+//! The author's local store of the validation receipts it got for the data it published.
+//!
+//! Receipts are kept in the instance's chain CAS and indexed in its EAV storage with one
+//! EAVI row per receipt:
+//!
+//! * entity: the address of the entry the receipt is for
+//! * attribute: `Attribute::ValidationReceipt`
+//! * value: the address of the receipt
+//!
+//! Validators send a receipt every time they hold one of our aspects, each with a new
+//! timestamp. Only the latest receipt of a validator for an aspect counts, and a receipt
+//! that does not change its result does not get stored at all.
+use crate::network::validation_receipt::ValidationReceipt;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EntityAttributeValueStorage, IndexFilter},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+#[derive(Clone, Debug)]
+pub struct ReceiptStore {
+    content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+}
+
+impl ReceiptStore {
+    pub fn new(
+        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+        eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    ) -> Self {
+        ReceiptStore {
+            content_storage,
+            eav_storage,
+        }
+    }
+
+    /// Stores the given receipt, unless the latest receipt of its validator for the same
+    /// aspect already has the same result.
+    pub fn add(&self, receipt: &ValidationReceipt) -> HcResult<()> {
+        let known = self.get(&receipt.entry_address)?.iter().any(|stored| {
+            stored.validator() == receipt.validator()
+                && stored.aspect_address == receipt.aspect_address
+                && stored.result == receipt.result
+        });
+        if known {
+            return Ok(());
+        }
+        self.content_storage.write()?.add(receipt)?;
+        let eavi = EntityAttributeValueIndex::new(
+            &receipt.entry_address,
+            &Attribute::ValidationReceipt,
+            &receipt.address(),
+        )?;
+        self.eav_storage.write()?.add_eavi(&eavi)?;
+        Ok(())
+    }
+
+    /// Returns the latest receipt of every validator for every aspect of the entry with the
+    /// given address, oldest first.
+    pub fn get(&self, entry_address: &Address) -> HcResult<Vec<ValidationReceipt>> {
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            Some(entry_address.clone()).into(),
+            Some(Attribute::ValidationReceipt).into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        let content_storage = self.content_storage.read()?;
+        let mut latest: HashMap<(Address, Address), ValidationReceipt> = HashMap::new();
+        for eavi in rows.iter() {
+            let content = content_storage.fetch(&eavi.value())?.ok_or_else(|| {
+                HolochainError::ErrorGeneric(format!(
+                    "Validation receipt {} is missing from the chain store",
+                    eavi.value()
+                ))
+            })?;
+            let receipt = ValidationReceipt::try_from_content(&content)?;
+            let key = (receipt.validator(), receipt.aspect_address.clone());
+            match latest.get(&key) {
+                Some(stored) if stored.timestamp > receipt.timestamp => (),
+                _ => {
+                    latest.insert(key, receipt);
+                }
+            }
+        }
+        let mut receipts: Vec<ValidationReceipt> =
+            latest.into_iter().map(|(_, receipt)| receipt).collect();
+        receipts.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        Ok(receipts)
+    }
+
+    /// Number of distinct validators that found an aspect of the entry with the given
+    /// address valid, i.e. how many peers hold it.
+    pub fn count(&self, entry_address: &Address) -> HcResult<usize> {
+        Ok(self
+            .get(entry_address)?
+            .iter()
+            .filter(|receipt| receipt.is_valid())
+            .map(|receipt| receipt.validator())
+            .collect::<HashSet<_>>()
+            .len())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::network::validation_receipt::{
+        tests::test_validation_receipt, ValidationReceiptResult,
+    };
+    use holochain_core_types::time::Iso8601;
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+    use test_utils::mock_signing::registered_test_agent;
+
+    fn test_receipt_store() -> ReceiptStore {
+        ReceiptStore::new(
+            Arc::new(RwLock::new(MemoryStorage::new())),
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+        )
+    }
+
+    #[test]
+    fn count_counts_distinct_validators_with_valid_receipts() {
+        let store = test_receipt_store();
+        let entry = Address::from("QmEntry");
+        let frank = registered_test_agent("receipt-validator-frank");
+        let grace = registered_test_agent("receipt-validator-grace");
+        let heidi = registered_test_agent("receipt-validator-heidi");
+
+        let content = test_validation_receipt(
+            &entry,
+            &Address::from("QmContent"),
+            ValidationReceiptResult::Valid,
+            &frank,
+        );
+        store.add(&content).unwrap();
+        // the same receipt again and a receipt for another aspect of the same validator
+        store.add(&content).unwrap();
+        store
+            .add(&test_validation_receipt(
+                &entry,
+                &Address::from("QmLink"),
+                ValidationReceiptResult::Valid,
+                &frank,
+            ))
+            .unwrap();
+        store
+            .add(&test_validation_receipt(
+                &entry,
+                &Address::from("QmContent"),
+                ValidationReceiptResult::Valid,
+                &grace,
+            ))
+            .unwrap();
+        store
+            .add(&test_validation_receipt(
+                &entry,
+                &Address::from("QmContent"),
+                ValidationReceiptResult::Invalid(String::from("nope")),
+                &heidi,
+            ))
+            .unwrap();
+
+        assert_eq!(store.get(&entry).unwrap().len(), 4);
+        assert_eq!(store.count(&entry).unwrap(), 2);
+        assert_eq!(store.count(&Address::from("QmOtherEntry")).unwrap(), 0);
+    }
+
+    #[test]
+    fn add_keeps_the_latest_receipt_of_a_validator_for_an_aspect() {
+        let store = test_receipt_store();
+        let entry = Address::from("QmEntry");
+        let ivan = registered_test_agent("receipt-validator-ivan");
+        let receipt_at = |seconds: i64, result: ValidationReceiptResult| {
+            let mut receipt =
+                test_validation_receipt(&entry, &Address::from("QmContent"), result, &ivan);
+            receipt.timestamp = Iso8601::from(seconds);
+            receipt
+        };
+        let stored_rows = || {
+            store
+                .eav_storage
+                .read()
+                .unwrap()
+                .fetch_eavi(&EaviQuery::new(
+                    Some(entry.clone()).into(),
+                    Some(Attribute::ValidationReceipt).into(),
+                    None.into(),
+                    IndexFilter::Range(None, None),
+                    None,
+                ))
+                .unwrap()
+                .len()
+        };
+
+        // Holding the aspect again sends the same result with a new timestamp
+        store.add(&receipt_at(1000, ValidationReceiptResult::Valid)).unwrap();
+        store.add(&receipt_at(2000, ValidationReceiptResult::Valid)).unwrap();
+        assert_eq!(stored_rows(), 1);
+        assert_eq!(store.count(&entry).unwrap(), 1);
+
+        let invalid = receipt_at(3000, ValidationReceiptResult::Invalid(String::from("nope")));
+        store.add(&invalid).unwrap();
+        assert_eq!(stored_rows(), 2);
+        assert_eq!(store.get(&entry).unwrap(), vec![invalid]);
+        assert_eq!(store.count(&entry).unwrap(), 0);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{
     action::{Action, ActionWrapper, AgentReduceFn},
-    agent::chain_store::{ChainStore, ChainStoreIterator},
+    agent::{
+        bundle::{BundleId, CommitBundle},
+        chain_store::{ChainStore, ChainStoreIterator},
+    },
     state::State,
 };
 use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
@@ -38,6 +41,8 @@ pub struct AgentState {
     chain_store: ChainStore,
     top_chain_header: Option<ChainHeader>,
     initial_agent_address: Address,
+    /// commit bundles of running zome calls, by id of the call
+    bundles: HashMap<BundleId, CommitBundle>,
 }
 
 impl AgentState {
@@ -48,6 +53,7 @@ impl AgentState {
             chain_store,
             top_chain_header: None,
             initial_agent_address,
+            bundles: HashMap::new(),
         }
     }
 
@@ -61,6 +67,7 @@ impl AgentState {
             chain_store,
             top_chain_header: chain_header,
             initial_agent_address,
+            bundles: HashMap::new(),
         }
     }
 
@@ -78,6 +85,20 @@ impl AgentState {
         self.top_chain_header.clone()
     }
 
+    /// Returns the open commit bundle of the zome call with the given id, if any.
+    pub fn bundle(&self, id: &BundleId) -> Option<CommitBundle> {
+        self.bundles.get(id).cloned()
+    }
+
+    /// Returns the entry with the given address if it is staged in the open bundle of
+    /// the zome call with the given id.
+    pub fn staged_entry(&self, id: &BundleId, address: &Address) -> Option<Entry> {
+        self.bundles
+            .get(id)
+            .and_then(|bundle| bundle.get(address))
+            .cloned()
+    }
+
     pub fn iter_chain(&self) -> ChainStoreIterator {
         self.chain_store.iter(&self.top_chain_header)
     }
@@ -168,6 +189,7 @@ impl AddressableContent for AgentStateSnapshot {
 // @see https://github.com/holochain/holochain-rust/issues/196
 pub enum ActionResponse {
     Commit(Result<Address, HolochainError>),
+    CommitBundle(Result<Vec<Address>, HolochainError>),
     FetchEntry(Option<Entry>),
     GetLinks(Result<Vec<Address>, HolochainError>),
     LinkEntries(Result<Entry, HolochainError>),
@@ -179,6 +201,20 @@ pub fn create_new_chain_header(
     root_state: &StateWrapper,
     crud_link: &Option<Address>,
     provenances: &Vec<Provenance>,
+) -> Result<ChainHeader, HolochainError> {
+    create_new_chain_header_after(entry, agent_state, root_state, crud_link, provenances, &[])
+}
+
+/// Same as `create_new_chain_header()` for an entry that goes on top of the given headers,
+/// which are not on the chain yet, like the ones of entries staged in a commit bundle
+/// before it. `preceding` has to be in chain order.
+pub fn create_new_chain_header_after(
+    entry: &Entry,
+    agent_state: &AgentState,
+    root_state: &StateWrapper,
+    crud_link: &Option<Address>,
+    provenances: &Vec<Provenance>,
+    preceding: &[ChainHeader],
 ) -> Result<ChainHeader, HolochainError> {
     let agent_address = agent_state.get_agent_address()?;
     let signature = Signature::from(
@@ -200,15 +236,22 @@ pub fn create_new_chain_header(
         &entry.entry_type(),
         &entry.address(),
         &provenances,
-        &agent_state
-            .top_chain_header
-            .clone()
-            .and_then(|chain_header| Some(chain_header.address())),
-        &agent_state
-            .chain_store()
-            .iter_type(&agent_state.top_chain_header, &entry.entry_type())
-            .nth(0)
-            .and_then(|chain_header| Some(chain_header.address())),
+        &preceding
+            .last()
+            .or(agent_state.top_chain_header.as_ref())
+            .map(|chain_header| chain_header.address()),
+        &preceding
+            .iter()
+            .rev()
+            .find(|chain_header| *chain_header.entry_type() == entry.entry_type())
+            .cloned()
+            .or_else(|| {
+                agent_state
+                    .chain_store()
+                    .iter_type(&agent_state.top_chain_header, &entry.entry_type())
+                    .nth(0)
+            })
+            .map(|chain_header| chain_header.address()),
         crud_link,
         &Iso8601::from(duration_since_epoch.as_secs()),
     ))
@@ -226,10 +269,11 @@ fn reduce_commit_entry(
     let action = action_wrapper.action();
     let (entry, maybe_link_update_delete, provenances) = unwrap_to!(action => Action::Commit);
 
+    let root_state = StateWrapper::from(root_state.clone());
     let result = create_new_chain_header(
         &entry,
         agent_state,
-        &StateWrapper::from(root_state.clone()),
+        &root_state,
         &maybe_link_update_delete,
         provenances,
     )
@@ -237,6 +281,13 @@ fn reduce_commit_entry(
         let storage = &agent_state.chain_store.content_storage().clone();
         storage.write().unwrap().add(entry)?;
         storage.write().unwrap().add(&chain_header)?;
+        // A header that fails to get indexed here is picked up by the next query,
+        // which indexes all missing headers of the chain it walks through.
+        // Agent updates rotate the agent's key and get rolled back if the conductor fails
+        // to switch over to it, so they are left to the next query as well.
+        if !is_agent_update(entry, maybe_link_update_delete) {
+            let _ = agent_state.chain_store.index_header(&chain_header);
+        }
         Ok((chain_header, entry.address()))
     })
     .and_then(|(chain_header, address)| {
@@ -249,10 +300,127 @@ fn reduce_commit_entry(
         .insert(action_wrapper.clone(), ActionResponse::Commit(result));
 }
 
+fn is_agent_update(entry: &Entry, crud_link: &Option<Address>) -> bool {
+    match (entry, crud_link) {
+        (Entry::AgentId(_), Some(_)) => true,
+        _ => false,
+    }
+}
+
+/// Takes the given header off the top of the chain again, if it still is on top.
+/// The header and its entry stay in the content storage but are not part of the chain anymore.
+fn reduce_roll_back_commit(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let chain_header = unwrap_to!(action => Action::RollBackCommit);
+    if agent_state.top_chain_header.as_ref() == Some(chain_header) {
+        agent_state.top_chain_header = agent_state
+            .chain_store
+            .iter(&Some(chain_header.clone()))
+            .nth(1);
+    }
+}
+
+fn reduce_start_bundle(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (id, bundle) = unwrap_to!(action => Action::StartBundle);
+    agent_state
+        .bundles
+        .entry(id.clone())
+        .or_insert_with(|| bundle.clone());
+}
+
+fn reduce_stage_entry(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (id, staged) = unwrap_to!(action => Action::StageEntry);
+    if let Some(bundle) = agent_state.bundles.get_mut(id) {
+        bundle.stage(staged.clone());
+    }
+}
+
+/// Writes all entries of a bundle to the chain, each one on top of the one before.
+/// The top chain header only moves once all of them got written, so a bundle that fails
+/// half way leaves the chain as it was.
+fn reduce_commit_bundle(
+    agent_state: &mut AgentState,
+    root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let id = unwrap_to!(action => Action::CommitBundle);
+    let result = match agent_state.bundles.remove(id) {
+        Some(bundle) => {
+            let root_state = StateWrapper::from(root_state.clone());
+            let previous_top_chain_header = agent_state.top_chain_header.clone();
+            let mut headers = Vec::new();
+            let written: Result<Vec<Address>, HolochainError> = bundle
+                .entries()
+                .iter()
+                .map(|staged| {
+                    let chain_header = create_new_chain_header(
+                        &staged.entry,
+                        agent_state,
+                        &root_state,
+                        &staged.maybe_link_update_delete,
+                        &staged.provenances,
+                    )?;
+                    let storage = &agent_state.chain_store.content_storage().clone();
+                    storage.write().unwrap().add(&staged.entry)?;
+                    storage.write().unwrap().add(&chain_header)?;
+                    agent_state.top_chain_header = Some(chain_header.clone());
+                    headers.push(chain_header);
+                    Ok(staged.entry.address())
+                })
+                .collect();
+            if written.is_ok() {
+                for chain_header in headers.iter() {
+                    let _ = agent_state.chain_store.index_header(chain_header);
+                }
+            } else {
+                agent_state.top_chain_header = previous_top_chain_header;
+            }
+            written
+        }
+        None => Err(HolochainError::ErrorGeneric(
+            "No open bundle to commit".to_string(),
+        )),
+    };
+
+    agent_state
+        .actions
+        .insert(action_wrapper.clone(), ActionResponse::CommitBundle(result));
+}
+
+fn reduce_discard_bundle(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let id = unwrap_to!(action => Action::DiscardBundle);
+    agent_state.bundles.remove(id);
+}
+
 /// maps incoming action to the correct handler
 fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<AgentReduceFn> {
     match action_wrapper.action() {
         Action::Commit(_) => Some(reduce_commit_entry),
+        Action::RollBackCommit(_) => Some(reduce_roll_back_commit),
+        Action::StartBundle(_) => Some(reduce_start_bundle),
+        Action::StageEntry(_) => Some(reduce_stage_entry),
+        Action::CommitBundle(_) => Some(reduce_commit_bundle),
+        Action::DiscardBundle(_) => Some(reduce_discard_bundle),
         _ => None,
     }
 }
@@ -278,12 +446,14 @@ pub fn reduce(
 pub mod tests {
     use super::*;
     use crate::{
-        action::tests::test_action_wrapper_commit, agent::chain_store::tests::test_chain_store,
-        instance::tests::test_context, state::State,
+        action::tests::test_action_wrapper_commit,
+        agent::{bundle::StagedEntry, chain_store::tests::test_chain_store},
+        instance::tests::test_context,
+        state::State,
     };
     use holochain_core_types::{
         chain_header::{test_chain_header, ChainHeader},
-        entry::{expected_entry_address, test_entry, Entry},
+        entry::{expected_entry_address, test_entry, test_entry_b, test_entry_with_value, Entry},
         error::HolochainError,
         signature::Signature,
     };
@@ -325,7 +495,7 @@ pub mod tests {
     fn test_reduce_commit_entry() {
         let netname = Some("test_reduce_commit_entry");
         let context = test_context("bob", netname);
-        let mut agent_state = test_agent_state(Some(context.agent_id.address()));
+        let mut agent_state = test_agent_state(Some(context.agent_id().address()));
         let state = State::new_with_agent(context, agent_state.clone());
         let action_wrapper = test_action_wrapper_commit();
 
@@ -337,6 +507,153 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// test that an agent entry update goes on the chain without the conductor being involved
+    fn test_reduce_commit_agent_update() {
+        let netname = Some("test_reduce_commit_agent_update");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id().address()));
+        let state = State::new_with_agent(context.clone(), agent_state.clone());
+        let new_agent = Entry::AgentId(AgentId::generate_fake("new bob"));
+        let action_wrapper = ActionWrapper::new(Action::Commit((
+            new_agent.clone(),
+            Some(context.agent_id().address()),
+            vec![],
+        )));
+
+        reduce_commit_entry(&mut agent_state, &state, &action_wrapper);
+
+        assert_eq!(
+            agent_state.actions().get(&action_wrapper),
+            Some(&ActionResponse::Commit(Ok(new_agent.address()))),
+        );
+        assert_eq!(
+            agent_state
+                .top_chain_header()
+                .map(|chain_header| chain_header.entry_address().clone()),
+            Some(new_agent.address()),
+        );
+    }
+
+    #[test]
+    /// test that rolling back a commit restores the previous top chain header, unless
+    /// another header got committed on top of it
+    fn test_reduce_roll_back_commit() {
+        let netname = Some("test_reduce_roll_back_commit");
+        let context = test_context("bob", netname);
+        let mut agent_state = test_agent_state(Some(context.agent_id().address()));
+        let state = State::new_with_agent(context, agent_state.clone());
+        let commit = |agent_state: &mut AgentState, entry: Entry| {
+            let action_wrapper = ActionWrapper::new(Action::Commit((entry, None, vec![])));
+            reduce_commit_entry(agent_state, &state, &action_wrapper);
+            agent_state.top_chain_header().unwrap()
+        };
+        let roll_back = |agent_state: &mut AgentState, chain_header: &ChainHeader| {
+            let action_wrapper = ActionWrapper::new(Action::RollBackCommit(chain_header.clone()));
+            reduce_roll_back_commit(agent_state, &state, &action_wrapper);
+        };
+
+        let header_a = commit(&mut agent_state, test_entry());
+        let header_b = commit(&mut agent_state, test_entry_b());
+        roll_back(&mut agent_state, &header_b);
+        assert_eq!(agent_state.top_chain_header(), Some(header_a.clone()));
+
+        let header_b = commit(&mut agent_state, test_entry_b());
+        roll_back(&mut agent_state, &header_a);
+        assert_eq!(agent_state.top_chain_header(), Some(header_b));
+    }
+
+    /// dummy agent state with an open bundle that has test_entry() and test_entry_b() staged
+    fn test_agent_state_with_bundle(state: &State, id: BundleId) -> AgentState {
+        let mut agent_state = (*state.agent()).clone();
+        reduce_start_bundle(
+            &mut agent_state,
+            state,
+            &ActionWrapper::new(Action::StartBundle((
+                id.clone(),
+                CommitBundle::new(None, JsonString::null()),
+            ))),
+        );
+        for entry in vec![test_entry(), test_entry_b()] {
+            let staged = StagedEntry {
+                entry,
+                maybe_link_update_delete: None,
+                provenances: vec![],
+            };
+            reduce_stage_entry(
+                &mut agent_state,
+                state,
+                &ActionWrapper::new(Action::StageEntry((id.clone(), staged))),
+            );
+        }
+        agent_state
+    }
+
+    #[test]
+    /// test that committing a bundle writes all its entries to the chain and closes it
+    fn test_reduce_commit_bundle() {
+        let netname = Some("test_reduce_commit_bundle");
+        let context = test_context("bob", netname);
+        let state = State::new_with_agent(
+            context.clone(),
+            test_agent_state(Some(context.agent_id().address())),
+        );
+        let id = snowflake::ProcessUniqueId::new();
+        let mut agent_state = test_agent_state_with_bundle(&state, id.clone());
+        assert_eq!(
+            agent_state.staged_entry(&id, &test_entry_b().address()),
+            Some(test_entry_b())
+        );
+        assert_eq!(
+            agent_state.staged_entry(
+                &snowflake::ProcessUniqueId::new(),
+                &test_entry_b().address()
+            ),
+            None
+        );
+        assert_eq!(agent_state.top_chain_header(), None);
+
+        let action_wrapper = ActionWrapper::new(Action::CommitBundle(id.clone()));
+        reduce_commit_bundle(&mut agent_state, &state, &action_wrapper);
+
+        assert_eq!(
+            agent_state.actions().get(&action_wrapper),
+            Some(&ActionResponse::CommitBundle(Ok(vec![
+                test_entry().address(),
+                test_entry_b().address()
+            ]))),
+        );
+        assert_eq!(agent_state.bundle(&id), None);
+        assert_eq!(
+            agent_state.top_chain_header().unwrap().entry_address(),
+            &test_entry_b().address()
+        );
+        assert_eq!(agent_state.iter_chain().count(), 2);
+    }
+
+    #[test]
+    /// test that discarding a bundle drops its entries without touching the chain
+    fn test_reduce_discard_bundle() {
+        let netname = Some("test_reduce_discard_bundle");
+        let context = test_context("bob", netname);
+        let state = State::new_with_agent(
+            context.clone(),
+            test_agent_state(Some(context.agent_id().address())),
+        );
+        let id = snowflake::ProcessUniqueId::new();
+        let mut agent_state = test_agent_state_with_bundle(&state, id.clone());
+
+        reduce_discard_bundle(
+            &mut agent_state,
+            &state,
+            &ActionWrapper::new(Action::DiscardBundle(id.clone())),
+        );
+
+        assert_eq!(agent_state.bundle(&id), None);
+        assert_eq!(agent_state.staged_entry(&id, &test_entry().address()), None);
+        assert_eq!(agent_state.top_chain_header(), None);
+    }
+
     #[test]
     /// test response to json
     fn test_commit_response_to_json() {
@@ -417,7 +734,7 @@ pub mod tests {
     fn test_create_new_chain_header() {
         let netname = Some("test_create_new_chain_header");
         let context = test_context("bob", netname);
-        let agent_state = test_agent_state(Some(context.agent_id.address()));
+        let agent_state = test_agent_state(Some(context.agent_id().address()));
         let state = State::new_with_agent(context.clone(), agent_state.clone());
 
         let header = create_new_chain_header(
@@ -428,7 +745,7 @@ pub mod tests {
             &vec![],
         )
         .unwrap();
-        let agent_id = context.agent_id.clone();
+        let agent_id = context.agent_id();
         assert_eq!(
             header,
             ChainHeader::new(
@@ -446,4 +763,29 @@ pub mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_create_new_chain_header_after_uncommitted_headers() {
+        let netname = Some("test_create_new_chain_header_after_uncommitted_headers");
+        let context = test_context("bob", netname);
+        let agent_state = test_agent_state(Some(context.agent_id().address()));
+        let state = StateWrapper::from(State::new_with_agent(
+            context.clone(),
+            agent_state.clone(),
+        ));
+
+        let first =
+            create_new_chain_header(&test_entry(), &agent_state, &state, &None, &vec![]).unwrap();
+        let second = create_new_chain_header_after(
+            &test_entry_with_value("second"),
+            &agent_state,
+            &state,
+            &None,
+            &vec![],
+            &[first.clone()],
+        )
+        .unwrap();
+        assert_eq!(second.link(), Some(first.address()));
+        assert_eq!(second.link_same_type(), Some(first.address()));
+    }
 }
@@ -61,6 +61,17 @@ impl ConductorApi {
         send_json_rpc(self.0.clone(), payload, request_response)
     }
 
+    /// Makes the conductor switch the agent over to the keybundle it prepared for a key
+    /// rotation. Returns the public signing key the agent has from now on.
+    /// Gets called by `update_agent` once the new agent entry is committed.
+    pub fn complete_key_rotation(&self) -> Result<String, HolochainError> {
+        send_json_rpc(
+            self.0.clone(),
+            String::new(),
+            (String::from("key_rotation/complete"), String::from("pub_key")),
+        )
+    }
+
     pub fn get(&self) -> &Arc<RwLock<IoHandler>> {
         &self.0
     }
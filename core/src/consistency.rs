@@ -1,5 +1,5 @@
 use crate::{
-    action::Action, context::Context, entry::CanPublish,
+    action::Action, agent::bundle::BundleId, context::Context, entry::CanPublish,
     network::entry_with_header::EntryWithHeader,
 };
 use holochain_core_types::{agent::AgentId, entry::Entry, link::link_data::LinkData};
@@ -67,7 +67,10 @@ pub enum ConsistencyEvent {
     AddLink(LinkData),                                    // <- Publish, entry_type=LinkAdd
     RemoveLink(Entry),                                    // <- Publish, entry_type=LinkRemove
     RemovePendingValidation(Address),                     // <- AddPendingValidation
+    DeadLetterValidation(Address),                        // <- AddPendingValidation, given up
     ReturnZomeFunctionResult(snowflake::ProcessUniqueId), // <- SignalZomeFunctionCall
+    Evict(Address),                                       // <- retention policy
+    HoldWarrant(Address),                                 // <- invalid data of this agent
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -88,6 +91,10 @@ pub struct ConsistencyModel {
     // later, when the corresponding Publish has been processed
     commit_cache: HashMap<Address, ConsistencySignalE>,
 
+    // entries staged in open commit bundles, which get cached like commits
+    // once their bundle gets committed
+    bundles: HashMap<BundleId, Vec<(Entry, Option<Address>)>>,
+
     // Stores the AgentId, once it has been committed
     agent_id: Option<AgentId>,
 
@@ -99,11 +106,48 @@ impl ConsistencyModel {
     pub fn new(context: Arc<Context>) -> Self {
         Self {
             commit_cache: HashMap::new(),
+            bundles: HashMap::new(),
             agent_id: None,
             context,
         }
     }
 
+    fn cache_commit(&mut self, entry: &Entry, crud_link: &Option<Address>) {
+        use ConsistencyEvent::*;
+        use ConsistencyGroup::*;
+        // XXX: Since can_publish relies on a properly initialized Context, there are a few ways
+        // can_publish can fail. If we hit the possiblity of failure, just add the commit to the cache
+        // anyway. The only reason to check is to avoid filling up the cache unnecessarily with
+        // commits that will never be published.
+        let do_cache = self.context.state().is_none()
+            || self.context.get_dna().is_none()
+            || entry.entry_type().can_publish(&self.context);
+
+        // If entry is publishable, construct the ConsistencySignal that should be emitted
+        // when the entry is finally published, and save it for later
+        if do_cache {
+            let address = entry.address();
+            let hold = Hold(address.clone());
+            let meta = crud_link.clone().and_then(|crud| match entry {
+                Entry::App(_, _) => Some(UpdateEntry(crud, address.clone())),
+                Entry::Deletion(_) => Some(RemoveEntry(crud, address.clone())),
+                Entry::LinkAdd(link_data) => Some(AddLink(link_data.clone())),
+                Entry::LinkRemove(_) => Some(RemoveLink(entry.clone())),
+                // Question: Why does Entry::LinkAdd take LinkData instead of Link?
+                // as of now, link data contains more information than just the link
+                _ => None,
+            });
+            let mut pending = vec![hold];
+            meta.map(|m| pending.push(m));
+            let signal = ConsistencySignal::new_pending(
+                Publish(address.clone()),
+                Validators,
+                pending,
+            );
+            self.commit_cache.insert(address, signal);
+        }
+    }
+
     pub fn process_action(&mut self, action: &Action) -> Option<ConsistencySignalE> {
         use ConsistencyEvent::*;
         use ConsistencyGroup::*;
@@ -114,39 +158,26 @@ impl ConsistencyModel {
             }
 
             Action::Commit((entry, crud_link, _)) => {
-                // XXX: Since can_publish relies on a properly initialized Context, there are a few ways
-                // can_publish can fail. If we hit the possiblity of failure, just add the commit to the cache
-                // anyway. The only reason to check is to avoid filling up the cache unnecessarily with
-                // commits that will never be published.
-                let do_cache = self.context.state().is_none()
-                    || self.context.get_dna().is_none()
-                    || entry.entry_type().can_publish(&self.context);
-
-                // If entry is publishable, construct the ConsistencySignal that should be emitted
-                // when the entry is finally published, and save it for later
-                if do_cache {
-                    let address = entry.address();
-                    let hold = Hold(address.clone());
-                    let meta = crud_link.clone().and_then(|crud| match entry {
-                        Entry::App(_, _) => Some(UpdateEntry(crud, address.clone())),
-                        Entry::Deletion(_) => Some(RemoveEntry(crud, address.clone())),
-                        Entry::LinkAdd(link_data) => Some(AddLink(link_data.clone())),
-                        Entry::LinkRemove(_) => Some(RemoveLink(entry.clone())),
-                        // Question: Why does Entry::LinkAdd take LinkData instead of Link?
-                        // as of now, link data contains more information than just the link
-                        _ => None,
-                    });
-                    let mut pending = vec![hold];
-                    meta.map(|m| pending.push(m));
-                    let signal = ConsistencySignal::new_pending(
-                        Publish(address.clone()),
-                        Validators,
-                        pending,
-                    );
-                    self.commit_cache.insert(address, signal);
+                self.cache_commit(entry, crud_link);
+                None
+            }
+            Action::StageEntry((id, staged)) => {
+                self.bundles.entry(id.clone()).or_insert_with(Vec::new).push((
+                    staged.entry.clone(),
+                    staged.maybe_link_update_delete.clone(),
+                ));
+                None
+            }
+            Action::CommitBundle(id) => {
+                for (entry, crud_link) in self.bundles.remove(id).unwrap_or_default() {
+                    self.cache_commit(&entry, &crud_link);
                 }
                 None
             }
+            Action::DiscardBundle(id) => {
+                self.bundles.remove(id);
+                None
+            }
             Action::Publish(address) => {
                 // Emit the signal that was created when observing the corresponding Commit
                 let maybe_signal = self.commit_cache.remove(address);
@@ -173,6 +204,12 @@ impl ConsistencyModel {
             Action::RemoveLink(entry) => Some(ConsistencySignal::new_terminal(
                 ConsistencyEvent::RemoveLink(entry.clone()),
             )),
+            Action::EvictEntry(address) => Some(ConsistencySignal::new_terminal(
+                ConsistencyEvent::Evict(address.clone()),
+            )),
+            Action::HoldWarrant(warrant) => warrant
+                .author()
+                .map(|author| ConsistencySignal::new_terminal(HoldWarrant(author))),
 
             Action::AddPendingValidation(validation) => {
                 let address = validation.entry_with_header.entry.address();
@@ -185,6 +222,9 @@ impl ConsistencyModel {
             Action::RemovePendingValidation((address, _)) => Some(ConsistencySignal::new_terminal(
                 RemovePendingValidation(address.clone()),
             )),
+            Action::AddDeadLetterValidation(dead_letter) => Some(ConsistencySignal::new_terminal(
+                DeadLetterValidation(dead_letter.pending.entry_with_header.entry.address()),
+            )),
 
             Action::SignalZomeFunctionCall(call) => Some(ConsistencySignal::new_pending(
                 SignalZomeFunctionCall(call.id()),
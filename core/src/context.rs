@@ -1,9 +1,17 @@
 use crate::{
     action::{Action, ActionWrapper},
+    agent::receipt_store::ReceiptStore,
     conductor_api::ConductorApi,
+    dht::retention::{EvictableStorage, RetentionPolicy},
     instance::Observer,
-    nucleus::actions::get_entry::get_entry_from_cas,
+    nucleus::{
+        actions::get_entry::get_entry_from_cas,
+        ribosome::{fuel::FuelLimits, pool::InstancePools, profile::Profiler},
+        validation::{cache::ValidationCache, custom_package::CustomPackageDefinitions},
+    },
     persister::Persister,
+    quota::{QuotaStatus, StorageQuota, StorageUsage},
+    scheduled_jobs::pending_validations::ValidationWorkers,
     signal::{Signal, SignalSender},
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
@@ -30,8 +38,10 @@ use holochain_persistence_api::{
     },
     eav::EntityAttributeValueStorage,
 };
+use holochain_persistence_mem::eav::memory::EavMemoryStorage;
 use jsonrpc_core::{self, IoHandler};
 use std::{
+    path::PathBuf,
     sync::{Arc, Mutex, RwLock, RwLockReadGuard},
     thread::sleep,
     time::Duration,
@@ -46,7 +56,8 @@ use test_utils::mock_signing::mock_conductor_api;
 #[derive(Clone)]
 pub struct Context {
     pub(crate) instance_name: String,
-    pub agent_id: AgentId,
+    /// The agent the instance runs as, which changes when the agent rotates its key
+    agent_id: Arc<RwLock<AgentId>>,
     pub persister: Arc<Mutex<dyn Persister>>,
     state: Option<Arc<RwLock<StateWrapper>>>,
     pub action_channel: Option<Sender<ActionWrapper>>,
@@ -54,6 +65,30 @@ pub struct Context {
     pub chain_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
     pub dht_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
     pub eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    /// Index of the source chain's headers, kept apart from the DHT's EAV storage
+    /// so that DHT eviction can't touch it
+    pub chain_index_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    pub eviction_storage: Option<Arc<dyn EvictableStorage>>,
+    pub retention_policy: RetentionPolicy,
+    pub storage_usage: Arc<StorageUsage>,
+    pub storage_quota: StorageQuota,
+    pub fuel_limits: FuelLimits,
+    /// Maximum number of pages of linear memory a WASM call may use
+    pub wasm_memory_limit: Option<u32>,
+    /// Reusable WASM instances of the zomes of the instance's DNA
+    pub wasm_instance_pools: Arc<InstancePools>,
+    /// Deadline of zome calls that don't come with one of their own
+    pub zome_call_timeout: Option<Duration>,
+    /// Profiles of the instance's zome calls, if profiling is enabled
+    pub profiler: Option<Arc<Profiler>>,
+    /// Whether the instance's WASM gets instrumented to report where failing calls were
+    pub wasm_backtraces: bool,
+    /// Directory that zome calls get recorded to, if recording is enabled
+    pub zome_call_recordings: Option<PathBuf>,
+    /// Threads that retry the instance's pending validations
+    pub validation_workers: Arc<ValidationWorkers>,
+    /// Which entry and link types of the DNA have a custom validation package
+    pub custom_package_definitions: Arc<CustomPackageDefinitions>,
     pub p2p_config: P2pConfig,
     pub conductor_api: ConductorApi,
     pub(crate) signal_tx: Option<Sender<Signal>>,
@@ -102,7 +137,7 @@ impl Context {
     ) -> Self {
         Context {
             instance_name: instance_name.to_owned(),
-            agent_id: agent_id.clone(),
+            agent_id: Arc::new(RwLock::new(agent_id.clone())),
             persister,
             state: None,
             action_channel: None,
@@ -111,6 +146,20 @@ impl Context {
             chain_storage,
             dht_storage,
             eav_storage: eav,
+            chain_index_storage: Arc::new(RwLock::new(EavMemoryStorage::new())),
+            eviction_storage: None,
+            retention_policy: RetentionPolicy::default(),
+            storage_usage: Arc::new(StorageUsage::default()),
+            storage_quota: StorageQuota::default(),
+            fuel_limits: FuelLimits::default(),
+            wasm_memory_limit: None,
+            wasm_instance_pools: Arc::new(InstancePools::default()),
+            zome_call_timeout: None,
+            profiler: None,
+            wasm_backtraces: false,
+            zome_call_recordings: None,
+            validation_workers: Arc::new(ValidationWorkers::default()),
+            custom_package_definitions: Arc::new(CustomPackageDefinitions::default()),
             p2p_config,
             conductor_api: ConductorApi::new(Self::test_check_conductor_api(
                 conductor_api,
@@ -135,7 +184,7 @@ impl Context {
     ) -> Result<Context, HolochainError> {
         Ok(Context {
             instance_name: instance_name.to_owned(),
-            agent_id: agent_id.clone(),
+            agent_id: Arc::new(RwLock::new(agent_id.clone())),
             persister,
             state: None,
             action_channel,
@@ -144,6 +193,20 @@ impl Context {
             chain_storage: cas.clone(),
             dht_storage: cas,
             eav_storage: eav,
+            chain_index_storage: Arc::new(RwLock::new(EavMemoryStorage::new())),
+            eviction_storage: None,
+            retention_policy: RetentionPolicy::default(),
+            storage_usage: Arc::new(StorageUsage::default()),
+            storage_quota: StorageQuota::default(),
+            fuel_limits: FuelLimits::default(),
+            wasm_memory_limit: None,
+            wasm_instance_pools: Arc::new(InstancePools::default()),
+            zome_call_timeout: None,
+            profiler: None,
+            wasm_backtraces: false,
+            zome_call_recordings: None,
+            validation_workers: Arc::new(ValidationWorkers::default()),
+            custom_package_definitions: Arc::new(CustomPackageDefinitions::default()),
             p2p_config,
             conductor_api: ConductorApi::new(Self::test_check_conductor_api(None, agent_id)),
             instance_is_alive: Arc::new(Mutex::new(true)),
@@ -156,6 +219,17 @@ impl Context {
         self.instance_name.clone()
     }
 
+    /// Returns the agent this instance currently runs as.
+    pub fn agent_id(&self) -> AgentId {
+        self.agent_id.read().unwrap().clone()
+    }
+
+    /// Switches the instance over to the given agent after it rotated its key.
+    /// All clones of this context see the new agent.
+    pub fn set_agent_id(&self, agent_id: AgentId) {
+        *self.agent_id.write().unwrap() = agent_id;
+    }
+
     pub fn set_state(&mut self, state: Arc<RwLock<StateWrapper>>) {
         self.state = Some(state);
     }
@@ -240,6 +314,37 @@ impl Context {
             .expect("Observer channel not initialized")
     }
 
+    /// Checks the storage usage of the instance against its quota.
+    /// Returns `HolochainError::QuotaExceeded` once a hard limit is reached,
+    /// crossing a soft limit only gets logged.
+    pub fn check_storage_quota(&self) -> HcResult<()> {
+        match self.storage_quota.check(&self.storage_usage.snapshot()) {
+            QuotaStatus::Ok => Ok(()),
+            QuotaStatus::SoftLimitExceeded(message) => {
+                log_warn!(
+                    self,
+                    "context/check_storage_quota: soft storage limit exceeded: {}",
+                    message
+                );
+                Ok(())
+            }
+            QuotaStatus::HardLimitExceeded(message) => Err(HolochainError::QuotaExceeded(
+                format!("instance {}: {}", self.instance_name, message),
+            )),
+        }
+    }
+
+    /// The store of the validation receipts we got for the data we published.
+    pub fn receipt_store(&self) -> ReceiptStore {
+        ReceiptStore::new(self.chain_storage.clone(), self.eav_storage.clone())
+    }
+
+    /// The cache of results of validations this instance already ran.
+    pub fn validation_cache(&self) -> ValidationCache {
+        ValidationCache::new(self.dht_storage.clone(), self.eav_storage.clone())
+            .with_eviction_storage(self.eviction_storage.clone())
+    }
+
     pub fn instance_still_alive(&self) -> bool {
         *self.instance_is_alive.lock().unwrap()
     }
@@ -397,6 +502,35 @@ pub mod tests {
         guard.flush();
     }
 
+    #[test]
+    fn clones_of_a_context_see_the_new_agent() {
+        let file_storage = Arc::new(RwLock::new(
+            FilesystemStorage::new(tempdir().unwrap().path().to_str().unwrap()).unwrap(),
+        ));
+        let ctx = Context::new(
+            "test",
+            AgentId::generate_fake("Bilbo"),
+            Arc::new(Mutex::new(SimplePersister::new(file_storage.clone()))),
+            file_storage.clone(),
+            file_storage.clone(),
+            Arc::new(RwLock::new(
+                EavFileStorage::new(tempdir().unwrap().path().to_str().unwrap().to_string())
+                    .unwrap(),
+            )),
+            P2pConfig::new_with_unique_memory_backend(),
+            None,
+            None,
+            false,
+        );
+        let clone = ctx.clone();
+
+        let new_agent = AgentId::generate_fake("Frodo");
+        ctx.set_agent_id(new_agent.clone());
+
+        assert_eq!(ctx.agent_id(), new_agent);
+        assert_eq!(clone.agent_id(), new_agent);
+    }
+
     #[test]
     #[should_panic]
     #[cfg(not(windows))] // RwLock does not panic on windows since mutexes are recursive
@@ -0,0 +1,53 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    instance::dispatch_action,
+    network::warrant::Warrant,
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::error::HolochainError;
+use holochain_persistence_api::cas::content::Address;
+use std::{pin::Pin, sync::Arc};
+
+/// HoldWarrant Action Creator
+/// This action creator dispatches a HoldWarrant action which is consumed by the DHT reducer.
+/// Note that this function does not verify the warrant.
+///
+/// Returns a future that resolves to the address of the warranted agent or an Err(HolochainError).
+pub fn hold_warrant(warrant: &Warrant, context: &Arc<Context>) -> HoldWarrantFuture {
+    let action_wrapper = ActionWrapper::new(Action::HoldWarrant(warrant.clone()));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+
+    HoldWarrantFuture {
+        context: context.clone(),
+        action: action_wrapper,
+    }
+}
+
+pub struct HoldWarrantFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+}
+
+impl Future for HoldWarrantFuture {
+    type Output = Result<Address, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("HoldWarrantFuture") {
+            return Poll::Ready(Err(err));
+        }
+        //
+        // TODO: connect the waker to state updates for performance reasons
+        // See: https://github.com/holochain/holochain-rust/issues/314
+        //
+        cx.waker().clone().wake();
+        if let Some(state) = self.context.state() {
+            match state.dht().actions().get(&self.action) {
+                Some(result) => Poll::Ready(result.clone()),
+                None => Poll::Pending,
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
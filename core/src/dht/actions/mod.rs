@@ -1,5 +1,6 @@
 pub mod add_link;
 pub mod hold;
+pub mod hold_warrant;
 pub mod remove_entry;
 pub mod remove_link;
 pub mod update_entry;
@@ -7,7 +7,10 @@
 ///
 /// It is up to the calling reducer function whether the new state object should be kept and what to do with the return value
 ///
-use crate::dht::dht_store::DhtStore;
+use crate::{
+    dht::dht_store::DhtStore,
+    network::warrant::{header_author, Warrant},
+};
 use holochain_core_types::{
     crud_status::{create_crud_link_eav, create_crud_status_eav, CrudStatus},
     eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
@@ -144,3 +147,38 @@ pub(crate) fn reduce_remove_entry_inner(
 
     Ok(latest_deleted_address.clone())
 }
+
+/// Stores the warrant, indexes it under the warranted agent and marks the warranted header
+/// as well as the headers of that agent of all held entries, as indexed by
+/// `DhtStore::add_header_for_entry`, as rejected.
+/// Only the headers get rejected, so content that other agents published too stays valid.
+pub(crate) fn reduce_hold_warrant_inner(
+    store: &mut DhtStore,
+    warrant: &Warrant,
+) -> HcResult<Address> {
+    let author = warrant
+        .author()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Warrant has no author".into()))?;
+    (*store.content_storage().write()?).add(warrant)?;
+    let warrant_eav =
+        EntityAttributeValueIndex::new(&author, &Attribute::Warrant, &warrant.address())?;
+    (*store.meta_storage().write()?).add_eavi(&warrant_eav)?;
+
+    let mut rejected = BTreeSet::new();
+    rejected.insert(warrant.header.address());
+    for entry_address in store.get_authored_entries(&author)? {
+        rejected.extend(
+            store
+                .get_headers(entry_address)?
+                .iter()
+                .filter(|header| header_author(header).as_ref() == Some(&author))
+                .map(|header| header.address()),
+        );
+    }
+    for header_address in rejected {
+        let status_eav = create_crud_status_eav(&header_address, CrudStatus::Rejected)?;
+        (*store.meta_storage().write()?).add_eavi(&status_eav)?;
+    }
+
+    Ok(author)
+}
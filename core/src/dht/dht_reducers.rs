@@ -8,8 +8,8 @@ use crate::{
 use std::sync::Arc;
 
 use super::dht_inner_reducers::{
-    reduce_add_remove_link_inner, reduce_remove_entry_inner, reduce_store_entry_inner,
-    reduce_update_entry_inner, LinkModification,
+    reduce_add_remove_link_inner, reduce_hold_warrant_inner, reduce_remove_entry_inner,
+    reduce_store_entry_inner, reduce_update_entry_inner, LinkModification,
 };
 
 use holochain_core_types::entry::Entry;
@@ -44,6 +44,8 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<DhtReducer> {
         Action::RemoveEntry(_) => Some(reduce_remove_entry),
         Action::AddLink(_) => Some(reduce_add_link),
         Action::RemoveLink(_) => Some(reduce_remove_link),
+        Action::EvictEntry(_) => Some(reduce_evict_entry),
+        Action::HoldWarrant(_) => Some(reduce_hold_warrant),
         _ => None,
     }
 }
@@ -122,6 +124,28 @@ pub(crate) fn reduce_remove_link(
     Some(store)
 }
 
+pub(crate) fn reduce_evict_entry(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let address = unwrap_to!(action_wrapper.action() => Action::EvictEntry);
+    let mut new_store = (*old_store).clone();
+    let res = new_store.evict_entry(address).map(|_| address.clone());
+    new_store.actions_mut().insert(action_wrapper.clone(), res);
+    Some(new_store)
+}
+
+pub(crate) fn reduce_hold_warrant(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let warrant = unwrap_to!(action_wrapper.action() => Action::HoldWarrant);
+    let mut new_store = (*old_store).clone();
+    let res = reduce_hold_warrant_inner(&mut new_store, warrant);
+    new_store.actions_mut().insert(action_wrapper.clone(), res);
+    Some(new_store)
+}
+
 pub(crate) fn reduce_update_entry(
     old_store: &DhtStore,
     action_wrapper: &ActionWrapper,
@@ -160,11 +184,14 @@ pub mod tests {
     use crate::{
         action::{Action, ActionWrapper},
         dht::{
-            dht_reducers::{reduce, reduce_hold_entry},
+            dht_reducers::{reduce, reduce_evict_entry, reduce_hold_entry, reduce_hold_warrant},
             dht_store::create_get_links_eavi_query,
         },
         instance::tests::test_context,
-        network::entry_with_header::EntryWithHeader,
+        network::{
+            entry_with_header::EntryWithHeader,
+            warrant::tests::{signed_header, test_warrant},
+        },
         state::test_store,
     };
     use holochain_core_types::{
@@ -176,6 +203,7 @@ pub mod tests {
     };
     use holochain_persistence_api::cas::content::AddressableContent;
     use std::convert::TryFrom;
+    use test_utils::mock_signing::registered_test_agent;
 
     #[test]
     fn reduce_hold_entry_test() {
@@ -433,4 +461,85 @@ pub mod tests {
         assert_eq!(&entry, &result_entry,);
     }
 
+    #[test]
+    fn reduce_evict_entry_test() {
+        let context = test_context("bob", None);
+        let store = test_store(context);
+
+        let entry = test_entry();
+        let entry_wh = EntryWithHeader {
+            entry: entry.clone(),
+            header: test_chain_header(),
+        };
+        let held_dht_store =
+            reduce_hold_entry(&store.dht(), &ActionWrapper::new(Action::Hold(entry_wh)))
+                .expect("there should be a new store for holding an entry");
+        assert_eq!(
+            held_dht_store.get_all_held_entry_addresses(),
+            &vec![entry.address()]
+        );
+
+        let action_wrapper = ActionWrapper::new(Action::EvictEntry(entry.address()));
+        let evicted_dht_store = reduce_evict_entry(&held_dht_store, &action_wrapper)
+            .expect("there should be a new store for evicting an entry");
+        assert!(evicted_dht_store.get_all_held_entry_addresses().is_empty());
+        assert_eq!(
+            evicted_dht_store.actions().get(&action_wrapper),
+            Some(&Ok(entry.address()))
+        );
+    }
+
+    #[test]
+    fn reduce_hold_warrant_test() {
+        let context = test_context("bob", None);
+        let store = test_store(context);
+
+        let author = registered_test_agent("dht-warrant-author");
+        let validator = registered_test_agent("dht-warrant-validator");
+        let warrant = test_warrant(&author, &validator);
+        let held_dht_store = reduce_hold_entry(
+            &store.dht(),
+            &ActionWrapper::new(Action::Hold(warrant.entry_with_header())),
+        )
+        .expect("there should be a new store for holding an entry");
+        assert!(!held_dht_store.is_warranted(&author.address()));
+
+        let action_wrapper = ActionWrapper::new(Action::HoldWarrant(warrant.clone()));
+        let warranted_dht_store = reduce_hold_warrant(&held_dht_store, &action_wrapper)
+            .expect("there should be a new store for holding a warrant");
+        assert_eq!(
+            warranted_dht_store.actions().get(&action_wrapper),
+            Some(&Ok(author.address()))
+        );
+        assert!(warranted_dht_store.is_warranted(&author.address()));
+        assert!(!warranted_dht_store.is_warranted(&validator.address()));
+        assert_eq!(
+            warranted_dht_store.get_warrants(&author.address()).unwrap(),
+            vec![warrant.clone()]
+        );
+
+        assert!(warranted_dht_store
+            .is_rejected_header(&warrant.header.address())
+            .unwrap());
+        assert!(warranted_dht_store
+            .is_rejected(&warrant.entry.address())
+            .unwrap());
+
+        // The same content published by an agent without a warrant stays valid
+        let entry_with_header = EntryWithHeader {
+            entry: warrant.entry.clone(),
+            header: signed_header(&validator, &warrant.entry),
+        };
+        let held_again_dht_store = reduce_hold_entry(
+            &warranted_dht_store,
+            &ActionWrapper::new(Action::Hold(entry_with_header.clone())),
+        )
+        .expect("there should be a new store for holding an entry");
+        assert!(!held_again_dht_store
+            .is_rejected_header(&entry_with_header.header.address())
+            .unwrap());
+        assert!(!held_again_dht_store
+            .is_rejected(&warrant.entry.address())
+            .unwrap());
+    }
 }
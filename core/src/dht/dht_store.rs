@@ -1,4 +1,8 @@
-use crate::action::ActionWrapper;
+use crate::{
+    action::ActionWrapper,
+    dht::retention::EvictableStorage,
+    network::warrant::{header_author, Warrant},
+};
 use holochain_core_types::{
     chain_header::ChainHeader,
     crud_status::CrudStatus,
@@ -20,7 +24,7 @@ use crate::state::StateWrapper;
 use holochain_json_api::error::JsonResult;
 use holochain_persistence_api::cas::content::Content;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     convert::TryFrom,
     sync::{Arc, RwLock},
 };
@@ -37,6 +41,9 @@ pub struct DhtStore {
     /// All the entries that the network has told us to hold
     holding_list: Vec<Address>,
 
+    /// Removes evicted entries from the storages above, if they support it
+    eviction_storage: Option<Arc<dyn EvictableStorage>>,
+
     actions: HashMap<ActionWrapper, Result<Address, HolochainError>>,
 }
 
@@ -119,10 +126,20 @@ impl DhtStore {
             content_storage,
             meta_storage,
             holding_list: Vec::new(),
+            eviction_storage: None,
             actions: HashMap::new(),
         }
     }
 
+    /// Sets the storage that evicted entries get removed from.
+    pub fn with_eviction_storage(
+        mut self,
+        eviction_storage: Option<Arc<dyn EvictableStorage>>,
+    ) -> Self {
+        self.eviction_storage = eviction_storage;
+        self
+    }
+
     pub fn new_with_holding_list(
         content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
         meta_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
@@ -166,7 +183,8 @@ impl DhtStore {
                 Attribute::LinkTag(_, _)
                 | Attribute::RemovedLink(_, _)
                 | Attribute::CrudLink
-                | Attribute::CrudStatus => true,
+                | Attribute::CrudStatus
+                | Attribute::Warrant => true,
                 _ => false,
             }),
             None.into(),
@@ -210,7 +228,8 @@ impl DhtStore {
             })
     }
 
-    /// Add an entry and header to the CAS and EAV, respectively
+    /// Add an entry and header to the CAS and EAV, respectively,
+    /// and index the entry under the author of the header
     pub fn add_header_for_entry(
         &self,
         entry: &Entry,
@@ -223,9 +242,95 @@ impl DhtStore {
         )?;
         self.content_storage().write().unwrap().add(header)?;
         self.meta_storage().write().unwrap().add_eavi(&eavi)?;
+        if let Some(author) = header_author(header) {
+            let author_eavi = EntityAttributeValueIndex::new(
+                &author,
+                &Attribute::AuthoredEntry,
+                &entry.address(),
+            )?;
+            self.meta_storage().write().unwrap().add_eavi(&author_eavi)?;
+        }
         Ok(())
     }
 
+    /// Get the addresses of all entries we hold a header of by the given agent
+    pub fn get_authored_entries(
+        &self,
+        agent: &Address,
+    ) -> Result<HashSet<Address>, HolochainError> {
+        Ok(self
+            .meta_storage
+            .read()?
+            .fetch_eavi(&EaviQuery::new(
+                Some(agent.to_owned()).into(),
+                Some(Attribute::AuthoredEntry).into(),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))?
+            .into_iter()
+            .map(|eavi| eavi.value())
+            .collect())
+    }
+
+    fn get_warrant_eavis(
+        &self,
+        agent: &Address,
+    ) -> Result<BTreeSet<EntityAttributeValueIndex>, HolochainError> {
+        Ok(self.meta_storage.read()?.fetch_eavi(&EaviQuery::new(
+            Some(agent.to_owned()).into(),
+            Some(Attribute::Warrant).into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?)
+    }
+
+    /// Get all warrants we hold against the given agent
+    pub fn get_warrants(&self, agent: &Address) -> Result<Vec<Warrant>, HolochainError> {
+        let content_storage = self.content_storage.read()?;
+        self.get_warrant_eavis(agent)?
+            .into_iter()
+            .filter_map(|eavi| content_storage.fetch(&eavi.value()).transpose())
+            .map(|content| Ok(Warrant::try_from_content(&content?)?))
+            .collect()
+    }
+
+    /// Whether we hold a warrant against the given agent
+    pub fn is_warranted(&self, agent: &Address) -> bool {
+        self.get_warrant_eavis(agent)
+            .map(|eavis| !eavis.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Whether the header with the given address got rejected by a warrant against its author
+    pub fn is_rejected_header(&self, header_address: &Address) -> Result<bool, HolochainError> {
+        Ok(self
+            .meta_storage
+            .read()?
+            .fetch_eavi(&EaviQuery::new(
+                Some(header_address.to_owned()).into(),
+                Some(Attribute::CrudStatus).into(),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))?
+            .iter()
+            .any(|eavi| String::from(eavi.value()) == String::from(CrudStatus::Rejected)))
+    }
+
+    /// Whether all headers we hold of the given entry got rejected.
+    /// The same content published by agents without a warrant stays valid.
+    pub fn is_rejected(&self, entry_address: &Address) -> Result<bool, HolochainError> {
+        let headers = self.get_headers(entry_address.clone())?;
+        for header in &headers {
+            if !self.is_rejected_header(&header.address())? {
+                return Ok(false);
+            }
+        }
+        Ok(!headers.is_empty())
+    }
+
     pub fn mark_entry_as_held(&mut self, entry: &Entry) {
         self.holding_list.push(entry.address());
     }
@@ -234,6 +339,22 @@ impl DhtStore {
         &self.holding_list
     }
 
+    /// Drops the given entry from the holding list and, if the storage supports it,
+    /// removes the entry, its headers and its metadata from CAS and EAV storage.
+    pub(crate) fn evict_entry(&mut self, address: &Address) -> Result<(), HolochainError> {
+        if let Some(ref eviction_storage) = self.eviction_storage {
+            let mut addresses: Vec<Address> = self
+                .get_headers(address.clone())?
+                .iter()
+                .map(|header| header.address())
+                .collect();
+            addresses.push(address.clone());
+            eviction_storage.evict(&addresses)?;
+        }
+        self.holding_list.retain(|held| held != address);
+        Ok(())
+    }
+
     // Getters (for reducers)
     // =======
     pub(crate) fn content_storage(&self) -> Arc<RwLock<dyn ContentAddressableStorage>> {
@@ -275,6 +396,14 @@ pub mod tests {
         store.add_header_for_entry(&entry, &header1).unwrap();
         store.add_header_for_entry(&entry, &header2).unwrap();
         let headers = store.get_headers(entry.address()).unwrap();
-        assert_eq!(headers, vec![header1, header2]);
+        assert_eq!(headers, vec![header1.clone(), header2]);
+
+        let author = header_author(&header1).unwrap();
+        let authored = store.get_authored_entries(&author).unwrap();
+        assert_eq!(authored.into_iter().collect::<Vec<_>>(), vec![entry.address()]);
+        assert!(store
+            .get_authored_entries(&Address::from("someone else"))
+            .unwrap()
+            .is_empty());
     }
 }
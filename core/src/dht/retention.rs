@@ -0,0 +1,336 @@
+//! Retention of the local DHT shard.
+//!
+//! Without a retention policy an instance holds every entry the network ever asked it to hold.
+//! A `RetentionPolicy` lets the instance evict held entries that are outside of its
+//! responsibility arc, older than a maximum age, or that make the shard exceed a storage budget.
+//! Entries of the instance's own source chain are never evicted since chain and DHT share
+//! their content storage.
+//!
+//! Evicting an entry removes it from the holding list, so it is no longer advertised in gossip.
+//! If the instance's storage implements `EvictableStorage`, its content, headers and metadata get
+//! removed from the CAS and EAV storage as well.
+
+use crate::dht::dht_store::DhtStore;
+use chrono::{DateTime, FixedOffset};
+use holochain_core_types::{chain_header::ChainHeader, error::HcResult};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use multihash::Hash;
+use std::{collections::HashSet, fmt::Debug};
+
+/// Configures which held DHT entries an instance evicts.
+/// All limits are optional, and the default policy retains everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionPolicy {
+    /// Entries whose newest header is older than this many seconds get evicted.
+    pub max_age_secs: Option<u64>,
+    /// Entries get evicted oldest first until the held content fits into this many bytes.
+    pub max_held_bytes: Option<u64>,
+    /// Fraction of the DHT's address space, centered on the agent's own location, that this
+    /// instance is responsible for. Entries located outside of it get evicted.
+    pub arc_fraction: Option<f64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_secs.is_some() || self.max_held_bytes.is_some() || self.arc_fraction.is_some()
+    }
+}
+
+/// Why an entry got evicted.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum EvictionReason {
+    OutOfArc,
+    Expired,
+    OverBudget,
+}
+
+/// Storage that can physically remove evicted DHT data.
+pub trait EvictableStorage: Debug + Send + Sync {
+    /// Removes the content stored at the given addresses together with all EAV rows
+    /// whose entity or value is one of them. Addresses that are not stored get skipped.
+    fn evict(&self, addresses: &[Address]) -> HcResult<()>;
+}
+
+/// The entry addresses of the instance's own source chain, which retention never evicts.
+/// Remembers the chain header it has seen last, so that updating it only walks the headers
+/// committed since.
+#[derive(Clone, Debug, Default)]
+pub struct AuthoredEntries {
+    top: Option<Address>,
+    addresses: HashSet<Address>,
+}
+
+impl AuthoredEntries {
+    /// Adds the entries of the given chain, which gets walked from the top down, up to the
+    /// header seen by the previous update. If that header is not part of the chain anymore,
+    /// the whole chain gets walked and replaces the entries collected before.
+    pub fn update<I: Iterator<Item = ChainHeader>>(&mut self, chain: I) {
+        let mut new_top = None;
+        let mut added = HashSet::new();
+        let mut reached_top = false;
+        for header in chain {
+            let address = header.address();
+            if Some(&address) == self.top.as_ref() {
+                reached_top = true;
+                break;
+            }
+            if new_top.is_none() {
+                new_top = Some(address);
+            }
+            added.insert(header.entry_address().clone());
+        }
+        if reached_top {
+            self.addresses.extend(added);
+        } else {
+            self.addresses = added;
+        }
+        if new_top.is_some() || !reached_top {
+            self.top = new_top;
+        }
+    }
+
+    pub fn addresses(&self) -> &HashSet<Address> {
+        &self.addresses
+    }
+}
+
+/// Location of the given address in the DHT's address space: the first four bytes
+/// of the SHA2-256 hash of the address.
+pub fn location(address: &Address) -> u32 {
+    let hash = multihash::encode(Hash::SHA2256, String::from(address.clone()).as_bytes())
+        .expect("SHA2-256 can hash any input");
+    // Skip the hash code and length prefix
+    (hash[2] as u32) << 24 | (hash[3] as u32) << 16 | (hash[4] as u32) << 8 | hash[5] as u32
+}
+
+/// Whether `location` lies within the arc spanning `arc_fraction` of the address space
+/// around `center`. The address space wraps around.
+pub fn in_arc(center: u32, location: u32, arc_fraction: f64) -> bool {
+    let distance = std::cmp::min(
+        location.wrapping_sub(center),
+        center.wrapping_sub(location),
+    );
+    f64::from(distance) <= arc_fraction * f64::from(u32::max_value()) / 2.0
+}
+
+struct HeldEntry {
+    address: Address,
+    timestamp: Option<i64>,
+    bytes: u64,
+}
+
+/// Selects the held entries of the given DHT shard that the policy evicts, given the location
+/// of the agent and the current time in seconds. Entries in `authored` never get evicted.
+/// Entries that exceed the storage budget get selected oldest first.
+pub fn select_evictions(
+    dht: &DhtStore,
+    authored: &HashSet<Address>,
+    policy: &RetentionPolicy,
+    agent_location: u32,
+    now: i64,
+) -> HcResult<Vec<(Address, EvictionReason)>> {
+    let mut evictions = Vec::new();
+    if !policy.is_enabled() {
+        return Ok(evictions);
+    }
+
+    let mut retained = Vec::new();
+    let mut seen = HashSet::new();
+    for address in dht.get_all_held_entry_addresses() {
+        if authored.contains(address) || !seen.insert(address.clone()) {
+            continue;
+        }
+        if let Some(arc_fraction) = policy.arc_fraction {
+            if !in_arc(agent_location, location(address), arc_fraction) {
+                evictions.push((address.clone(), EvictionReason::OutOfArc));
+                continue;
+            }
+        }
+        let timestamp = dht
+            .get_headers(address.clone())?
+            .iter()
+            .map(|header| DateTime::<FixedOffset>::from(header.timestamp()).timestamp())
+            .max();
+        if let (Some(max_age), Some(timestamp)) = (policy.max_age_secs, timestamp) {
+            if now.saturating_sub(timestamp) > max_age as i64 {
+                evictions.push((address.clone(), EvictionReason::Expired));
+                continue;
+            }
+        }
+        let bytes = match policy.max_held_bytes {
+            Some(_) => dht
+                .content_storage()
+                .read()?
+                .fetch(address)?
+                .map(|content| String::from(content).len() as u64)
+                .unwrap_or(0),
+            None => 0,
+        };
+        retained.push(HeldEntry {
+            address: address.clone(),
+            timestamp,
+            bytes,
+        });
+    }
+
+    if let Some(max_held_bytes) = policy.max_held_bytes {
+        // Entries without a known header time count as the newest ones
+        retained.sort_by_key(|entry| entry.timestamp.unwrap_or(now));
+        let mut held_bytes: u64 = retained.iter().map(|entry| entry.bytes).sum();
+        for entry in retained {
+            if held_bytes <= max_held_bytes {
+                break;
+            }
+            held_bytes -= entry.bytes;
+            evictions.push((entry.address, EvictionReason::OverBudget));
+        }
+    }
+
+    Ok(evictions)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        entry::{test_entry_a, test_entry_b, test_entry_c, Entry},
+        signature::Provenance,
+        time::Iso8601,
+    };
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+    use std::sync::{Arc, RwLock};
+
+    fn hold(store: &mut DhtStore, entry: &Entry, timestamp: i64) {
+        let header = header_for(entry, timestamp);
+        store.content_storage().write().unwrap().add(entry).unwrap();
+        store.mark_entry_as_held(&entry);
+        store.add_header_for_entry(&entry, &header).unwrap();
+    }
+
+    fn test_store() -> DhtStore {
+        let mut store = DhtStore::new(
+            Arc::new(RwLock::new(MemoryStorage::new())),
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+        );
+        hold(&mut store, &test_entry_a(), 1000);
+        hold(&mut store, &test_entry_b(), 2000);
+        hold(&mut store, &test_entry_c(), 3000);
+        store
+    }
+
+    #[test]
+    fn default_policy_evicts_nothing() {
+        let store = test_store();
+        let evictions =
+            select_evictions(&store, &HashSet::new(), &RetentionPolicy::default(), 0, 4000)
+                .unwrap();
+        assert!(evictions.is_empty());
+    }
+
+    #[test]
+    fn expired_entries_get_evicted_unless_authored() {
+        let store = test_store();
+        let policy = RetentionPolicy {
+            max_age_secs: Some(1500),
+            ..Default::default()
+        };
+        assert_eq!(
+            select_evictions(&store, &HashSet::new(), &policy, 0, 4000).unwrap(),
+            vec![
+                (test_entry_a().address(), EvictionReason::Expired),
+                (test_entry_b().address(), EvictionReason::Expired),
+            ]
+        );
+
+        let authored = vec![test_entry_a().address()].into_iter().collect();
+        assert_eq!(
+            select_evictions(&store, &authored, &policy, 0, 4000).unwrap(),
+            vec![(test_entry_b().address(), EvictionReason::Expired)]
+        );
+    }
+
+    #[test]
+    fn oldest_entries_get_evicted_over_budget() {
+        let store = test_store();
+        let entry_bytes = |entry: Entry| String::from(entry.content()).len() as u64;
+        let policy = RetentionPolicy {
+            max_held_bytes: Some(entry_bytes(test_entry_c())),
+            ..Default::default()
+        };
+        assert_eq!(
+            select_evictions(&store, &HashSet::new(), &policy, 0, 4000).unwrap(),
+            vec![
+                (test_entry_a().address(), EvictionReason::OverBudget),
+                (test_entry_b().address(), EvictionReason::OverBudget),
+            ]
+        );
+    }
+
+    fn header_for(entry: &Entry, timestamp: i64) -> ChainHeader {
+        ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &vec![Provenance::new("agent".into(), "sig".into())],
+            &None,
+            &None,
+            &None,
+            &Iso8601::from(timestamp),
+        )
+    }
+
+    #[test]
+    fn authored_entries_only_walk_new_headers() {
+        let a = header_for(&test_entry_a(), 1000);
+        let b = header_for(&test_entry_b(), 2000);
+        let c = header_for(&test_entry_c(), 3000);
+        let mut authored = AuthoredEntries::default();
+
+        authored.update(vec![b.clone(), a.clone()].into_iter());
+        assert_eq!(authored.addresses().len(), 2);
+
+        // Headers below the one seen last do not get visited again
+        let mut visited = 0;
+        authored.update(vec![c.clone(), b.clone(), a.clone()].into_iter().inspect(|_| {
+            visited += 1;
+        }));
+        assert_eq!(visited, 2);
+        assert_eq!(authored.addresses().len(), 3);
+        assert!(authored.addresses().contains(&test_entry_c().address()));
+
+        authored.update(vec![c.clone(), b.clone(), a.clone()].into_iter());
+        assert_eq!(authored.addresses().len(), 3);
+
+        // A chain that does not contain the header seen last replaces everything
+        authored.update(vec![a].into_iter());
+        assert_eq!(
+            authored.addresses(),
+            &vec![test_entry_a().address()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn arc_wraps_around_address_space() {
+        assert!(in_arc(0, 0, 0.0));
+        assert!(in_arc(u32::max_value() - 10, 10, 0.01));
+        assert!(!in_arc(0, u32::max_value() / 2, 0.5));
+        assert!(in_arc(0, u32::max_value() / 2, 1.0));
+    }
+
+    #[test]
+    fn entries_outside_of_arc_get_evicted() {
+        let store = test_store();
+        let center = location(&test_entry_a().address());
+        let policy = RetentionPolicy {
+            arc_fraction: Some(0.0),
+            ..Default::default()
+        };
+        let evictions = select_evictions(&store, &HashSet::new(), &policy, center, 4000).unwrap();
+        assert_eq!(
+            evictions,
+            vec![
+                (test_entry_b().address(), EvictionReason::OutOfArc),
+                (test_entry_c().address(), EvictionReason::OutOfArc),
+            ]
+        );
+    }
+}
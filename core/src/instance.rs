@@ -242,7 +242,7 @@ impl Instance {
             *state = new_state;
         }
 
-        if let Err(e) = self.save() {
+        if let Err(e) = self.save_action(action_wrapper) {
             log_error!(
                 context,
                 "instance/process_action: could not save state: {:?}",
@@ -335,6 +335,18 @@ impl Instance {
             .save(&self.state())
     }
 
+    /// Persists the changes the given action made to the state.
+    fn save_action(&self, action_wrapper: &ActionWrapper) -> HcResult<()> {
+        self.persister
+            .as_ref()
+            .ok_or(HolochainError::new(
+                "Instance::save_action() called without persister set.",
+            ))?
+            .try_lock()
+            .map_err(|_| HolochainError::new("Could not get lock on persister"))?
+            .save_action(action_wrapper, &self.state())
+    }
+
     pub async fn shutdown_network(&self) -> HcResult<()> {
         await!(network::actions::shutdown::shutdown(
             self.state.clone(),
@@ -537,7 +549,7 @@ pub mod tests {
         let agent_state = AgentState::new_with_top_chain_header(
             chain_store,
             Some(chain_header),
-            context.agent_id.address(),
+            context.agent_id().address(),
         );
         let state = StateWrapper::new_with_agent(Arc::new(context.clone()), agent_state);
         let global_state = Arc::new(RwLock::new(state));
@@ -833,7 +845,7 @@ pub mod tests {
         let netname = Some("can_commit_agent");
         // Create Context, Agent and Commit AgentIdEntry Action
         let context = test_context("alex", netname);
-        let agent_entry = Entry::AgentId(context.agent_id.clone());
+        let agent_entry = Entry::AgentId(context.agent_id());
         let commit_agent_action =
             ActionWrapper::new(Action::Commit((agent_entry.clone(), None, vec![])));
 
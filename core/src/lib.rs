@@ -40,6 +40,7 @@ pub mod logger;
 pub mod network;
 pub mod nucleus;
 pub mod persister;
+pub mod quota;
 pub mod scheduled_jobs;
 pub mod signal;
 pub mod state;
@@ -1,3 +1,4 @@
+use crate::network::validation_receipt::ValidationReceipt;
 use holochain_persistence_api::cas::content::Address;
 
 use holochain_json_api::{error::JsonError, json::JsonString};
@@ -34,4 +35,9 @@ pub enum DirectMessage {
     /// Option<> since there has to be a way to respond saying
     /// "I can't"
     ValidationPackage(Option<ValidationPackage>),
+
+    /// With this message a validator tells the author that it has
+    /// validated one of the author's aspects.
+    /// Gets sent without expecting a response.
+    ValidationReceipt(ValidationReceipt),
 }
@@ -17,6 +17,7 @@ use crate::{
             send::*,
             store::*,
         },
+        warrant::Warrant,
     },
     nucleus,
     workflows::get_entry_result::get_entry_with_meta_workflow,
@@ -25,7 +26,7 @@ use boolinator::*;
 use holochain_core_types::{eav::Attribute, entry::Entry, error::HolochainError, time::Timeout};
 use holochain_json_api::json::JsonString;
 use holochain_net::connection::net_connection::NetHandler;
-use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use lib3h_protocol::{
     data_types::{DirectMessageData, StoreEntryAspectData},
     protocol_server::Lib3hServerProtocol,
@@ -40,14 +41,28 @@ fn is_my_dna(my_dna_address: &String, dna_address: &String) -> bool {
 
 // FIXME: Temporary hack to ignore messages incorrectly sent to us by the networking
 // module that aren't really meant for us
+// Compares against the id the network got joined with, which stays the same until the
+// instance restarts, even if the agent rotates its key in the meantime.
 fn is_my_id(context: &Arc<Context>, agent_id: &str) -> bool {
-    if agent_id != "" && context.agent_id.pub_sign_key != agent_id {
+    let my_id = context
+        .state()
+        .and_then(|state| state.network().agent_id.clone())
+        .unwrap_or_else(|| context.agent_id().pub_sign_key);
+    if agent_id != "" && my_id != agent_id {
         log_debug!(context, "net/handle: ignoring, same id");
         return false;
     }
     true
 }
 
+/// Whether we hold a warrant against the given agent, in which case we refuse to talk to it
+pub(crate) fn is_warranted(context: &Arc<Context>, agent_id: &Address) -> bool {
+    context
+        .state()
+        .map(|state| state.dht().is_warranted(agent_id))
+        .unwrap_or(false)
+}
+
 // Since StoreEntryAspectData lives in the net crate and EntryAspect is specific
 // to core we can't implement fmt::Debug so that it spans over both, StoreEntryAspectData
 // and the type that is represented as opaque byte vector.
@@ -110,7 +125,7 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
     NetHandler::new(Box::new(move |message| {
         let message = message.unwrap();
         // log_trace!(context, "net/handle:({}): {:?}",
-        //   context.agent_id.nick, message
+        //   context.agent_id().nick, message
         // );
 
         let maybe_json_msg = Lib3hServerProtocol::try_from(message);
@@ -196,6 +211,13 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                 if !is_my_id(&context, &message_data.to_agent_id.to_string()) {
                     return Ok(());
                 }
+                // ignore if it's from an agent we hold a warrant against
+                if is_warranted(&context, &Address::from(message_data.from_agent_id.clone())) {
+                    log_warn!(context, "net/handle: HandleSendMessage: ignoring message of warranted agent {}",
+                        message_data.from_agent_id,
+                    );
+                    return Ok(());
+                }
                 log_debug!(context,
                     "net/handle: HandleSendMessage: {}",
                     format_message_data(&message_data)
@@ -300,9 +322,13 @@ fn get_meta_aspects(
             Attribute::LinkTag(_, _) => true,
             Attribute::RemovedLink(_, _) => true,
             Attribute::CrudLink => true,
+            Attribute::Warrant => true,
             _ => false,
         })
         .map(|eavi| {
+            if let Attribute::Warrant = eavi.attribute() {
+                return get_warrant_aspect(&eavi.value(), context.clone());
+            }
             let value_entry = context
                 .block_on(get_entry_with_meta_workflow(
                     &context,
@@ -342,3 +368,20 @@ fn get_meta_aspects(
         Ok(aspects.into_iter().map(Result::unwrap).collect())
     }
 }
+
+fn get_warrant_aspect(
+    warrant_address: &Address,
+    context: Arc<Context>,
+) -> Result<EntryAspect, HolochainError> {
+    let content = context
+        .state()
+        .expect("Could not get state for handle_fetch_entry")
+        .dht()
+        .content_storage()
+        .read()?
+        .fetch(warrant_address)?
+        .ok_or_else(|| {
+            HolochainError::from("Warrant linked in EAV not found! This should never happen.")
+        })?;
+    Ok(EntryAspect::Warrant(Warrant::try_from_content(&content)?))
+}
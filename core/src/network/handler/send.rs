@@ -5,6 +5,7 @@ use crate::{
     network::direct_message::DirectMessage,
     workflows::{
         handle_custom_direct_message::handle_custom_direct_message,
+        handle_validation_receipt::handle_validation_receipt,
         respond_validation_package_request::respond_validation_package_request,
     },
 };
@@ -83,6 +84,15 @@ pub fn handle_send_message(message_data: DirectMessageData, context: Arc<Context
         DirectMessage::ValidationPackage(_) => log_error!(context, 
             "net: Got DirectMessage::ValidationPackage as initial message. This should not happen.",
         ),
+        DirectMessage::ValidationReceipt(receipt) => {
+            if let Err(error) = handle_validation_receipt(
+                Address::from(message_data.from_agent_id),
+                receipt,
+                &context,
+            ) {
+                log_warn!(context, "net: Refusing validation receipt: {}", error);
+            }
+        }
     };
 }
 
@@ -129,6 +139,9 @@ pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<
         DirectMessage::RequestValidationPackage(_) => log_error!(context, 
             "net: Got DirectMessage::RequestValidationPackage as a response. This should not happen.",
         ),
+        DirectMessage::ValidationReceipt(_) => log_error!(context, 
+            "net: Got DirectMessage::ValidationReceipt as a response. This should not happen.",
+        ),
         DirectMessage::ValidationPackage(maybe_validation_package) => {
             if initial_message.is_none() {
                 log_error!(context, "net: Received a validation package but could not find message ID in history. Not able to process.");
@@ -1,15 +1,18 @@
 use crate::{
     context::Context,
-    network::{entry_aspect::EntryAspect, entry_with_header::EntryWithHeader},
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader, handler::is_warranted,
+        warrant::header_author,
+    },
     workflows::{
         hold_entry::hold_entry_workflow, hold_entry_remove::hold_remove_workflow,
         hold_entry_update::hold_update_workflow, hold_link::hold_link_workflow,
-        remove_link::remove_link_workflow,
+        hold_warrant::hold_warrant_workflow, remove_link::remove_link_workflow,
     },
 };
 use holochain_core_types::entry::{deletion_entry::DeletionEntry, Entry};
 use holochain_json_api::json::JsonString;
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use lib3h_protocol::data_types::StoreEntryAspectData;
 use snowflake::ProcessUniqueId;
 use std::{convert::TryInto, sync::Arc, thread};
@@ -18,7 +21,15 @@ use std::{convert::TryInto, sync::Arc, thread};
 pub fn handle_store(dht_data: StoreEntryAspectData, context: Arc<Context>) {
     let aspect_json =
         JsonString::from_json(&String::from_utf8(dht_data.entry_aspect.aspect).unwrap());
-    if let Ok(aspect) = aspect_json.clone().try_into() {
+    let maybe_aspect: Result<EntryAspect, _> = aspect_json.clone().try_into();
+    if let Ok(aspect) = maybe_aspect {
+        if let Some(author) = warranted_author(&aspect, &context) {
+            log_warn!(context, "net/handle_store: Refusing to hold {:?} of warranted agent {}",
+                aspect,
+                author,
+            );
+            return;
+        }
         match aspect {
             EntryAspect::Content(entry, header) => {
                 log_debug!(context, "net/handle: handle_store: Got EntryAspect::Content. processing...");
@@ -130,6 +141,22 @@ pub fn handle_store(dht_data: StoreEntryAspectData, context: Arc<Context>) {
                     })
                     .expect("Could not spawn thread for storing EntryAspect::Deletion");
             }
+            EntryAspect::Warrant(warrant) => {
+                log_debug!(context, "net/handle: handle_store: Got EntryAspect::Warrant. processing...");
+                thread::Builder::new()
+                    .name(format!(
+                        "store_warrant/{}",
+                        ProcessUniqueId::new().to_string()
+                    ))
+                    .spawn(move || {
+                        if let Err(error) =
+                            context.block_on(hold_warrant_workflow(&warrant, context.clone()))
+                        {
+                            log_error!(context, "net/handle_store: {}", error)
+                        }
+                    })
+                    .expect("Could not spawn thread for storing EntryAspect::Warrant");
+            }
         }
     } else {
         log_error!(context,
@@ -139,6 +166,15 @@ pub fn handle_store(dht_data: StoreEntryAspectData, context: Arc<Context>) {
     }
 }
 
+/// The author of the given aspect if we hold a warrant against them.
+/// Warrants are never refused since they are about the warranted agent, not by them.
+fn warranted_author(aspect: &EntryAspect, context: &Arc<Context>) -> Option<Address> {
+    match aspect {
+        EntryAspect::Warrant(_) => None,
+        aspect => header_author(&aspect.header()).filter(|author| is_warranted(context, author)),
+    }
+}
+
 /*
 /// The network requests us to store meta information (links/CRUD/etc) for an
 /// entry that we hold.
@@ -218,3 +254,34 @@ pub fn handle_store_meta(dht_meta_data: DhtMetaData, context: Arc<Context>) {
     }
 }
 */
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        action::{Action, ActionWrapper},
+        dht::dht_reducers::reduce_hold_warrant,
+        instance::tests::test_context_with_state,
+        network::warrant::tests::test_warrant,
+    };
+    use test_utils::mock_signing::registered_test_agent;
+
+    #[test]
+    fn refuses_aspects_of_warranted_agents() {
+        let context = test_context_with_state(None);
+        let author = registered_test_agent("store-warranted-author");
+        let validator = registered_test_agent("store-warrant-validator");
+        let warrant = test_warrant(&author, &validator);
+        let aspect = EntryAspect::Content(warrant.entry.clone(), warrant.header.clone());
+        assert_eq!(warranted_author(&aspect, &context), None);
+
+        // The DHT store shares its storages with the one in the state
+        let dht = context.state().unwrap().dht();
+        reduce_hold_warrant(&dht, &ActionWrapper::new(Action::HoldWarrant(warrant.clone())))
+            .expect("there should be a new store for holding a warrant");
+        assert_eq!(warranted_author(&aspect, &context), Some(author.address()));
+
+        let warrant_aspect = EntryAspect::Warrant(warrant);
+        assert_eq!(warranted_author(&warrant_aspect, &context), None);
+    }
+}
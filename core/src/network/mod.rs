@@ -6,6 +6,8 @@ pub mod handler;
 pub mod query;
 pub mod reducers;
 pub mod state;
+pub mod validation_receipt;
+pub mod warrant;
 #[cfg(test)]
 pub mod test_utils;
 
@@ -6,6 +6,7 @@ use crate::{
         entry_with_header::{fetch_entry_with_header, EntryWithHeader},
         reducers::send,
         state::NetworkState,
+        warrant::Warrant,
     },
     state::State,
 };
@@ -128,18 +129,20 @@ fn reduce_publish_inner(
 
     let entry_with_header = fetch_entry_with_header(&address, root_state)?;
     match entry_with_header.entry.entry_type() {
-        EntryType::AgentId => publish_entry(network_state, &entry_with_header),
-        EntryType::App(_) => publish_entry(network_state, &entry_with_header).and_then(|_| {
-            match entry_with_header.header.link_update_delete() {
-                Some(modified_entry) => publish_update_delete_meta(
-                    network_state,
-                    modified_entry,
-                    CrudStatus::Modified,
-                    &entry_with_header.clone(),
-                ),
-                None => Ok(()),
-            }
-        }),
+        // Agent entries with a link replace the previous agent entry after a key rotation
+        EntryType::AgentId | EntryType::App(_) => {
+            publish_entry(network_state, &entry_with_header).and_then(|_| {
+                match entry_with_header.header.link_update_delete() {
+                    Some(modified_entry) => publish_update_delete_meta(
+                        network_state,
+                        modified_entry,
+                        CrudStatus::Modified,
+                        &entry_with_header.clone(),
+                    ),
+                    None => Ok(()),
+                }
+            })
+        }
         EntryType::LinkAdd => publish_entry(network_state, &entry_with_header)
             .and_then(|_| publish_link_meta(network_state, &entry_with_header)),
         EntryType::LinkRemove => publish_entry(network_state, &entry_with_header)
@@ -179,6 +182,45 @@ pub fn reduce_publish(
     );
 }
 
+/// Send to network a publish request for the given warrant at the address of the warranted
+/// agent, so that it reaches the agent's neighborhood
+fn reduce_publish_warrant_inner(
+    network_state: &mut NetworkState,
+    warrant: &Warrant,
+) -> Result<Address, HolochainError> {
+    network_state.initialized()?;
+
+    let author = warrant
+        .author()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Warrant has no author".to_string()))?;
+    send(
+        network_state,
+        Lib3hClientProtocol::PublishEntry(ProvidedEntryData {
+            space_address: network_state.dna_address.clone().unwrap(),
+            provider_agent_id: network_state.agent_id.clone().unwrap().into(),
+            entry: EntryData {
+                entry_address: author.clone(),
+                aspect_list: vec![EntryAspect::Warrant(warrant.clone()).into()],
+            },
+        }),
+    )?;
+    Ok(author)
+}
+
+pub fn reduce_publish_warrant(
+    network_state: &mut NetworkState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let warrant = unwrap_to!(action => crate::action::Action::PublishWarrant);
+
+    let result = reduce_publish_warrant_inner(network_state, &warrant);
+    network_state
+        .actions
+        .insert(action_wrapper.clone(), ActionResponse::Publish(result));
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -0,0 +1,168 @@
+//! Validation receipts that validators return to the authors of the aspects they validated.
+//!
+//! After a hold workflow has validated an aspect, the validating node signs a receipt with the
+//! address of the aspect, the address of the entry it belongs to, the result and the time of
+//! the validation and sends it to the author as `DirectMessage::ValidationReceipt`. The author
+//! keeps the receipts in its [ReceiptStore](../../agent/receipt_store/struct.ReceiptStore.html),
+//! which gives it evidence of how many peers hold what it published.
+
+use crate::{context::Context, network::entry_aspect::EntryAspect};
+use holochain_core_types::{
+    error::{HcResult, HolochainError},
+    signature::{Provenance, Signature},
+    time::Iso8601,
+};
+use holochain_dpki::utils::Verify;
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
+use std::{convert::TryFrom, sync::Arc, time::SystemTime};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub enum ValidationReceiptResult {
+    Valid,
+    /// The entry failed validation with the given error
+    Invalid(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct ValidationReceipt {
+    /// Address of the entry the validated aspect belongs to, i.e. the entry address in
+    /// the aspect's header
+    pub entry_address: Address,
+    pub aspect_address: Address,
+    pub result: ValidationReceiptResult,
+    /// When the validation finished
+    pub timestamp: Iso8601,
+    /// The validating agent and its signature of `ValidationReceipt::signed_data`
+    pub validator: Provenance,
+}
+
+impl AddressableContent for ValidationReceipt {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+}
+
+impl ValidationReceipt {
+    /// Creates a receipt for the given aspect, signed by our agent.
+    pub fn create(
+        aspect: &EntryAspect,
+        result: ValidationReceiptResult,
+        context: &Arc<Context>,
+    ) -> HcResult<ValidationReceipt> {
+        let duration_since_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("System time must not be before UNIX EPOCH");
+        let entry_address = aspect.header().entry_address().clone();
+        let aspect_address = aspect.address();
+        let timestamp = Iso8601::from(duration_since_epoch.as_secs());
+        let data = Self::signed_data(&entry_address, &aspect_address, &result, &timestamp);
+        let signature = context.conductor_api.execute(data, CryptoMethod::Sign)?;
+        Ok(ValidationReceipt {
+            entry_address,
+            aspect_address,
+            result,
+            timestamp,
+            validator: Provenance::new(context.agent_id().address(), Signature::from(signature)),
+        })
+    }
+
+    /// The data a validator signs: everything in the receipt but the signature.
+    fn signed_data(
+        entry_address: &Address,
+        aspect_address: &Address,
+        result: &ValidationReceiptResult,
+        timestamp: &Iso8601,
+    ) -> String {
+        format!(
+            "receipt:{}:{}:{}:{}",
+            entry_address,
+            aspect_address,
+            JsonString::from(result.to_owned()),
+            timestamp,
+        )
+    }
+
+    /// The agent that validated the aspect.
+    pub fn validator(&self) -> Address {
+        self.validator.source()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.result == ValidationReceiptResult::Valid
+    }
+
+    /// Checks that the receipt carries a valid signature of its validator.
+    pub fn verify(&self) -> HcResult<()> {
+        let data = Self::signed_data(
+            &self.entry_address,
+            &self.aspect_address,
+            &self.result,
+            &self.timestamp,
+        );
+        if !self.validator.verify(data).unwrap_or(false) {
+            return Err(HolochainError::ErrorGeneric(String::from(
+                "Invalid validation receipt: signature of the validator does not verify",
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{agent::AgentId, time::test_iso_8601};
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    pub fn test_validation_receipt(
+        entry_address: &Address,
+        aspect_address: &Address,
+        result: ValidationReceiptResult,
+        validator: &AgentId,
+    ) -> ValidationReceipt {
+        let timestamp = test_iso_8601();
+        let data =
+            ValidationReceipt::signed_data(entry_address, aspect_address, &result, &timestamp);
+        ValidationReceipt {
+            entry_address: entry_address.clone(),
+            aspect_address: aspect_address.clone(),
+            result,
+            timestamp,
+            validator: Provenance::new(
+                validator.address(),
+                Signature::from(mock_signer(data, validator)),
+            ),
+        }
+    }
+
+    #[test]
+    fn verify_accepts_signed_receipt_and_refuses_tampered_ones() {
+        let validator = registered_test_agent("receipt-validator-erin");
+        let receipt = test_validation_receipt(
+            &Address::from("QmEntry"),
+            &Address::from("QmAspect"),
+            ValidationReceiptResult::Valid,
+            &validator,
+        );
+        receipt.verify().unwrap();
+        let receipt = ValidationReceipt::try_from(JsonString::from(receipt.clone())).unwrap();
+        receipt.verify().unwrap();
+
+        let mut other_entry = receipt.clone();
+        other_entry.entry_address = Address::from("QmOtherEntry");
+        assert!(other_entry.verify().is_err());
+
+        let mut other_result = receipt.clone();
+        other_result.result = ValidationReceiptResult::Invalid(String::from("nope"));
+        assert!(other_result.verify().is_err());
+    }
+}
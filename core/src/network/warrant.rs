@@ -0,0 +1,201 @@
+//! Warrants against agents that published invalid data.
+//!
+//! When a hold workflow finds that an entry an agent published does not validate, the
+//! validating node creates a warrant holding the offending header and entry together with
+//! the validation error and signs it. The warrant gets published as `EntryAspect::Warrant` at
+//! the author's agent address, so it gossips through the author's neighborhood. Nodes that
+//! receive a warrant check both signatures and validate the entry themselves before they hold
+//! the warrant, mark the author's entries as rejected and stop talking to the author.
+
+use crate::{context::Context, network::entry_with_header::EntryWithHeader};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::Entry,
+    error::{HcResult, HolochainError},
+    signature::{Provenance, Signature},
+};
+use holochain_dpki::utils::Verify;
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
+use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
+use std::{convert::TryFrom, sync::Arc};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct Warrant {
+    /// Header of the invalid entry, signed by its author
+    pub header: ChainHeader,
+    pub entry: Entry,
+    /// Error the validation of the entry failed with
+    pub error: String,
+    /// The validating agent and its signature of `Warrant::signed_data`
+    pub validator: Provenance,
+}
+
+impl AddressableContent for Warrant {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+}
+
+impl Warrant {
+    /// Creates a warrant against the author of the given entry, signed by our agent.
+    pub fn create(
+        entry_with_header: &EntryWithHeader,
+        error: String,
+        context: &Arc<Context>,
+    ) -> HcResult<Warrant> {
+        let data = Self::signed_data(&entry_with_header.header, &error);
+        let signature = context.conductor_api.execute(data, CryptoMethod::Sign)?;
+        Ok(Warrant {
+            header: entry_with_header.header.clone(),
+            entry: entry_with_header.entry.clone(),
+            error,
+            validator: Provenance::new(context.agent_id().address(), Signature::from(signature)),
+        })
+    }
+
+    /// The data a validator signs: the header of the invalid entry and the validation error.
+    /// The header covers the entry by its address.
+    fn signed_data(header: &ChainHeader, error: &str) -> String {
+        format!("warrant:{}:{}", header.address(), error)
+    }
+
+    /// The agent that authored the invalid entry.
+    pub fn author(&self) -> Option<Address> {
+        header_author(&self.header)
+    }
+
+    pub fn entry_with_header(&self) -> EntryWithHeader {
+        EntryWithHeader {
+            entry: self.entry.clone(),
+            header: self.header.clone(),
+        }
+    }
+
+    /// Checks that the entry matches its header, that the header carries a valid signature of
+    /// its author and that the warrant carries a valid signature of its validator.
+    /// Does not check whether the entry is actually invalid.
+    pub fn verify(&self) -> HcResult<()> {
+        if self.entry.address() != *self.header.entry_address()
+            || self.entry.entry_type() != *self.header.entry_type()
+        {
+            return Err(invalid_warrant("entry does not match its header"));
+        }
+        let author = self
+            .header
+            .provenances()
+            .last()
+            .ok_or_else(|| invalid_warrant("header has no provenance"))?;
+        if !author
+            .verify(self.header.entry_address().to_string())
+            .unwrap_or(false)
+        {
+            return Err(invalid_warrant("signature of the author does not verify"));
+        }
+        if !self
+            .validator
+            .verify(Self::signed_data(&self.header, &self.error))
+            .unwrap_or(false)
+        {
+            return Err(invalid_warrant(
+                "signature of the validator does not verify",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The agent that authored the entry of the given header, i.e. its last provenance.
+pub fn header_author(header: &ChainHeader) -> Option<Address> {
+    header
+        .provenances()
+        .last()
+        .map(|provenance| provenance.source())
+}
+
+fn invalid_warrant(reason: &str) -> HolochainError {
+    HolochainError::ErrorGeneric(format!("Invalid warrant: {}", reason))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        agent::AgentId,
+        entry::{test_entry, test_entry_b},
+        time::test_iso_8601,
+    };
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    pub fn signed_header(agent: &AgentId, entry: &Entry) -> ChainHeader {
+        let signature = Signature::from(mock_signer(entry.address().to_string(), agent));
+        ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &[Provenance::new(agent.address(), signature)],
+            &None,
+            &None,
+            &None,
+            &test_iso_8601(),
+        )
+    }
+
+    pub fn test_warrant(author: &AgentId, validator: &AgentId) -> Warrant {
+        let entry = test_entry();
+        let header = signed_header(author, &entry);
+        let error = String::from("entry is not valid");
+        let signature = mock_signer(Warrant::signed_data(&header, &error), validator);
+        Warrant {
+            header,
+            entry,
+            error,
+            validator: Provenance::new(validator.address(), Signature::from(signature)),
+        }
+    }
+
+    #[test]
+    fn verify_accepts_signed_warrant() {
+        let author = registered_test_agent("warrant-author-alice");
+        let validator = registered_test_agent("warrant-validator-bob");
+        let warrant = test_warrant(&author, &validator);
+        warrant.verify().unwrap();
+        assert_eq!(warrant.author(), Some(author.address()));
+
+        let warrant = Warrant::try_from(JsonString::from(warrant.clone())).unwrap();
+        warrant.verify().unwrap();
+    }
+
+    #[test]
+    fn verify_refuses_tampered_warrants() {
+        let author = registered_test_agent("warrant-author-carol");
+        let validator = registered_test_agent("warrant-validator-dave");
+        let warrant = test_warrant(&author, &validator);
+
+        let mut swapped_entry = warrant.clone();
+        swapped_entry.entry = test_entry_b();
+        assert!(swapped_entry.verify().is_err());
+
+        let mut changed_error = warrant.clone();
+        changed_error.error = String::from("something else");
+        assert!(changed_error.verify().is_err());
+
+        let mut forged_header = warrant.clone();
+        forged_header.header = ChainHeader::new(
+            &warrant.entry.entry_type(),
+            &warrant.entry.address(),
+            &[Provenance::new(author.address(), Signature::from("forged"))],
+            &None,
+            &None,
+            &None,
+            &test_iso_8601(),
+        );
+        assert!(forged_header.verify().is_err());
+    }
+}
@@ -21,7 +21,19 @@ pub fn add_pending_validation(
                 entry_with_header,
                 dependencies,
                 workflow,
+                attempts: 0,
             },
         ))),
     );
 }
+
+pub fn count_pending_validation_attempt(
+    address: Address,
+    workflow: ValidatingWorkflow,
+    context: &Arc<Context>,
+) {
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::CountPendingValidationAttempt((address, workflow))),
+    );
+}
@@ -4,6 +4,7 @@ use crate::{
     context::Context,
     entry::CanPublish,
     nucleus::ribosome::callback::{
+        custom_validation_package::get_custom_validation_package,
         validation_package::get_validation_package_definition, CallbackResult,
     },
 };
@@ -14,8 +15,12 @@ use holochain_core_types::{
     error::HolochainError,
     signature::Provenance,
     ugly::lax_send_sync,
-    validation::{ValidationPackage, ValidationPackageDefinition::*},
+    validation::{
+        ValidationPackage, ValidationPackageDefinition::*, MAX_CUSTOM_VALIDATION_PACKAGE_SIZE,
+    },
 };
+use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_wasm_utils::api_serialization::validation::CustomValidationPackage;
 use snowflake;
 use std::{convert::TryInto, pin::Pin, sync::Arc, thread, vec::Vec};
 
@@ -23,6 +28,28 @@ pub async fn build_validation_package<'a>(
     entry: &'a Entry,
     context: Arc<Context>,
     provenances: &'a Vec<Provenance>,
+) -> Result<ValidationPackage, HolochainError> {
+    await!(build_package(entry, context, provenances, None))
+}
+
+/// Builds the validation package of an entry staged in a commit bundle.
+/// `preceding` are the entries staged before it, in staging order and with the pre-flight
+/// headers they got: the entry's pre-flight header goes on top of them, and the package
+/// sees them as part of the chain although they are not committed yet.
+pub async fn build_bundle_validation_package<'a>(
+    entry: &'a Entry,
+    context: Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+    preceding: &'a [(ChainHeader, Entry)],
+) -> Result<ValidationPackage, HolochainError> {
+    await!(build_package(entry, context, provenances, Some(preceding)))
+}
+
+async fn build_package<'a>(
+    entry: &'a Entry,
+    context: Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+    preceding: Option<&'a [(ChainHeader, Entry)]>,
 ) -> Result<ValidationPackage, HolochainError> {
     let id = snowflake::ProcessUniqueId::new();
 
@@ -75,7 +102,12 @@ pub async fn build_validation_package<'a>(
         let id = id.clone();
         let entry = entry.clone();
         let context = context.clone();
-        let maybe_entry_header = find_chain_header(&entry.clone(), &context.state().unwrap());
+        let maybe_entry_header = match preceding {
+            // Entries of a bundle always get a new header, even if they are on the chain already
+            Some(_) => None,
+            None => find_chain_header(&entry.clone(), &context.state().unwrap()),
+        };
+        let preceding = preceding.map(|preceding| preceding.to_vec()).unwrap_or_default();
         let entry_header = if maybe_entry_header.is_none() {
             // TODO: make sure that we don't run into race conditions with respect to the chain
             // We need the source chain header as part of the validation package.
@@ -90,12 +122,17 @@ pub async fn build_validation_package<'a>(
             // If it was a problem, we would have to make sure that the whole commit process
             // (including validtion) is atomic.
             let state = &context.state()?;
-            agent::state::create_new_chain_header(
+            let preceding_headers: Vec<ChainHeader> = preceding
+                .iter()
+                .map(|(chain_header, _)| chain_header.clone())
+                .collect();
+            agent::state::create_new_chain_header_after(
                 &entry,
                 &state.agent(),
                 &*state,
                 &None,
                 provenances,
+                &preceding_headers,
             )?
         } else {
             maybe_entry_header.unwrap()
@@ -132,7 +169,9 @@ pub async fn build_validation_package<'a>(
                                         &all_chain_headers_before_header(
                                             &context,
                                             &package.chain_header,
+                                            &preceding,
                                         ),
+                                        &preceding,
                                     ));
                                 package
                             }
@@ -142,6 +181,7 @@ pub async fn build_validation_package<'a>(
                                     Some(all_chain_headers_before_header(
                                         &context,
                                         &package.chain_header,
+                                        &preceding,
                                     ));
                                 package
                             }
@@ -150,16 +190,29 @@ pub async fn build_validation_package<'a>(
                                 let headers = all_chain_headers_before_header(
                                     &context,
                                     &package.chain_header,
+                                    &preceding,
+                                );
+                                package.source_chain_entries = Some(
+                                    public_chain_entries_from_headers(
+                                        &context, &headers, &preceding,
+                                    ),
                                 );
-                                package.source_chain_entries =
-                                    Some(public_chain_entries_from_headers(&context, &headers));
                                 package.source_chain_headers = Some(headers);
                                 package
                             }
-                            Custom(string) => {
-                                let mut package = ValidationPackage::only_header(entry_header);
-                                package.custom = Some(string);
-                                package
+                            Custom(definition) => {
+                                let custom_package = get_custom_validation_package(
+                                    &entry,
+                                    &entry_header,
+                                    definition,
+                                    context.clone(),
+                                )?;
+                                custom_validation_package(
+                                    &context,
+                                    entry_header,
+                                    custom_package,
+                                    &preceding,
+                                )?
                             }
                         })
                     });
@@ -183,15 +236,20 @@ pub async fn build_validation_package<'a>(
     })
 }
 
-// given a slice of headers return the entries for those marked public
+// given a slice of headers return the entries for those marked public,
+// taking the ones of uncommitted bundle entries from `preceding`
 fn public_chain_entries_from_headers(
     context: &Arc<Context>,
     headers: &[ChainHeader],
+    preceding: &[(ChainHeader, Entry)],
 ) -> Vec<Entry> {
     headers
         .iter()
         .filter(|ref chain_header| chain_header.entry_type().can_publish(context))
         .map(|chain_header| {
+            if let Some((_, entry)) = preceding.iter().find(|(header, _)| header == chain_header) {
+                return entry.clone();
+            }
             let storage = context
                 .state()
                 .unwrap()
@@ -209,12 +267,70 @@ fn public_chain_entries_from_headers(
         .collect::<Vec<_>>()
 }
 
+/// Looks up the headers selected by the custom validation package callback in our chain
+/// before the given header, so we only ever send headers we have authored and signed,
+/// and checks the resulting package against the size limit.
+fn custom_validation_package(
+    context: &Arc<Context>,
+    header: ChainHeader,
+    custom_package: CustomValidationPackage,
+    preceding: &[(ChainHeader, Entry)],
+) -> Result<ValidationPackage, HolochainError> {
+    let chain_headers = all_chain_headers_before_header(context, &header, preceding);
+    let headers = custom_package
+        .chain_headers
+        .iter()
+        .map(|address| {
+            chain_headers
+                .iter()
+                .find(|chain_header| chain_header.address() == *address)
+                .cloned()
+                .ok_or_else(|| {
+                    HolochainError::ValidationFailed(format!(
+                        "Custom validation package contains header {} which is not in the chain before header {}",
+                        address,
+                        header.address(),
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut package = ValidationPackage::only_header(header);
+    package.source_chain_entries = Some(public_chain_entries_from_headers(
+        context, &headers, preceding,
+    ));
+    package.source_chain_headers = Some(headers);
+    package.custom = custom_package.custom;
+
+    let size = package.size();
+    if size > MAX_CUSTOM_VALIDATION_PACKAGE_SIZE {
+        return Err(HolochainError::ValidationFailed(format!(
+            "Custom validation package of {} bytes exceeds the limit of {} bytes",
+            size,
+            MAX_CUSTOM_VALIDATION_PACKAGE_SIZE,
+        )));
+    }
+    Ok(package)
+}
+
+/// Headers of the chain before the given header, latest first.
+/// A header on top of uncommitted bundle entries gets the headers of these
+/// (see `build_bundle_validation_package()`) before the ones of the chain.
 fn all_chain_headers_before_header(
     context: &Arc<Context>,
     header: &ChainHeader,
+    preceding: &[(ChainHeader, Entry)],
 ) -> Vec<ChainHeader> {
     let chain = context.state().unwrap().agent().chain_store();
-    chain.iter(&Some(header.clone())).skip(1).collect()
+    match preceding.first() {
+        Some((first, _)) => preceding
+            .iter()
+            .rev()
+            .map(|(chain_header, _)| chain_header.clone())
+            .chain(chain.iter(&Some(first.clone())).skip(1))
+            .collect(),
+        None => chain.iter(&Some(header.clone())).skip(1).collect(),
+    }
 }
 
 /// ValidationPackageFuture resolves to the ValidationPackage or a HolochainError.
@@ -313,7 +429,8 @@ mod tests {
             chain_header: chain_header.clone(),
             source_chain_entries: Some(public_chain_entries_from_headers(
                 &context,
-                &all_chain_headers_before_header(&context, &chain_header),
+                &all_chain_headers_before_header(&context, &chain_header, &[]),
+                &[],
             )),
             source_chain_headers: None,
             custom: None,
@@ -343,7 +460,11 @@ mod tests {
         let expected = ValidationPackage {
             chain_header: chain_header.clone(),
             source_chain_entries: None,
-            source_chain_headers: Some(all_chain_headers_before_header(&context, &chain_header)),
+            source_chain_headers: Some(all_chain_headers_before_header(
+                &context,
+                &chain_header,
+                &[],
+            )),
             custom: None,
         };
 
@@ -368,11 +489,15 @@ mod tests {
         ));
         assert!(maybe_validation_package.is_ok());
 
-        let headers = all_chain_headers_before_header(&context, &chain_header);
+        let headers = all_chain_headers_before_header(&context, &chain_header, &[]);
 
         let expected = ValidationPackage {
             chain_header: chain_header,
-            source_chain_entries: Some(public_chain_entries_from_headers(&context, &headers)),
+            source_chain_entries: Some(public_chain_entries_from_headers(
+                &context,
+                &headers,
+                &[],
+            )),
             source_chain_headers: Some(headers),
             custom: None,
         };
@@ -380,6 +505,48 @@ mod tests {
         assert_eq!(maybe_validation_package.unwrap(), expected);
     }
 
+    #[test]
+    fn test_building_validation_package_custom() {
+        let (_instance, context) = instance(None);
+
+        let entry_header = commit(test_entry_package_entry(), &context);
+        commit(test_entry_package_chain_full(), &context);
+
+        // commit entry to build validation package for
+        let chain_header = commit(test_entry_package_custom(), &context);
+
+        let maybe_validation_package = context.block_on(build_validation_package(
+            &test_entry_package_custom(),
+            context.clone(),
+            &vec![],
+        ));
+        assert!(maybe_validation_package.is_ok());
+
+        // only the headers selected by the callback end up in the package
+        let expected = ValidationPackage {
+            chain_header,
+            source_chain_entries: Some(vec![test_entry_package_entry()]),
+            source_chain_headers: Some(vec![entry_header]),
+            custom: Some(String::from("package_entry headers")),
+        };
+
+        assert_eq!(maybe_validation_package.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_custom_validation_package_refuses_headers_not_in_chain() {
+        let (_instance, context) = instance(None);
+        let chain_header = commit(test_entry_package_custom(), &context);
+
+        let custom_package = CustomValidationPackage {
+            chain_headers: vec![Address::from("Qmnotinchain")],
+            custom: None,
+        };
+        assert!(
+            custom_validation_package(&context, chain_header, custom_package, &[]).is_err()
+        );
+    }
+
     // test can make validation package with empty chain
     #[test]
     fn test_all_chain_headers_before_header_empty_chain() {
@@ -390,7 +557,7 @@ mod tests {
             .agent()
             .top_chain_header()
             .expect("There must be a top chain header");
-        let headers = all_chain_headers_before_header(&context, &top_header);
+        let headers = all_chain_headers_before_header(&context, &top_header, &[]);
         assert_eq!(headers.len(), 1) // includes the DNA entry only (no agent entry)
     }
 
@@ -415,7 +582,7 @@ mod tests {
             &Iso8601::new(0, 0),
         );
 
-        let headers = all_chain_headers_before_header(&context, &new_entry_header);
+        let headers = all_chain_headers_before_header(&context, &new_entry_header, &[]);
         // entry should not appear in the validating chain
         assert_eq!(headers.contains(&new_entry_header), false);
         assert_eq!(headers.len(), 2) // includes the DNA and agent entries
@@ -426,7 +593,7 @@ mod tests {
         let (_instance, context) = instance(None);
         // entry is added to the local chain
         let chain_header = commit(test_entry_package_chain_full(), &context);
-        let headers = all_chain_headers_before_header(&context, &chain_header);
+        let headers = all_chain_headers_before_header(&context, &chain_header, &[]);
         // entry should not appear in the validating chain
         assert_eq!(headers.contains(&chain_header), false);
         assert_eq!(headers.len(), 2) // includes the DNA and agent entries
@@ -437,13 +604,13 @@ mod tests {
         let (_instance, context) = instance(None);
         // entry is added to the local chain
         let chain_header = commit(test_entry_package_chain_full(), &context);
-        let pre_commit_headers = all_chain_headers_before_header(&context, &chain_header);
+        let pre_commit_headers = all_chain_headers_before_header(&context, &chain_header, &[]);
 
         // commit come more entries
         commit(test_entry_package_chain_entries(), &context);
         commit(test_entry_package_entry(), &context);
 
-        let post_commit_headers = all_chain_headers_before_header(&context, &chain_header);
+        let post_commit_headers = all_chain_headers_before_header(&context, &chain_header, &[]);
         assert_eq!(pre_commit_headers, post_commit_headers)
     }
 }
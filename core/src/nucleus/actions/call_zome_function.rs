@@ -3,7 +3,7 @@ use crate::{
     context::Context,
     nucleus::{
         actions::get_entry::get_entry_from_agent_chain,
-        ribosome::{self, WasmCallData},
+        ribosome::{self, CallStats, WasmCallData},
         ZomeFnCall, ZomeFnResult,
     },
 };
@@ -34,11 +34,22 @@ use std::{pin::Pin, sync::Arc, thread};
 pub struct ExecuteZomeFnResponse {
     call: ZomeFnCall,
     result: ZomeFnResult,
+    stats: CallStats,
 }
 
 impl ExecuteZomeFnResponse {
     pub fn new(call: ZomeFnCall, result: Result<JsonString, HolochainError>) -> Self {
-        ExecuteZomeFnResponse { call, result }
+        ExecuteZomeFnResponse {
+            call,
+            result,
+            stats: CallStats::default(),
+        }
+    }
+
+    /// Adds the resources the call used, which makes them show up in trace signals.
+    pub fn with_stats(mut self, stats: CallStats) -> Self {
+        self.stats = stats;
+        self
     }
 
     /// read only access to call
@@ -50,6 +61,11 @@ impl ExecuteZomeFnResponse {
     pub fn result(&self) -> Result<JsonString, HolochainError> {
         self.result.clone()
     }
+
+    /// read only access to the resources used by the call
+    pub fn stats(&self) -> CallStats {
+        self.stats.clone()
+    }
 }
 
 /// Execution of zome calls
@@ -65,10 +81,19 @@ impl ExecuteZomeFnResponse {
 /// the call result gets added there through the `RetunrZomeFunctionResult` action.
 ///
 /// Use Context::block_on to wait for the call result.
+///
+/// Calls without a deadline of their own get the instance's default timeout (if any).
+/// Once the deadline has passed, the future resolves to `HolochainError::Timeout` and the
+/// Ribosome aborts the running call at its next Zome API function call.
 pub async fn call_zome_function(
     zome_call: ZomeFnCall,
     context: Arc<Context>,
 ) -> Result<JsonString, HolochainError> {
+    let zome_call = match (zome_call.deadline(), context.zome_call_timeout) {
+        (None, Some(timeout)) => zome_call.with_timeout(timeout),
+        _ => zome_call,
+    };
+
     log_debug!(context,
         "actions/call_zome_fn: Validating call: {:?}",
         zome_call
@@ -101,13 +126,14 @@ pub async fn call_zome_function(
         ))
         .spawn(move || {
             // Have Ribosome spin up DNA and call the zome function
-            let call_result = ribosome::run_dna(
+            let (call_result, stats) = ribosome::run_dna_with_stats(
                 Some(zome_call_clone.clone().parameters.to_bytes()),
                 WasmCallData::new_zome_call(context_clone.clone(), zome_call_clone.clone()),
             );
             log_debug!(context_clone, "actions/call_zome_fn: got call_result from ribosome::run_dna.");
             // Construct response
-            let response = ExecuteZomeFnResponse::new(zome_call_clone, call_result);
+            let response =
+                ExecuteZomeFnResponse::new(zome_call_clone, call_result).with_stats(stats);
             // Send ReturnZomeFunctionResult Action
             log_debug!(context_clone, "actions/call_zome_fn: sending ReturnZomeFunctionResult action.");
             lax_send_sync(
@@ -170,7 +196,7 @@ pub fn validate_call(
 }
 
 fn is_token_the_agent(context: Arc<Context>, request: &CapabilityRequest) -> bool {
-    context.agent_id.pub_sign_key == request.cap_token.to_string()
+    context.agent_id().pub_sign_key == request.cap_token.to_string()
 }
 
 fn get_grant(context: &Arc<Context>, address: &Address) -> Option<CapTokenGrant> {
@@ -228,7 +254,7 @@ pub fn make_cap_request_for_call<J: Into<JsonString>>(
 ) -> CapabilityRequest {
     CapabilityRequest::new(
         cap_token,
-        callers_context.agent_id.address(),
+        callers_context.agent_id().address(),
         make_call_sig(callers_context, function, parameters),
     )
 }
@@ -291,6 +317,7 @@ pub fn verify_grant(context: Arc<Context>, grant: &CapTokenGrant, fn_call: &Zome
 
 /// CallResultFuture resolves to an Result<JsonString, HolochainError>.
 /// Tracks the nucleus State, waiting for a result to the given zome function call to appear.
+/// Resolves to `HolochainError::Timeout` if the call's deadline passes before that.
 pub struct CallResultFuture {
     context: Arc<Context>,
     zome_call: ZomeFnCall,
@@ -312,6 +339,9 @@ impl Future for CallResultFuture {
         if let Some(state) = self.context.state() {
             match state.nucleus().zome_call_result(&self.zome_call) {
                 Some(result) => Poll::Ready(result),
+                None if self.zome_call.is_expired() => {
+                    Poll::Ready(Err(HolochainError::Timeout(None)))
+                }
                 None => Poll::Pending,
             }
         } else {
@@ -344,7 +374,7 @@ pub mod tests {
     #[test]
     fn test_agent_as_token() {
         let context = test_context("alice", None);
-        let agent_token = context.agent_id.address();
+        let agent_token = context.agent_id().address();
         let cap_request =
             make_cap_request_for_call(context.clone(), agent_token.clone(), "test", "{}");
         assert!(is_token_the_agent(context.clone(), &cap_request));
@@ -365,12 +395,12 @@ pub mod tests {
 
         // only exact same call signed by the same person should verify
         let call_sig1 = make_call_sig(context1.clone(), "func", "{}");
-        let provenance1 = Provenance::new(context1.agent_id.address(), call_sig1.clone());
+        let provenance1 = Provenance::new(context1.agent_id().address(), call_sig1.clone());
         assert!(verify_call_sig(&provenance1, "func", "{}"));
         assert!(!verify_call_sig(&provenance1, "func1", "{}"));
         assert!(!verify_call_sig(&provenance1, "func", "{\"x\":1}"));
 
-        let bad_provenance = Provenance::new(context2.agent_id.address(), call_sig1);
+        let bad_provenance = Provenance::new(context2.agent_id().address(), call_sig1);
 
         assert!(!verify_call_sig(&bad_provenance, "func", "{}"));
     }
@@ -383,7 +413,7 @@ pub mod tests {
         assert_eq!(cap_request.cap_token, dummy_capability_token());
         assert_eq!(
             cap_request.provenance.source().to_string(),
-            context.agent_id.pub_sign_key
+            context.agent_id().pub_sign_key
         );
         assert_eq!(
             cap_request.provenance.signature(),
@@ -414,7 +444,7 @@ pub mod tests {
     fn test_verify_grant() {
         let context = test_context("alice", None);
         let context2 = test_context("bob", None);
-        let test_address1 = context.agent_id.address();
+        let test_address1 = context.agent_id().address();
 
         fn zome_call_valid(context: Arc<Context>, token: &Address) -> ZomeFnCall {
             ZomeFnCall::new(
@@ -0,0 +1,41 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    instance::dispatch_action,
+    scheduled_jobs::pending_validations::{DeadLetterValidation, ValidatingWorkflow},
+};
+use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
+
+/// Moves a pending validation that ran out of attempts to the dead letters.
+pub fn dead_letter_validation(dead_letter: DeadLetterValidation, context: &Arc<Context>) {
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::AddDeadLetterValidation(dead_letter)),
+    );
+}
+
+/// Moves a dead-lettered validation back to the pending validations,
+/// where it gets retried with a fresh number of attempts.
+pub fn retry_dead_letter_validation(
+    address: Address,
+    workflow: ValidatingWorkflow,
+    context: &Arc<Context>,
+) {
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::RetryDeadLetterValidation((address, workflow))),
+    );
+}
+
+/// Drops a dead-lettered validation for good.
+pub fn remove_dead_letter_validation(
+    address: Address,
+    workflow: ValidatingWorkflow,
+    context: &Arc<Context>,
+) {
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::RemoveDeadLetterValidation((address, workflow))),
+    );
+}
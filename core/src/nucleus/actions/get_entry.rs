@@ -96,7 +96,10 @@ pub(crate) fn get_entry_crud_meta_from_dht(
     }
     let mut crud_status = CrudStatus::Live;
     // TODO waiting for update/remove_eav() assert!(status_eavs.len() <= 1);
-    // For now look for crud-status by life-cycle order: Deleted, Modified, Live
+    // For now look for crud-status by life-cycle order: Rejected, Deleted, Modified, Live
+    let has_rejected = status_eavs.iter().any(|e| {
+        CrudStatus::from_str(String::from(e.value()).as_ref()) == Ok(CrudStatus::Rejected)
+    });
     let has_deleted = status_eavs
         .clone()
         .into_iter()
@@ -106,7 +109,9 @@ pub(crate) fn get_entry_crud_meta_from_dht(
         .collect::<BTreeSet<EntityAttributeValueIndex>>()
         .len()
         > 0;
-    if has_deleted {
+    if has_rejected || state_dht.is_rejected(address)? {
+        crud_status = CrudStatus::Rejected;
+    } else if has_deleted {
         crud_status = CrudStatus::Deleted;
     } else {
         let has_modified = status_eavs
@@ -90,7 +90,7 @@ pub async fn initialize_chain(
     }
 
     // Commit AgentId to chain
-    let agent_id_entry = Entry::AgentId(context_clone.agent_id.clone());
+    let agent_id_entry = Entry::AgentId(context_clone.agent_id());
     let agent_id_commit = await!(commit_entry(agent_id_entry, None, &context_clone));
 
     // Let initialization fail if AgentId could not be committed.
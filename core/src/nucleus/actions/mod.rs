@@ -2,6 +2,7 @@ pub mod add_pending_validation;
 pub mod build_validation_package;
 pub mod call_init;
 pub mod call_zome_function;
+pub mod dead_letter_validation;
 pub mod get_entry;
 pub mod initialize;
 pub mod remove_pending_validation;
@@ -79,6 +80,11 @@ pub mod tests {
             .unwrap()
             .entry_types
             .insert("package_chain_full".into(), EntryTypeDef::new());
+        dna.zomes
+            .get_mut("test_zome")
+            .unwrap()
+            .entry_types
+            .insert("package_custom".into(), EntryTypeDef::new());
 
         dna
     }
@@ -115,6 +121,11 @@ pub mod tests {
         Entry::App("package_chain_full".into(), "test value".into())
     }
 
+    #[cfg_attr(tarpaulin, skip)]
+    pub fn test_entry_package_custom() -> Entry {
+        Entry::App("package_custom".into(), "test value".into())
+    }
+
     #[cfg_attr(tarpaulin, skip)]
     pub fn commit(entry: Entry, context: &Arc<Context>) -> ChainHeader {
         let chain = context.state().unwrap().agent().chain_store();
@@ -18,6 +18,8 @@ use hdk::holochain_core_types::{
 };
 
 use hdk::holochain_json_api::{error::JsonError, json::JsonString};
+use hdk::holochain_persistence_api::cas::content::AddressableContent;
+use hdk::holochain_wasm_utils::api_serialization::{QueryArgsOptions, QueryResult};
 
 #[derive(Serialize, Deserialize, DefaultJson, Debug,Clone)]
 struct TestEntryType {
@@ -150,6 +152,34 @@ define_zome! {
                    }
                 }
             }
+        ),
+
+        entry!(
+            name: "package_custom",
+            description: "asdfda",
+            sharing: Sharing::Public,
+
+            validation_package: || {
+                hdk::ValidationPackageDefinition::Custom("package_entry headers".into())
+            },
+
+            validation: | _validation_data: hdk::EntryValidationData<TestEntryType>| {
+                Ok(())
+            },
+
+            custom_validation_package: | _args: hdk::CustomValidationPackageArgs | {
+                let options = QueryArgsOptions {
+                    headers: true,
+                    ..Default::default()
+                };
+                match hdk::query_result("package_entry".into(), options) {
+                    Ok(QueryResult::Headers(headers)) => Ok(hdk::CustomValidationPackage {
+                        chain_headers: headers.iter().map(|header| header.address()).collect(),
+                        custom: None,
+                    }),
+                    _ => Err("Could not query package_entry headers".to_string()),
+                }
+            }
         )
     ]
 
@@ -22,16 +22,45 @@ use holochain_persistence_api::cas::content::Address;
 use holochain_json_api::json::JsonString;
 
 use snowflake;
-use std::sync::Arc;
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// Struct holding data for requesting the execution of a Zome function (ExecutionZomeFunction Action)
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, Eq, Serialize)]
 pub struct ZomeFnCall {
     id: snowflake::ProcessUniqueId,
     pub zome_name: String,
     pub cap: CapabilityRequest,
     pub fn_name: String,
     pub parameters: JsonString,
+    /// Point in time after which the call gets aborted, if any
+    #[serde(skip)]
+    deadline: Option<Instant>,
+}
+
+// The deadline is not part of the call's identity: a call stays the same call
+// after a deadline got set on it.
+impl PartialEq for ZomeFnCall {
+    fn eq(&self, other: &ZomeFnCall) -> bool {
+        self.id == other.id
+            && self.zome_name == other.zome_name
+            && self.cap == other.cap
+            && self.fn_name == other.fn_name
+            && self.parameters == other.parameters
+    }
+}
+
+impl Hash for ZomeFnCall {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.zome_name.hash(state);
+        self.cap.hash(state);
+        self.fn_name.hash(state);
+        self.parameters.hash(state);
+    }
 }
 
 impl ZomeFnCall {
@@ -49,9 +78,43 @@ impl ZomeFnCall {
             cap: cap,
             fn_name: function.to_string(),
             parameters: parameters.into(),
+            deadline: None,
         }
     }
 
+    /// Sets the point in time after which the call gets aborted with a timeout error.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline of the call to the given duration from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Time left until the deadline, `None` if the call has no deadline.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            let now = Instant::now();
+            if now < deadline {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            }
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false)
+    }
+
     pub fn create<J: Into<JsonString>>(
         context: Arc<Context>,
         zome: &str,
@@ -178,7 +241,7 @@ pub mod tests {
     ) -> CapabilityRequest {
         make_cap_request_for_call(
             context.clone(),
-            Address::from(context.agent_id.address()),
+            Address::from(context.agent_id().address()),
             function,
             parameters,
         )
@@ -280,6 +343,54 @@ pub mod tests {
         assert_eq!(JsonString::from(RawString::from(1337)), result.unwrap());
     }
 
+    #[test]
+    /// tests that consecutive zome calls, which run on threads of their own, reuse the
+    /// pooled WASM instance of their zome
+    fn consecutive_zome_calls_reuse_pooled_instances() {
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let test_setup = setup_test(dna, "consecutive_zome_calls_reuse_pooled_instances");
+        let context = test_setup.context.clone();
+        let token = context.get_public_token().unwrap();
+        let call = || {
+            let zome_call = ZomeFnCall::create(
+                context.clone(),
+                "test_zome",
+                token.clone(),
+                "public_test_fn",
+                "",
+            );
+            context.block_on(call_zome_function(zome_call, context.clone()))
+        };
+
+        assert!(call().is_ok());
+        let pool = context.wasm_instance_pools.get("test_zome").unwrap();
+        let created = pool.created_count();
+        assert!(pool.idle_count() > 0);
+
+        assert!(call().is_ok());
+        assert_eq!(pool.created_count(), created);
+    }
+
+    #[test]
+    /// tests that a call whose deadline has passed gets aborted with a timeout
+    fn test_call_zome_function_after_deadline() {
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let test_setup = setup_test(dna, "test_call_zome_function_after_deadline");
+        let context = test_setup.context.clone();
+        let token = context.get_public_token().unwrap();
+
+        let zome_call =
+            ZomeFnCall::create(context.clone(), "test_zome", token, "public_test_fn", "")
+                .with_deadline(Instant::now());
+
+        let result = context.block_on(call_zome_function(zome_call, context.clone()));
+
+        match result {
+            Err(HolochainError::Timeout(_)) => (),
+            result => panic!("expected a timeout, got {:?}", result),
+        }
+    }
+
     #[test]
     /// tests that calling an invalid DNA returns the correct error
     fn call_ribosome_wrong_dna() {
@@ -373,4 +484,22 @@ pub mod tests {
         assert!(!base.same_fn_as(&diff1));
         assert!(!base.same_fn_as(&diff2));
     }
+
+    #[test]
+    fn test_zomefncall_deadline() {
+        let call = test_zome_call();
+        assert_eq!(call.deadline(), None);
+        assert_eq!(call.remaining_time(), None);
+        assert!(!call.is_expired());
+
+        let call = test_zome_call().with_timeout(Duration::from_secs(60));
+        assert!(!call.is_expired());
+        assert!(call.remaining_time().unwrap() <= Duration::from_secs(60));
+
+        let call = test_zome_call().with_deadline(Instant::now());
+        assert!(call.is_expired());
+        assert_eq!(call.remaining_time(), Some(Duration::from_secs(0)));
+
+        assert_eq!(call.clone().with_timeout(Duration::from_secs(60)), call);
+    }
 }
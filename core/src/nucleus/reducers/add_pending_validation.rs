@@ -4,10 +4,13 @@ use crate::{
     state::State,
 };
 use holochain_persistence_api::cas::content::AddressableContent;
+use std::sync::Arc;
 
 /// Reduce AddPendingValidation Action.
 /// Inserts boxed EntryWithHeader and dependencies into state, referenced with
 /// the entry's address.
+/// A workflow adds the validation again every time a retry fails, which keeps
+/// the attempts counted so far.
 #[allow(unknown_lints)]
 #[allow(needless_pass_by_value)]
 pub fn reduce_add_pending_validation(
@@ -19,10 +22,33 @@ pub fn reduce_add_pending_validation(
     let pending = unwrap_to!(action => Action::AddPendingValidation);
     let address = pending.entry_with_header.entry.address();
     let workflow = pending.workflow.clone();
-    state.pending_validations.insert(
-        PendingValidationKey::new(address, workflow),
-        pending.clone(),
-    );
+    let key = PendingValidationKey::new(address, workflow);
+    let pending = match state.pending_validations.get(&key) {
+        Some(existing) if existing.attempts > pending.attempts => {
+            let mut pending = (**pending).clone();
+            pending.attempts = existing.attempts;
+            Arc::new(pending)
+        }
+        _ => pending.clone(),
+    };
+    state.pending_validations.insert(key, pending);
+}
+
+/// Reduce CountPendingValidationAttempt Action.
+/// Counts a failed attempt of the pending validation.
+#[allow(unknown_lints)]
+#[allow(needless_pass_by_value)]
+pub fn reduce_count_pending_validation_attempt(
+    state: &mut NucleusState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (address, workflow) = unwrap_to!(action => Action::CountPendingValidationAttempt).clone();
+    let key = PendingValidationKey::new(address, workflow);
+    if let Some(pending) = state.pending_validations.get_mut(&key) {
+        Arc::make_mut(pending).attempts += 1;
+    }
 }
 
 #[cfg(test)]
@@ -37,7 +63,6 @@ pub mod tests {
     };
     use holochain_core_types::{chain_header::test_chain_header, entry::Entry};
     use holochain_json_api::json::RawString;
-    use std::sync::Arc;
 
     #[test]
     fn test_reduce_add_pending_validation() {
@@ -56,6 +81,7 @@ pub mod tests {
                 entry_with_header,
                 dependencies: Vec::new(),
                 workflow: ValidatingWorkflow::HoldEntry,
+                attempts: 0,
             },
         )));
 
@@ -68,4 +94,38 @@ pub mod tests {
                 ValidatingWorkflow::HoldEntry
             )));
     }
+
+    #[test]
+    fn test_reduce_count_pending_validation_attempt() {
+        let context = test_context("jimmy", None);
+        let mut state = test_nucleus_state();
+        let root_state = test_store(context);
+
+        let entry = Entry::App("package_entry".into(), RawString::from("test value").into());
+        let key = PendingValidationKey::new(entry.address(), ValidatingWorkflow::HoldEntry);
+        let add_action = ActionWrapper::new(Action::AddPendingValidation(Arc::new(
+            PendingValidationStruct {
+                entry_with_header: EntryWithHeader {
+                    entry,
+                    header: test_chain_header(),
+                },
+                dependencies: Vec::new(),
+                workflow: ValidatingWorkflow::HoldEntry,
+                attempts: 0,
+            },
+        )));
+        let count_action = ActionWrapper::new(Action::CountPendingValidationAttempt((
+            key.address.clone(),
+            key.workflow.clone(),
+        )));
+
+        reduce_add_pending_validation(&mut state, &root_state, &add_action);
+        reduce_count_pending_validation_attempt(&mut state, &root_state, &count_action);
+        reduce_count_pending_validation_attempt(&mut state, &root_state, &count_action);
+        assert_eq!(state.pending_validations[&key].attempts, 2);
+
+        // A failed retry adds the validation again without resetting its attempts
+        reduce_add_pending_validation(&mut state, &root_state, &add_action);
+        assert_eq!(state.pending_validations[&key].attempts, 2);
+    }
 }
@@ -0,0 +1,136 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    nucleus::state::{NucleusState, PendingValidationKey},
+    state::State,
+};
+use std::sync::Arc;
+
+/// Reduce AddDeadLetterValidation Action.
+/// Moves the pending validation from the pending list to the dead letters.
+#[allow(unknown_lints)]
+#[allow(needless_pass_by_value)]
+pub fn reduce_add_dead_letter_validation(
+    state: &mut NucleusState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let dead_letter = unwrap_to!(action => Action::AddDeadLetterValidation);
+    let key = dead_letter.pending.key();
+    state.pending_validations.remove(&key);
+    state
+        .dead_letter_validations
+        .insert(key, dead_letter.clone());
+}
+
+/// Reduce RetryDeadLetterValidation Action.
+/// Moves the dead-lettered validation back to the pending list,
+/// where it gets all attempts again.
+#[allow(unknown_lints)]
+#[allow(needless_pass_by_value)]
+pub fn reduce_retry_dead_letter_validation(
+    state: &mut NucleusState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (address, workflow) = unwrap_to!(action => Action::RetryDeadLetterValidation).clone();
+    let key = PendingValidationKey::new(address, workflow);
+    if let Some(dead_letter) = state.dead_letter_validations.remove(&key) {
+        let mut pending = (*dead_letter.pending).clone();
+        pending.attempts = 0;
+        state.pending_validations.insert(key, Arc::new(pending));
+    }
+}
+
+/// Reduce RemoveDeadLetterValidation Action.
+/// Drops the dead-lettered validation.
+#[allow(unknown_lints)]
+#[allow(needless_pass_by_value)]
+pub fn reduce_remove_dead_letter_validation(
+    state: &mut NucleusState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (address, workflow) = unwrap_to!(action => Action::RemoveDeadLetterValidation).clone();
+    state
+        .dead_letter_validations
+        .remove(&PendingValidationKey::new(address, workflow));
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_context,
+        network::entry_with_header::EntryWithHeader,
+        nucleus::{
+            reducers::add_pending_validation::reduce_add_pending_validation,
+            state::tests::test_nucleus_state,
+        },
+        scheduled_jobs::pending_validations::{
+            DeadLetterValidation, PendingValidationStruct, ValidatingWorkflow,
+        },
+        state::test_store,
+    };
+    use holochain_core_types::{chain_header::test_chain_header, entry::Entry};
+    use holochain_json_api::json::RawString;
+    use holochain_persistence_api::cas::content::AddressableContent;
+
+    #[test]
+    fn test_reduce_dead_letter_validation() {
+        let context = test_context("jimmy", None);
+        let mut nucleus_state = test_nucleus_state();
+        let state = test_store(context);
+
+        let entry = Entry::App("package_entry".into(), RawString::from("test value").into());
+        let pending = Arc::new(PendingValidationStruct {
+            entry_with_header: EntryWithHeader {
+                entry: entry.clone(),
+                header: test_chain_header(),
+            },
+            dependencies: Vec::new(),
+            workflow: ValidatingWorkflow::HoldEntry,
+            attempts: 0,
+        });
+        let key = PendingValidationKey::new(entry.address(), ValidatingWorkflow::HoldEntry);
+
+        let action_wrapper = ActionWrapper::new(Action::AddPendingValidation(pending.clone()));
+        reduce_add_pending_validation(&mut nucleus_state, &state, &action_wrapper);
+
+        let action_wrapper =
+            ActionWrapper::new(Action::AddDeadLetterValidation(DeadLetterValidation {
+                pending: Arc::new(PendingValidationStruct {
+                    attempts: 9,
+                    ..(*pending).clone()
+                }),
+                attempts: 10,
+            }));
+        reduce_add_dead_letter_validation(&mut nucleus_state, &state, &action_wrapper);
+        assert!(!nucleus_state.pending_validations.contains_key(&key));
+        assert_eq!(nucleus_state.dead_letter_validations[&key].attempts, 10);
+
+        let action_wrapper = ActionWrapper::new(Action::RetryDeadLetterValidation((
+            entry.address(),
+            ValidatingWorkflow::HoldEntry,
+        )));
+        reduce_retry_dead_letter_validation(&mut nucleus_state, &state, &action_wrapper);
+        assert!(nucleus_state.dead_letter_validations.is_empty());
+        assert_eq!(nucleus_state.pending_validations.get(&key), Some(&pending));
+
+        let action_wrapper =
+            ActionWrapper::new(Action::AddDeadLetterValidation(DeadLetterValidation {
+                pending,
+                attempts: 10,
+            }));
+        reduce_add_dead_letter_validation(&mut nucleus_state, &state, &action_wrapper);
+        let action_wrapper = ActionWrapper::new(Action::RemoveDeadLetterValidation((
+            entry.address(),
+            ValidatingWorkflow::HoldEntry,
+        )));
+        reduce_remove_dead_letter_validation(&mut nucleus_state, &state, &action_wrapper);
+        assert!(nucleus_state.dead_letter_validations.is_empty());
+        assert!(nucleus_state.pending_validations.is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 mod add_pending_validation;
+mod dead_letter_validation;
 pub mod init_application;
 mod remove_pending_validation;
 pub mod return_initialization_result;
@@ -10,7 +11,13 @@ use crate::{
     action::{Action, ActionWrapper, NucleusReduceFn},
     nucleus::{
         reducers::{
-            add_pending_validation::reduce_add_pending_validation,
+            add_pending_validation::{
+                reduce_add_pending_validation, reduce_count_pending_validation_attempt,
+            },
+            dead_letter_validation::{
+                reduce_add_dead_letter_validation, reduce_remove_dead_letter_validation,
+                reduce_retry_dead_letter_validation,
+            },
             init_application::reduce_initialize_chain,
             remove_pending_validation::reduce_remove_pending_validation,
             return_initialization_result::reduce_return_initialization_result,
@@ -32,6 +39,10 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NucleusReduceFn> {
     match action_wrapper.action() {
         Action::AddPendingValidation(_) => Some(reduce_add_pending_validation),
         Action::RemovePendingValidation(_) => Some(reduce_remove_pending_validation),
+        Action::CountPendingValidationAttempt(_) => Some(reduce_count_pending_validation_attempt),
+        Action::AddDeadLetterValidation(_) => Some(reduce_add_dead_letter_validation),
+        Action::RetryDeadLetterValidation(_) => Some(reduce_retry_dead_letter_validation),
+        Action::RemoveDeadLetterValidation(_) => Some(reduce_remove_dead_letter_validation),
         Action::ReturnInitializationResult(_) => Some(reduce_return_initialization_result),
         Action::InitializeChain(_) => Some(reduce_initialize_chain),
         Action::ReturnZomeFunctionResult(_) => Some(reduce_return_zome_function_result),
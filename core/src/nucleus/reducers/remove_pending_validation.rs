@@ -57,6 +57,7 @@ pub mod tests {
                 entry_with_header,
                 dependencies: Vec::new(),
                 workflow: ValidatingWorkflow::HoldEntry,
+                attempts: 0,
             },
         )));
 
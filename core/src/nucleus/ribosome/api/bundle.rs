@@ -0,0 +1,129 @@
+use crate::{
+    agent::{
+        actions::bundle::{discard_bundle, stage_entry, start_bundle},
+        bundle::{CommitBundle, StagedEntry},
+    },
+    nucleus::ribosome::{api::ZomeApiResult, Runtime},
+    workflows::{author_bundle::author_bundle, author_entry::author_entry},
+};
+use holochain_core_types::{entry::Entry, error::HolochainError, signature::Provenance};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use holochain_wasm_utils::api_serialization::{
+    bundle::{BundleOnClose, StartBundleArgs},
+    commit_entry::CommitEntryResult,
+};
+use std::{convert::TryFrom, time::Duration};
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// Authors the given entry, or stages it if the calling zome function has opened a bundle.
+/// Staging fails with a timeout once the bundle timed out.
+pub fn author_or_stage_entry(
+    runtime: &Runtime,
+    entry: &Entry,
+    maybe_link_update_delete: Option<Address>,
+    provenances: &Vec<Provenance>,
+) -> Result<CommitEntryResult, HolochainError> {
+    let context = runtime.context().map_err(|_| {
+        HolochainError::ErrorGeneric("Could not get context of call".to_string())
+    })?;
+    let open_bundle = runtime.zome_call_data().ok().and_then(|data| {
+        context
+            .state()
+            .and_then(|state| state.agent().bundle(&data.call.id()))
+            .map(|bundle| (data.call.id(), bundle))
+    });
+    match open_bundle {
+        Some((_, ref bundle)) if bundle.is_expired() => Err(HolochainError::Timeout(None)),
+        Some((id, _)) => {
+            let staged = StagedEntry {
+                entry: entry.clone(),
+                maybe_link_update_delete,
+                provenances: provenances.clone(),
+            };
+            stage_entry(id, staged, &context);
+            Ok(CommitEntryResult::new(entry.address()))
+        }
+        None => context.block_on(author_entry(
+            entry,
+            maybe_link_update_delete,
+            &context,
+            provenances,
+        )),
+    }
+}
+
+/// ZomeApiFunction::StartBundle function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: StartBundleArgs
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_start_bundle(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    let call_data = runtime.zome_call_data()?;
+    let args_str = runtime.load_json_string_from_args(&args);
+    let start_bundle_args = match StartBundleArgs::try_from(args_str.clone()) {
+        Ok(args) => args,
+        // Exit on error
+        Err(error) => {
+            log_error!(context,
+                "zome: invoke_start_bundle failed to \
+                 deserialize arguments: {:?} with error {:?}",
+                args_str, error
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let id = call_data.call.id();
+    let result = match context.state() {
+        Some(state) if state.agent().bundle(&id).is_some() => Err(HolochainError::new(
+            "A bundle is already open, close it before starting another one",
+        )),
+        Some(_) => {
+            let timeout = Some(start_bundle_args.timeout)
+                .filter(|timeout| *timeout > 0)
+                .map(|timeout| Duration::from_millis(timeout as u64));
+            start_bundle(
+                id,
+                CommitBundle::new(timeout, start_bundle_args.user_param),
+                &context,
+            );
+            Ok(())
+        }
+        None => Err(HolochainError::new("Could not get state")),
+    };
+
+    runtime.store_result(result)
+}
+
+/// ZomeApiFunction::CloseBundle function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: BundleOnClose
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_close_bundle(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    let call_data = runtime.zome_call_data()?;
+    let args_str = runtime.load_json_string_from_args(&args);
+    let action = match BundleOnClose::try_from(args_str.clone()) {
+        Ok(action) => action,
+        // Exit on error
+        Err(error) => {
+            log_error!(context,
+                "zome: invoke_close_bundle failed to \
+                 deserialize arguments: {:?} with error {:?}",
+                args_str, error
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let id = call_data.call.id();
+    let result = match action {
+        BundleOnClose::Commit => context.block_on(author_bundle(id, &context)).map(|_| ()),
+        BundleOnClose::Discard => {
+            discard_bundle(id, &context);
+            Ok(())
+        }
+    };
+
+    runtime.store_result(result)
+}
@@ -91,6 +91,11 @@ fn local_call(runtime: &mut Runtime, input: ZomeFnCallArgs) -> Result<JsonString
     })?;
     // ZomeFnCallArgs to ZomeFnCall
     let zome_call = ZomeFnCall::from_args(context.clone(), input);
+    // The called function has to finish within the deadline of the calling one
+    let zome_call = match runtime.zome_call_data().ok().and_then(|data| data.call.deadline()) {
+        Some(deadline) => zome_call.with_deadline(deadline),
+        None => zome_call,
+    };
     context.block_on(call_zome_function(zome_call, context.clone()))
 }
 
@@ -102,9 +107,15 @@ fn bridge_call(runtime: &mut Runtime, input: ZomeFnCallArgs) -> Result<JsonStrin
     })?;
     let conductor_api = context.conductor_api.clone();
 
+    // The bridged call gets the time that is left until the deadline of the calling one
+    let timeout = runtime
+        .remaining_time()
+        .map(|remaining| format!(r#", "timeout": {}"#, remaining.as_millis()))
+        .unwrap_or_default();
+
     let params = format!(
-        r#"{{"instance_id":"{}", "zome": "{}", "function": "{}", "args": {}}}"#,
-        input.instance_handle, input.zome_name, input.fn_name, input.fn_args
+        r#"{{"instance_id":"{}", "zome": "{}", "function": "{}", "args": {}{}}}"#,
+        input.instance_handle, input.zome_name, input.fn_name, input.fn_args, timeout
     );
 
     let handler = conductor_api.get().write().unwrap();
@@ -378,7 +389,7 @@ pub mod tests {
         test_reduce_call(&test_setup, cap_request, expected_failure.clone());
 
         // test assigned capability where the caller is the agent
-        let agent_token_str = test_setup.context.agent_id.address();
+        let agent_token_str = test_setup.context.agent_id().address();
         let cap_request = make_cap_request_for_call(
             test_setup.context.clone(),
             Address::from(agent_token_str.clone()),
@@ -389,7 +400,7 @@ pub mod tests {
 
         // test assigned capability where the caller is someone else
         let other_agent_context = test_context("other agent", None);
-        let someone = other_agent_context.agent_id.address();
+        let someone = other_agent_context.agent_id().address();
         let mut cap_functions = CapFunctions::new();
         cap_functions.insert("test_zome".to_string(), vec![String::from("test")]);
         let grant = CapTokenGrant::create(
@@ -467,7 +478,7 @@ pub mod tests {
             "test_zome",
             make_cap_request_for_call(
                 context.clone(),
-                Address::from(context.agent_id.address()), // <- agent token
+                Address::from(context.agent_id().address()), // <- agent token
                 "foo_function",                            //<- not the function in the zome_call!
                 "{}",
             ),
@@ -483,7 +494,7 @@ pub mod tests {
             "test_zome",
             make_cap_request_for_call(
                 context.clone(),
-                Address::from(context.agent_id.address()), // <- agent token
+                Address::from(context.agent_id().address()), // <- agent token
                 "test",
                 "{}",
             ),
@@ -1,6 +1,6 @@
-use crate::{
-    nucleus::ribosome::{api::ZomeApiResult, Runtime},
-    workflows::author_entry::author_entry,
+use crate::nucleus::ribosome::{
+    api::{bundle::author_or_stage_entry, ZomeApiResult},
+    Runtime,
 };
 use holochain_core_types::error::HolochainError;
 
@@ -30,12 +30,12 @@ pub fn invoke_commit_app_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> Zom
         }
     };
     // Wait for future to be resolved
-    let task_result: Result<CommitEntryResult, HolochainError> = context.block_on(author_entry(
+    let task_result: Result<CommitEntryResult, HolochainError> = author_or_stage_entry(
+        runtime,
         &commit_entry_arg.entry(),
         None,
-        &context,
         &commit_entry_arg.options().provenance(),
-    ));
+    );
 
     runtime.store_result(task_result)
 }
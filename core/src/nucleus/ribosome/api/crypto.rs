@@ -33,7 +33,7 @@ pub fn invoke_crypto(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
 
     log_debug!(context,
         "zome: crypto method {:?} of data:{:?} by:{:?} is:{:?}",
-        crypto_args.method, crypto_args.payload, context.agent_id, message
+        crypto_args.method, crypto_args.payload, context.agent_id(), message
     );
 
     runtime.store_result(message)
@@ -13,7 +13,7 @@ use snowflake::ProcessUniqueId;
 use std::sync::Arc;
 use wasmi::{RuntimeArgs, RuntimeValue};
 
-fn conductor_callback<S: Into<String>>(
+pub fn conductor_callback<S: Into<String>>(
     method: S,
     params: S,
     context: Arc<Context>,
@@ -1,6 +1,6 @@
-use crate::{
-    nucleus::ribosome::{api::ZomeApiResult, runtime::Runtime},
-    workflows::author_entry::author_entry,
+use crate::nucleus::ribosome::{
+    api::{bundle::author_or_stage_entry, ZomeApiResult},
+    runtime::Runtime,
 };
 use holochain_core_types::{
     entry::Entry,
@@ -49,15 +49,14 @@ pub fn invoke_link_entries(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApi
         &link,
         LinkActionKind::ADD,
         top_chain_header,
-        context.agent_id.clone(),
+        context.agent_id(),
     );
     let entry = Entry::LinkAdd(link_add);
 
     // Wait for future to be resolved
     // This is where the link entry actually gets created.
-    let result: Result<Address, HolochainError> = context
-        .block_on(author_entry(&entry, None, &context, &vec![]))
-        .map(|_| entry.address().clone());
+    let result: Result<Address, HolochainError> =
+        author_or_stage_entry(runtime, &entry, None, &vec![]).map(|_| entry.address().clone());
 
     runtime.store_result(result)
 }
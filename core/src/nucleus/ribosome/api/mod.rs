@@ -1,6 +1,7 @@
 //! Module for ZomeApiFunctions
 //! ZomeApiFunctions are the functions provided by the ribosome that are callable by Zomes.
 
+pub mod bundle;
 pub mod call;
 pub mod commit;
 pub mod debug;
@@ -17,17 +18,22 @@ pub mod capabilities;
 pub mod crypto;
 pub mod keystore;
 pub mod query;
+pub mod random_bytes;
 pub mod remove_entry;
 pub mod remove_link;
 pub mod send;
 pub mod sign;
 pub mod sleep;
+pub mod sys_time;
 pub mod meta;
+pub mod update_agent;
 pub mod update_entry;
+pub mod validation_receipt_count;
 pub mod verify_signature;
 
 use crate::nucleus::ribosome::{
     api::{
+        bundle::{invoke_close_bundle, invoke_start_bundle},
         call::invoke_call,
         capabilities::{invoke_commit_capability_claim, invoke_commit_capability_grant},
         commit::invoke_commit_app_entry,
@@ -46,13 +52,17 @@ use crate::nucleus::ribosome::{
         },
         link_entries::invoke_link_entries,
         query::invoke_query,
+        random_bytes::invoke_random_bytes,
         remove_entry::invoke_remove_entry,
         remove_link::invoke_remove_link,
         send::invoke_send,
         sign::invoke_sign_one_time,
         sleep::invoke_sleep,
+        sys_time::invoke_sys_time,
         meta::invoke_meta,
+        update_agent::invoke_update_agent,
         update_entry::invoke_update_entry,
+        validation_receipt_count::invoke_validation_receipt_count,
         verify_signature::invoke_verify_signature,
     },
     runtime::Runtime,
@@ -154,6 +164,33 @@ link_zome_api! {
     
     ///send a meta
     "hc_meta",Meta,invoke_meta;
+
+    /// Rotate the agent's key by committing a new agent entry that replaces the current one
+    /// update_agent() -> Address
+    "hc_update_agent", UpdateAgent, invoke_update_agent;
+
+    /// Get the host's current time and the timestamp of the agent's chain head.
+    /// Not available in validation callbacks.
+    /// sys_time() -> SysTimeResult
+    "hc_sys_time", SysTime, invoke_sys_time;
+
+    /// Get cryptographically secure random bytes.
+    /// Not available in validation callbacks.
+    /// random_bytes(length: usize) -> RandomBytesResult
+    "hc_random_bytes", RandomBytes, invoke_random_bytes;
+
+    /// Start staging commits and links of the calling zome function in a bundle
+    /// start_bundle(timeout: usize, user_param: JsonString)
+    "hc_start_bundle", StartBundle, invoke_start_bundle;
+
+    /// Commit all entries of the open bundle together, or discard them
+    /// close_bundle(action: BundleOnClose)
+    "hc_close_bundle", CloseBundle, invoke_close_bundle;
+
+    /// Count the distinct validators that returned a valid receipt for an entry we authored.
+    /// Not available in validation callbacks.
+    /// validation_receipt_count(address: Address) -> ValidationReceiptCountResult
+    "hc_validation_receipt_count", ValidationReceiptCount, invoke_validation_receipt_count;
 }
 
 #[cfg(test)]
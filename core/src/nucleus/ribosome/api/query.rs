@@ -61,6 +61,10 @@ use wasmi::{RuntimeArgs, RuntimeValue};
 /// `*`         Zero or more of any character
 /// `**/`       Zero or more of any namespace component
 ///
+/// Results are returned latest first, unless the `ordering` option asks for `Forward` order, and
+/// can be restricted to entries committed within a time range with the `filter_by` option.
+/// Chains with an index answer both from the index instead of walking the whole chain.
+///
 pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     let context = runtime.context()?;
     // deserialize args.
@@ -84,6 +88,8 @@ pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
                     start: query.options.start,
                     limit: query.options.limit,
                     headers: query.options.headers,
+                    ordering: query.options.ordering,
+                    filter_by: query.options.filter_by.clone(),
                 },
             )
         }
@@ -96,6 +102,8 @@ pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
                     start: query.options.start,
                     limit: query.options.limit,
                     headers: query.options.headers,
+                    ordering: query.options.ordering,
+                    filter_by: query.options.filter_by.clone(),
                 },
             )
         }
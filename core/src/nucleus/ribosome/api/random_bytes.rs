@@ -0,0 +1,70 @@
+use crate::nucleus::ribosome::{api::ZomeApiResult, Runtime};
+use holochain_core_types::error::HolochainError;
+use holochain_dpki::utils::generate_random_buf;
+use holochain_wasm_utils::api_serialization::random_bytes::{RandomBytesArgs, RandomBytesResult};
+use std::convert::TryFrom;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// Upper bound for the number of bytes a single call can ask for
+pub const MAX_RANDOM_BYTES: usize = 1024 * 1024;
+
+/// ZomeApiFunction::RandomBytes function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: RandomBytesArgs
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_random_bytes(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    if runtime.is_validation_call() {
+        log_warn!(context, "zome: random_bytes is not available in validation");
+        return runtime.store_result::<RandomBytesResult>(Err(HolochainError::new(
+            "random_bytes is not available in validation callbacks",
+        )));
+    }
+
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let random_bytes_args = match RandomBytesArgs::try_from(args_str.clone()) {
+        Ok(args) => args,
+        // Exit on error
+        Err(err) => {
+            log_error!(context,
+                "zome: invoke_random_bytes failed to deserialize RandomBytesArgs: {:?} got err: {:?}",
+                args_str, err
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    runtime.store_result(random_bytes(random_bytes_args.length))
+}
+
+/// Returns `length` cryptographically secure random bytes
+pub fn random_bytes(length: usize) -> Result<RandomBytesResult, HolochainError> {
+    if length > MAX_RANDOM_BYTES {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Can not get more than {} random bytes at once",
+            MAX_RANDOM_BYTES
+        )));
+    }
+    if length == 0 {
+        return Ok(RandomBytesResult::default());
+    }
+    let mut buf = generate_random_buf(length);
+    let bytes = buf.read_lock().to_vec();
+    Ok(RandomBytesResult { bytes })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{random_bytes, MAX_RANDOM_BYTES};
+
+    #[test]
+    fn test_random_bytes() {
+        let first = random_bytes(32).unwrap().bytes;
+        let second = random_bytes(32).unwrap().bytes;
+        assert_eq!(first.len(), 32);
+        assert_ne!(first, second);
+        assert!(random_bytes(0).unwrap().bytes.is_empty());
+        assert!(random_bytes(MAX_RANDOM_BYTES + 1).is_err());
+    }
+}
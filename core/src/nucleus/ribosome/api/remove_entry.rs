@@ -1,6 +1,9 @@
 use crate::{
-    nucleus::ribosome::{api::ZomeApiResult, Runtime},
-    workflows::{author_entry::author_entry, get_entry_result::get_entry_result_workflow},
+    nucleus::ribosome::{
+        api::{bundle::author_or_stage_entry, ZomeApiResult},
+        Runtime,
+    },
+    workflows::get_entry_result::get_entry_result_workflow,
 };
 use holochain_core_types::{
     entry::{deletion_entry::DeletionEntry, Entry},
@@ -57,14 +60,13 @@ pub fn invoke_remove_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApi
     // Create deletion entry
     let deletion_entry = Entry::Deletion(DeletionEntry::new(deleted_entry_address.clone()));
 
-    let res: Result<Address, HolochainError> = context
-        .block_on(author_entry(
-            &deletion_entry.clone(),
-            Some(deleted_entry_address.clone()),
-            &context.clone(),
-            &vec![],
-        ))
-        .map(|_| deletion_entry.address());
+    let res: Result<Address, HolochainError> = author_or_stage_entry(
+        runtime,
+        &deletion_entry.clone(),
+        Some(deleted_entry_address.clone()),
+        &vec![],
+    )
+    .map(|_| deletion_entry.address());
 
     runtime.store_result(res)
 }
@@ -3,8 +3,10 @@ use crate::{
         actions::query::{query, QueryMethod},
         query::{GetLinksNetworkQuery, GetLinksNetworkResult, GetLinksQueryConfiguration,NetworkQueryResult},
     },
-    nucleus::ribosome::{api::ZomeApiResult, Runtime},
-    workflows::author_entry::author_entry,
+    nucleus::ribosome::{
+        api::{bundle::author_or_stage_entry, ZomeApiResult},
+        Runtime,
+    },
 };
 
 use holochain_core_types::{
@@ -58,7 +60,7 @@ pub fn invoke_remove_link(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiR
         &link,
         LinkActionKind::REMOVE,
         top_chain_header,
-        context.agent_id.clone(),
+        context.agent_id(),
     );
     let get_links_args = GetLinksArgs {
         entry_address: link.base().clone(),
@@ -98,9 +100,8 @@ pub fn invoke_remove_link(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiR
             let entry = Entry::LinkRemove((link_remove, filtered_links));
 
             // Wait for future to be resolved
-            let result: Result<(), HolochainError> = context
-                .block_on(author_entry(&entry, None, &context, &vec![]))
-                .map(|_| ());
+            let result: Result<(), HolochainError> =
+                author_or_stage_entry(runtime, &entry, None, &vec![]).map(|_| ());
 
             runtime.store_result(result)
         }
@@ -2,15 +2,17 @@ use crate::{
     network::{actions::custom_send::custom_send, direct_message::CustomDirectMessage},
     nucleus::ribosome::{api::ZomeApiResult, Runtime},
 };
+use holochain_core_types::time::{Period, Timeout};
 use holochain_json_api::json::JsonString;
 use holochain_wasm_utils::api_serialization::send::SendArgs;
-use std::convert::TryFrom;
+use std::{convert::TryFrom, time::Duration};
 use wasmi::{RuntimeArgs, RuntimeValue};
 
 /// ZomeApiFunction::Send function code
 /// args: [0] encoded MemoryAllocation as u64
 /// Expected complex argument: SendArgs
 /// Returns an HcApiReturnCode as I64
+/// The timeout of the send gets cut short to the time left until the zome call's deadline.
 pub fn invoke_send(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     let call_data = runtime.call_data()?;
     // deserialize args
@@ -20,6 +22,13 @@ pub fn invoke_send(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
         Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
     };
 
+    let timeout = match runtime.remaining_time() {
+        Some(remaining) if remaining < Duration::from(&args.options.0) => {
+            Timeout::from(Period::from(remaining))
+        }
+        _ => args.options.0,
+    };
+
     let message = CustomDirectMessage {
         payload: Ok(args.payload),
         zome: call_data.zome_name.clone(),
@@ -30,7 +39,7 @@ pub fn invoke_send(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
         .block_on(custom_send(
             args.to_agent,
             message,
-            args.options.0,
+            timeout,
             call_data.context.clone(),
         ))
         .map(|s| JsonString::from_json(&s));
@@ -6,6 +6,8 @@ use wasmi::{RuntimeArgs, RuntimeValue};
 /// args: [0] encoded MemoryAllocation as u64
 /// Expected argument: u64
 /// Returns an HcApiReturnCode as I64
+/// Sleeps no longer than until the deadline of the zome call and aborts the call if it
+/// got reached.
 pub fn invoke_sleep(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     // deserialize args
     let args_str = runtime.load_json_string_from_args(&args);
@@ -14,7 +16,12 @@ pub fn invoke_sleep(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
         Err(..) => return ribosome_error_code!(ArgumentDeserializationFailed),
     };
 
-    thread::sleep(Duration::from_nanos(nanos));
+    let duration = Duration::from_nanos(nanos);
+    thread::sleep(match runtime.remaining_time() {
+        Some(remaining) if remaining < duration => remaining,
+        _ => duration,
+    });
+    runtime.check_deadline()?;
 
     ribosome_success!()
 }
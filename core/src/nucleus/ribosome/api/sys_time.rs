@@ -0,0 +1,64 @@
+use crate::nucleus::ribosome::{api::ZomeApiResult, Runtime};
+use holochain_core_types::{error::HolochainError, time::Iso8601};
+use holochain_wasm_utils::api_serialization::sys_time::SysTimeResult;
+use std::time::SystemTime;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// ZomeApiFunction::SysTime function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: none
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_sys_time(runtime: &mut Runtime, _args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    if runtime.is_validation_call() {
+        log_warn!(context, "zome: sys_time is not available in validation");
+        return runtime.store_result::<SysTimeResult>(Err(HolochainError::new(
+            "sys_time is not available in validation callbacks, use the timestamp of the header instead",
+        )));
+    }
+
+    let since_epoch = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("System time must not be before UNIX EPOCH");
+    let chain_head_timestamp = context.state().and_then(|state| {
+        state
+            .agent()
+            .top_chain_header()
+            .map(|header| header.timestamp().clone())
+    });
+
+    runtime.store_result(Ok(SysTimeResult {
+        now: Iso8601::new(since_epoch.as_secs() as i64, since_epoch.subsec_nanos()),
+        chain_head_timestamp,
+    }))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::nucleus::ribosome::{
+        api::{tests::test_zome_api_function, ZomeApiFunction},
+        Defn,
+    };
+    use holochain_core_types::error::ZomeApiInternalResult;
+    use holochain_json_api::json::JsonString;
+    use holochain_wasm_utils::api_serialization::sys_time::SysTimeResult;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_sys_time() {
+        let (call_result, context) = test_zome_api_function(
+            ZomeApiFunction::SysTime.as_str(),
+            JsonString::empty_object().to_bytes(),
+        );
+        let result = ZomeApiInternalResult::try_from(call_result).unwrap();
+        assert!(result.ok);
+        let sys_time = SysTimeResult::try_from(JsonString::from_json(&result.value)).unwrap();
+
+        let top_header = context.state().unwrap().agent().top_chain_header().unwrap();
+        assert_eq!(
+            sys_time.chain_head_timestamp,
+            Some(top_header.timestamp().clone())
+        );
+        assert!(sys_time.now >= *top_header.timestamp());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    agent::actions::commit::{commit_entry_with_provenances, roll_back_commit},
+    context::Context,
+    nucleus::ribosome::{
+        api::{keystore::conductor_callback, ZomeApiResult},
+        Runtime,
+    },
+    workflows::author_entry::{publish_authored_entry, validate_authored_entry},
+};
+use holochain_core_types::{
+    agent::AgentId,
+    entry::Entry,
+    error::HolochainError,
+    signature::{Provenance, Signature},
+};
+use holochain_json_api::json::JsonString;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::sync::Arc;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// Reads a string field from the result of a conductor callback.
+fn result_field(result: JsonString, field: &str) -> Result<String, HolochainError> {
+    let value: serde_json::Value = serde_json::from_str(&String::from(result))?;
+    value[field]
+        .as_str()
+        .map(|field| field.to_string())
+        .ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("Conductor callback returned no {}", field))
+        })
+}
+
+/// Rotates the agent's key:
+/// 1. The conductor creates the next keybundle in the agent's keystore.
+/// 2. The new agent entry with the next key gets signed with that key and committed as
+///    an update of the current agent entry. Like every header, its header gets signed
+///    with the current key, so it carries the signatures of both keys.
+/// 3. The conductor makes the next keybundle the agent's primary keybundle, so everything
+///    committed afterwards gets signed with the new key. If that fails, the new agent entry
+///    gets taken off the chain again.
+/// 4. The instance switches over to the new agent and publishes its entry.
+fn update_agent(context: &Arc<Context>) -> Result<Address, HolochainError> {
+    let old_agent = context
+        .state()
+        .ok_or_else(|| HolochainError::new("Could not get state"))?
+        .agent()
+        .get_agent()?;
+
+    let prepared = conductor_callback("agent/key_rotation/prepare", "{}", context.clone())?;
+    let new_agent = AgentId::new(&old_agent.nick, result_field(prepared, "pub_key")?);
+
+    let params = format!(r#"{{"payload": "{}"}}"#, new_agent.address());
+    let signed = conductor_callback("agent/key_rotation/sign", &params, context.clone())?;
+    let signature = Signature::from(result_field(signed, "signature")?);
+
+    let entry = Entry::AgentId(new_agent.clone());
+    let provenances = vec![Provenance::new(new_agent.address(), signature)];
+    context.block_on(validate_authored_entry(
+        &entry,
+        Some(old_agent.address()),
+        context,
+        &provenances,
+    ))?;
+    let new_agent_address = context.block_on(commit_entry_with_provenances(
+        entry.clone(),
+        Some(old_agent.address()),
+        provenances,
+        context,
+    ))?;
+
+    if let Err(err) = complete_key_rotation(&new_agent, context) {
+        roll_back_key_rotation(&new_agent_address, context)?;
+        return Err(err);
+    }
+
+    context.set_agent_id(new_agent);
+    context.block_on(publish_authored_entry(&entry, context))?;
+
+    Ok(new_agent_address)
+}
+
+fn complete_key_rotation(
+    new_agent: &AgentId,
+    context: &Arc<Context>,
+) -> Result<(), HolochainError> {
+    let pub_key = context.conductor_api.complete_key_rotation()?;
+    if pub_key != new_agent.pub_sign_key {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Conductor switched agent to key {} instead of {}",
+            pub_key, new_agent.pub_sign_key
+        )));
+    }
+    Ok(())
+}
+
+/// Takes the agent entry that failed to complete a key rotation off the chain again.
+/// Fails if something else got committed on top of it in the meantime, which leaves the
+/// chain with an agent entry whose key the conductor does not sign with.
+fn roll_back_key_rotation(
+    new_agent_address: &Address,
+    context: &Arc<Context>,
+) -> Result<(), HolochainError> {
+    let top_chain_header = || {
+        context
+            .state()
+            .and_then(|state| state.agent().top_chain_header())
+            .ok_or_else(|| HolochainError::new("Could not get top chain header"))
+    };
+    let chain_header = top_chain_header()?;
+    if chain_header.entry_address() == new_agent_address {
+        roll_back_commit(chain_header.clone(), context);
+        if top_chain_header()? != chain_header {
+            return Ok(());
+        }
+    }
+    Err(HolochainError::ErrorGeneric(format!(
+        "Could not roll back agent entry {} of the failed key rotation",
+        new_agent_address
+    )))
+}
+
+/// ZomeApiFunction::UpdateAgent function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: none
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_update_agent(runtime: &mut Runtime, _args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    let result = update_agent(&context);
+    if let Err(ref err) = result {
+        log_error!(context, "zome: update_agent failed: {:?}", err);
+    }
+    runtime.store_result(result)
+}
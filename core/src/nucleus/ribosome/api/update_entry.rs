@@ -1,6 +1,9 @@
 use crate::{
-    nucleus::ribosome::{api::ZomeApiResult, Runtime},
-    workflows::{author_entry::author_entry, get_entry_result::get_entry_result_workflow},
+    nucleus::ribosome::{
+        api::{bundle::author_or_stage_entry, ZomeApiResult},
+        Runtime,
+    },
+    workflows::get_entry_result::get_entry_result_workflow,
 };
 use holochain_core_types::{entry::Entry, error::HolochainError};
 
@@ -49,14 +52,13 @@ pub fn invoke_update_entry(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApi
     // Create Chain Entry
     let entry = Entry::from(entry_args.new_entry.clone());
 
-    let res: Result<Address, HolochainError> = context
-        .block_on(author_entry(
-            &entry,
-            Some(latest_entry.clone().address()),
-            &context.clone(),
-            &vec![], // TODO should provenance be a parameter?
-        ))
-        .map(|result| result.address())
+    let res: Result<Address, HolochainError> = author_or_stage_entry(
+        runtime,
+        &entry,
+        Some(latest_entry.clone().address()),
+        &vec![], // TODO should provenance be a parameter?
+    )
+    .map(|result| result.address())
         .map_err(|validation_error| HolochainError::from(validation_error));
 
     runtime.store_result(res)
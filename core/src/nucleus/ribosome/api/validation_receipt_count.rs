@@ -0,0 +1,44 @@
+use crate::nucleus::ribosome::{api::ZomeApiResult, Runtime};
+use holochain_core_types::error::HolochainError;
+use holochain_wasm_utils::api_serialization::validation_receipt_count::{
+    ValidationReceiptCountArgs, ValidationReceiptCountResult,
+};
+use std::convert::TryFrom;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// ZomeApiFunction::ValidationReceiptCount function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: ValidationReceiptCountArgs
+/// Returns an HcApiReturnCode as I64
+pub fn invoke_validation_receipt_count(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let context = runtime.context()?;
+    if runtime.is_validation_call() {
+        log_warn!(
+            context,
+            "zome: validation_receipt_count is not available in validation"
+        );
+        return runtime.store_result::<ValidationReceiptCountResult>(Err(HolochainError::new(
+            "validation_receipt_count is not available in validation callbacks",
+        )));
+    }
+
+    // deserialize args
+    let args_str = runtime.load_json_string_from_args(&args);
+    let receipt_count_args = match ValidationReceiptCountArgs::try_from(args_str.clone()) {
+        Ok(args) => args,
+        // Exit on error
+        Err(err) => {
+            log_error!(context,
+                "zome: invoke_validation_receipt_count failed to deserialize ValidationReceiptCountArgs: {:?} got err: {:?}",
+                args_str, err
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    let result = context
+        .receipt_store()
+        .count(&receipt_count_args.address)
+        .map(|count| ValidationReceiptCountResult { count });
+    runtime.store_result(result)
+}
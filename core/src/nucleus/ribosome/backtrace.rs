@@ -0,0 +1,422 @@
+//! Backtraces of WASM calls that trap.
+//!
+//! wasmi does not tell where in the WASM code a trap happened. So when a WASM module of an
+//! instance with backtraces enabled gets loaded (see `wasm_module_factory()` and
+//! `Context::wasm_backtraces`), every function defined in it gets wrapped in a
+//! function that calls the host function `trace_call` with the index of the wrapped function
+//! before calling it, and once more after it returned. The `Runtime` keeps these calls on a
+//! shadow stack. A trap unwinds the WASM stack without returning, so the shadow stack still
+//! holds the functions that were running when it happened.
+//!
+//! The function indexes get resolved to names with the name section of the WASM binary,
+//! which gets read before the module is instrumented.
+//!
+//! This costs two host function calls per WASM function call, so it is opt-in. The last
+//! Zome API functions a call made get reported either way.
+
+use parity_wasm::{
+    builder,
+    elements::{
+        Func, FuncBody, ImportCountType, Instruction, Instructions, Internal, Module, Section,
+        Type, ValueType,
+    },
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+/// Name of the host function that instrumented modules import from the "env" module.
+pub const TRACE_FUNCTION_NAME: &str = "trace_call";
+
+/// Host function index of `trace_call`.
+/// Lies outside of the indexes of `ZomeApiFunction` so that it can't be confused with them.
+pub const TRACE_FUNCTION_INDEX: usize = usize::max_value() - 2;
+
+/// Argument of `trace_call` after a function returned.
+/// All other arguments are the index of the function that gets called.
+pub const RETURN_MARKER: i32 = -1;
+
+/// Number of Zome API function calls that show up in a backtrace.
+pub const HOST_CALL_HISTORY: usize = 10;
+
+/// Id of the subsection of the name section that holds the function names.
+const FUNCTION_NAMES_SUBSECTION: u8 = 1;
+
+/// Reads an unsigned LEB128 number from the start of `bytes` and advances past it.
+fn read_leb128(bytes: &mut &[u8]) -> Option<u32> {
+    let mut result: u32 = 0;
+    for shift in (0..35).step_by(7) {
+        let (byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        result |= u32::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Reads `len` bytes from the start of `bytes` and advances past them.
+fn read_bytes<'a>(bytes: &mut &'a [u8], len: u32) -> Option<&'a [u8]> {
+    let len = len as usize;
+    if bytes.len() < len {
+        return None;
+    }
+    let (read, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Some(read)
+}
+
+fn parse_function_names(mut payload: &[u8]) -> Option<HashMap<u32, String>> {
+    let mut names = HashMap::new();
+    while !payload.is_empty() {
+        let id = *payload.first()?;
+        payload = &payload[1..];
+        let size = read_leb128(&mut payload)?;
+        let mut subsection = read_bytes(&mut payload, size)?;
+        if id != FUNCTION_NAMES_SUBSECTION {
+            continue;
+        }
+        let count = read_leb128(&mut subsection)?;
+        for _ in 0..count {
+            let index = read_leb128(&mut subsection)?;
+            let len = read_leb128(&mut subsection)?;
+            let name = read_bytes(&mut subsection, len)?;
+            names.insert(index, String::from_utf8_lossy(name).into_owned());
+        }
+    }
+    Some(names)
+}
+
+/// Returns the names of the functions of the given module by their index, as found in its
+/// name section. Modules without or with a malformed name section have no names.
+pub fn function_names(module: &Module) -> HashMap<u32, String> {
+    module
+        .sections()
+        .iter()
+        .filter_map(|section| match section {
+            Section::Custom(custom) if custom.name() == "name" => {
+                parse_function_names(custom.payload())
+            }
+            _ => None,
+        })
+        .next()
+        .unwrap_or_default()
+}
+
+/// Wraps every function defined in the given module, except for the start function,
+/// in a function that reports calling and returning from it to the host function
+/// `trace_call`. The functions keep their indexes, so the wrappers get called
+/// in their place, while the original code moves to new functions at the end.
+/// Modules that don't define functions are left as they are.
+pub fn instrument(module: Module) -> Module {
+    let defined_functions = module
+        .function_section()
+        .map(|functions| functions.entries().len())
+        .unwrap_or(0);
+    if defined_functions == 0 {
+        return module;
+    }
+
+    let mut module_builder = builder::from_module(module);
+    let signature =
+        module_builder.push_signature(builder::signature().with_param(ValueType::I32).build_sig());
+    module_builder.push_import(
+        builder::import()
+            .module("env")
+            .field(TRACE_FUNCTION_NAME)
+            .external()
+            .func(signature)
+            .build(),
+    );
+    let mut module = module_builder.build();
+
+    // The new import comes last among the imported functions,
+    // so all functions defined in the module move up by one index
+    let trace_function = module.import_count(ImportCountType::Function) as u32 - 1;
+    let shift = |index: &mut u32| {
+        if *index >= trace_function {
+            *index += 1
+        }
+    };
+    for section in module.sections_mut() {
+        match section {
+            Section::Code(code) => {
+                for body in code.bodies_mut() {
+                    for instruction in body.code_mut().elements_mut() {
+                        if let Instruction::Call(index) = instruction {
+                            shift(index)
+                        }
+                    }
+                }
+            }
+            Section::Export(exports) => {
+                for export in exports.entries_mut() {
+                    if let Internal::Function(index) = export.internal_mut() {
+                        shift(index)
+                    }
+                }
+            }
+            Section::Element(elements) => {
+                for segment in elements.entries_mut() {
+                    for index in segment.members_mut() {
+                        shift(index)
+                    }
+                }
+            }
+            Section::Start(index) => shift(index),
+            _ => {}
+        }
+    }
+
+    let start_function = module.start_section();
+    let param_counts: Vec<u32> = module
+        .type_section()
+        .map(|types| {
+            types
+                .types()
+                .iter()
+                .map(|Type::Function(function_type)| function_type.params().len() as u32)
+                .collect()
+        })
+        .unwrap_or_default();
+    let type_refs: Vec<u32> = module
+        .function_section()
+        .map(|functions| functions.entries().iter().map(Func::type_ref).collect())
+        .unwrap_or_default();
+
+    let mut inner_type_refs = Vec::new();
+    let mut inner_bodies = Vec::new();
+    if let Some(code) = module.code_section_mut() {
+        let first_defined_function = trace_function + 1;
+        let first_inner_function = first_defined_function + defined_functions as u32;
+        for (position, body) in code.bodies_mut().iter_mut().enumerate() {
+            let index = first_defined_function + position as u32;
+            if Some(index) == start_function {
+                continue;
+            }
+            let type_ref = type_refs[position];
+            let inner_function = first_inner_function + inner_bodies.len() as u32;
+            // Index of the function in the binary, which the name section refers to
+            let original_index = index - 1;
+
+            let mut instructions = vec![
+                Instruction::I32Const(original_index as i32),
+                Instruction::Call(trace_function),
+            ];
+            let param_count = param_counts.get(type_ref as usize).cloned().unwrap_or(0);
+            instructions.extend((0..param_count).map(Instruction::GetLocal));
+            instructions.extend(vec![
+                Instruction::Call(inner_function),
+                Instruction::I32Const(RETURN_MARKER),
+                Instruction::Call(trace_function),
+                Instruction::End,
+            ]);
+            let wrapper = FuncBody::new(Vec::new(), Instructions::new(instructions));
+            inner_bodies.push(std::mem::replace(body, wrapper));
+            inner_type_refs.push(type_ref);
+        }
+        code.bodies_mut().extend(inner_bodies);
+    }
+    if let Some(functions) = module.function_section_mut() {
+        functions
+            .entries_mut()
+            .extend(inner_type_refs.into_iter().map(Func::new));
+    }
+    module
+}
+
+/// The functions a WASM call is in and the last Zome API functions it called.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CallTracer {
+    stack: Vec<u32>,
+    host_calls: VecDeque<&'static str>,
+}
+
+impl CallTracer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Handles a call to `trace_call` with the given argument.
+    pub fn trace(&mut self, function: i32) {
+        if function == RETURN_MARKER {
+            self.stack.pop();
+        } else {
+            self.stack.push(function as u32);
+        }
+    }
+
+    /// Records a call to the Zome API function with the given name.
+    pub fn host_call(&mut self, name: &'static str) {
+        if self.host_calls.len() == HOST_CALL_HISTORY {
+            self.host_calls.pop_front();
+        }
+        self.host_calls.push_back(name);
+    }
+
+    /// Depth of WASM functions the call is in.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Resolves the functions the call is in with the given names.
+    pub fn backtrace(&self, function_names: &HashMap<u32, String>) -> Backtrace {
+        Backtrace {
+            frames: self
+                .stack
+                .iter()
+                .rev()
+                .map(|index| {
+                    function_names
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_else(|| format!("<function {}>", index))
+                })
+                .collect(),
+            host_calls: self
+                .host_calls
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Where a WASM call was when it trapped.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Backtrace {
+    /// Names of the functions the call was in, innermost first.
+    pub frames: Vec<String>,
+    /// The last Zome API functions the call made, oldest first.
+    pub host_calls: Vec<String>,
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "WASM backtrace:")?;
+        for (number, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "  {:>3}: {}", number, frame)?;
+        }
+        if self.host_calls.is_empty() {
+            write!(f, "No Zome API functions called")
+        } else {
+            write!(f, "Last Zome API calls: {}", self.host_calls.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use self::wabt::Wat2Wasm;
+    use super::*;
+    use crate::nucleus::ribosome::{
+        factories::{wasm_instance_factory, wasm_module_factory},
+        fuel::FuelMeter,
+        memory::WasmPageManager,
+        memory_limit::MemoryMeter,
+        runtime::{Runtime, WasmCallData},
+    };
+    use std::sync::Arc;
+    use wabt;
+    use wasmi::RuntimeValue;
+
+    fn test_wasm() -> Arc<Vec<u8>> {
+        let wasm = Wat2Wasm::new()
+            .canonicalize_lebs(false)
+            .write_debug_names(true)
+            .convert(
+                r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func $fail
+        (unreachable)
+    )
+
+    (func $check
+        (param $ok i64)
+
+        (if (i64.eqz (get_local $ok)) (then (call $fail)))
+    )
+
+    (func
+        $entry
+        (export "entry")
+        (param $allocation i64)
+        (result i64)
+
+        (call $check (get_local $allocation))
+        (i64.const 0)
+    )
+)
+                "#,
+            )
+            .unwrap()
+            .as_ref()
+            .to_vec();
+        Arc::new(wasm)
+    }
+
+    fn call_entry(
+        allocation: i64,
+        trace_calls: bool,
+    ) -> (Result<Option<RuntimeValue>, wasmi::Error>, Backtrace) {
+        let module = wasm_module_factory(test_wasm(), trace_calls).unwrap();
+        let instance = wasm_instance_factory(&module).unwrap();
+        let mut runtime = Runtime {
+            memory_manager: WasmPageManager::new(&instance),
+            data: WasmCallData::DirectCall("entry".to_string(), test_wasm()),
+            fuel: FuelMeter::new(None),
+            memory: MemoryMeter::new(None, 1),
+            tracer: CallTracer::new(),
+            profile: None,
+            host_call_log: None,
+        };
+        let result =
+            instance.invoke_export("entry", &[RuntimeValue::I64(allocation)], &mut runtime);
+        let backtrace = runtime.tracer.backtrace(module.function_names());
+        (result, backtrace)
+    }
+
+    #[test]
+    fn trap_leaves_functions_on_backtrace() {
+        let (result, backtrace) = call_entry(0, true);
+        assert!(result.is_err());
+        assert_eq!(backtrace.frames, vec!["fail", "check", "entry"]);
+    }
+
+    #[test]
+    fn untraced_calls_have_no_frames() {
+        let (result, backtrace) = call_entry(0, false);
+        assert!(result.is_err());
+        assert!(backtrace.frames.is_empty());
+    }
+
+    #[test]
+    fn returned_functions_leave_backtrace() {
+        let (result, backtrace) = call_entry(1, true);
+        assert_eq!(result.unwrap(), Some(RuntimeValue::I64(0)));
+        assert!(backtrace.frames.is_empty());
+    }
+
+    #[test]
+    fn tracer_keeps_last_host_calls() {
+        let mut tracer = CallTracer::new();
+        tracer.trace(7);
+        for _ in 0..HOST_CALL_HISTORY {
+            tracer.host_call("hc_debug");
+        }
+        tracer.host_call("hc_commit_entry");
+        let backtrace = tracer.backtrace(&HashMap::new());
+        assert_eq!(backtrace.frames, vec!["<function 7>"]);
+        assert_eq!(backtrace.host_calls.len(), HOST_CALL_HISTORY);
+        assert_eq!(
+            backtrace.host_calls.last(),
+            Some(&"hc_commit_entry".to_string())
+        );
+        assert!(backtrace.to_string().ends_with("hc_debug, hc_commit_entry"));
+    }
+}
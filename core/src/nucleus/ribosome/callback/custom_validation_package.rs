@@ -0,0 +1,78 @@
+use crate::{
+    context::Context,
+    nucleus::{
+        ribosome::{self, runtime::WasmCallData},
+        CallbackFnCall,
+    },
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::{entry_type::EntryType, Entry},
+    error::HolochainError,
+};
+
+use holochain_wasm_utils::api_serialization::validation::{
+    CustomValidationPackage, CustomValidationPackageArgs,
+};
+use std::{convert::TryFrom, sync::Arc};
+
+/// Runs the zome's custom validation package callback for an entry whose validation
+/// package definition is `ValidationPackageDefinition::Custom(definition)`.
+/// Only app entries have a zome that can select chain data, all other entry types
+/// get a package that only carries the definition string.
+pub fn get_custom_validation_package(
+    entry: &Entry,
+    chain_header: &ChainHeader,
+    definition: String,
+    context: Arc<Context>,
+) -> Result<CustomValidationPackage, HolochainError> {
+    let app_entry_type = match entry.entry_type() {
+        EntryType::App(app_entry_type) => app_entry_type,
+        _ => {
+            return Ok(CustomValidationPackage {
+                chain_headers: Vec::new(),
+                custom: Some(definition),
+            });
+        }
+    };
+
+    let dna = context.get_dna().expect("Callback called without DNA set!");
+    let zome_name = dna
+        .get_zome_name_for_app_entry_type(&app_entry_type)
+        .ok_or_else(|| {
+            HolochainError::ValidationFailed(format!(
+                "Unknown app entry type '{}'",
+                String::from(app_entry_type.clone()),
+            ))
+        })?;
+
+    let params = CustomValidationPackageArgs {
+        entry_type: app_entry_type.to_string(),
+        entry: entry.clone(),
+        chain_header: chain_header.clone(),
+        definition: definition.clone(),
+    };
+
+    let call = CallbackFnCall::new(&zome_name, "__hdk_get_custom_validation_package", params);
+
+    let result = ribosome::run_dna(
+        Some(call.parameters.to_bytes()),
+        WasmCallData::new_callback_call(context, call),
+    )?;
+
+    if result.is_null() {
+        return Err(HolochainError::SerializationError(String::from(
+            "__hdk_get_custom_validation_package returned empty result",
+        )));
+    }
+
+    // The HDK returns the fail string of the zome's callback if it could not build the package
+    let mut package = CustomValidationPackage::try_from(result.clone()).map_err(|_| {
+        HolochainError::ValidationFailed(format!(
+            "Custom validation package callback failed: {}",
+            String::from(result),
+        ))
+    })?;
+    package.custom = package.custom.or(Some(definition));
+    Ok(package)
+}
@@ -1,47 +1,54 @@
-use crate::{context::Context, workflows::get_entry_result::get_entry_result_workflow};
+use crate::{
+    agent::bundle::BundleId, context::Context,
+    workflows::get_entry_result::get_entry_result_workflow,
+};
 use holochain_core_types::{
     entry::{entry_type::EntryType, Entry},
     error::HolochainError,
     link::Link,
 };
+use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::{get_entry::*, validation::LinkDirection};
 use std::sync::Arc;
 
-/// Retrieves the base and target entries of the link and returns both.
-pub fn get_link_entries(
-    link: &Link,
+/// Retrieves the given entry, either from the DHT or from the given commit bundle
+/// if it is staged there.
+fn get_linked_entry(
+    address: &Address,
+    bundle: Option<&BundleId>,
     context: &Arc<Context>,
-) -> Result<(Entry, Entry), HolochainError> {
-    let base_address = link.base();
-    let target_address = link.target();
-    let entry_args = &GetEntryArgs {
-        address: base_address.clone(),
-        options: Default::default(),
-    };
-    let base_entry_get_result =
-        context.block_on(get_entry_result_workflow(&context, entry_args))?;
-    if !base_entry_get_result.found() {
-        return Err(HolochainError::ErrorGeneric(String::from(
-            "Base for link not found",
-        )));
-    }
-    let base_entry = base_entry_get_result.latest().unwrap();
+) -> Result<Option<Entry>, HolochainError> {
     let entry_args = &GetEntryArgs {
-        address: target_address.clone(),
+        address: address.clone(),
         options: Default::default(),
     };
-    let target_entry_get_result =
-        context.block_on(get_entry_result_workflow(&context, entry_args))?;
-    if !target_entry_get_result.found() {
-        return Err(HolochainError::ErrorGeneric(String::from(
-            "Target for link not found",
-        )));
+    let get_result = context.block_on(get_entry_result_workflow(&context, entry_args))?;
+    if get_result.found() {
+        return Ok(get_result.latest());
     }
+    Ok(bundle.and_then(|id| {
+        context
+            .state()
+            .and_then(|state| state.agent().staged_entry(id, address))
+    }))
+}
+
+/// Retrieves the base and target entries of the link and returns both.
+/// Entries staged in the given commit bundle count as found, so links between
+/// entries of the same bundle can be validated before the bundle gets committed.
+pub fn get_link_entries(
+    link: &Link,
+    bundle: Option<&BundleId>,
+    context: &Arc<Context>,
+) -> Result<(Entry, Entry), HolochainError> {
+    let base_entry = get_linked_entry(link.base(), bundle, context)?.ok_or_else(|| {
+        HolochainError::ErrorGeneric(String::from("Base for link not found"))
+    })?;
+    let target_entry = get_linked_entry(link.target(), bundle, context)?.ok_or_else(|| {
+        HolochainError::ErrorGeneric(String::from("Target for link not found"))
+    })?;
 
-    Ok((
-        base_entry.clone(),
-        target_entry_get_result.latest().unwrap(),
-    ))
+    Ok((base_entry, target_entry))
 }
 
 /// This is a "path" in the DNA tree.
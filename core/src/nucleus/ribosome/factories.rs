@@ -1,14 +1,43 @@
-use crate::nucleus::ribosome::api::ZomeApiFunction;
-use holochain_core_types::error::HolochainError;
-use std::{str::FromStr, sync::Arc};
+use crate::nucleus::ribosome::{
+    api::ZomeApiFunction,
+    backtrace::{self, TRACE_FUNCTION_INDEX, TRACE_FUNCTION_NAME},
+    fuel::{self, GAS_FUNCTION_INDEX, GAS_FUNCTION_NAME},
+    memory_limit::{self, MEMORY_GROW_FUNCTION_INDEX, MEMORY_GROW_FUNCTION_NAME},
+    pool,
+};
+use holochain_core_types::{dna::wasm::ModuleArc, error::HolochainError};
+use parity_wasm::elements;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 use wasmi::{
     self, Error as InterpreterError, FuncInstance, FuncRef, ImportsBuilder, Module,
     ModuleImportResolver, ModuleInstance, ModuleRef, NopExternals, Signature, ValueType,
 };
 
 /// Creates a WASM module, that is the executable program, from a given WASM binary byte array.
-pub fn wasm_module_factory(wasm: Arc<Vec<u8>>) -> Result<Module, HolochainError> {
-    wasmi::Module::from_buffer(&*wasm).map_err(|e| HolochainError::ErrorGeneric(e.into()))
+/// The module gets instrumented with memory limits and fuel metering, and gets a function that
+/// resets it for reuse in an instance pool.
+/// With `trace_calls`, it also gets instrumented with call tracing and the names of its
+/// functions get read from the binary so that backtraces can show them.
+pub fn wasm_module_factory(
+    wasm: Arc<Vec<u8>>,
+    trace_calls: bool,
+) -> Result<ModuleArc, HolochainError> {
+    let module: elements::Module = elements::deserialize_buffer(&*wasm)
+        .map_err(|e| HolochainError::ErrorGeneric(format!("Invalid WASM binary: {}", e)))?;
+    let (module, function_names) = if trace_calls {
+        let function_names = backtrace::function_names(&module);
+        (backtrace::instrument(module), function_names)
+    } else {
+        (module, HashMap::new())
+    };
+    // The gas metering renumbers all functions after the import it adds, including the
+    // ones added by the memory instrumentation and call tracing, so it has to come after them.
+    // The reset function gets added last so that it runs without being metered or traced.
+    let instrumented =
+        pool::make_resettable(fuel::instrument(memory_limit::instrument(module))?);
+    let module = wasmi::Module::from_parity_wasm_module(instrumented)
+        .map_err(|e| HolochainError::ErrorGeneric(e.into()))?;
+    Ok(ModuleArc::with_function_names(module, function_names))
 }
 
 /// Creates a runnable WASM module instance from a module reference.
@@ -27,6 +56,30 @@ pub fn wasm_instance_factory(module: &Module) -> Result<ModuleRef, HolochainErro
             field_name: &str,
             _signature: &Signature,
         ) -> Result<FuncRef, InterpreterError> {
+            // Injected by the fuel metering, takes the amount of fuel to consume
+            if field_name == GAS_FUNCTION_NAME {
+                return Ok(FuncInstance::alloc_host(
+                    Signature::new(&[ValueType::I32][..], None),
+                    GAS_FUNCTION_INDEX,
+                ));
+            }
+            // Injected by the call tracing, takes the index of the function that gets called
+            // or RETURN_MARKER
+            if field_name == TRACE_FUNCTION_NAME {
+                return Ok(FuncInstance::alloc_host(
+                    Signature::new(&[ValueType::I32][..], None),
+                    TRACE_FUNCTION_INDEX,
+                ));
+            }
+            // Replaces grow_memory, takes the number of pages to grow by
+            // and returns the previous number of pages
+            if field_name == MEMORY_GROW_FUNCTION_NAME {
+                return Ok(FuncInstance::alloc_host(
+                    Signature::new(&[ValueType::I32][..], Some(ValueType::I32)),
+                    MEMORY_GROW_FUNCTION_INDEX,
+                ));
+            }
+
             let api_fn = match ZomeApiFunction::from_str(&field_name) {
                 Ok(api_fn) => api_fn,
                 Err(_) => {
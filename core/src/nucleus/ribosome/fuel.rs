@@ -0,0 +1,203 @@
+//! Deterministic execution metering of WASM calls.
+//!
+//! Every WASM module gets instrumented with a gas counter when it is loaded
+//! (see `wasm_module_factory()`): before a block of code runs, it calls the host function
+//! `gas` with the number of instructions in it. The `Runtime` adds these up as the fuel
+//! used by the call and traps it as soon as it exceeds the call's limit. This way a zome
+//! function or callback that loops forever can't pin the thread it runs on.
+//!
+//! Since fuel gets counted in WASM instructions, the same call with the same input
+//! always uses the same amount of fuel.
+
+use holochain_core_types::error::{HcResult, HolochainError};
+use parity_wasm::elements::Module;
+use pwasm_utils::{self, rules};
+use std::fmt;
+use wasmi::{HostError, Trap, TrapKind};
+
+/// Name of the host function that instrumented modules import from the "env" module.
+pub const GAS_FUNCTION_NAME: &str = "gas";
+
+/// Host function index of `gas`.
+/// Lies outside of the indexes of `ZomeApiFunction` so that it can't be confused with them.
+pub const GAS_FUNCTION_INDEX: usize = usize::max_value();
+
+/// Fuel a call uses between two checks of its deadline in the `gas` host function.
+/// Checking the clock on every metered block would slow down all calls.
+pub const DEADLINE_CHECK_INTERVAL: u64 = 100_000;
+
+/// Fuel limits of the WASM calls of an instance. `None` means unlimited.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FuelLimits {
+    /// Limit of every zome function call.
+    pub zome_call: Option<u64>,
+    /// Limit of every callback call, i.e. validation and validation package callbacks
+    /// as well as init and receive.
+    pub validation: Option<u64>,
+}
+
+/// Fuel used by a single WASM call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FuelMeter {
+    limit: Option<u64>,
+    used: u64,
+    next_deadline_check: u64,
+}
+
+/// Trap raised by the `gas` host function once a call exceeds its fuel limit.
+#[derive(Debug)]
+struct OutOfFuel(u64);
+
+impl fmt::Display for OutOfFuel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Out of fuel after {} units", self.0)
+    }
+}
+
+impl HostError for OutOfFuel {}
+
+impl FuelMeter {
+    pub fn new(limit: Option<u64>) -> Self {
+        FuelMeter {
+            limit,
+            used: 0,
+            next_deadline_check: 0,
+        }
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.limit.map(|limit| self.used > limit).unwrap_or(false)
+    }
+
+    /// Adds the given amount to the fuel used so far.
+    /// Returns a trap that aborts the call once the limit is exceeded.
+    pub fn consume(&mut self, amount: u64) -> Result<(), Trap> {
+        self.used = self.used.saturating_add(amount);
+        if self.is_exhausted() {
+            Err(Trap::new(TrapKind::Host(Box::new(OutOfFuel(self.used)))))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether the call used another `DEADLINE_CHECK_INTERVAL` units of fuel since
+    /// this last returned true.
+    pub fn deadline_check_due(&mut self) -> bool {
+        if self.used >= self.next_deadline_check {
+            self.next_deadline_check = self.used.saturating_add(DEADLINE_CHECK_INTERVAL);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Error a call that ran out of fuel fails with, given where it was when it did.
+    pub fn out_of_fuel_error(&self, backtrace: Option<String>) -> HolochainError {
+        HolochainError::OutOfFuel(self.used, backtrace)
+    }
+}
+
+/// Injects calls to the `gas` host function into the given module that
+/// meter every block of code with the number of instructions in it.
+pub fn instrument(module: Module) -> HcResult<Module> {
+    pwasm_utils::inject_gas_counter(module, &rules::Set::default()).map_err(|_| {
+        HolochainError::ErrorGeneric(
+            "Could not inject gas metering into WASM module".to_string(),
+        )
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use self::wabt::Wat2Wasm;
+    use super::*;
+    use crate::nucleus::ribosome::{
+        backtrace::CallTracer,
+        factories::{wasm_instance_factory, wasm_module_factory},
+        memory::WasmPageManager,
+        memory_limit::MemoryMeter,
+        runtime::{Runtime, WasmCallData},
+    };
+    use std::sync::Arc;
+    use wabt;
+    use wasmi::RuntimeValue;
+
+    #[test]
+    fn unlimited_meter_never_runs_out() {
+        let mut meter = FuelMeter::new(None);
+        assert!(meter.consume(u64::max_value()).is_ok());
+        assert!(meter.consume(1).is_ok());
+        assert_eq!(meter.used(), u64::max_value());
+    }
+
+    #[test]
+    fn meter_runs_out_above_limit() {
+        let mut meter = FuelMeter::new(Some(10));
+        assert!(meter.consume(10).is_ok());
+        assert!(!meter.is_exhausted());
+        assert!(meter.consume(1).is_err());
+        assert!(meter.is_exhausted());
+        assert_eq!(
+            meter.out_of_fuel_error(None),
+            HolochainError::OutOfFuel(11, None)
+        );
+    }
+
+    #[test]
+    fn deadline_checks_are_due_every_interval() {
+        let mut meter = FuelMeter::new(None);
+        assert!(meter.deadline_check_due());
+        meter.consume(DEADLINE_CHECK_INTERVAL - 1).unwrap();
+        assert!(!meter.deadline_check_due());
+        meter.consume(1).unwrap();
+        assert!(meter.deadline_check_due());
+        assert!(!meter.deadline_check_due());
+    }
+
+    #[test]
+    fn endless_loop_runs_out_of_fuel() {
+        let wasm = Wat2Wasm::new()
+            .canonicalize_lebs(false)
+            .write_debug_names(true)
+            .convert(
+                r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func
+        (export "spin")
+        (param $allocation i64)
+        (result i64)
+
+        (loop $forever (br $forever))
+        (i64.const 0)
+    )
+)
+                "#,
+            )
+            .unwrap()
+            .as_ref()
+            .to_vec();
+        let module = wasm_module_factory(Arc::new(wasm.clone()), false).unwrap();
+        let instance = wasm_instance_factory(&module).unwrap();
+        let mut runtime = Runtime {
+            memory_manager: WasmPageManager::new(&instance),
+            data: WasmCallData::DirectCall("spin".to_string(), Arc::new(wasm)),
+            fuel: FuelMeter::new(Some(1000)),
+            memory: MemoryMeter::new(None, 1),
+            tracer: CallTracer::new(),
+            profile: None,
+            host_call_log: None,
+        };
+
+        let result = instance.invoke_export("spin", &[RuntimeValue::I64(0)], &mut runtime);
+        assert!(result.is_err());
+        assert!(runtime.fuel.is_exhausted());
+        assert!(runtime.fuel.used() > 1000);
+    }
+}
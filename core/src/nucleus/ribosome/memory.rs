@@ -47,6 +47,11 @@ impl WasmPageManager {
         };
     }
 
+    /// The linear memory of the WASM instance
+    pub fn wasm_memory(&self) -> &MemoryRef {
+        &self.wasm_memory
+    }
+
     /// Allocate on stack without writing in it
     pub fn allocate(&mut self, length: Length) -> AllocationResult {
         let allocation = self.stack.next_allocation(length)?;
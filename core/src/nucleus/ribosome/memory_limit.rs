@@ -0,0 +1,253 @@
+//! Limits on the linear memory of WASM calls.
+//!
+//! When a WASM module gets loaded (see `wasm_module_factory()`), every `grow_memory`
+//! instruction in it gets replaced with a call to the host function `memory_grow`.
+//! The `Runtime` grows the memory on behalf of the module, so it can keep track of the
+//! peak size of memory and trap the call as soon as it tries to grow memory beyond the
+//! page limit of its instance.
+
+use holochain_core_types::error::HolochainError;
+use parity_wasm::{
+    builder,
+    elements::{ImportCountType, Instruction, Internal, Module, Section, ValueType},
+};
+use std::fmt;
+use wasmi::{memory_units::Pages, HostError, MemoryRef, Trap, TrapKind};
+
+/// Name of the host function that instrumented modules import from the "env" module.
+pub const MEMORY_GROW_FUNCTION_NAME: &str = "memory_grow";
+
+/// Host function index of `memory_grow`.
+/// Lies outside of the indexes of `ZomeApiFunction` so that it can't be confused with them.
+pub const MEMORY_GROW_FUNCTION_INDEX: usize = usize::max_value() - 1;
+
+/// Size of linear memory used by a single WASM call, in pages of 64KiB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryMeter {
+    limit: Option<u32>,
+    peak_pages: u32,
+    exceeded: bool,
+}
+
+/// Trap raised by the `memory_grow` host function when memory would exceed its limit.
+#[derive(Debug)]
+struct MemoryLimitExceeded(u32);
+
+impl fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Memory limit of {} pages exceeded", self.0)
+    }
+}
+
+impl HostError for MemoryLimitExceeded {}
+
+impl MemoryMeter {
+    /// Creates a meter for memory that starts out with `initial_pages`.
+    pub fn new(limit: Option<u32>, initial_pages: u32) -> Self {
+        MemoryMeter {
+            limit,
+            peak_pages: initial_pages,
+            exceeded: limit.map(|limit| initial_pages > limit).unwrap_or(false),
+        }
+    }
+
+    pub fn peak_pages(&self) -> u32 {
+        self.peak_pages
+    }
+
+    /// Whether memory was or would have been larger than the limit at some point.
+    pub fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+
+    /// Grows the given memory by `delta` pages, like `grow_memory` does.
+    /// Returns the previous size in pages, or -1 if the memory can't grow any further.
+    /// Returns a trap that aborts the call if the memory would grow beyond the limit.
+    pub fn grow(&mut self, memory: &MemoryRef, delta: u32) -> Result<i32, Trap> {
+        let Pages(current) = memory.current_size();
+        let requested = (current as u32).saturating_add(delta);
+        if let Some(limit) = self.limit {
+            if requested > limit {
+                self.exceeded = true;
+                return Err(Trap::new(TrapKind::Host(Box::new(MemoryLimitExceeded(
+                    limit,
+                )))));
+            }
+        }
+        match memory.grow(Pages(delta as usize)) {
+            Ok(Pages(previous)) => {
+                self.peak_pages = self.peak_pages.max(requested);
+                Ok(previous as i32)
+            }
+            Err(_) => Ok(-1),
+        }
+    }
+
+    /// Error a call that exceeded its memory limit fails with, given where it was when it did.
+    pub fn limit_exceeded_error(&self, backtrace: Option<String>) -> HolochainError {
+        HolochainError::MemoryLimitExceeded(self.limit.unwrap_or_default(), backtrace)
+    }
+}
+
+/// Replaces every `grow_memory` instruction in the given module with a call to the
+/// imported host function `memory_grow`.
+/// Modules that never grow their memory are left as they are.
+pub fn instrument(module: Module) -> Module {
+    let grows_memory = module
+        .code_section()
+        .map(|code| {
+            code.bodies().iter().any(|body| {
+                body.code().elements().iter().any(|instruction| match instruction {
+                    Instruction::GrowMemory(_) => true,
+                    _ => false,
+                })
+            })
+        })
+        .unwrap_or(false);
+    if !grows_memory {
+        return module;
+    }
+
+    let mut module_builder = builder::from_module(module);
+    let signature = module_builder.push_signature(
+        builder::signature()
+            .with_param(ValueType::I32)
+            .with_return_type(Some(ValueType::I32))
+            .build_sig(),
+    );
+    module_builder.push_import(
+        builder::import()
+            .module("env")
+            .field(MEMORY_GROW_FUNCTION_NAME)
+            .external()
+            .func(signature)
+            .build(),
+    );
+    let mut module = module_builder.build();
+
+    // The new import comes last among the imported functions,
+    // so all functions defined in the module move up by one index
+    let grow_function = module.import_count(ImportCountType::Function) as u32 - 1;
+    let shift = |index: &mut u32| {
+        if *index >= grow_function {
+            *index += 1
+        }
+    };
+    for section in module.sections_mut() {
+        match section {
+            Section::Code(code) => {
+                for body in code.bodies_mut() {
+                    for instruction in body.code_mut().elements_mut() {
+                        match instruction {
+                            Instruction::Call(index) => shift(index),
+                            Instruction::GrowMemory(_) => {
+                                *instruction = Instruction::Call(grow_function)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Section::Export(exports) => {
+                for export in exports.entries_mut() {
+                    if let Internal::Function(index) = export.internal_mut() {
+                        shift(index)
+                    }
+                }
+            }
+            Section::Element(elements) => {
+                for segment in elements.entries_mut() {
+                    for index in segment.members_mut() {
+                        shift(index)
+                    }
+                }
+            }
+            Section::Start(index) => shift(index),
+            _ => {}
+        }
+    }
+    module
+}
+
+#[cfg(test)]
+pub mod tests {
+    use self::wabt::Wat2Wasm;
+    use super::*;
+    use crate::nucleus::ribosome::{
+        backtrace::CallTracer,
+        factories::{wasm_instance_factory, wasm_module_factory},
+        fuel::FuelMeter,
+        memory::WasmPageManager,
+        runtime::{Runtime, WasmCallData},
+    };
+    use std::sync::Arc;
+    use wabt;
+    use wasmi::RuntimeValue;
+
+    fn test_runtime(pages: i32, limit: Option<u32>) -> (wasmi::ModuleRef, Runtime) {
+        let wasm = Wat2Wasm::new()
+            .canonicalize_lebs(false)
+            .write_debug_names(true)
+            .convert(format!(
+                r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func
+        (export "grow")
+        (param $allocation i64)
+        (result i64)
+
+        (drop (grow_memory (i32.const {})))
+        (i64.const 0)
+    )
+)
+                "#,
+                pages
+            ))
+            .unwrap()
+            .as_ref()
+            .to_vec();
+        let module = wasm_module_factory(Arc::new(wasm.clone()), false).unwrap();
+        let instance = wasm_instance_factory(&module).unwrap();
+        let runtime = Runtime {
+            memory_manager: WasmPageManager::new(&instance),
+            data: WasmCallData::DirectCall("grow".to_string(), Arc::new(wasm)),
+            fuel: FuelMeter::new(None),
+            memory: MemoryMeter::new(limit, 1),
+            tracer: CallTracer::new(),
+            profile: None,
+            host_call_log: None,
+        };
+        (instance, runtime)
+    }
+
+    #[test]
+    fn meter_tracks_peak_memory() {
+        let (instance, mut runtime) = test_runtime(2, Some(3));
+        let result = instance.invoke_export("grow", &[RuntimeValue::I64(0)], &mut runtime);
+        assert!(result.is_ok());
+        assert!(!runtime.memory.is_exceeded());
+        assert_eq!(runtime.memory.peak_pages(), 3);
+    }
+
+    #[test]
+    fn growing_beyond_limit_traps() {
+        let (instance, mut runtime) = test_runtime(10, Some(4));
+        let result = instance.invoke_export("grow", &[RuntimeValue::I64(0)], &mut runtime);
+        assert!(result.is_err());
+        assert!(runtime.memory.is_exceeded());
+        assert_eq!(runtime.memory.peak_pages(), 1);
+        assert_eq!(
+            runtime.memory.limit_exceeded_error(None),
+            HolochainError::MemoryLimitExceeded(4, None)
+        );
+    }
+
+    #[test]
+    fn initial_memory_above_limit_is_exceeded() {
+        assert!(MemoryMeter::new(Some(1), 2).is_exceeded());
+        assert!(!MemoryMeter::new(None, 2).is_exceeded());
+    }
+}
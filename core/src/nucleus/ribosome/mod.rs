@@ -1,9 +1,15 @@
 //! The virtual machine that runs DNA written in WASM
 
 pub mod api;
+pub mod backtrace;
 pub mod callback;
 pub mod factories;
+pub mod fuel;
 pub mod memory;
+pub mod memory_limit;
+pub mod pool;
+pub mod profile;
+pub mod recording;
 mod run_dna;
 pub mod runtime;
 
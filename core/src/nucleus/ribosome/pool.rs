@@ -0,0 +1,403 @@
+//! Pools of reusable WASM instances.
+//!
+//! Instantiating a module allocates its linear memory and copies its data segments into it,
+//! which costs more than most zome calls and callbacks themselves. So `run_dna()` checks
+//! out instances from a pool per zome and returns them after the call.
+//!
+//! Zome calls run on a thread of their own, so idle instances are shared between threads
+//! and a pooled instance can be used by a different thread than the one that created it
+//! (see `IdleInstance` for why that is safe).
+//!
+//! Before an instance goes back into its pool, it gets reset to the state it was in right
+//! after instantiation: its memory gets overwritten with a snapshot of the initial memory
+//! and its mutable globals get set to their initial values by a function that gets added
+//! to every module when it is loaded (see `wasm_module_factory()`). Instances that can't be
+//! reset are dropped, so nothing one call leaves in memory is visible to the next.
+
+use crate::nucleus::ribosome::factories::wasm_instance_factory;
+use holochain_core_types::{dna::wasm::ModuleArc, error::HcResult};
+use parity_wasm::{
+    builder,
+    elements::{ImportCountType, Instruction, Instructions, Module},
+};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use wasmi::{MemoryRef, ModuleRef, NopExternals};
+
+/// Name of the function that resets the mutable globals of a module.
+pub const RESET_FUNCTION_NAME: &str = "__hc_reset_globals";
+
+/// Number of idle instances kept per zome if not configured otherwise.
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+/// Adds an exported function to the given module that sets all its mutable globals
+/// back to their initial values.
+/// Modules with a start function or with globals that get initialized from imports
+/// can't be reset this way and are left as they are, which keeps them out of the pools.
+pub fn make_resettable(module: Module) -> Module {
+    if module.start_section().is_some() {
+        return module;
+    }
+    let imported_globals = module.import_count(ImportCountType::Global) as u32;
+    let mut instructions = Vec::new();
+    if let Some(globals) = module.global_section() {
+        for (index, global) in globals.entries().iter().enumerate() {
+            if !global.global_type().is_mutable() {
+                continue;
+            }
+            match global.init_expr().code().first() {
+                Some(init @ Instruction::I32Const(_))
+                | Some(init @ Instruction::I64Const(_))
+                | Some(init @ Instruction::F32Const(_))
+                | Some(init @ Instruction::F64Const(_)) => {
+                    instructions.push(init.clone());
+                    instructions.push(Instruction::SetGlobal(imported_globals + index as u32));
+                }
+                _ => return module,
+            }
+        }
+    }
+    instructions.push(Instruction::End);
+
+    let imported_functions = module.import_count(ImportCountType::Function) as u32;
+    let mut module_builder = builder::from_module(module);
+    let location = module_builder.push_function(
+        builder::function()
+            .with_signature(builder::signature().build_sig())
+            .body()
+            .with_instructions(Instructions::new(instructions))
+            .build()
+            .build(),
+    );
+    module_builder.push_export(
+        builder::export()
+            .field(RESET_FUNCTION_NAME)
+            .internal()
+            .func(imported_functions + location.body)
+            .build(),
+    );
+    module_builder.build()
+}
+
+fn linear_memory(instance: &ModuleRef) -> Option<MemoryRef> {
+    instance
+        .export_by_name("memory")
+        .and_then(|export| export.as_memory().cloned())
+}
+
+/// An instance waiting in its pool to be checked out.
+struct IdleInstance(ModuleRef);
+
+// wasmi reference counts the memory, globals and functions of an instance without
+// synchronization, which is why `ModuleRef` is not `Send`. But an instance does not share
+// any of them with other instances, its module or the host functions it imports, which get
+// allocated for every instance. And an idle instance is only referred to by its pool, since
+// a checked out instance only goes back into its pool when its `PooledInstance` gets dropped
+// at the end of a call, after the runtime and everything else that refers into the instance.
+// So an idle instance can be moved to whichever thread checks it out next.
+unsafe impl Send for IdleInstance {}
+
+/// Idle instances of one module.
+pub struct InstancePool {
+    module: ModuleArc,
+    max_idle: usize,
+    initial_memory: Option<Vec<u8>>,
+    idle: Mutex<Vec<IdleInstance>>,
+    created: AtomicUsize,
+}
+
+impl InstancePool {
+    /// Creates a pool with one idle instance, which also provides the snapshot of initial
+    /// memory that returned instances get reset to.
+    pub fn new(module: ModuleArc, max_idle: usize) -> HcResult<Arc<Self>> {
+        let instance = wasm_instance_factory(&module)?;
+        let resettable = instance.export_by_name(RESET_FUNCTION_NAME).is_some();
+        let initial_memory = linear_memory(&instance)
+            .filter(|_| resettable && max_idle > 0)
+            .map(|memory| memory.with_direct_access(|buffer| buffer.to_vec()));
+        let pool = Arc::new(InstancePool {
+            module,
+            max_idle,
+            initial_memory,
+            idle: Mutex::new(Vec::new()),
+            created: AtomicUsize::new(1),
+        });
+        pool.check_in(instance);
+        Ok(pool)
+    }
+
+    pub fn module(&self) -> &ModuleArc {
+        &self.module
+    }
+
+    /// Number of instances waiting to be checked out.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Number of instances the pool created so far, including the ones it dropped.
+    pub fn created_count(&self) -> usize {
+        self.created.load(Ordering::SeqCst)
+    }
+
+    /// Takes an idle instance out of the pool, or creates a new one if there is none.
+    pub fn check_out(pool: &Arc<InstancePool>) -> HcResult<PooledInstance> {
+        let idle = pool.idle.lock().unwrap().pop();
+        let instance = match idle {
+            Some(IdleInstance(instance)) => instance,
+            None => {
+                pool.created.fetch_add(1, Ordering::SeqCst);
+                wasm_instance_factory(&pool.module)?
+            }
+        };
+        Ok(PooledInstance {
+            pool: Some(pool.clone()),
+            instance: Some(instance),
+        })
+    }
+
+    /// Resets the given instance and puts it back into the pool, unless the pool is full
+    /// or the instance can't be reset.
+    fn check_in(&self, instance: ModuleRef) {
+        if self.idle_count() >= self.max_idle || !self.reset(&instance) {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        // Another instance could have been checked in while this one got reset
+        if idle.len() < self.max_idle {
+            idle.push(IdleInstance(instance));
+        }
+    }
+
+    fn reset(&self, instance: &ModuleRef) -> bool {
+        let (initial_memory, memory) = match (&self.initial_memory, linear_memory(instance)) {
+            (Some(initial_memory), Some(memory)) => (initial_memory, memory),
+            _ => return false,
+        };
+        // Memory can't shrink, so instances whose memory grew can't be reset
+        let memory_reset = memory.with_direct_access_mut(|buffer| {
+            if buffer.len() == initial_memory.len() {
+                buffer.copy_from_slice(initial_memory);
+                true
+            } else {
+                false
+            }
+        });
+        memory_reset
+            && instance
+                .invoke_export(RESET_FUNCTION_NAME, &[], &mut NopExternals)
+                .is_ok()
+    }
+}
+
+/// An instance checked out of an `InstancePool`.
+/// Goes back into its pool when it gets dropped, which happens on the thread it got
+/// checked out on since `PooledInstance` is not `Send`.
+pub struct PooledInstance {
+    pool: Option<Arc<InstancePool>>,
+    instance: Option<ModuleRef>,
+}
+
+impl PooledInstance {
+    /// Wraps an instance that does not belong to any pool and gets dropped after use.
+    pub fn unpooled(instance: ModuleRef) -> Self {
+        PooledInstance {
+            pool: None,
+            instance: Some(instance),
+        }
+    }
+}
+
+impl Deref for PooledInstance {
+    type Target = ModuleRef;
+    fn deref(&self) -> &ModuleRef {
+        self.instance.as_ref().expect("instance is only taken on drop")
+    }
+}
+
+impl Drop for PooledInstance {
+    fn drop(&mut self) {
+        if let (Some(pool), Some(instance)) = (&self.pool, self.instance.take()) {
+            pool.check_in(instance);
+        }
+    }
+}
+
+/// The instance pools of all zomes of a Holochain instance.
+pub struct InstancePools {
+    max_idle: usize,
+    pools: Mutex<HashMap<String, Arc<InstancePool>>>,
+}
+
+impl Default for InstancePools {
+    fn default() -> Self {
+        InstancePools::new(DEFAULT_POOL_SIZE)
+    }
+}
+
+impl InstancePools {
+    /// Creates pools that keep up to `max_idle` instances per zome.
+    /// With a size of 0, every call gets a fresh instance.
+    pub fn new(max_idle: usize) -> Self {
+        InstancePools {
+            max_idle,
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the pool of the given zome, if there is one.
+    pub fn get(&self, zome_name: &str) -> Option<Arc<InstancePool>> {
+        self.pools.lock().unwrap().get(zome_name).cloned()
+    }
+
+    /// Returns the pool of the given zome, which gets created if there is none for
+    /// the given module yet.
+    pub fn pool(&self, zome_name: &str, module: &ModuleArc) -> HcResult<Arc<InstancePool>> {
+        let mut pools = self.pools.lock().unwrap();
+        if let Some(pool) = pools.get(zome_name) {
+            if Arc::ptr_eq(&**pool.module(), &**module) {
+                return Ok(pool.clone());
+            }
+        }
+        let pool = InstancePool::new(module.clone(), self.max_idle)?;
+        pools.insert(zome_name.to_string(), pool.clone());
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use self::wabt::Wat2Wasm;
+    use super::*;
+    use crate::nucleus::ribosome::{
+        backtrace::CallTracer,
+        factories::wasm_module_factory,
+        fuel::FuelMeter,
+        memory::WasmPageManager,
+        memory_limit::MemoryMeter,
+        runtime::{Runtime, WasmCallData},
+    };
+    use wabt;
+    use wasmi::RuntimeValue;
+
+    fn test_wasm(grow: bool) -> Arc<Vec<u8>> {
+        let wasm = Wat2Wasm::new()
+            .canonicalize_lebs(false)
+            .write_debug_names(true)
+            .convert(format!(
+                r#"
+(module
+    (memory 1)
+    (export "memory" (memory 0))
+    (global $counter (mut i32) (i32.const 0))
+
+    (func
+        (export "touch")
+        (param $allocation i64)
+        (result i64)
+
+        (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+        (set_global $counter (i32.add (get_global $counter) (i32.const 1)))
+        {}
+        (i64.extend_u/i32 (i32.add (get_global $counter) (i32.load (i32.const 0))))
+    )
+)
+                "#,
+                if grow {
+                    "(drop (grow_memory (i32.const 1)))"
+                } else {
+                    ""
+                }
+            ))
+            .unwrap()
+            .as_ref()
+            .to_vec();
+        Arc::new(wasm)
+    }
+
+    fn test_module(grow: bool) -> ModuleArc {
+        wasm_module_factory(test_wasm(grow), false).unwrap()
+    }
+
+    fn touch(instance: &ModuleRef) -> Option<RuntimeValue> {
+        let mut runtime = Runtime {
+            memory_manager: WasmPageManager::new(instance),
+            data: WasmCallData::DirectCall("touch".to_string(), test_wasm(false)),
+            fuel: FuelMeter::new(None),
+            memory: MemoryMeter::new(None, 1),
+            tracer: CallTracer::new(),
+            profile: None,
+            host_call_log: None,
+        };
+        instance
+            .invoke_export("touch", &[RuntimeValue::I64(0)], &mut runtime)
+            .unwrap()
+    }
+
+    #[test]
+    fn returned_instances_get_reset() {
+        let pool = InstancePool::new(test_module(false), 1).unwrap();
+        assert_eq!(pool.idle_count(), 1);
+        {
+            let instance = InstancePool::check_out(&pool).unwrap();
+            assert_eq!(pool.idle_count(), 0);
+            assert_eq!(touch(&instance), Some(RuntimeValue::I64(2)));
+            assert_eq!(touch(&instance), Some(RuntimeValue::I64(4)));
+        }
+        assert_eq!(pool.idle_count(), 1);
+        let instance = InstancePool::check_out(&pool).unwrap();
+        assert_eq!(touch(&instance), Some(RuntimeValue::I64(2)));
+    }
+
+    #[test]
+    fn pool_size_is_bounded() {
+        let pool = InstancePool::new(test_module(false), 1).unwrap();
+        let first = InstancePool::check_out(&pool).unwrap();
+        let second = InstancePool::check_out(&pool).unwrap();
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle_count(), 1);
+    }
+
+    #[test]
+    fn idle_instances_get_reused_by_other_threads() {
+        let pool = InstancePool::new(test_module(false), 1).unwrap();
+        for _ in 0..2 {
+            let other_pool = pool.clone();
+            std::thread::spawn(move || {
+                assert_eq!(other_pool.idle_count(), 1);
+                let instance = InstancePool::check_out(&other_pool).unwrap();
+                assert_eq!(touch(&instance), Some(RuntimeValue::I64(2)));
+            })
+            .join()
+            .unwrap();
+        }
+        assert_eq!(pool.idle_count(), 1);
+        assert_eq!(pool.created_count(), 1);
+    }
+
+    #[test]
+    fn instances_with_grown_memory_get_dropped() {
+        let pool = InstancePool::new(test_module(true), 1).unwrap();
+        let instance = InstancePool::check_out(&pool).unwrap();
+        assert_eq!(touch(&instance), Some(RuntimeValue::I64(2)));
+        drop(instance);
+        assert_eq!(pool.idle_count(), 0);
+    }
+
+    #[test]
+    fn pools_get_replaced_with_new_modules() {
+        let pools = InstancePools::new(2);
+        let module = test_module(false);
+        let pool = pools.pool("zome", &module).unwrap();
+        assert!(Arc::ptr_eq(&pool, &pools.pool("zome", &module).unwrap()));
+        let other = pools.pool("zome", &test_module(false)).unwrap();
+        assert!(!Arc::ptr_eq(&pool, &other));
+    }
+}
@@ -0,0 +1,297 @@
+//! Profiling of zome calls.
+//!
+//! With profiling enabled for an instance, the `Runtime` of every zome call records each
+//! Zome API function the call makes, with the size of its arguments, its result and how long
+//! it took, as well as how long the call spent running WASM code. These records end up in
+//! the `stats` of the `ReturnZomeFunctionResult` trace signal of the call and get added up
+//! per zome function by the instance's `Profiler`.
+
+use crate::nucleus::ribosome::{api::ZomeApiResult, memory::WasmPageManager};
+use holochain_core_types::error::{
+    RibosomeEncodedValue, RibosomeEncodingBits, ZomeApiInternalResult,
+};
+use holochain_json_api::json::JsonString;
+use holochain_wasm_utils::memory::allocation::WasmAllocation;
+use std::{collections::BTreeMap, convert::TryFrom, sync::Mutex, time::Duration};
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// Upper bounds of the buckets of a `Histogram`, in microseconds.
+/// Durations above the last bound go into an extra bucket.
+pub const HISTOGRAM_BOUNDS_US: [u64; 12] = [
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000,
+];
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+/// Number of bytes in the encoded allocation a Zome API function gets as argument.
+pub fn args_size(args: &RuntimeArgs) -> usize {
+    if args.len() != 1 {
+        return 0;
+    }
+    match args
+        .nth_checked::<RibosomeEncodingBits>(0)
+        .map(RibosomeEncodedValue::from)
+    {
+        Ok(RibosomeEncodedValue::Allocation(allocation)) => WasmAllocation::try_from(allocation)
+            .map(|allocation| usize::from(allocation.length()))
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Result code of a Zome API function call: "Success", "Failure" for results that carry an
+/// error, the error code of calls that failed with one, or "Trap" for calls that trapped.
+pub fn result_code(memory_manager: &WasmPageManager, result: &ZomeApiResult) -> String {
+    let encoded = match result {
+        Err(_) => return "Trap".to_string(),
+        Ok(Some(RuntimeValue::I64(encoded))) => {
+            RibosomeEncodedValue::from(*encoded as RibosomeEncodingBits)
+        }
+        Ok(_) => return "Success".to_string(),
+    };
+    match encoded {
+        RibosomeEncodedValue::Allocation(allocation) => {
+            let ok = WasmAllocation::try_from(allocation)
+                .ok()
+                .and_then(|allocation| String::from_utf8(memory_manager.read(allocation)).ok())
+                .and_then(|json| {
+                    ZomeApiInternalResult::try_from(JsonString::from_json(
+                        json.trim_end_matches('\u{0}'),
+                    ))
+                    .ok()
+                })
+                .map(|result| result.ok)
+                .unwrap_or(true);
+            if ok {
+                "Success".to_string()
+            } else {
+                "Failure".to_string()
+            }
+        }
+        code => code.to_string(),
+    }
+}
+
+/// A single Zome API function call.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct HostCallRecord {
+    pub function: String,
+    pub args_bytes: usize,
+    pub result: String,
+    pub duration_us: u64,
+}
+
+impl HostCallRecord {
+    pub fn new(function: &str, args_bytes: usize, result: String, duration: Duration) -> Self {
+        HostCallRecord {
+            function: function.to_string(),
+            args_bytes,
+            result,
+            duration_us: micros(duration),
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.result != "Success"
+    }
+}
+
+/// The Zome API function calls of a zome call and the time it spent in WASM code.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct CallProfile {
+    pub host_calls: Vec<HostCallRecord>,
+    /// Time the call took, including its Zome API function calls
+    pub total_time_us: u64,
+    /// Time the call spent running WASM code
+    pub wasm_time_us: u64,
+}
+
+impl CallProfile {
+    pub fn record(&mut self, host_call: HostCallRecord) {
+        self.host_calls.push(host_call);
+    }
+
+    /// Sets the time the whole call took, which the time spent running WASM code
+    /// gets derived from.
+    pub fn finish(&mut self, total_time: Duration) {
+        self.total_time_us = micros(total_time);
+        let host_time_us: u64 = self.host_calls.iter().map(|call| call.duration_us).sum();
+        self.wasm_time_us = self.total_time_us.saturating_sub(host_time_us);
+    }
+}
+
+/// Number of durations per bucket, see `HISTOGRAM_BOUNDS_US`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Histogram {
+    pub count: u64,
+    pub sum_us: u64,
+    pub min_us: u64,
+    pub max_us: u64,
+    pub buckets: Vec<u64>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            count: 0,
+            sum_us: 0,
+            min_us: 0,
+            max_us: 0,
+            buckets: vec![0; HISTOGRAM_BOUNDS_US.len() + 1],
+        }
+    }
+}
+
+impl Histogram {
+    pub fn add(&mut self, duration_us: u64) {
+        self.min_us = if self.count == 0 {
+            duration_us
+        } else {
+            self.min_us.min(duration_us)
+        };
+        self.max_us = self.max_us.max(duration_us);
+        self.count += 1;
+        self.sum_us = self.sum_us.saturating_add(duration_us);
+        let bucket = HISTOGRAM_BOUNDS_US
+            .iter()
+            .position(|bound| duration_us <= *bound)
+            .unwrap_or_else(|| HISTOGRAM_BOUNDS_US.len());
+        self.buckets[bucket] += 1;
+    }
+}
+
+/// Calls of one Zome API function made by one zome function.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HostFunctionProfile {
+    pub calls: u64,
+    pub failures: u64,
+    pub args_bytes: u64,
+    pub time: Histogram,
+}
+
+/// Calls of one zome function.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ZomeFunctionProfile {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_time: Histogram,
+    pub wasm_time: Histogram,
+    pub host_calls: BTreeMap<String, HostFunctionProfile>,
+}
+
+impl ZomeFunctionProfile {
+    fn add(&mut self, profile: &CallProfile, failed: bool) {
+        self.calls += 1;
+        if failed {
+            self.errors += 1;
+        }
+        self.total_time.add(profile.total_time_us);
+        self.wasm_time.add(profile.wasm_time_us);
+        for host_call in &profile.host_calls {
+            let host_function = self
+                .host_calls
+                .entry(host_call.function.clone())
+                .or_insert_with(HostFunctionProfile::default);
+            host_function.calls += 1;
+            if host_call.is_failure() {
+                host_function.failures += 1;
+            }
+            host_function.args_bytes += host_call.args_bytes as u64;
+            host_function.time.add(host_call.duration_us);
+        }
+    }
+}
+
+/// Profiles of all zome functions that got called, by zome and function name.
+pub type ProfileReport = BTreeMap<String, BTreeMap<String, ZomeFunctionProfile>>;
+
+/// Adds up the profiles of the zome calls of an instance.
+#[derive(Default)]
+pub struct Profiler {
+    report: Mutex<ProfileReport>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the profile of a call of the given zome function.
+    pub fn record(&self, zome_name: &str, fn_name: &str, profile: &CallProfile, failed: bool) {
+        self.report
+            .lock()
+            .unwrap()
+            .entry(zome_name.to_string())
+            .or_insert_with(BTreeMap::new)
+            .entry(fn_name.to_string())
+            .or_insert_with(ZomeFunctionProfile::default)
+            .add(profile, failed);
+    }
+
+    /// Returns the profiles of all zome functions called so far.
+    pub fn report(&self) -> ProfileReport {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn test_profile() -> CallProfile {
+        let mut profile = CallProfile::default();
+        profile.record(HostCallRecord::new(
+            "hc_commit_entry",
+            120,
+            "Success".to_string(),
+            Duration::from_micros(300),
+        ));
+        profile.record(HostCallRecord::new(
+            "hc_get_entry",
+            60,
+            "Failure".to_string(),
+            Duration::from_micros(200),
+        ));
+        profile.finish(Duration::from_micros(2000));
+        profile
+    }
+
+    #[test]
+    fn profile_separates_wasm_time() {
+        let profile = test_profile();
+        assert_eq!(profile.total_time_us, 2000);
+        assert_eq!(profile.wasm_time_us, 1500);
+    }
+
+    #[test]
+    fn histogram_counts_durations_in_buckets() {
+        let mut histogram = Histogram::default();
+        histogram.add(5);
+        histogram.add(10);
+        histogram.add(700);
+        histogram.add(10_000_000);
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.min_us, 5);
+        assert_eq!(histogram.max_us, 10_000_000);
+        assert_eq!(histogram.buckets[0], 2);
+        assert_eq!(histogram.buckets[4], 1);
+        assert_eq!(histogram.buckets[HISTOGRAM_BOUNDS_US.len()], 1);
+    }
+
+    #[test]
+    fn profiler_adds_up_calls_per_zome_function() {
+        let profiler = Profiler::new();
+        profiler.record("blog", "create_post", &test_profile(), false);
+        profiler.record("blog", "create_post", &test_profile(), true);
+        let report = profiler.report();
+        let create_post = &report["blog"]["create_post"];
+        assert_eq!(create_post.calls, 2);
+        assert_eq!(create_post.errors, 1);
+        assert_eq!(create_post.wasm_time.sum_us, 3000);
+        assert_eq!(create_post.host_calls["hc_commit_entry"].calls, 2);
+        assert_eq!(create_post.host_calls["hc_commit_entry"].args_bytes, 240);
+        assert_eq!(create_post.host_calls["hc_get_entry"].failures, 2);
+    }
+}
@@ -0,0 +1,386 @@
+//! Recording and replaying of zome calls.
+//!
+//! The results of Zome API functions like `hc_get_entry`, `hc_get_links`, `hc_query` or
+//! `hc_send` depend on the state of the source chain and the DHT, which makes bugs hard to
+//! reproduce. An instance that has a recording directory configured writes a
+//! `ZomeCallRecording` of every zome call into it, which holds the arguments and results
+//! of all Zome API function calls the zome function made.
+//!
+//! `replay_zome_call()` runs the zome function again against such a recording. Instead of
+//! calling the Zome API functions, the `Runtime` hands the recorded results back to the WASM
+//! code, so the call runs offline, without network, storage or even an instance.
+//! A replay fails as soon as the WASM code makes a Zome API call that differs from the
+//! recorded one, which happens if the WASM code changed since the recording.
+
+use crate::nucleus::{
+    ribosome::{
+        api::{ZomeApiFunction, ZomeApiResult},
+        memory::WasmPageManager,
+        run_dna_with_host_call_log,
+        runtime::{Runtime, WasmCallData},
+        Defn,
+    },
+    ZomeFnCall, ZomeFnResult,
+};
+use holochain_core_types::error::{
+    HcResult, HolochainError, RibosomeEncodedValue, RibosomeEncodingBits, RibosomeRuntimeBits,
+};
+use holochain_json_api::json::JsonString;
+use holochain_wasm_utils::memory::allocation::WasmAllocation;
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use wasmi::{HostError, RuntimeArgs, RuntimeValue, Trap, TrapKind};
+
+/// What a Zome API function call returned to the WASM code.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecordedResult {
+    /// The function returned no value, like `abort`
+    Nothing,
+    /// Encoded return value without allocation, i.e. success or an error code
+    Code(String),
+    /// JSON that the function wrote into WASM memory
+    Json(String),
+    /// The function trapped, which aborted the call
+    Trap(String),
+}
+
+/// A single Zome API function call.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedHostCall {
+    pub function: String,
+    /// JSON the WASM code passed in, empty for functions that don't take JSON
+    pub args: String,
+    pub result: RecordedResult,
+}
+
+/// A zome call together with the Zome API function calls it made and its result.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZomeCallRecording {
+    pub zome_name: String,
+    pub fn_name: String,
+    pub parameters: String,
+    pub host_calls: Vec<RecordedHostCall>,
+    pub result: Result<String, HolochainError>,
+}
+
+impl ZomeCallRecording {
+    pub fn new(
+        call: &ZomeFnCall,
+        host_calls: Vec<RecordedHostCall>,
+        result: &ZomeFnResult,
+    ) -> Self {
+        ZomeCallRecording {
+            zome_name: call.zome_name.clone(),
+            fn_name: call.fn_name.clone(),
+            parameters: String::from(call.parameters.clone()),
+            host_calls,
+            result: result.clone().map(String::from),
+        }
+    }
+
+    /// Name of the file the recording of the given call gets saved to.
+    pub fn file_name(call: &ZomeFnCall) -> String {
+        format!("{}.{}.{}.json", call.zome_name, call.fn_name, call.id())
+    }
+
+    pub fn save(&self, path: &Path) -> HcResult<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> HcResult<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// The Zome API function calls of a zome call that gets recorded,
+/// or the ones that are left to replay.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostCallLog {
+    Recording(Vec<RecordedHostCall>),
+    Replaying {
+        calls: VecDeque<RecordedHostCall>,
+        replayed: usize,
+    },
+}
+
+impl HostCallLog {
+    pub fn recording() -> Self {
+        HostCallLog::Recording(Vec::new())
+    }
+
+    pub fn replaying(recording: &ZomeCallRecording) -> Self {
+        HostCallLog::Replaying {
+            calls: recording.host_calls.iter().cloned().collect(),
+            replayed: 0,
+        }
+    }
+
+    /// Recorded calls, or calls that were not replayed.
+    pub fn into_calls(self) -> Vec<RecordedHostCall> {
+        match self {
+            HostCallLog::Recording(calls) => calls,
+            HostCallLog::Replaying { calls, .. } => calls.into_iter().collect(),
+        }
+    }
+}
+
+/// Trap raised when a replayed call makes a different Zome API call than the recorded one,
+/// or when a recorded call trapped.
+#[derive(Debug)]
+struct ReplayTrap(String);
+
+impl fmt::Display for ReplayTrap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl HostError for ReplayTrap {}
+
+fn replay_trap(message: String) -> Trap {
+    Trap::new(TrapKind::Host(Box::new(ReplayTrap(message))))
+}
+
+/// Reads the JSON string of a Zome API function that takes its argument in WASM memory.
+fn read_args(memory_manager: &WasmPageManager, args: &RuntimeArgs) -> String {
+    if args.len() != 1 {
+        return String::new();
+    }
+    match args
+        .nth_checked::<RibosomeEncodingBits>(0)
+        .map(RibosomeEncodedValue::from)
+    {
+        Ok(RibosomeEncodedValue::Allocation(allocation)) => WasmAllocation::try_from(allocation)
+            .ok()
+            .and_then(|allocation| String::from_utf8(memory_manager.read(allocation)).ok())
+            .map(|json| json.trim_end_matches('\u{0}').to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn record_result(memory_manager: &WasmPageManager, result: &ZomeApiResult) -> RecordedResult {
+    match result {
+        Err(trap) => RecordedResult::Trap(trap.to_string()),
+        Ok(Some(RuntimeValue::I64(encoded))) => {
+            match RibosomeEncodedValue::from(*encoded as RibosomeEncodingBits) {
+                RibosomeEncodedValue::Allocation(allocation) => {
+                    let json = WasmAllocation::try_from(allocation)
+                        .ok()
+                        .and_then(|allocation| {
+                            String::from_utf8(memory_manager.read(allocation)).ok()
+                        })
+                        .unwrap_or_default();
+                    // Strings stored for the WASM code are null terminated
+                    RecordedResult::Json(json.trim_end_matches('\u{0}').to_string())
+                }
+                code => RecordedResult::Code(code.to_string()),
+            }
+        }
+        Ok(_) => RecordedResult::Nothing,
+    }
+}
+
+fn replay_result(runtime: &mut Runtime, result: RecordedResult) -> ZomeApiResult {
+    match result {
+        RecordedResult::Nothing => Ok(None),
+        RecordedResult::Code(code) => {
+            let encoded = RibosomeEncodedValue::from_str(&code)
+                .map_err(|_| replay_trap(format!("Invalid recorded result code: {}", code)))?;
+            Ok(Some(RuntimeValue::I64(
+                RibosomeEncodingBits::from(encoded) as RibosomeRuntimeBits
+            )))
+        }
+        RecordedResult::Json(json) => runtime.store_as_json_string(JsonString::from_json(&json)),
+        RecordedResult::Trap(message) => Err(replay_trap(message)),
+    }
+}
+
+/// Calls the given Zome API function and adds it to the log, or takes its result from the
+/// log if the call gets replayed.
+pub fn log_host_call(
+    runtime: &mut Runtime,
+    log: &Mutex<HostCallLog>,
+    function: &ZomeApiFunction,
+    args: &RuntimeArgs,
+) -> ZomeApiResult {
+    let name = function.as_str();
+    let call_args = read_args(&runtime.memory_manager, args);
+    let replaying = match *log.lock().unwrap() {
+        HostCallLog::Recording(_) => false,
+        HostCallLog::Replaying { .. } => true,
+    };
+
+    if !replaying {
+        let result = function.as_fn()(runtime, args);
+        let recorded = RecordedHostCall {
+            function: name.to_string(),
+            args: call_args,
+            result: record_result(&runtime.memory_manager, &result),
+        };
+        if let HostCallLog::Recording(calls) = &mut *log.lock().unwrap() {
+            calls.push(recorded);
+        }
+        return result;
+    }
+
+    let recorded = match &mut *log.lock().unwrap() {
+        HostCallLog::Replaying { calls, replayed } => {
+            *replayed += 1;
+            match calls.pop_front() {
+                Some(recorded) => {
+                    if recorded.function != name || recorded.args != call_args {
+                        return Err(replay_trap(format!(
+                            "Replay diverged at Zome API call {}: recorded {}({}), but got {}({})",
+                            replayed, recorded.function, recorded.args, name, call_args
+                        )));
+                    }
+                    recorded
+                }
+                None => {
+                    return Err(replay_trap(format!(
+                        "Replay diverged at Zome API call {}: {}({}) was not recorded",
+                        replayed, name, call_args
+                    )));
+                }
+            }
+        }
+        HostCallLog::Recording(_) => unreachable!(),
+    };
+    replay_result(runtime, recorded.result)
+}
+
+/// Runs the zome call of the given recording in the given WASM code, with the recorded
+/// Zome API function results. Fails if the call made different Zome API calls than the
+/// recorded ones, or fewer of them.
+/// Returns the result of the replayed call, which can be compared with `recording.result`.
+pub fn replay_zome_call(wasm: Arc<Vec<u8>>, recording: &ZomeCallRecording) -> ZomeFnResult {
+    let log = Arc::new(Mutex::new(HostCallLog::replaying(recording)));
+    let result = run_dna_with_host_call_log(
+        Some(JsonString::from_json(&recording.parameters).to_bytes()),
+        WasmCallData::DirectCall(recording.fn_name.clone(), wasm),
+        log.clone(),
+    );
+    let left = log.lock().unwrap().clone().into_calls();
+    if result.is_ok() && !left.is_empty() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Replay diverged: {} recorded Zome API calls were not made, starting with {}",
+            left.len(),
+            left[0].function
+        )));
+    }
+    result
+}
+
+/// Path of the file in the given directory that the recording of the given call gets saved to.
+pub fn recording_path(dir: &Path, call: &ZomeFnCall) -> PathBuf {
+    dir.join(ZomeCallRecording::file_name(call))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use self::wabt::Wat2Wasm;
+    use super::*;
+    use wabt;
+
+    /// WASM that passes its input on to `hc_debug` twice and returns it.
+    fn test_wasm() -> Arc<Vec<u8>> {
+        let wasm = Wat2Wasm::new()
+            .canonicalize_lebs(false)
+            .write_debug_names(true)
+            .convert(
+                r#"
+(module
+    (import "env" "hc_debug"
+        (func $debug (param i64) (result i64)))
+
+    (memory 1)
+    (export "memory" (memory 0))
+
+    (func
+        (export "echo")
+        (param $allocation i64)
+        (result i64)
+
+        (drop (call $debug (get_local $allocation)))
+        (drop (call $debug (get_local $allocation)))
+        (get_local $allocation)
+    )
+)
+                "#,
+            )
+            .unwrap()
+            .as_ref()
+            .to_vec();
+        Arc::new(wasm)
+    }
+
+    fn test_recording(host_calls: Vec<RecordedHostCall>) -> ZomeCallRecording {
+        ZomeCallRecording {
+            zome_name: "test_zome".to_string(),
+            fn_name: "echo".to_string(),
+            parameters: r#"{"msg":"hi"}"#.to_string(),
+            host_calls,
+            result: Ok(r#"{"Ok":null}"#.to_string()),
+        }
+    }
+
+    fn debug_call(result: RecordedResult) -> RecordedHostCall {
+        RecordedHostCall {
+            function: "hc_debug".to_string(),
+            args: r#"{"msg":"hi"}"#.to_string(),
+            result,
+        }
+    }
+
+    #[test]
+    fn replay_returns_recorded_results() {
+        let recording = test_recording(vec![
+            debug_call(RecordedResult::Code("Success".to_string())),
+            debug_call(RecordedResult::Json(r#"{"Ok":null}"#.to_string())),
+        ]);
+        let result = replay_zome_call(test_wasm(), &recording);
+        assert_eq!(result, Ok(JsonString::from_json(r#"{"msg":"hi"}"#)));
+    }
+
+    #[test]
+    fn replay_fails_on_missing_host_calls() {
+        let recording = test_recording(vec![debug_call(RecordedResult::Code(
+            "Success".to_string(),
+        ))]);
+        let result = replay_zome_call(test_wasm(), &recording);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Replay diverged at Zome API call 2"));
+    }
+
+    #[test]
+    fn replay_fails_on_unused_host_calls() {
+        let mut host_calls = vec![debug_call(RecordedResult::Code("Success".to_string())); 2];
+        host_calls.push(debug_call(RecordedResult::Nothing));
+        let result = replay_zome_call(test_wasm(), &test_recording(host_calls));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("1 recorded Zome API calls were not made"));
+    }
+
+    #[test]
+    fn recording_can_be_saved_and_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.json");
+        let recording = test_recording(vec![debug_call(RecordedResult::Trap(
+            "Trap: Unreachable".to_string(),
+        ))]);
+        recording.save(&path).unwrap();
+        assert_eq!(ZomeCallRecording::load(&path).unwrap(), recording);
+    }
+}
@@ -1,10 +1,19 @@
-use crate::nucleus::{
-    ribosome::{
-        factories::{wasm_instance_factory, wasm_module_factory},
-        memory::WasmPageManager,
-        runtime::{Runtime, WasmCallData},
+use crate::{
+    agent::actions::bundle::discard_bundle,
+    nucleus::{
+        ribosome::{
+            backtrace::{Backtrace, CallTracer},
+            factories::{wasm_instance_factory, wasm_module_factory},
+            fuel::FuelMeter,
+            memory::WasmPageManager,
+            memory_limit::MemoryMeter,
+            pool::{InstancePool, PooledInstance},
+            profile::CallProfile,
+            recording::{recording_path, HostCallLog, ZomeCallRecording},
+            runtime::{Runtime, WasmCallData},
+        },
+        ZomeFnResult,
     },
-    ZomeFnResult,
 };
 use holochain_core_types::{
     dna::wasm::ModuleArc,
@@ -15,8 +24,23 @@ use holochain_core_types::{
 use holochain_json_api::json::JsonString;
 
 use holochain_wasm_utils::memory::allocation::{AllocationError, WasmAllocation};
-use std::convert::TryFrom;
-use wasmi::RuntimeValue;
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use wasmi::{memory_units::Pages, RuntimeValue};
+
+/// Resources used by a single WASM call.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct CallStats {
+    pub fuel_used: u64,
+    pub peak_memory_pages: u32,
+    /// Where the call was when it trapped, if it did.
+    pub backtrace: Option<Backtrace>,
+    /// Zome API function calls and WASM time of zome calls to instances with profiling enabled.
+    pub profile: Option<CallProfile>,
+}
 
 /// Returns the WASM module, i.e. the WASM binary program code to run
 /// for the given WasmCallData.
@@ -27,7 +51,7 @@ use wasmi::RuntimeValue;
 /// For ZomeCalls and CallbackCalls it gets the according module from the DNA.
 fn get_module(data: WasmCallData) -> Result<ModuleArc, HolochainError> {
     let (context, zome_name) = if let WasmCallData::DirectCall(_, wasm) = data {
-        let transient_module = ModuleArc::new(wasm_module_factory(wasm.clone())?);
+        let transient_module = wasm_module_factory(wasm.clone(), false)?;
         return Ok(transient_module);
     } else {
         match data {
@@ -50,26 +74,130 @@ fn get_module(data: WasmCallData) -> Result<ModuleArc, HolochainError> {
             zome_name
         )))?
         .code
-        .get_wasm_module()?;
+        .get_wasm_module(|wasm| wasm_module_factory(wasm, context.wasm_backtraces))?;
 
     Ok(module)
 }
 
+/// Checks out an instance of the given module from the pool of the called zome.
+/// Direct calls are not made to a zome and get a fresh instance.
+fn get_instance(
+    data: &WasmCallData,
+    module: &ModuleArc,
+) -> Result<PooledInstance, HolochainError> {
+    let (context, zome_name) = match data {
+        WasmCallData::ZomeCall(d) => (&d.context, &d.call.zome_name),
+        WasmCallData::CallbackCall(d) => (&d.context, &d.call.zome_name),
+        WasmCallData::DirectCall(_, _) => {
+            return Ok(PooledInstance::unpooled(wasm_instance_factory(module)?));
+        }
+    };
+    let pool = context.wasm_instance_pools.pool(zome_name, module)?;
+    InstancePool::check_out(&pool)
+}
+
 /// Executes an exposed zome function in a wasm binary.
 /// Multithreaded function
 /// panics if wasm binary isn't valid.
 pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult {
+    run_dna_with_stats(parameters, data).0
+}
+
+/// Executes an exposed zome function like `run_dna()`, logging its Zome API function calls
+/// to the given log, or replaying them from it.
+pub fn run_dna_with_host_call_log(
+    parameters: Option<Vec<u8>>,
+    data: WasmCallData,
+    host_call_log: Arc<Mutex<HostCallLog>>,
+) -> ZomeFnResult {
+    execute(parameters, data, Some(host_call_log), &mut CallStats::default())
+}
+
+/// Executes an exposed zome function like `run_dna()` and also returns the
+/// resources the call used.
+pub fn run_dna_with_stats(
+    parameters: Option<Vec<u8>>,
+    data: WasmCallData,
+) -> (ZomeFnResult, CallStats) {
+    let mut stats = CallStats::default();
+    let zome_call = match &data {
+        WasmCallData::ZomeCall(d) => Some((d.context.clone(), d.call.clone())),
+        _ => None,
+    };
+    let host_call_log = zome_call
+        .as_ref()
+        .filter(|(context, _)| context.zome_call_recordings.is_some())
+        .map(|_| Arc::new(Mutex::new(HostCallLog::recording())));
+    let result = execute(parameters, data, host_call_log.clone(), &mut stats);
+    if let (Some((context, call)), Some(log)) = (&zome_call, host_call_log) {
+        let host_calls = log.lock().unwrap().clone().into_calls();
+        let recording = ZomeCallRecording::new(call, host_calls, &result);
+        let dir = context.zome_call_recordings.as_ref().unwrap();
+        if let Err(error) = recording.save(&recording_path(dir, call)) {
+            log_warn!(context, "nucleus/run_dna: could not save recording of {:?}: {}", call, error);
+        }
+    }
+    if let (Some((context, call)), Some(profile)) = (&zome_call, &stats.profile) {
+        if let Some(profiler) = &context.profiler {
+            profiler.record(&call.zome_name, &call.fn_name, profile, result.is_err());
+        }
+    }
+    // Entries staged in a bundle that did not get closed are not committed
+    if let Some((context, call)) = zome_call {
+        let bundle_open = context
+            .state()
+            .map(|state| state.agent().bundle(&call.id()).is_some())
+            .unwrap_or(false);
+        if bundle_open {
+            log_warn!(context, "nucleus/run_dna: discarding bundle that was not closed by: {:?}", call);
+            discard_bundle(call.id(), &context);
+        }
+    }
+    (result, stats)
+}
+
+fn execute(
+    parameters: Option<Vec<u8>>,
+    data: WasmCallData,
+    host_call_log: Option<Arc<Mutex<HostCallLog>>>,
+    stats: &mut CallStats,
+) -> ZomeFnResult {
     let wasm_module = get_module(data.clone())?;
-    let wasm_instance = wasm_instance_factory(&wasm_module)?;
+    // Goes back into its pool at the end of the call, after the runtime that refers to it
+    let wasm_instance = get_instance(&data, &wasm_module)?;
     // write input arguments for module call in memory Buffer
     let input_parameters: Vec<_> = parameters.unwrap_or_default();
 
     let fn_name = data.fn_name();
+    let fuel_limit = match &data {
+        WasmCallData::ZomeCall(d) => d.context.fuel_limits.zome_call,
+        WasmCallData::CallbackCall(d) => d.context.fuel_limits.validation,
+        WasmCallData::DirectCall(_, _) => None,
+    };
+    let memory_limit = match &data {
+        WasmCallData::ZomeCall(d) => d.context.wasm_memory_limit,
+        WasmCallData::CallbackCall(d) => d.context.wasm_memory_limit,
+        WasmCallData::DirectCall(_, _) => None,
+    };
+    let profile = match &data {
+        WasmCallData::ZomeCall(d) if d.context.profiler.is_some() => Some(CallProfile::default()),
+        _ => None,
+    };
+    let memory_manager = WasmPageManager::new(&wasm_instance);
+    let Pages(initial_pages) = memory_manager.wasm_memory().current_size();
     // instantiate runtime struct for passing external state data over wasm but not to wasm
     let mut runtime = Runtime {
-        memory_manager: WasmPageManager::new(&wasm_instance),
+        memory_manager,
         data,
+        fuel: FuelMeter::new(fuel_limit),
+        memory: MemoryMeter::new(memory_limit, initial_pages as u32),
+        tracer: CallTracer::new(),
+        profile,
+        host_call_log,
     };
+    if runtime.memory.is_exceeded() {
+        return Err(runtime.memory.limit_exceeded_error(None));
+    }
 
     // Write input arguments in wasm memory
     // scope for mutable borrow of runtime
@@ -104,16 +232,73 @@ pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult
         // invoke function in wasm instance
         // arguments are info for wasm on how to retrieve complex input arguments
         // which have been set in memory module
-        wasm_instance
-            .invoke_export(
-                &fn_name,
-                &[RuntimeValue::I64(
-                    RibosomeEncodingBits::from(encoded_allocation_of_input) as RibosomeRuntimeBits,
-                )],
-                mut_runtime,
-            )
+        let started = Instant::now();
+        let invocation = wasm_instance.invoke_export(
+            &fn_name,
+            &[RuntimeValue::I64(
+                RibosomeEncodingBits::from(encoded_allocation_of_input) as RibosomeRuntimeBits,
+            )],
+            mut_runtime,
+        );
+        stats.fuel_used = mut_runtime.fuel.used();
+        stats.peak_memory_pages = mut_runtime.memory.peak_pages();
+        stats.profile = mut_runtime.profile.take().map(|mut profile| {
+            profile.finish(started.elapsed());
+            profile
+        });
+        if invocation.is_err() {
+            stats.backtrace = Some(mut_runtime.tracer.backtrace(wasm_module.function_names()));
+        }
+        let backtrace = stats.backtrace.as_ref().map(Backtrace::to_string);
+        if mut_runtime.fuel.is_exhausted() {
+            let error = mut_runtime.fuel.out_of_fuel_error(backtrace);
+            match &mut_runtime.data {
+                WasmCallData::ZomeCall(d) => {
+                    log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", error, d.call)
+                }
+                WasmCallData::CallbackCall(d) => {
+                    log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", error, d.call)
+                }
+                _ => {}
+            };
+            return Err(error);
+        }
+        if mut_runtime.memory.is_exceeded() {
+            let error = mut_runtime.memory.limit_exceeded_error(backtrace);
+            match &mut_runtime.data {
+                WasmCallData::ZomeCall(d) => {
+                    log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", error, d.call)
+                }
+                WasmCallData::CallbackCall(d) => {
+                    log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", error, d.call)
+                }
+                _ => {}
+            };
+            return Err(error);
+        }
+        if invocation.is_err() && mut_runtime.is_expired() {
+            if let WasmCallData::ZomeCall(d) = &mut_runtime.data {
+                log_warn!(d.context, "nucleus/run_dna: deadline exceeded, aborted call: {:?}", d.call);
+            }
+            return Err(HolochainError::Timeout(backtrace));
+        }
+        invocation
             .map_err(|err| {
-                HolochainError::RibosomeFailed(format!("WASM invocation failed: {}", err))
+                let message = format!(
+                    "WASM invocation failed: {}\n{}",
+                    err,
+                    backtrace.unwrap_or_default()
+                );
+                match &mut_runtime.data {
+                    WasmCallData::ZomeCall(d) => {
+                        log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", message, d.call)
+                    }
+                    WasmCallData::CallbackCall(d) => {
+                        log_warn!(d.context, "nucleus/run_dna: {}, when calling: {:?}", message, d.call)
+                    }
+                    _ => {}
+                };
+                HolochainError::RibosomeFailed(message)
             })?
             .unwrap()
             .try_into() // Option<_>
@@ -3,7 +3,12 @@ use crate::{
     nucleus::{
         ribosome::{
             api::{ZomeApiFunction, ZomeApiResult},
+            backtrace::{CallTracer, TRACE_FUNCTION_INDEX},
+            fuel::{FuelMeter, GAS_FUNCTION_INDEX},
             memory::WasmPageManager,
+            memory_limit::{MemoryMeter, MEMORY_GROW_FUNCTION_INDEX},
+            profile::{self, CallProfile, HostCallRecord},
+            recording::{log_host_call, HostCallLog},
             Defn,
         },
         CallbackFnCall, ZomeFnCall,
@@ -17,7 +22,12 @@ use holochain_core_types::error::{
 use holochain_json_api::json::JsonString;
 
 use holochain_wasm_utils::memory::allocation::WasmAllocation;
-use std::{convert::TryFrom, fmt, sync::Arc};
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use wasmi::{Externals, HostError, RuntimeArgs, RuntimeValue, Trap, TrapKind};
 
 #[derive(Clone)]
@@ -53,6 +63,17 @@ impl fmt::Display for BadCallError {
 
 impl HostError for BadCallError {}
 
+/// Trap that aborts a zome call once its deadline has passed.
+#[derive(Debug)]
+struct DeadlineExceeded;
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Zome call deadline exceeded")
+    }
+}
+
+impl HostError for DeadlineExceeded {}
+
 impl WasmCallData {
     pub fn new_zome_call(context: Arc<Context>, call: ZomeFnCall) -> Self {
         WasmCallData::ZomeCall(ZomeCallData { context, call })
@@ -109,6 +130,21 @@ pub struct Runtime {
 
     /// data to be made available to the function at runtime
     pub data: WasmCallData,
+
+    /// Fuel used by the call so far
+    pub fuel: FuelMeter,
+
+    /// Size of memory used by the call so far
+    pub memory: MemoryMeter,
+
+    /// WASM functions the call is in and Zome API functions it called
+    pub tracer: CallTracer,
+
+    /// Zome API functions calls recorded so far, if the call gets profiled
+    pub profile: Option<CallProfile>,
+
+    /// Zome API function calls recorded so far, or the ones left to replay
+    pub host_call_log: Option<Arc<Mutex<HostCallLog>>>,
 }
 
 impl Runtime {
@@ -153,6 +189,45 @@ impl Runtime {
         }
     }
 
+    /// True if this runs a validation callback or builds a validation package.
+    /// Validation has to give the same result on every node, so Zome API functions that
+    /// are not deterministic are not available in these calls.
+    pub fn is_validation_call(&self) -> bool {
+        match &self.data {
+            WasmCallData::CallbackCall(ref data) => {
+                data.call.fn_name.starts_with("__hdk_validate")
+                    || data.call.fn_name.starts_with("__hdk_get_validation_package")
+                    || data.call.fn_name == "__hdk_get_custom_validation_package"
+            }
+            _ => false,
+        }
+    }
+
+    /// Time left until the deadline of the zome call, `None` if it has no deadline
+    /// or this is not a zome call.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        match &self.data {
+            WasmCallData::ZomeCall(ref data) => data.call.remaining_time(),
+            _ => None,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match &self.data {
+            WasmCallData::ZomeCall(ref data) => data.call.is_expired(),
+            _ => false,
+        }
+    }
+
+    /// Returns a trap that aborts the call if its deadline has passed.
+    pub fn check_deadline(&self) -> Result<(), Trap> {
+        if self.is_expired() {
+            Err(Trap::new(TrapKind::Host(Box::new(DeadlineExceeded))))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn context(&self) -> Result<Arc<Context>, Trap> {
         match &self.data {
             WasmCallData::ZomeCall(ref data) => Ok(data.context.clone()),
@@ -216,6 +291,18 @@ impl Runtime {
         }
     }
 
+    /// Calls the given Zome API function, or replays its recorded result.
+    fn call_zome_api_function(
+        &mut self,
+        zf: &ZomeApiFunction,
+        args: &RuntimeArgs,
+    ) -> ZomeApiResult {
+        match self.host_call_log.clone() {
+            Some(log) => log_host_call(self, &log, zf, args),
+            None => zf.as_fn()(self, args),
+        }
+    }
+
     pub fn store_result<J: Into<JsonString>>(
         &mut self,
         result: Result<J, HolochainError>,
@@ -231,11 +318,52 @@ impl Runtime {
 // by implementing the Externals trait from Wasmi.
 impl Externals for Runtime {
     fn invoke_index(&mut self, index: usize, args: RuntimeArgs) -> ZomeApiResult {
+        if index == GAS_FUNCTION_INDEX {
+            let amount: u32 = args.nth_checked(0)?;
+            self.fuel.consume(u64::from(amount))?;
+            // Aborts calls that ran past their deadline without calling the Zome API
+            if self.fuel.deadline_check_due() {
+                self.check_deadline()?;
+            }
+            return Ok(None);
+        }
+        if index == MEMORY_GROW_FUNCTION_INDEX {
+            let pages: u32 = args.nth_checked(0)?;
+            let previous = self
+                .memory
+                .grow(self.memory_manager.wasm_memory(), pages)?;
+            return Ok(Some(RuntimeValue::I32(previous)));
+        }
+        if index == TRACE_FUNCTION_INDEX {
+            let function: i32 = args.nth_checked(0)?;
+            self.tracer.trace(function);
+            return Ok(None);
+        }
         let zf = ZomeApiFunction::from_index(index);
         match zf {
             ZomeApiFunction::MissingNo => panic!("unknown function index"),
             // convert the function to its callable form and call it with the given arguments
-            _ => zf.as_fn()(self, &args),
+            _ => {
+                // Nothing gets committed or sent after the deadline
+                self.check_deadline()?;
+                self.tracer.host_call(zf.as_str());
+                if self.profile.is_none() {
+                    return self.call_zome_api_function(&zf, &args);
+                }
+                let args_bytes = profile::args_size(&args);
+                let started = Instant::now();
+                let result = self.call_zome_api_function(&zf, &args);
+                let record = HostCallRecord::new(
+                    zf.as_str(),
+                    args_bytes,
+                    profile::result_code(&self.memory_manager, &result),
+                    started.elapsed(),
+                );
+                if let Some(profile) = self.profile.as_mut() {
+                    profile.record(record);
+                }
+                result
+            }
         }
     }
 }
@@ -1,6 +1,8 @@
 use crate::{
     nucleus::{actions::initialize::Initialization, validation::ValidationResult, ZomeFnCall},
-    scheduled_jobs::pending_validations::{PendingValidation, ValidatingWorkflow},
+    scheduled_jobs::pending_validations::{
+        DeadLetterValidation, PendingValidation, ValidatingWorkflow,
+    },
 };
 use holochain_core_types::{dna::Dna, error::HolochainError, validation::ValidationPackage};
 
@@ -97,6 +99,7 @@ pub struct NucleusState {
     // Persisted fields:
     pub status: NucleusStatus,
     pub pending_validations: HashMap<PendingValidationKey, PendingValidation>,
+    pub dead_letter_validations: HashMap<PendingValidationKey, DeadLetterValidation>,
 
     // Transient fields:
     pub dna: Option<Dna>, //DNA is transient here because it is stored in the chain and gets
@@ -121,6 +124,7 @@ impl NucleusState {
             validation_results: HashMap::new(),
             validation_packages: HashMap::new(),
             pending_validations: HashMap::new(),
+            dead_letter_validations: HashMap::new(),
         }
     }
 
@@ -167,6 +171,8 @@ impl NucleusState {
 pub struct NucleusStateSnapshot {
     pub status: NucleusStatus,
     pub pending_validations: HashMap<PendingValidationKey, PendingValidation>,
+    #[serde(default)]
+    pub dead_letter_validations: HashMap<PendingValidationKey, DeadLetterValidation>,
 }
 
 impl From<&StateWrapper> for NucleusStateSnapshot {
@@ -174,6 +180,7 @@ impl From<&StateWrapper> for NucleusStateSnapshot {
         NucleusStateSnapshot {
             status: state.nucleus().status(),
             pending_validations: state.nucleus().pending_validations.clone(),
+            dead_letter_validations: state.nucleus().dead_letter_validations.clone(),
         }
     }
 }
@@ -187,6 +194,7 @@ impl From<NucleusStateSnapshot> for NucleusState {
             validation_results: HashMap::new(),
             validation_packages: HashMap::new(),
             pending_validations: snapshot.pending_validations,
+            dead_letter_validations: snapshot.dead_letter_validations,
         }
     }
 }
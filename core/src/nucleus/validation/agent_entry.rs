@@ -2,7 +2,7 @@ use crate::{
     context::Context,
     nucleus::{
         actions::run_validation_callback::run_validation_callback,
-        validation::{ValidationError, ValidationResult},
+        validation::{get_entry_with_header, ValidationError, ValidationResult},
         CallbackFnCall,
     },
 };
@@ -11,15 +11,51 @@ use holochain_core_types::{
     entry::Entry,
     validation::{EntryValidationData, ValidationData},
 };
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use holochain_wasm_utils::api_serialization::validation::AgentIdValidationArgs;
 
 use futures::future;
 use futures_util::future::FutureExt;
 use std::sync::Arc;
 
+/// Checks the rules every agent key rotation has to follow before the app gets asked:
+/// the key has to actually change and the header has to be signed by both the old
+/// and the new key.
+fn validate_agent_update(
+    old_agent_id: &AgentId,
+    new_agent_id: &AgentId,
+    validation_data: &ValidationData,
+) -> ValidationResult {
+    if old_agent_id.pub_sign_key == new_agent_id.pub_sign_key {
+        return Err(ValidationError::Fail(
+            "Agent update does not change the agent's key".to_string(),
+        ));
+    }
+    let signers: Vec<Address> = validation_data
+        .package
+        .chain_header
+        .provenances()
+        .iter()
+        .map(|provenance| provenance.source())
+        .collect();
+    for agent_id in &[old_agent_id, new_agent_id] {
+        if !signers.contains(&agent_id.address()) {
+            return Err(ValidationError::Fail(format!(
+                "Agent update is not signed by {}",
+                agent_id.address()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Validates agent entries, both the initial one committed during genesis and
+/// updates that rotate the agent's key.
+/// Updates are recognized by the link to the replaced agent entry, which is either
+/// given explicitly or found in the entry's header.
 pub async fn validate_agent_entry(
     entry: Entry,
+    link: Option<Address>,
     validation_data: ValidationData,
     context: &Arc<Context>,
 ) -> ValidationResult {
@@ -27,13 +63,39 @@ pub async fn validate_agent_entry(
 
     let agent_id = unwrap_to!(entry => Entry::AgentId);
 
-    let params = AgentIdValidationArgs {
-        validation_data: EntryValidationData::<AgentId>::Create {
+    let link = link.or_else(|| validation_data.package.chain_header.link_update_delete());
+    let agent_validation_data = match link {
+        Some(old_agent_address) => {
+            let (old_entry, old_entry_header) =
+                get_entry_with_header(context.clone(), &old_agent_address).map_err(|_| {
+                    ValidationError::UnresolvedDependencies(vec![old_agent_address.clone()])
+                })?;
+            let old_agent_id = match old_entry.entry {
+                Entry::AgentId(old_agent_id) => old_agent_id,
+                _ => {
+                    return Err(ValidationError::Fail(
+                        "Agent update must replace an agent entry".to_string(),
+                    ))
+                }
+            };
+            validate_agent_update(&old_agent_id, agent_id, &validation_data)?;
+            EntryValidationData::<AgentId>::Modify {
+                new_entry: agent_id.to_owned(),
+                old_entry: old_agent_id,
+                old_entry_header,
+                validation_data,
+            }
+        }
+        None => EntryValidationData::<AgentId>::Create {
             entry: agent_id.to_owned(),
             validation_data,
         },
     };
 
+    let params = AgentIdValidationArgs {
+        validation_data: agent_validation_data,
+    };
+
     log_debug!(context, "Validating agent entry with args: {:?}", params);
 
     let results = await!(future::join_all(dna.zomes.iter().map(|(zome_name, _)| {
@@ -60,3 +122,68 @@ pub async fn validate_agent_entry(
         ))
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        agent::test_agent_id_with_name,
+        chain_header::{test_chain_header, ChainHeader},
+        signature::{Provenance, Signature},
+        validation::{EntryLifecycle, ValidationPackage},
+    };
+
+    fn validation_data_signed_by(agent_ids: &[&AgentId]) -> ValidationData {
+        let header = test_chain_header();
+        let provenances: Vec<Provenance> = agent_ids
+            .iter()
+            .map(|agent_id| Provenance::new(agent_id.address(), Signature::fake()))
+            .collect();
+        let header = ChainHeader::new(
+            header.entry_type(),
+            header.entry_address(),
+            &provenances,
+            &header.link(),
+            &header.link_same_type(),
+            &header.link_update_delete(),
+            header.timestamp(),
+        );
+        ValidationData {
+            package: ValidationPackage::only_header(header),
+            lifecycle: EntryLifecycle::Chain,
+        }
+    }
+
+    #[test]
+    fn agent_update_signed_by_both_keys_is_valid() {
+        let old_agent = test_agent_id_with_name("old");
+        let new_agent = test_agent_id_with_name("new");
+        let validation_data = validation_data_signed_by(&[&new_agent, &old_agent]);
+        assert_eq!(
+            validate_agent_update(&old_agent, &new_agent, &validation_data),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn agent_update_needs_signatures_of_both_keys() {
+        let old_agent = test_agent_id_with_name("old");
+        let new_agent = test_agent_id_with_name("new");
+        for signers in &[vec![&old_agent], vec![&new_agent]] {
+            let validation_data = validation_data_signed_by(signers);
+            assert!(validate_agent_update(&old_agent, &new_agent, &validation_data).is_err());
+        }
+    }
+
+    #[test]
+    fn agent_update_has_to_change_the_key() {
+        let old_agent = test_agent_id_with_name("old");
+        let validation_data = validation_data_signed_by(&[&old_agent]);
+        assert_eq!(
+            validate_agent_update(&old_agent, &old_agent, &validation_data),
+            Err(ValidationError::Fail(
+                "Agent update does not change the agent's key".to_string()
+            ))
+        );
+    }
+}
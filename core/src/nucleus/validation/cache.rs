@@ -0,0 +1,366 @@
+//! A persistent cache of validation results.
+//!
+//! The same aspect can reach validation many times: on every gossip re-delivery, on every
+//! retry of a pending validation and when authoring. Running the validation callbacks is
+//! deterministic for a given DNA, entry, header and validation package though, so once we
+//! got a definitive result (valid or `ValidationError::Fail`) we keep it and skip running
+//! the ribosome the next time.
+//!
+//! Results are kept in the instance's DHT CAS and indexed in its EAV storage with one
+//! EAVI row per validation:
+//!
+//! * entity: the address of the [ValidationCacheKey](struct.ValidationCacheKey.html)
+//! * attribute: `Attribute::ValidationResult`
+//! * value: the address of the cached result
+//!
+//! The key contains the address of the DNA the instance runs, so results cached for a
+//! different DNA never get hit. Keys get stored in the DHT CAS as well, so that
+//! [purge_other_dnas](struct.ValidationCache.html#method.purge_other_dnas) can drop the
+//! results of other DNAs once the instance runs a new one. A row with the entity
+//! `VALIDATION_CACHE_DNA_ADDRESS` records the DNA the cache got purged for last.
+use crate::{
+    context::Context,
+    dht::retention::EvictableStorage,
+    nucleus::validation::{ValidationError, ValidationResult},
+};
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    entry::Entry,
+    error::{HcResult, HolochainError},
+    validation::{EntryLifecycle, ValidationData},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EntityAttributeValueStorage, IndexFilter},
+};
+use multihash::Hash;
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    sync::{Arc, RwLock},
+};
+
+/// Entity of the row that records which DNA the cached results belong to.
+pub const VALIDATION_CACHE_DNA_ADDRESS: &str = "ValidationCacheDna";
+
+/// Everything the result of validating an entry depends on.
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson)]
+pub struct ValidationCacheKey {
+    pub dna_address: Address,
+    pub entry_address: Address,
+    pub header_address: Address,
+    /// Hash of the validation package serialized as JSON
+    pub package_address: Address,
+    /// The updated or removed entry, for updates and removals
+    pub link: Option<Address>,
+    pub lifecycle: EntryLifecycle,
+}
+
+impl AddressableContent for ValidationCacheKey {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+}
+
+impl ValidationCacheKey {
+    /// Builds the key for validating the given entry with the given validation data.
+    /// Returns None as long as the instance has no DNA, since we need the DNA's address
+    /// to tell results of different DNAs apart.
+    pub fn new(
+        entry: &Entry,
+        link: &Option<Address>,
+        validation_data: &ValidationData,
+        context: &Arc<Context>,
+    ) -> Option<ValidationCacheKey> {
+        let dna_address = context.get_dna()?.address();
+        let package = JsonString::from(validation_data.package.clone());
+        Some(ValidationCacheKey {
+            dna_address,
+            entry_address: entry.address(),
+            header_address: validation_data.package.chain_header.address(),
+            package_address: Address::encode_from_str(&String::from(package), Hash::SHA2256),
+            link: link.clone(),
+            lifecycle: validation_data.lifecycle.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub enum CachedValidationResult {
+    Valid,
+    /// The validation callback failed with the given error
+    Invalid(String),
+}
+
+impl CachedValidationResult {
+    /// Only definitive results get cached. Missing dependencies or errors can go away
+    /// when trying again.
+    pub fn from_validation_result(result: &ValidationResult) -> Option<CachedValidationResult> {
+        match result {
+            Ok(()) => Some(CachedValidationResult::Valid),
+            Err(ValidationError::Fail(reason)) => {
+                Some(CachedValidationResult::Invalid(reason.clone()))
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+impl AddressableContent for CachedValidationResult {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+}
+
+impl From<CachedValidationResult> for ValidationResult {
+    fn from(result: CachedValidationResult) -> ValidationResult {
+        match result {
+            CachedValidationResult::Valid => Ok(()),
+            CachedValidationResult::Invalid(reason) => Err(ValidationError::Fail(reason)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ValidationCache {
+    content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    eviction_storage: Option<Arc<dyn EvictableStorage>>,
+}
+
+impl ValidationCache {
+    pub fn new(
+        content_storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+        eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    ) -> Self {
+        ValidationCache {
+            content_storage,
+            eav_storage,
+            eviction_storage: None,
+        }
+    }
+
+    /// Sets the storage that stale results get removed from.
+    /// Without one, results of other DNAs stay in storage, although they never get hit.
+    pub fn with_eviction_storage(
+        mut self,
+        eviction_storage: Option<Arc<dyn EvictableStorage>>,
+    ) -> Self {
+        self.eviction_storage = eviction_storage;
+        self
+    }
+
+    /// Stores the result of a validation under the given key.
+    pub fn add(&self, key: &ValidationCacheKey, result: &CachedValidationResult) -> HcResult<()> {
+        self.content_storage.write()?.add(key)?;
+        self.content_storage.write()?.add(result)?;
+        let eavi = EntityAttributeValueIndex::new(
+            &key.address(),
+            &Attribute::ValidationResult,
+            &result.address(),
+        )?;
+        self.eav_storage.write()?.add_eavi(&eavi)?;
+        Ok(())
+    }
+
+    /// Returns the latest result stored under the given key, if any.
+    pub fn get(&self, key: &ValidationCacheKey) -> HcResult<Option<CachedValidationResult>> {
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            Some(key.address()).into(),
+            Some(Attribute::ValidationResult).into(),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        ))?;
+        let eavi = match rows.into_iter().last() {
+            Some(eavi) => eavi,
+            None => return Ok(None),
+        };
+        let content = self
+            .content_storage
+            .read()?
+            .fetch(&eavi.value())?
+            .ok_or_else(|| {
+                HolochainError::ErrorGeneric(format!(
+                    "Cached validation result {} is missing from the DHT store",
+                    eavi.value()
+                ))
+            })?;
+        Ok(Some(CachedValidationResult::try_from_content(&content)?))
+    }
+
+    /// Removes all results cached for another DNA than the given one, unless the cache
+    /// got purged for that DNA already. Results whose key is not stored can't be told
+    /// apart and get removed as well.
+    pub fn purge_other_dnas(&self, dna_address: &Address) -> HcResult<()> {
+        let eviction_storage = match self.eviction_storage {
+            Some(ref eviction_storage) => eviction_storage,
+            None => return Ok(()),
+        };
+        let stamp_address = Address::from(VALIDATION_CACHE_DNA_ADDRESS);
+        let stamp = self
+            .eav_storage
+            .read()?
+            .fetch_eavi(&EaviQuery::new(
+                Some(stamp_address.clone()).into(),
+                Some(Attribute::ValidationResult).into(),
+                None.into(),
+                IndexFilter::LatestByAttribute,
+                None,
+            ))?
+            .into_iter()
+            .last()
+            .map(|eavi| eavi.value());
+        if stamp.as_ref() == Some(dna_address) {
+            return Ok(());
+        }
+
+        let rows = self.eav_storage.read()?.fetch_eavi(&EaviQuery::new(
+            None.into(),
+            Some(Attribute::ValidationResult).into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        ))?;
+        let mut stale = HashSet::new();
+        {
+            let content_storage = self.content_storage.read()?;
+            for key_address in rows.iter().map(|eavi| eavi.entity()) {
+                if key_address == stamp_address || stale.contains(&key_address) {
+                    continue;
+                }
+                let is_current = match content_storage.fetch(&key_address)? {
+                    Some(content) => {
+                        ValidationCacheKey::try_from_content(&content)?.dna_address == *dna_address
+                    }
+                    None => false,
+                };
+                if !is_current {
+                    stale.insert(key_address);
+                }
+            }
+        }
+        eviction_storage.evict(&stale.into_iter().collect::<Vec<_>>())?;
+
+        let stamp_eavi = EntityAttributeValueIndex::new(
+            &stamp_address,
+            &Attribute::ValidationResult,
+            dna_address,
+        )?;
+        self.eav_storage.write()?.add_eavi(&stamp_eavi)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::entry::test_entry;
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+    use std::sync::Mutex;
+
+    /// Records what it is asked to evict instead of removing it.
+    #[derive(Debug, Default)]
+    struct RecordedEvictions(Mutex<Vec<Address>>);
+
+    impl EvictableStorage for RecordedEvictions {
+        fn evict(&self, addresses: &[Address]) -> HcResult<()> {
+            self.0.lock().unwrap().extend_from_slice(addresses);
+            Ok(())
+        }
+    }
+
+    fn test_cache_key(dna_address: &str) -> ValidationCacheKey {
+        ValidationCacheKey {
+            dna_address: Address::from(dna_address),
+            entry_address: test_entry().address(),
+            header_address: Address::from("QmHeader"),
+            package_address: Address::from("QmPackage"),
+            link: None,
+            lifecycle: EntryLifecycle::Dht,
+        }
+    }
+
+    #[test]
+    fn cache_returns_stored_results_for_the_same_dna_only() {
+        let cache = ValidationCache::new(
+            Arc::new(RwLock::new(MemoryStorage::new())),
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+        );
+        let key = test_cache_key("QmDna");
+        assert_eq!(cache.get(&key).unwrap(), None);
+
+        cache
+            .add(&key, &CachedValidationResult::Invalid(String::from("nope")))
+            .unwrap();
+        assert_eq!(
+            cache.get(&key).unwrap(),
+            Some(CachedValidationResult::Invalid(String::from("nope")))
+        );
+        assert_eq!(cache.get(&test_cache_key("QmOtherDna")).unwrap(), None);
+
+        let mut other_package = key.clone();
+        other_package.package_address = Address::from("QmOtherPackage");
+        assert_eq!(cache.get(&other_package).unwrap(), None);
+    }
+
+    #[test]
+    fn purge_evicts_results_of_other_dnas_once() {
+        let evictions = Arc::new(RecordedEvictions::default());
+        let cache = ValidationCache::new(
+            Arc::new(RwLock::new(MemoryStorage::new())),
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+        )
+        .with_eviction_storage(Some(evictions.clone() as Arc<dyn EvictableStorage>));
+        let current = test_cache_key("QmDna");
+        let other = test_cache_key("QmOtherDna");
+        cache.add(&current, &CachedValidationResult::Valid).unwrap();
+        cache.add(&other, &CachedValidationResult::Valid).unwrap();
+
+        cache.purge_other_dnas(&Address::from("QmDna")).unwrap();
+        assert_eq!(*evictions.0.lock().unwrap(), vec![other.address()]);
+
+        // The cache is stamped with the DNA, so purging again does nothing
+        cache.purge_other_dnas(&Address::from("QmDna")).unwrap();
+        assert_eq!(evictions.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn only_definitive_results_get_cached() {
+        assert_eq!(
+            CachedValidationResult::from_validation_result(&Ok(())),
+            Some(CachedValidationResult::Valid)
+        );
+        assert_eq!(
+            CachedValidationResult::from_validation_result(&Err(ValidationError::Fail(
+                String::from("nope")
+            ))),
+            Some(CachedValidationResult::Invalid(String::from("nope")))
+        );
+        assert_eq!(
+            CachedValidationResult::from_validation_result(&Err(
+                ValidationError::UnresolvedDependencies(vec![Address::from("QmBase")])
+            )),
+            None
+        );
+        assert_eq!(
+            CachedValidationResult::from_validation_result(&Err(ValidationError::NotImplemented)),
+            None
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    context::Context,
+    nucleus::{
+        ribosome::callback::{validation_package::get_validation_package_definition, CallbackResult},
+        validation::{ValidationError, ValidationResult},
+    },
+};
+use holochain_core_types::{
+    entry::{entry_type::EntryType, Entry},
+    validation::{ValidationData, ValidationPackageDefinition, MAX_CUSTOM_VALIDATION_PACKAGE_SIZE},
+};
+use holochain_dpki::utils::Verify;
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Checks a validation package that was built from a custom definition before its contents
+/// get passed to the validation callback:
+/// * the serialized package must not exceed `MAX_CUSTOM_VALIDATION_PACKAGE_SIZE`,
+/// * all source chain headers must be signed by the author of the validated entry,
+/// * all source chain entries must belong to one of these headers.
+///
+/// A package counts as custom if it has a `custom` field or if the DNA defines a custom
+/// validation package for the entry's type, so dropping the field does not skip the checks.
+pub fn validate_custom_package(
+    entry: &Entry,
+    validation_data: &ValidationData,
+    context: &Arc<Context>,
+) -> ValidationResult {
+    let package = &validation_data.package;
+    if package.custom.is_none()
+        && !context
+            .custom_package_definitions
+            .has_custom_definition(entry, || has_custom_definition(entry, context))?
+    {
+        return Ok(());
+    }
+
+    let size = package.size();
+    if size > MAX_CUSTOM_VALIDATION_PACKAGE_SIZE {
+        return Err(ValidationError::Fail(format!(
+            "Validation package of {} bytes exceeds the limit of {} bytes",
+            size, MAX_CUSTOM_VALIDATION_PACKAGE_SIZE,
+        )));
+    }
+
+    let author = package
+        .chain_header
+        .provenances()
+        .last()
+        .map(|provenance| provenance.source());
+    let headers = package.source_chain_headers.clone().unwrap_or_default();
+
+    for header in headers.iter() {
+        let header_author = header
+            .provenances()
+            .last()
+            .map(|provenance| provenance.source());
+        if header_author.is_none() || header_author != author {
+            return Err(ValidationError::Fail(format!(
+                "Header {} in validation package is not authored by the author of the entry",
+                header.address(),
+            )));
+        }
+        for provenance in header.provenances().iter() {
+            if !provenance
+                .verify(header.entry_address().to_string())
+                .unwrap_or(false)
+            {
+                return Err(ValidationError::Fail(format!(
+                    "Signature of header {} in validation package from author {} invalid",
+                    header.address(),
+                    provenance.source(),
+                )));
+            }
+        }
+    }
+
+    for entry in package.source_chain_entries.iter().flatten() {
+        if !headers
+            .iter()
+            .any(|header| *header.entry_address() == entry.address())
+        {
+            return Err(ValidationError::Fail(format!(
+                "Entry {} in validation package has no header in the package",
+                entry.address(),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The types that validation package definitions get looked up by.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum DefinitionType {
+    App(String),
+    Link(String),
+}
+
+impl DefinitionType {
+    /// Only app entries and links can have a custom validation package.
+    fn of(entry: &Entry) -> Option<Self> {
+        match entry {
+            Entry::App(app_entry_type, _) => Some(DefinitionType::App(app_entry_type.to_string())),
+            Entry::LinkAdd(link_data) | Entry::LinkRemove((link_data, _)) => Some(
+                DefinitionType::Link(link_data.link().link_type().to_owned()),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the DNA defines a custom validation package, by app entry type and link type.
+/// Definitions only depend on the DNA, so the callback that returns them only needs to run
+/// once per type instead of for every validated entry.
+#[derive(Default)]
+pub struct CustomPackageDefinitions {
+    definitions: Mutex<HashMap<DefinitionType, bool>>,
+}
+
+impl CustomPackageDefinitions {
+    /// Whether there is a custom definition for the type of the given entry. Calls `lookup`
+    /// for types that were not looked up successfully before.
+    pub fn has_custom_definition<F>(
+        &self,
+        entry: &Entry,
+        lookup: F,
+    ) -> Result<bool, ValidationError>
+    where
+        F: FnOnce() -> Result<bool, ValidationError>,
+    {
+        let definition_type = match DefinitionType::of(entry) {
+            Some(definition_type) => definition_type,
+            None => return Ok(false),
+        };
+        if let Some(custom) = self.definitions.lock().unwrap().get(&definition_type) {
+            return Ok(*custom);
+        }
+        // The lock is not held during the lookup, which runs a callback that may take a while
+        let custom = lookup()?;
+        self.definitions
+            .lock()
+            .unwrap()
+            .insert(definition_type, custom);
+        Ok(custom)
+    }
+}
+
+/// Whether the DNA defines a custom validation package for the type of the given entry.
+/// Only app entries and links can have one.
+fn has_custom_definition(entry: &Entry, context: &Arc<Context>) -> Result<bool, ValidationError> {
+    match entry.entry_type() {
+        EntryType::App(_) | EntryType::LinkAdd | EntryType::LinkRemove => (),
+        _ => return Ok(false),
+    }
+    match get_validation_package_definition(entry, context.clone()) {
+        Ok(CallbackResult::ValidationPackageDefinition(definition)) => match definition {
+            ValidationPackageDefinition::Custom(_) => Ok(true),
+            _ => Ok(false),
+        },
+        Ok(_) => Ok(false),
+        Err(error) => Err(ValidationError::Error(error)),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::instance::tests::test_context;
+    use holochain_core_types::{
+        agent::AgentId,
+        chain_header::ChainHeader,
+        entry::{test_entry, test_entry_b},
+        signature::{Provenance, Signature},
+        time::test_iso_8601,
+        validation::ValidationPackage,
+    };
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    fn signed_header(agent: &AgentId, entry: &Entry) -> ChainHeader {
+        let signature = Signature::from(mock_signer(entry.address().to_string(), agent));
+        ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &[Provenance::new(agent.address(), signature)],
+            &None,
+            &None,
+            &None,
+            &test_iso_8601(),
+        )
+    }
+
+    fn custom_validation_data(
+        header: ChainHeader,
+        headers: Vec<ChainHeader>,
+        entries: Vec<Entry>,
+    ) -> ValidationData {
+        let mut validation_data = ValidationData::default();
+        validation_data.package = ValidationPackage {
+            chain_header: header,
+            source_chain_entries: Some(entries),
+            source_chain_headers: Some(headers),
+            custom: Some(String::from("custom")),
+        };
+        validation_data
+    }
+
+    #[test]
+    fn validate_custom_package_accepts_signed_headers() {
+        let author = registered_test_agent("custom-package-alice");
+        let header = signed_header(&author, &test_entry());
+        let previous = signed_header(&author, &test_entry_b());
+        let validation_data = custom_validation_data(header, vec![previous], vec![test_entry_b()]);
+        let context = test_context("custom-package-alice", None);
+        assert_eq!(
+            validate_custom_package(&test_entry(), &validation_data, &context),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_custom_package_refuses_foreign_and_forged_headers() {
+        let author = registered_test_agent("custom-package-bob");
+        let other = registered_test_agent("custom-package-carol");
+        let header = signed_header(&author, &test_entry());
+        let context = test_context("custom-package-bob", None);
+
+        let foreign = signed_header(&other, &test_entry_b());
+        let validation_data = custom_validation_data(header.clone(), vec![foreign], vec![]);
+        assert!(validate_custom_package(&test_entry(), &validation_data, &context).is_err());
+
+        let forged = ChainHeader::new(
+            &test_entry_b().entry_type(),
+            &test_entry_b().address(),
+            &[Provenance::new(author.address(), Signature::from("forged"))],
+            &None,
+            &None,
+            &None,
+            &test_iso_8601(),
+        );
+        let validation_data = custom_validation_data(header.clone(), vec![forged], vec![]);
+        assert!(validate_custom_package(&test_entry(), &validation_data, &context).is_err());
+
+        let validation_data = custom_validation_data(header, vec![], vec![test_entry_b()]);
+        assert!(validate_custom_package(&test_entry(), &validation_data, &context).is_err());
+    }
+
+    #[test]
+    fn validate_custom_package_refuses_oversized_packages() {
+        let author = registered_test_agent("custom-package-dave");
+        let header = signed_header(&author, &test_entry());
+        let context = test_context("custom-package-dave", None);
+        let mut validation_data = custom_validation_data(header, vec![], vec![]);
+        validation_data.package.custom = Some("x".repeat(MAX_CUSTOM_VALIDATION_PACKAGE_SIZE + 1));
+        assert!(validate_custom_package(&test_entry(), &validation_data, &context).is_err());
+    }
+
+    #[test]
+    fn validate_custom_package_skips_entry_types_without_custom_definition() {
+        let author = registered_test_agent("custom-package-erin");
+        let entry = Entry::AgentId(author.clone());
+        let header = signed_header(&author, &entry);
+        let context = test_context("custom-package-erin", None);
+
+        // System entries can't have a custom validation package, so foreign headers are
+        // left to the validation of the entry type
+        let other = registered_test_agent("custom-package-frank");
+        let foreign = signed_header(&other, &test_entry_b());
+        let mut validation_data = custom_validation_data(header, vec![foreign], vec![]);
+        validation_data.package.custom = None;
+        assert_eq!(
+            validate_custom_package(&entry, &validation_data, &context),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn custom_definitions_get_looked_up_once_per_type() {
+        let definitions = CustomPackageDefinitions::default();
+        assert_eq!(
+            definitions.has_custom_definition(&test_entry(), || Ok(true)),
+            Ok(true)
+        );
+        assert_eq!(
+            definitions.has_custom_definition(&test_entry(), || panic!("looked up again")),
+            Ok(true)
+        );
+        assert_eq!(
+            definitions.has_custom_definition(&test_entry_b(), || Ok(false)),
+            Ok(false)
+        );
+
+        // Failed lookups are not cached
+        let definitions = CustomPackageDefinitions::default();
+        assert!(definitions
+            .has_custom_definition(&test_entry(), || Err(ValidationError::NotImplemented))
+            .is_err());
+        assert_eq!(
+            definitions.has_custom_definition(&test_entry(), || Ok(false)),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn validate_custom_package_uses_cached_definitions() {
+        let author = registered_test_agent("custom-package-grace");
+        let header = signed_header(&author, &test_entry());
+        let context = test_context("custom-package-grace", None);
+        context
+            .custom_package_definitions
+            .has_custom_definition(&test_entry(), || Ok(true))
+            .unwrap();
+
+        // Dropping the custom field does not skip the checks for types with a custom definition
+        let other = registered_test_agent("custom-package-heidi");
+        let foreign = signed_header(&other, &test_entry_b());
+        let mut validation_data = custom_validation_data(header, vec![foreign], vec![]);
+        validation_data.package.custom = None;
+        assert!(validate_custom_package(&test_entry(), &validation_data, &context).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    agent::bundle::BundleId,
     context::Context,
     nucleus::{
         actions::run_validation_callback::run_validation_callback,
@@ -21,6 +22,7 @@ use std::sync::Arc;
 pub async fn validate_link_entry(
     entry: Entry,
     validation_data: ValidationData,
+    bundle: Option<BundleId>,
     context: &Arc<Context>,
 ) -> ValidationResult {
     let address = entry.address().clone();
@@ -34,11 +36,12 @@ pub async fn validate_link_entry(
         }
     };
     let link = link.link().clone();
-    let (base, target) = links_utils::get_link_entries(&link, context).map_err(|_| {
-        ValidationError::UnresolvedDependencies(
-            [link.base().clone(), link.target().clone()].to_vec(),
-        )
-    })?;
+    let (base, target) = links_utils::get_link_entries(&link, bundle.as_ref(), context)
+        .map_err(|_| {
+            ValidationError::UnresolvedDependencies(
+                [link.base().clone(), link.target().clone()].to_vec(),
+            )
+        })?;
 
     let link_definition_path = links_utils::find_link_definition_by_type(link.link_type(), context)
         .map_err(|_| ValidationError::NotImplemented)?;
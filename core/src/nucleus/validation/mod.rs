@@ -1,4 +1,9 @@
-use crate::{context::Context, workflows::get_entry_result::get_entry_with_meta_workflow};
+use crate::{
+    agent::bundle::BundleId,
+    context::Context,
+    nucleus::validation::cache::{CachedValidationResult, ValidationCacheKey},
+    workflows::get_entry_result::get_entry_with_meta_workflow,
+};
 use holochain_core_types::{
     chain_header::ChainHeader,
     entry::{entry_type::EntryType, Entry, EntryWithMeta},
@@ -12,6 +17,8 @@ use std::sync::Arc;
 
 mod agent_entry;
 mod app_entry;
+pub mod cache;
+pub mod custom_package;
 mod header_address;
 mod link_entry;
 mod provenances;
@@ -63,11 +70,16 @@ impl From<ValidationError> for HolochainError {
 /// This is the high-level validate function that wraps the whole validation process and is what should
 /// be called from other workflows for validating an entry.
 ///
+/// 0. Returns the cached result if this entry was already validated with the same header,
+///    validation package and DNA (see [cache](cache/index.html)).
 /// 1. Checks if the entry's address matches the address in given header provided by
 ///    the validation package.
 /// 2. Validates provenances given in the header by verifying the cryptographic signatures
 ///    against the source agent addresses.
-/// 3. Finally spawns a thread to run the type specific validation callback in a Ribosome.
+/// 3. Checks size and signatures of packages that were built by a custom validation
+///    package callback.
+/// 4. Finally spawns a thread to run the type specific validation callback in a Ribosome.
+/// 5. Caches the result if it is definitive, i.e. valid or `ValidationError::Fail`.
 ///
 /// All of this actually happens in the functions of the sub modules. This function is the
 /// main validation entry point and, like a workflow, stays high-level.
@@ -76,10 +88,71 @@ pub async fn validate_entry(
     link: Option<Address>,
     validation_data: ValidationData,
     context: &Arc<Context>,
+) -> ValidationResult {
+    await!(validate_entry_in_bundle(
+        entry,
+        link,
+        validation_data,
+        None,
+        context
+    ))
+}
+
+/// Same as `validate_entry()` for an entry staged in the commit bundle with the given id:
+/// links can refer to entries staged in the same bundle.
+pub async fn validate_entry_in_bundle(
+    entry: Entry,
+    link: Option<Address>,
+    validation_data: ValidationData,
+    bundle: Option<BundleId>,
+    context: &Arc<Context>,
+) -> ValidationResult {
+    let cache_key = ValidationCacheKey::new(&entry, &link, &validation_data, context);
+    if let Some(ref key) = cache_key {
+        match context.validation_cache().get(key) {
+            Ok(Some(cached)) => {
+                log_debug!(context, "validation: Using cached result for entry {}: {:?}",
+                    key.entry_address,
+                    cached,
+                );
+                return cached.into();
+            }
+            Ok(None) => (),
+            Err(err) => {
+                log_warn!(context, "validation: Could not read validation cache: {:?}", err);
+            }
+        }
+    }
+
+    let result = await!(run_validation(
+        entry,
+        link,
+        validation_data,
+        bundle,
+        context
+    ));
+
+    if let Some(key) = cache_key {
+        if let Some(cached) = CachedValidationResult::from_validation_result(&result) {
+            if let Err(err) = context.validation_cache().add(&key, &cached) {
+                log_warn!(context, "validation: Could not cache validation result: {:?}", err);
+            }
+        }
+    }
+    result
+}
+
+async fn run_validation(
+    entry: Entry,
+    link: Option<Address>,
+    validation_data: ValidationData,
+    bundle: Option<BundleId>,
+    context: &Arc<Context>,
 ) -> ValidationResult {
     //check_entry_type(entry.entry_type(), context)?;
     header_address::validate_header_address(&entry, &validation_data.package.chain_header)?;
     provenances::validate_provenances(&validation_data)?;
+    custom_package::validate_custom_package(&entry, &validation_data, context)?;
 
     match entry.entry_type() {
         // DNA entries are not validated currently and always valid
@@ -97,12 +170,14 @@ pub async fn validate_entry(
         EntryType::LinkAdd => await!(link_entry::validate_link_entry(
             entry.clone(),
             validation_data,
+            bundle,
             context
         )),
 
         EntryType::LinkRemove => await!(link_entry::validate_link_entry(
             entry.clone(),
             validation_data,
+            bundle,
             context
         )),
 
@@ -119,6 +194,7 @@ pub async fn validate_entry(
 
         EntryType::AgentId => await!(agent_entry::validate_agent_entry(
             entry.clone(),
+            link,
             validation_data,
             context,
         )),
@@ -0,0 +1,696 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::state::AgentStateSnapshot,
+    context::Context,
+    dht::dht_store::DhtStoreSnapshot,
+    nucleus::state::{NucleusStateSnapshot, NucleusStatus, PendingValidationKey},
+    persister::Persister,
+    scheduled_jobs::pending_validations::{
+        DeadLetterValidation, PendingValidationStruct, ValidatingWorkflow,
+    },
+    state::{State, StateWrapper},
+};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    error::{HcResult, HolochainError},
+};
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Number of journal records after which a new checkpoint gets written
+/// if not configured otherwise.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 1000;
+
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+const CHECKPOINT_TMP_FILE: &str = "checkpoint.json.tmp";
+
+/// Encrypts what a `JournalPersister` writes to disk, so that the journal can be kept
+/// next to encrypted storage.
+pub trait JournalCipher: Send + Sync {
+    fn encrypt(&self, data: &[u8]) -> HcResult<Vec<u8>>;
+    fn decrypt(&self, data: &[u8]) -> HcResult<Vec<u8>>;
+}
+
+/// A change to the persisted part of the state.
+/// These get appended to the journal, one JSON object per line.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StateDelta {
+    TopChainHeader(Option<ChainHeader>),
+    NucleusStatus(NucleusStatus),
+    AddPendingValidation(PendingValidationStruct),
+    RemovePendingValidation(Address, ValidatingWorkflow),
+    AddDeadLetterValidation(DeadLetterValidation),
+    RemoveDeadLetterValidation(Address, ValidatingWorkflow),
+    AddHeldEntries(Vec<Address>),
+}
+
+/// All persisted state slices at the time the checkpoint was taken.
+/// Only the journal of the same generation gets replayed on top of it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    generation: u64,
+    agent: AgentStateSnapshot,
+    nucleus: NucleusStateSnapshot,
+    dht: DhtStoreSnapshot,
+}
+
+impl Checkpoint {
+    fn empty() -> Self {
+        Checkpoint {
+            generation: 0,
+            agent: AgentStateSnapshot::new(None),
+            nucleus: NucleusStateSnapshot {
+                status: NucleusStatus::New,
+                pending_validations: HashMap::new(),
+                dead_letter_validations: HashMap::new(),
+            },
+            dht: DhtStoreSnapshot {
+                holding_list: Vec::new(),
+            },
+        }
+    }
+
+    fn apply(&mut self, delta: StateDelta) {
+        match delta {
+            StateDelta::TopChainHeader(header) => {
+                self.agent = AgentStateSnapshot::new(header);
+            }
+            StateDelta::NucleusStatus(status) => {
+                self.nucleus.status = status;
+            }
+            StateDelta::AddPendingValidation(pending) => {
+                let key = PendingValidationKey::new(
+                    pending.entry_with_header.entry.address(),
+                    pending.workflow.clone(),
+                );
+                self.nucleus
+                    .pending_validations
+                    .insert(key, Arc::new(pending));
+            }
+            StateDelta::RemovePendingValidation(address, workflow) => {
+                self.nucleus
+                    .pending_validations
+                    .remove(&PendingValidationKey::new(address, workflow));
+            }
+            StateDelta::AddDeadLetterValidation(dead_letter) => {
+                let key = dead_letter.pending.key();
+                self.nucleus.pending_validations.remove(&key);
+                self.nucleus.dead_letter_validations.insert(key, dead_letter);
+            }
+            StateDelta::RemoveDeadLetterValidation(address, workflow) => {
+                self.nucleus
+                    .dead_letter_validations
+                    .remove(&PendingValidationKey::new(address, workflow));
+            }
+            StateDelta::AddHeldEntries(mut addresses) => {
+                self.dht.holding_list.append(&mut addresses);
+            }
+        }
+    }
+}
+
+/// What the persister has written so far, used to compute the next deltas.
+struct PersistedView {
+    top_chain_header: Option<Address>,
+    nucleus_status: NucleusStatus,
+    holding_list_len: usize,
+}
+
+impl From<&StateWrapper> for PersistedView {
+    fn from(state: &StateWrapper) -> Self {
+        PersistedView {
+            top_chain_header: state.agent().top_chain_header().map(|h| h.address()),
+            nucleus_status: state.nucleus().status(),
+            holding_list_len: state.dht().get_all_held_entry_addresses().len(),
+        }
+    }
+}
+
+/// Persister that appends state deltas to a write-ahead journal instead of
+/// serializing the complete state after every action.
+///
+/// Every `checkpoint_interval` records (and on every explicit `save()`) the complete
+/// state gets written to a checkpoint file and a new, empty journal is started.
+/// Checkpoints are written to a temporary file first and then renamed, and each one
+/// carries a generation number that names the journal belonging to it, so a crash
+/// in the middle of a checkpoint leaves either the old or the new pair intact.
+/// Loading reads the checkpoint and replays its journal. A torn record at the
+/// end of the journal (i.e. the one being written when the process died) is ignored.
+///
+/// With a cipher, the checkpoint and every journal record get encrypted and written
+/// base64 encoded, one record per line as before.
+pub struct JournalPersister {
+    dir: PathBuf,
+    cipher: Option<Arc<dyn JournalCipher>>,
+    checkpoint_interval: usize,
+    generation: Option<u64>,
+    journal: Option<BufWriter<File>>,
+    records_since_checkpoint: usize,
+    view: Option<PersistedView>,
+}
+
+impl JournalPersister {
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        checkpoint_interval: Option<usize>,
+    ) -> Result<Self, HolochainError> {
+        let dir: PathBuf = dir.as_ref().into();
+        fs::create_dir_all(&dir)?;
+        Ok(JournalPersister {
+            dir,
+            cipher: None,
+            checkpoint_interval: checkpoint_interval.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL),
+            generation: None,
+            journal: None,
+            records_since_checkpoint: 0,
+            view: None,
+        })
+    }
+
+    /// Encrypts the checkpoint and journal with the given cipher.
+    pub fn with_cipher(mut self, cipher: Arc<dyn JournalCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Serializes a checkpoint or record the way it gets written to disk.
+    fn encode<T: serde::Serialize>(&self, value: &T) -> Result<Vec<u8>, HolochainError> {
+        let json = serde_json::to_vec(value)?;
+        Ok(match self.cipher {
+            Some(ref cipher) => base64::encode(&cipher.encrypt(&json)?).into_bytes(),
+            None => json,
+        })
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, HolochainError> {
+        let json = match self.cipher {
+            Some(ref cipher) => cipher.decrypt(&base64::decode(bytes)?)?,
+            None => bytes.to_vec(),
+        };
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    fn journal_path(&self, generation: u64) -> PathBuf {
+        self.dir.join(format!("journal-{}.log", generation))
+    }
+
+    fn read_checkpoint(&self) -> Result<Option<Checkpoint>, HolochainError> {
+        let path = self.dir.join(CHECKPOINT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Ok(Some(self.decode(&bytes)?))
+    }
+
+    /// Reads all complete records of the journal with the given generation.
+    fn read_journal(&self, generation: u64) -> Result<Vec<StateDelta>, HolochainError> {
+        let path = self.journal_path(generation);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut deltas = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            match self.decode(line?.as_bytes()) {
+                Ok(delta) => deltas.push(delta),
+                // Only the last record can be incomplete, everything after it was never written
+                Err(_) => break,
+            }
+        }
+        Ok(deltas)
+    }
+
+    /// Writes the complete state as a new checkpoint and starts a new journal for it.
+    fn checkpoint(&mut self, state: &StateWrapper) -> Result<(), HolochainError> {
+        let previous_generation = match self.generation {
+            Some(generation) => generation,
+            None => self
+                .read_checkpoint()?
+                .map(|checkpoint| checkpoint.generation)
+                .unwrap_or(0),
+        };
+        let generation = previous_generation + 1;
+        let checkpoint = Checkpoint {
+            generation,
+            agent: AgentStateSnapshot::from(state),
+            nucleus: NucleusStateSnapshot::from(state),
+            dht: DhtStoreSnapshot::from(state),
+        };
+
+        let tmp_path = self.dir.join(CHECKPOINT_TMP_FILE);
+        {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&self.encode(&checkpoint)?)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, self.dir.join(CHECKPOINT_FILE))?;
+
+        self.journal = Some(BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(self.journal_path(generation))?,
+        ));
+        let _ = fs::remove_file(self.journal_path(previous_generation));
+
+        self.generation = Some(generation);
+        self.records_since_checkpoint = 0;
+        self.view = Some(PersistedView::from(state));
+        Ok(())
+    }
+
+    /// Computes what changed in the persisted parts of the state since the last write.
+    /// Pending validations can only change through their own actions, so they are
+    /// taken from the action instead of diffing the whole map.
+    fn deltas(
+        view: &PersistedView,
+        action_wrapper: &ActionWrapper,
+        state: &StateWrapper,
+    ) -> Option<Vec<StateDelta>> {
+        let mut deltas = Vec::new();
+
+        let top_chain_header = state.agent().top_chain_header();
+        if top_chain_header.as_ref().map(|h| h.address()) != view.top_chain_header {
+            deltas.push(StateDelta::TopChainHeader(top_chain_header));
+        }
+
+        let status = state.nucleus().status();
+        if status != view.nucleus_status {
+            deltas.push(StateDelta::NucleusStatus(status));
+        }
+
+        match action_wrapper.action() {
+            Action::AddPendingValidation(pending) => {
+                // The reducer keeps the attempts of a pending validation that gets added again
+                let pending = state
+                    .nucleus()
+                    .pending_validations
+                    .get(&pending.key())
+                    .cloned()
+                    .unwrap_or_else(|| pending.clone());
+                deltas.push(StateDelta::AddPendingValidation((*pending).clone()))
+            }
+            Action::RemovePendingValidation((address, workflow)) => deltas.push(
+                StateDelta::RemovePendingValidation(address.clone(), workflow.clone()),
+            ),
+            Action::CountPendingValidationAttempt((address, workflow)) => {
+                let key = PendingValidationKey::new(address.clone(), workflow.clone());
+                if let Some(pending) = state.nucleus().pending_validations.get(&key) {
+                    deltas.push(StateDelta::AddPendingValidation((**pending).clone()));
+                }
+            }
+            Action::AddDeadLetterValidation(dead_letter) => {
+                deltas.push(StateDelta::AddDeadLetterValidation(dead_letter.clone()))
+            }
+            Action::RetryDeadLetterValidation((address, workflow)) => {
+                deltas.push(StateDelta::RemoveDeadLetterValidation(
+                    address.clone(),
+                    workflow.clone(),
+                ));
+                let key = PendingValidationKey::new(address.clone(), workflow.clone());
+                if let Some(pending) = state.nucleus().pending_validations.get(&key) {
+                    deltas.push(StateDelta::AddPendingValidation((**pending).clone()));
+                }
+            }
+            Action::RemoveDeadLetterValidation((address, workflow)) => deltas.push(
+                StateDelta::RemoveDeadLetterValidation(address.clone(), workflow.clone()),
+            ),
+            _ => (),
+        }
+
+        let dht = state.dht();
+        let holding_list = dht.get_all_held_entry_addresses();
+        if holding_list.len() < view.holding_list_len {
+            // The holding list shrank, which can't be expressed as a delta
+            return None;
+        } else if holding_list.len() > view.holding_list_len {
+            deltas.push(StateDelta::AddHeldEntries(
+                holding_list[view.holding_list_len..].to_vec(),
+            ));
+        }
+
+        Some(deltas)
+    }
+
+    fn append(&mut self, deltas: &[StateDelta]) -> Result<(), HolochainError> {
+        let records = deltas
+            .iter()
+            .map(|delta| self.encode(delta))
+            .collect::<Result<Vec<_>, _>>()?;
+        let journal = self
+            .journal
+            .as_mut()
+            .ok_or_else(|| HolochainError::new("Journal not opened"))?;
+        for record in records {
+            journal.write_all(&record)?;
+            journal.write_all(b"\n")?;
+        }
+        journal.flush()?;
+        self.records_since_checkpoint += deltas.len();
+        Ok(())
+    }
+}
+
+impl Persister for JournalPersister {
+    fn save(&mut self, state: &StateWrapper) -> Result<(), HolochainError> {
+        self.checkpoint(state)
+    }
+
+    fn save_action(
+        &mut self,
+        action_wrapper: &ActionWrapper,
+        state: &StateWrapper,
+    ) -> Result<(), HolochainError> {
+        let deltas = match self.view {
+            Some(ref view) => Self::deltas(view, action_wrapper, state),
+            None => None,
+        };
+        match deltas {
+            Some(deltas) => {
+                if deltas.is_empty() {
+                    return Ok(());
+                }
+                self.append(&deltas)?;
+                self.view = Some(PersistedView::from(state));
+                if self.records_since_checkpoint >= self.checkpoint_interval {
+                    self.checkpoint(state)?;
+                }
+                Ok(())
+            }
+            None => self.checkpoint(state),
+        }
+    }
+
+    fn load(&self, context: Arc<Context>) -> Result<Option<State>, HolochainError> {
+        let (mut checkpoint, found_checkpoint) = match self.read_checkpoint()? {
+            Some(checkpoint) => (checkpoint, true),
+            None => (Checkpoint::empty(), false),
+        };
+        let deltas = self.read_journal(checkpoint.generation)?;
+        if !found_checkpoint && deltas.is_empty() {
+            return Ok(None);
+        }
+        for delta in deltas {
+            checkpoint.apply(delta);
+        }
+        State::try_from_snapshots(
+            context,
+            checkpoint.agent,
+            checkpoint.nucleus,
+            checkpoint.dht,
+        )
+        .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_context_with_agent_state,
+        network::entry_with_header::EntryWithHeader,
+    };
+    use holochain_core_types::{chain_header::test_chain_header, entry::test_entry};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn test_pending_validation() -> PendingValidationStruct {
+        PendingValidationStruct {
+            entry_with_header: EntryWithHeader {
+                entry: test_entry(),
+                header: test_chain_header(),
+            },
+            dependencies: Vec::new(),
+            workflow: ValidatingWorkflow::HoldEntry,
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn journal_round_trip() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None).unwrap();
+        let state = context.state().unwrap().clone();
+
+        persister.save(&state).unwrap();
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert_eq!(state.agent(), loaded.agent());
+        assert_eq!(state.nucleus(), loaded.nucleus());
+        assert_eq!(state.dht(), loaded.dht());
+    }
+
+    fn pending_key(pending: &PendingValidationStruct) -> PendingValidationKey {
+        PendingValidationKey::new(
+            pending.entry_with_header.entry.address(),
+            pending.workflow.clone(),
+        )
+    }
+
+    #[test]
+    fn journal_replays_actions_after_checkpoint() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None).unwrap();
+        let state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+
+        let pending = test_pending_validation();
+        persister
+            .save_action(
+                &ActionWrapper::new(Action::AddPendingValidation(Arc::new(pending.clone()))),
+                &state,
+            )
+            .unwrap();
+        assert_eq!(
+            persister
+                .read_journal(persister.generation.unwrap())
+                .unwrap(),
+            vec![StateDelta::AddPendingValidation(pending.clone())]
+        );
+
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded
+            .nucleus()
+            .pending_validations
+            .contains_key(&pending_key(&pending)));
+        assert_eq!(state.agent(), loaded.agent());
+
+        persister
+            .save_action(
+                &ActionWrapper::new(Action::RemovePendingValidation((
+                    pending.entry_with_header.entry.address(),
+                    pending.workflow.clone(),
+                ))),
+                &state,
+            )
+            .unwrap();
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded.nucleus().pending_validations.is_empty());
+    }
+
+    #[test]
+    fn journal_keeps_pending_validation_attempts() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None).unwrap();
+        let mut state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+
+        let pending = test_pending_validation();
+        for action in vec![
+            Action::AddPendingValidation(Arc::new(pending.clone())),
+            Action::CountPendingValidationAttempt((
+                pending.entry_with_header.entry.address(),
+                pending.workflow.clone(),
+            )),
+            Action::AddPendingValidation(Arc::new(pending.clone())),
+        ] {
+            let action_wrapper = ActionWrapper::new(action);
+            state = state.reduce(action_wrapper.clone());
+            persister.save_action(&action_wrapper, &state).unwrap();
+        }
+
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert_eq!(
+            loaded.nucleus().pending_validations[&pending_key(&pending)].attempts,
+            1
+        );
+    }
+
+    #[test]
+    fn journal_maps_dead_letter_actions_to_deltas() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None).unwrap();
+        let state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+
+        let pending = test_pending_validation();
+        let dead_letter = DeadLetterValidation {
+            pending: Arc::new(pending.clone()),
+            attempts: 10,
+        };
+        for action in vec![
+            Action::AddPendingValidation(Arc::new(pending.clone())),
+            Action::AddDeadLetterValidation(dead_letter.clone()),
+        ] {
+            persister
+                .save_action(&ActionWrapper::new(action), &state)
+                .unwrap();
+        }
+
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded.nucleus().pending_validations.is_empty());
+        assert_eq!(
+            loaded
+                .nucleus()
+                .dead_letter_validations
+                .get(&pending_key(&pending))
+                .map(|dead_letter| dead_letter.attempts),
+            Some(10)
+        );
+
+        persister
+            .save_action(
+                &ActionWrapper::new(Action::RemoveDeadLetterValidation((
+                    pending.entry_with_header.entry.address(),
+                    pending.workflow.clone(),
+                ))),
+                &state,
+            )
+            .unwrap();
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded.nucleus().dead_letter_validations.is_empty());
+    }
+
+    #[test]
+    fn journal_ignores_torn_tail() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None).unwrap();
+        let state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+        let pending = test_pending_validation();
+        persister
+            .save_action(
+                &ActionWrapper::new(Action::AddPendingValidation(Arc::new(pending.clone()))),
+                &state,
+            )
+            .unwrap();
+
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(persister.journal_path(persister.generation.unwrap()))
+            .unwrap();
+        journal.write_all(b"{\"RemovePendingValid").unwrap();
+
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded
+            .nucleus()
+            .pending_validations
+            .contains_key(&pending_key(&pending)));
+    }
+
+    /// Flips all bits, which is enough to tell whether records went through the cipher
+    struct FlipCipher;
+
+    impl JournalCipher for FlipCipher {
+        fn encrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+            Ok(data.iter().map(|byte| !byte).collect())
+        }
+
+        fn decrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+            self.encrypt(data)
+        }
+    }
+
+    #[test]
+    fn journal_encrypts_checkpoint_and_records() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), None)
+            .unwrap()
+            .with_cipher(Arc::new(FlipCipher));
+        let state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+        let pending = test_pending_validation();
+        persister
+            .save_action(
+                &ActionWrapper::new(Action::AddPendingValidation(Arc::new(pending.clone()))),
+                &state,
+            )
+            .unwrap();
+
+        let journal =
+            fs::read_to_string(persister.journal_path(persister.generation.unwrap())).unwrap();
+        let checkpoint = fs::read_to_string(dir.path().join(CHECKPOINT_FILE)).unwrap();
+        assert!(!journal.contains("AddPendingValidation"));
+        assert!(!checkpoint.contains("generation"));
+
+        let loaded = persister.load(context.clone()).unwrap().unwrap();
+        assert!(loaded
+            .nucleus()
+            .pending_validations
+            .contains_key(&pending_key(&pending)));
+        assert_eq!(state.agent(), loaded.agent());
+
+        // Without the cipher, the checkpoint can't be read
+        let plain = JournalPersister::new(dir.path(), None).unwrap();
+        assert!(plain.load(context.clone()).is_err());
+    }
+
+    #[test]
+    fn checkpoint_starts_new_generation() {
+        let dir = tempdir().unwrap();
+        let context = test_context_with_agent_state(None);
+        let mut persister = JournalPersister::new(dir.path(), Some(1)).unwrap();
+        let state = context.state().unwrap().clone();
+        persister.save(&state).unwrap();
+        let first_generation = persister.generation.unwrap();
+
+        persister.save(&state).unwrap();
+        assert_eq!(persister.generation, Some(first_generation + 1));
+        assert!(!persister.journal_path(first_generation).exists());
+        assert!(persister.journal_path(first_generation + 1).exists());
+    }
+
+    #[test]
+    fn apply_removes_pending_validation() {
+        let mut checkpoint = Checkpoint::empty();
+        let pending = test_pending_validation();
+        checkpoint.apply(StateDelta::AddPendingValidation(pending.clone()));
+        assert_eq!(checkpoint.nucleus.pending_validations.len(), 1);
+        checkpoint.apply(StateDelta::RemovePendingValidation(
+            pending.entry_with_header.entry.address(),
+            ValidatingWorkflow::HoldEntry,
+        ));
+        assert!(checkpoint.nucleus.pending_validations.is_empty());
+    }
+
+    #[test]
+    fn apply_moves_dead_letter_validation() {
+        let mut checkpoint = Checkpoint::empty();
+        let pending = test_pending_validation();
+        checkpoint.apply(StateDelta::AddPendingValidation(pending.clone()));
+        checkpoint.apply(StateDelta::AddDeadLetterValidation(DeadLetterValidation {
+            pending: Arc::new(pending.clone()),
+            attempts: 10,
+        }));
+        assert!(checkpoint.nucleus.pending_validations.is_empty());
+        assert_eq!(checkpoint.nucleus.dead_letter_validations.len(), 1);
+        checkpoint.apply(StateDelta::RemoveDeadLetterValidation(
+            pending.entry_with_header.entry.address(),
+            ValidatingWorkflow::HoldEntry,
+        ));
+        assert!(checkpoint.nucleus.dead_letter_validations.is_empty());
+    }
+}
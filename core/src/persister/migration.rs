@@ -0,0 +1,445 @@
+//! Versioning of persisted instance storage.
+//!
+//! The chain and DHT content addressable storages of every persisted instance carry a
+//! `StorageVersion` stamp under a fixed address, and its EAV storage carries one as a row
+//! with the `StorageVersion` attribute. Storage that was written before stamps existed counts
+//! as version 0. When the conductor opens an instance, outdated storage gets upgraded by
+//! running the registered migration steps in order, and storage of a newer format version than
+//! `CURRENT_STORAGE_VERSION` gets refused instead of starting a new chain on top of it.
+//! Storage can also be upgraded offline, e.g. with `hc storage migrate`.
+//!
+//! Any change to how snapshots or entries get serialized has to bump `CURRENT_STORAGE_VERSION`
+//! and register a `Migration` from the previous version in `migrations()`.
+
+use crate::persister::{upgrade_legacy_snapshots, SimplePersister};
+use crate::context::Context;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EavFilter, EntityAttributeValueStorage, IndexFilter},
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+/// Format version of the storage this version of Holochain writes.
+pub const CURRENT_STORAGE_VERSION: u32 = 1;
+
+pub const STORAGE_VERSION_ADDRESS: &str = "StorageVersion";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct StorageVersion {
+    pub version: u32,
+}
+
+impl AddressableContent for StorageVersion {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        Address::from(STORAGE_VERSION_ADDRESS)
+    }
+}
+
+/// The stores of an instance that migrations operate on.
+/// `cas` is the chain storage, which holds the persisted state, and `dht_cas` the DHT storage.
+/// Both are often the same store.
+#[derive(Clone)]
+pub struct MigrationStorage {
+    pub cas: Arc<RwLock<dyn ContentAddressableStorage>>,
+    pub dht_cas: Arc<RwLock<dyn ContentAddressableStorage>>,
+    pub eav: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+}
+
+impl From<&Context> for MigrationStorage {
+    fn from(context: &Context) -> Self {
+        MigrationStorage {
+            cas: context.chain_storage.clone(),
+            dht_cas: context.dht_storage.clone(),
+            eav: context.eav_storage.clone(),
+        }
+    }
+}
+
+/// A step that upgrades storage from version `from` to version `from + 1`.
+/// `run` gets called with the `dry_run` flag and returns a description of every change
+/// it made, or would have made.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    pub run: fn(&MigrationStorage, bool) -> HcResult<Vec<String>>,
+}
+
+/// All registered migrations, ordered by the version they upgrade from.
+pub fn migrations() -> Vec<Migration> {
+    vec![Migration {
+        from: 0,
+        description: "Move unversioned state snapshots into a checksummed snapshot generation",
+        run: migrate_legacy_snapshots,
+    }]
+}
+
+fn migrate_legacy_snapshots(storage: &MigrationStorage, dry_run: bool) -> HcResult<Vec<String>> {
+    if upgrade_legacy_snapshots(storage.cas.clone(), dry_run)? {
+        Ok(vec![format!(
+            "State snapshots {} the first snapshot generation",
+            if dry_run {
+                "would be copied into"
+            } else {
+                "were copied into"
+            }
+        )])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn stamped_version(cas: &Arc<RwLock<dyn ContentAddressableStorage>>) -> HcResult<Option<u32>> {
+    match cas.read()?.fetch(&Address::from(STORAGE_VERSION_ADDRESS))? {
+        Some(content) => Ok(Some(StorageVersion::try_from_content(&content)?.version)),
+        None => Ok(None),
+    }
+}
+
+/// Returns the format version the given EAV storage got stamped with, if any.
+/// Stamps use the version as their index, so the latest one is the highest version.
+pub fn eav_storage_version(
+    eav: &Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+) -> HcResult<Option<u32>> {
+    let rows = eav.read()?.fetch_eavi(&EaviQuery::new(
+        EavFilter::single(Address::from(STORAGE_VERSION_ADDRESS)),
+        EavFilter::single(Attribute::StorageVersion),
+        None.into(),
+        IndexFilter::LatestByAttribute,
+        None,
+    ))?;
+    rows.iter()
+        .map(|eavi| {
+            String::from(eavi.value()).parse::<u32>().map_err(|_| {
+                HolochainError::ErrorGeneric(format!(
+                    "Invalid EAV storage version stamp: {}",
+                    eavi.value()
+                ))
+            })
+        })
+        .next()
+        .transpose()
+}
+
+/// Returns the format version of the given storage, or `None` if it is empty.
+/// Storage that holds persisted state but no version stamp is version 0.
+pub fn storage_version(cas: &Arc<RwLock<dyn ContentAddressableStorage>>) -> HcResult<Option<u32>> {
+    if let Some(version) = stamped_version(cas)? {
+        return Ok(Some(version));
+    }
+    let has_state = SimplePersister::new(cas.clone())
+        .load_agent_snapshot()
+        .map(|snapshot| snapshot.is_some())
+        .unwrap_or(true);
+    Ok(if has_state { Some(0) } else { None })
+}
+
+fn write_storage_version(
+    cas: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    version: u32,
+) -> HcResult<()> {
+    cas.write()?.add(&StorageVersion { version })?;
+    Ok(())
+}
+
+fn write_eav_storage_version(
+    eav: &Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    version: u32,
+) -> HcResult<()> {
+    eav.write()?.add_eavi(&EntityAttributeValueIndex::new_with_index(
+        &Address::from(STORAGE_VERSION_ADDRESS),
+        &Attribute::StorageVersion,
+        &Address::from(version.to_string()),
+        i64::from(version),
+    )?)?;
+    Ok(())
+}
+
+fn newer_version_error(version: u32) -> HolochainError {
+    HolochainError::ErrorGeneric(format!(
+        "Storage has format version {}, which is newer than version {} that this version of Holochain supports",
+        version, CURRENT_STORAGE_VERSION
+    ))
+}
+
+/// Stamps the DHT CAS and the EAV storage with the current version unless they already
+/// carry it. The chain CAS gets stamped by the caller.
+fn stamp_dht_and_eav(storage: &MigrationStorage) -> HcResult<()> {
+    match stamped_version(&storage.dht_cas)? {
+        Some(version) if version > CURRENT_STORAGE_VERSION => {
+            return Err(newer_version_error(version))
+        }
+        Some(CURRENT_STORAGE_VERSION) => (),
+        _ => write_storage_version(&storage.dht_cas, CURRENT_STORAGE_VERSION)?,
+    }
+    match eav_storage_version(&storage.eav)? {
+        Some(version) if version > CURRENT_STORAGE_VERSION => Err(newer_version_error(version)),
+        Some(CURRENT_STORAGE_VERSION) => Ok(()),
+        _ => write_eav_storage_version(&storage.eav, CURRENT_STORAGE_VERSION),
+    }
+}
+
+/// Makes sure this version of Holochain can use the given storage when opening it.
+/// Empty storage gets stamped with the current version, and outdated storage, including
+/// storage that holds state but no stamp, gets migrated. Returns the report of that
+/// migration, if one ran.
+pub fn check_storage_version(storage: &MigrationStorage) -> HcResult<Option<MigrationReport>> {
+    match storage_version(&storage.cas)? {
+        Some(version) if version > CURRENT_STORAGE_VERSION => Err(newer_version_error(version)),
+        Some(version) if version < CURRENT_STORAGE_VERSION => migrate(storage, false).map(Some),
+        Some(_) => stamp_dht_and_eav(storage).map(|_| None),
+        None => {
+            write_storage_version(&storage.cas, CURRENT_STORAGE_VERSION)?;
+            stamp_dht_and_eav(storage).map(|_| None)
+        }
+    }
+}
+
+/// A migration step as it got run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigrationStep {
+    pub from: u32,
+    pub description: String,
+    pub changes: Vec<String>,
+}
+
+/// Result of running `migrate()`.
+/// `from` is the version of the storage before, `None` if it was empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigrationReport {
+    pub from: Option<u32>,
+    pub to: u32,
+    pub dry_run: bool,
+    pub steps: Vec<MigrationStep>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verb = if self.dry_run { "would be" } else { "was" };
+        let from = match self.from {
+            Some(from) => from,
+            None => {
+                return writeln!(
+                    f,
+                    "Storage is empty and {} stamped with version {}",
+                    verb, self.to
+                );
+            }
+        };
+        if self.steps.is_empty() {
+            return writeln!(f, "Storage is at version {}, nothing to migrate", from);
+        }
+        writeln!(
+            f,
+            "Storage {} migrated from version {} to version {}:",
+            verb, from, self.to
+        )?;
+        for step in self.steps.iter() {
+            writeln!(f, "  {} -> {}: {}", step.from, step.from + 1, step.description)?;
+            if step.changes.is_empty() {
+                writeln!(f, "    nothing to change")?;
+            }
+            for change in step.changes.iter() {
+                writeln!(f, "    - {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Upgrades the given storage to `CURRENT_STORAGE_VERSION` by running all migrations
+/// from its current version on. The version stamp gets updated after every step, so an
+/// interrupted migration continues with the step that did not finish.
+/// With `dry_run` set, nothing gets written and the report lists what would change.
+pub fn migrate(storage: &MigrationStorage, dry_run: bool) -> HcResult<MigrationReport> {
+    let from = storage_version(&storage.cas)?;
+    let mut version = from.unwrap_or(CURRENT_STORAGE_VERSION);
+    if version > CURRENT_STORAGE_VERSION {
+        return Err(newer_version_error(version));
+    }
+
+    let mut steps = Vec::new();
+    while version < CURRENT_STORAGE_VERSION {
+        let migration = migrations()
+            .into_iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| {
+                HolochainError::ErrorGeneric(format!(
+                    "No migration registered for storage version {}",
+                    version
+                ))
+            })?;
+        let changes = (migration.run)(storage, dry_run)?;
+        version += 1;
+        if !dry_run {
+            write_storage_version(&storage.cas, version)?;
+        }
+        steps.push(MigrationStep {
+            from: migration.from,
+            description: migration.description.to_string(),
+            changes,
+        });
+    }
+    if !dry_run {
+        if from.is_none() {
+            write_storage_version(&storage.cas, CURRENT_STORAGE_VERSION)?;
+        }
+        stamp_dht_and_eav(storage)?;
+    }
+
+    Ok(MigrationReport {
+        from,
+        to: CURRENT_STORAGE_VERSION,
+        dry_run,
+        steps,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        agent::state::AgentStateSnapshot, dht::dht_store::DhtStoreSnapshot,
+        instance::tests::test_context_with_agent_state, nucleus::state::NucleusStateSnapshot,
+        persister::Persister, state::StateWrapper,
+    };
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+
+    fn empty_storage() -> MigrationStorage {
+        MigrationStorage {
+            cas: Arc::new(RwLock::new(MemoryStorage::new())),
+            dht_cas: Arc::new(RwLock::new(MemoryStorage::new())),
+            eav: Arc::new(RwLock::new(EavMemoryStorage::new())),
+        }
+    }
+
+    fn add_legacy_snapshots(storage: &MigrationStorage, state: &StateWrapper) {
+        let mut store = storage.cas.write().unwrap();
+        store.add(&AgentStateSnapshot::from(state)).unwrap();
+        store.add(&NucleusStateSnapshot::from(state)).unwrap();
+        store.add(&DhtStoreSnapshot::from(state)).unwrap();
+    }
+
+    #[test]
+    fn registered_migrations_cover_all_versions() {
+        let versions: Vec<u32> = migrations()
+            .iter()
+            .map(|migration| migration.from)
+            .collect();
+        assert_eq!(versions, (0..CURRENT_STORAGE_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn empty_storage_gets_stamped() {
+        let storage = empty_storage();
+        assert_eq!(storage_version(&storage.cas).unwrap(), None);
+        assert_eq!(check_storage_version(&storage).unwrap(), None);
+        assert_eq!(
+            storage_version(&storage.cas).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+        assert_eq!(
+            stamped_version(&storage.dht_cas).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+        assert_eq!(
+            eav_storage_version(&storage.eav).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+
+        // Opening it again does not stamp anything twice
+        check_storage_version(&storage).unwrap();
+        let stamps = storage
+            .eav
+            .read()
+            .unwrap()
+            .fetch_eavi(&EaviQuery::default())
+            .unwrap();
+        assert_eq!(stamps.len(), 1);
+    }
+
+    #[test]
+    fn newer_storage_gets_refused() {
+        let storage = empty_storage();
+        write_storage_version(&storage.cas, CURRENT_STORAGE_VERSION + 1).unwrap();
+        assert!(check_storage_version(&storage).is_err());
+        assert!(migrate(&storage, true).is_err());
+
+        let storage = empty_storage();
+        write_eav_storage_version(&storage.eav, CURRENT_STORAGE_VERSION + 1).unwrap();
+        assert!(check_storage_version(&storage).is_err());
+    }
+
+    #[test]
+    fn unstamped_storage_gets_migrated_on_open() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let storage = MigrationStorage::from(&*context);
+        add_legacy_snapshots(&storage, &state);
+        assert_eq!(storage_version(&storage.cas).unwrap(), Some(0));
+
+        let report = check_storage_version(&storage).unwrap().unwrap();
+        assert_eq!(report.from, Some(0));
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(
+            storage_version(&storage.cas).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+        assert_eq!(
+            eav_storage_version(&storage.eav).unwrap(),
+            Some(CURRENT_STORAGE_VERSION)
+        );
+        assert_eq!(check_storage_version(&storage).unwrap(), None);
+    }
+
+    #[test]
+    fn legacy_storage_gets_migrated() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let storage = MigrationStorage::from(&*context);
+        add_legacy_snapshots(&storage, &state);
+        assert_eq!(storage_version(&storage.cas).unwrap(), Some(0));
+
+        let report = migrate(&storage, true).unwrap();
+        assert_eq!(report.from, Some(0));
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.steps[0].changes.len(), 1);
+        assert_eq!(storage_version(&storage.cas).unwrap(), Some(0));
+
+        let report = migrate(&storage, false).unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(check_storage_version(&storage).unwrap(), None);
+        let restored = SimplePersister::new(storage.cas.clone())
+            .load(context.clone())
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.agent(), restored.agent());
+
+        let report = migrate(&storage, false).unwrap();
+        assert_eq!(report.from, Some(CURRENT_STORAGE_VERSION));
+        assert!(report.steps.is_empty());
+    }
+}
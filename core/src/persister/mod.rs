@@ -0,0 +1,596 @@
+pub mod journal;
+pub mod migration;
+
+pub use self::journal::{JournalCipher, JournalPersister};
+
+use crate::{
+    action::ActionWrapper,
+    agent::state::{AgentStateSnapshot, AGENT_SNAPSHOT_ADDRESS},
+    context::Context,
+    nucleus::state::{NucleusStateSnapshot, NUCLEUS_SNAPSHOT_ADDRESS},
+    state::State,
+};
+use holochain_core_types::error::HolochainError;
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::{
+    content::{Address, AddressableContent, Content},
+    storage::ContentAddressableStorage,
+};
+use multihash::Hash;
+
+use crate::{
+    dht::dht_store::{DhtStoreSnapshot, DHT_STORE_SNAPSHOT_ADDRESS},
+    state::StateWrapper,
+};
+use std::{
+    cmp::Reverse,
+    convert::TryFrom,
+    sync::{Arc, RwLock},
+};
+
+/// trait that defines the persistence functionality that holochain_core requires
+pub trait Persister: Send {
+    // @TODO how does save/load work with snowflake IDs?
+    // snowflake is only unique across a single process, not a reboot save/load round trip
+    // we'd need real UUIDs for persistant uniqueness
+    // @see https://github.com/holochain/holochain-rust/issues/203
+    fn save(&mut self, state: &StateWrapper) -> Result<(), HolochainError>;
+
+    /// Gets called after every reduced action with the new state.
+    /// Persisters that can persist incrementally only write what the action changed,
+    /// the default is to save the whole state.
+    fn save_action(
+        &mut self,
+        _action_wrapper: &ActionWrapper,
+        state: &StateWrapper,
+    ) -> Result<(), HolochainError> {
+        self.save(state)
+    }
+
+    fn load(&self, context: Arc<Context>) -> Result<Option<State>, HolochainError>;
+}
+
+/// Number of snapshot generations a `SimplePersister` keeps by default.
+pub const DEFAULT_SNAPSHOT_GENERATIONS: usize = 3;
+
+static STATE_SNAPSHOT_ADDRESS_PREFIX: &'static str = "StateSnapshot";
+
+/// The three snapshots that make up one persisted state.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SnapshotSet {
+    agent: AgentStateSnapshot,
+    nucleus: NucleusStateSnapshot,
+    dht: DhtStoreSnapshot,
+}
+
+/// One generation of persisted state as it gets written to the CAS.
+/// The snapshot set is kept serialized next to its SHA2-256 checksum so that a
+/// torn or otherwise damaged write is detected on load instead of being restored.
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson)]
+struct SnapshotRecord {
+    generation: u64,
+    slot: usize,
+    checksum: Address,
+    payload: String,
+}
+
+impl SnapshotRecord {
+    fn new(generation: u64, slot: usize, set: &SnapshotSet) -> Result<Self, HolochainError> {
+        let payload = serde_json::to_string(set)?;
+        Ok(SnapshotRecord {
+            generation,
+            slot,
+            checksum: checksum(&payload),
+            payload,
+        })
+    }
+
+    fn verified_set(&self) -> Result<SnapshotSet, HolochainError> {
+        if checksum(&self.payload) != self.checksum {
+            return Err(HolochainError::CorruptState(format!(
+                "snapshot generation {} failed its checksum",
+                self.generation
+            )));
+        }
+        serde_json::from_str(&self.payload).map_err(|e| {
+            HolochainError::CorruptState(format!(
+                "snapshot generation {} could not be deserialized: {}",
+                self.generation, e
+            ))
+        })
+    }
+}
+
+impl AddressableContent for SnapshotRecord {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        slot_address(self.slot)
+    }
+}
+
+fn slot_address(slot: usize) -> Address {
+    Address::from(format!("{}-{}", STATE_SNAPSHOT_ADDRESS_PREFIX, slot))
+}
+
+/// Whether the given address is one that state snapshots get saved under, by this
+/// persister or by versions before snapshot generations.
+pub fn is_snapshot_address(address: &Address) -> bool {
+    let address = String::from(address.clone());
+    address == AGENT_SNAPSHOT_ADDRESS
+        || address == NUCLEUS_SNAPSHOT_ADDRESS
+        || address == DHT_STORE_SNAPSHOT_ADDRESS
+        || address.starts_with(&format!("{}-", STATE_SNAPSHOT_ADDRESS_PREFIX))
+}
+
+fn checksum(payload: &str) -> Address {
+    Address::encode_from_str(payload, Hash::SHA2256)
+}
+
+enum Slot {
+    Empty,
+    Valid(u64, SnapshotSet),
+    Corrupt(HolochainError),
+}
+
+/// Persists the state as whole snapshots into a content addressable storage.
+///
+/// Every save writes a new generation into a ring of `generations` slots, overwriting
+/// the oldest one, so a save that gets interrupted can only ever damage the generation
+/// that was being written. Loading restores the newest generation that passes its
+/// checksum and logs every generation it had to discard.
+#[derive(Clone)]
+pub struct SimplePersister {
+    storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    generations: usize,
+    next_generation: Option<u64>,
+}
+
+impl PartialEq for SimplePersister {
+    fn eq(&self, other: &SimplePersister) -> bool {
+        (&*self.storage.read().unwrap()).get_id() == (&*other.storage.read().unwrap()).get_id()
+    }
+}
+
+impl Persister for SimplePersister {
+    fn save(&mut self, state: &StateWrapper) -> Result<(), HolochainError> {
+        let lock = &*self.storage.clone();
+        let mut store = lock
+            .try_write()
+            .map_err(|_| HolochainError::new("Could not get write lock on storage"))?;
+        let set = SnapshotSet {
+            agent: AgentStateSnapshot::from(state),
+            nucleus: NucleusStateSnapshot::from(state),
+            dht: DhtStoreSnapshot::from(state),
+        };
+        self.write_set(&mut *store, &set)
+    }
+
+    fn load(&self, context: Arc<Context>) -> Result<Option<State>, HolochainError> {
+        let lock = &*self.storage.clone();
+        let store = lock
+            .read()
+            .map_err(|_| HolochainError::new("Could not get read lock on storage"))?;
+
+        let mut discarded = Vec::new();
+        let mut valid = Vec::new();
+        for slot in self.read_slots(&*store) {
+            match slot {
+                Slot::Empty => (),
+                Slot::Valid(generation, set) => valid.push((generation, set)),
+                Slot::Corrupt(error) => discarded.push(error),
+            }
+        }
+
+        if valid.is_empty() && discarded.is_empty() {
+            return load_legacy_snapshots(&*store, context);
+        }
+
+        valid.sort_by_key(|(generation, _)| Reverse(*generation));
+        for (generation, set) in valid {
+            match State::try_from_snapshots(context.clone(), set.agent, set.nucleus, set.dht) {
+                Ok(state) => {
+                    for error in discarded.iter() {
+                        log_warn!(
+                            context,
+                            "persister/load: discarded snapshot while restoring generation {}: {}",
+                            generation,
+                            error
+                        );
+                    }
+                    return Ok(Some(state));
+                }
+                Err(error) => discarded.push(HolochainError::CorruptState(format!(
+                    "snapshot generation {} could not be restored: {}",
+                    generation, error
+                ))),
+            }
+        }
+
+        Err(HolochainError::List(discarded))
+    }
+}
+
+impl SimplePersister {
+    pub fn new(storage: Arc<RwLock<dyn ContentAddressableStorage>>) -> Self {
+        SimplePersister::with_generations(storage, DEFAULT_SNAPSHOT_GENERATIONS)
+    }
+
+    /// Creates a persister that keeps the given number of snapshot generations (at least one).
+    pub fn with_generations(
+        storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+        generations: usize,
+    ) -> Self {
+        SimplePersister {
+            storage,
+            generations: generations.max(1),
+            next_generation: None,
+        }
+    }
+
+    /// Loads the agent snapshot of the newest valid generation without restoring the whole
+    /// state, so tools can get to the source chain of an instance that is not running.
+    pub fn load_agent_snapshot(&self) -> Result<Option<AgentStateSnapshot>, HolochainError> {
+        let store = self
+            .storage
+            .read()
+            .map_err(|_| HolochainError::new("Could not get read lock on storage"))?;
+        Ok(self.newest_set(&*store)?.map(|set| set.agent))
+    }
+
+    /// Writes a new generation that holds the given agent snapshot next to the nucleus and
+    /// DHT snapshots of the newest valid generation.
+    /// Fails if there is no persisted state yet that the agent snapshot could be added to.
+    pub fn save_agent_snapshot(&mut self, agent: AgentStateSnapshot) -> Result<(), HolochainError> {
+        let lock = &*self.storage.clone();
+        let mut store = lock
+            .try_write()
+            .map_err(|_| HolochainError::new("Could not get write lock on storage"))?;
+        let set = self
+            .newest_set(&*store)?
+            .ok_or_else(|| HolochainError::new("No persisted state found to update"))?;
+        self.write_set(&mut *store, &SnapshotSet { agent, ..set })
+    }
+
+    /// Writes the given set as the next generation, overwriting the oldest one.
+    fn write_set(
+        &mut self,
+        store: &mut dyn ContentAddressableStorage,
+        set: &SnapshotSet,
+    ) -> Result<(), HolochainError> {
+        let generation = match self.next_generation {
+            Some(generation) => generation,
+            None => self
+                .read_slots(store)
+                .into_iter()
+                .filter_map(|slot| match slot {
+                    Slot::Valid(generation, _) => Some(generation + 1),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0),
+        };
+        let slot = (generation % self.generations as u64) as usize;
+        store.add(&SnapshotRecord::new(generation, slot, set)?)?;
+        self.next_generation = Some(generation + 1);
+        Ok(())
+    }
+
+    /// The snapshot set of the newest valid generation, or the legacy snapshots if no
+    /// generation has been written yet.
+    fn newest_set(
+        &self,
+        store: &dyn ContentAddressableStorage,
+    ) -> Result<Option<SnapshotSet>, HolochainError> {
+        let mut discarded = Vec::new();
+        let mut newest: Option<(u64, SnapshotSet)> = None;
+        for slot in self.read_slots(store) {
+            match slot {
+                Slot::Empty => (),
+                Slot::Corrupt(error) => discarded.push(error),
+                Slot::Valid(generation, set) => {
+                    if newest.as_ref().map(|(n, _)| generation > *n).unwrap_or(true) {
+                        newest = Some((generation, set));
+                    }
+                }
+            }
+        }
+        match newest {
+            Some((_, set)) => Ok(Some(set)),
+            None if discarded.is_empty() => fetch_legacy_set(store),
+            None => Err(HolochainError::List(discarded)),
+        }
+    }
+
+    fn read_slots(&self, store: &dyn ContentAddressableStorage) -> Vec<Slot> {
+        (0..self.generations)
+            .map(|slot| match store.fetch(&slot_address(slot)) {
+                Ok(None) => Slot::Empty,
+                Ok(Some(content)) => SnapshotRecord::try_from_content(&content)
+                    .map_err(|e| {
+                        HolochainError::CorruptState(format!(
+                            "snapshot slot {} could not be deserialized: {}",
+                            slot, e
+                        ))
+                    })
+                    .and_then(|record| {
+                        let set = record.verified_set()?;
+                        Ok(Slot::Valid(record.generation, set))
+                    })
+                    .unwrap_or_else(Slot::Corrupt),
+                Err(e) => Slot::Corrupt(HolochainError::CorruptState(format!(
+                    "snapshot slot {} could not be read: {}",
+                    slot, e
+                ))),
+            })
+            .collect()
+    }
+}
+
+/// Copies the unversioned snapshot set that was written before snapshots got generations into
+/// a new generation, unless there is a generation already.
+/// Returns whether there was a snapshot set to copy. With `dry_run` set, nothing gets written.
+pub(crate) fn upgrade_legacy_snapshots(
+    storage: Arc<RwLock<dyn ContentAddressableStorage>>,
+    dry_run: bool,
+) -> Result<bool, HolochainError> {
+    let mut persister = SimplePersister::new(storage.clone());
+    let mut store = storage.write()?;
+    let has_generation = persister.read_slots(&*store).iter().any(|slot| match slot {
+        Slot::Empty => false,
+        _ => true,
+    });
+    if has_generation {
+        return Ok(false);
+    }
+    match fetch_legacy_set(&*store)? {
+        Some(set) => {
+            if !dry_run {
+                persister.write_set(&mut *store, &set)?;
+            }
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Loads the single unversioned snapshot set that was written before snapshots got generations.
+fn load_legacy_snapshots(
+    store: &dyn ContentAddressableStorage,
+    context: Arc<Context>,
+) -> Result<Option<State>, HolochainError> {
+    match fetch_legacy_set(store)? {
+        Some(set) => State::try_from_snapshots(context, set.agent, set.nucleus, set.dht).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn fetch_legacy_set(
+    store: &dyn ContentAddressableStorage,
+) -> Result<Option<SnapshotSet>, HolochainError> {
+    let agent_snapshot = fetch_legacy_snapshot::<AgentStateSnapshot>(store, AGENT_SNAPSHOT_ADDRESS)?;
+    let nucleus_snapshot =
+        fetch_legacy_snapshot::<NucleusStateSnapshot>(store, NUCLEUS_SNAPSHOT_ADDRESS)?;
+    let dht_store_snapshot =
+        fetch_legacy_snapshot::<DhtStoreSnapshot>(store, DHT_STORE_SNAPSHOT_ADDRESS)?;
+
+    match (agent_snapshot, nucleus_snapshot, dht_store_snapshot) {
+        (Some(agent), Some(nucleus), Some(dht)) => Ok(Some(SnapshotSet {
+            agent,
+            nucleus,
+            dht,
+        })),
+        _ => Ok(None),
+    }
+}
+
+fn fetch_legacy_snapshot<T: AddressableContent>(
+    store: &dyn ContentAddressableStorage,
+    address: &str,
+) -> Result<Option<T>, HolochainError> {
+    match store.fetch(&Address::from(address))? {
+        Some(content) => T::try_from_content(&content).map(Some).map_err(|e| {
+            HolochainError::CorruptState(format!("could not load {} snapshot: {}", address, e))
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use self::tempfile::tempdir;
+    use super::*;
+    use crate::instance::tests::test_context_with_agent_state;
+    use holochain_json_api::json::RawString;
+    use std::fs::File;
+    use tempfile;
+
+    /// Content stored under an arbitrary address, used to simulate a torn write.
+    struct RawContent {
+        address: Address,
+        content: Content,
+    }
+
+    impl AddressableContent for RawContent {
+        fn content(&self) -> Content {
+            self.content.clone()
+        }
+
+        fn try_from_content(_content: &Content) -> JsonResult<Self> {
+            Err(JsonError::ErrorGeneric(String::from(
+                "RawContent carries its address outside of the content",
+            )))
+        }
+
+        fn address(&self) -> Address {
+            self.address.clone()
+        }
+    }
+
+    #[test]
+    fn persistence_round_trip() {
+        let dir = tempdir().unwrap();
+        let temp_path = dir.path().join("test");
+        let _tempfile = temp_path.to_str().unwrap();
+        let context = test_context_with_agent_state(None);
+        File::create(temp_path.clone()).unwrap();
+        let mut persistance = SimplePersister::new(context.dht_storage.clone());
+        let state = context.state().unwrap().clone();
+        persistance.save(&state).unwrap();
+        let state_from_file = persistance.load(context).unwrap().unwrap();
+        assert_eq!(state.agent(), state_from_file.agent());
+        assert_eq!(state.nucleus(), state_from_file.nucleus());
+        assert_eq!(state.dht(), state_from_file.dht());
+
+        // the network is NOT the same because it can't be serialzied rationally
+        // need to fix this so `persitance.load()` takes a networks or something
+        assert_ne!(state.network(), state_from_file.network());
+    }
+
+    #[test]
+    fn save_overwrites_oldest_generation() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let mut persister = SimplePersister::with_generations(context.dht_storage.clone(), 2);
+        for _ in 0..3 {
+            persister.save(&state).unwrap();
+        }
+
+        let generations: Vec<u64> = persister
+            .read_slots(&*context.dht_storage.read().unwrap())
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::Valid(generation, _) => generation,
+                _ => panic!("expected a valid snapshot in every slot"),
+            })
+            .collect();
+        assert_eq!(generations, vec![2, 1]);
+
+        // a new persister on the same storage continues counting generations
+        let mut persister = SimplePersister::with_generations(context.dht_storage.clone(), 2);
+        persister.save(&state).unwrap();
+        assert_eq!(persister.next_generation, Some(4));
+    }
+
+    #[test]
+    fn load_falls_back_to_newest_valid_generation() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let mut persister = SimplePersister::new(context.dht_storage.clone());
+        persister.save(&state).unwrap();
+        persister.save(&state).unwrap();
+
+        // generation 1 got torn mid write
+        context
+            .dht_storage
+            .write()
+            .unwrap()
+            .add(&RawContent {
+                address: slot_address(1),
+                content: JsonString::from_json("{\"generation\":1,\"slot\":1,\"chec"),
+            })
+            .unwrap();
+
+        let restored = persister.load(context.clone()).unwrap().unwrap();
+        assert_eq!(state.agent(), restored.agent());
+        assert_eq!(state.nucleus(), restored.nucleus());
+        assert_eq!(state.dht(), restored.dht());
+    }
+
+    #[test]
+    fn load_reports_discarded_generations() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let mut persister = SimplePersister::new(context.dht_storage.clone());
+        persister.save(&state).unwrap();
+
+        // generation 0 got its payload damaged but kept the old checksum
+        let mut record = SnapshotRecord::try_from_content(
+            &context
+                .dht_storage
+                .read()
+                .unwrap()
+                .fetch(&slot_address(0))
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        record.payload.truncate(record.payload.len() / 2);
+        context.dht_storage.write().unwrap().add(&record).unwrap();
+
+        match persister.load(context.clone()) {
+            Err(HolochainError::List(discarded)) => assert_eq!(
+                discarded,
+                vec![HolochainError::CorruptState(
+                    "snapshot generation 0 failed its checksum".to_string()
+                )]
+            ),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("a corrupt snapshot must not be restored"),
+        }
+    }
+
+    #[test]
+    fn agent_snapshot_can_be_replaced_without_context() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        let mut persister = SimplePersister::new(context.dht_storage.clone());
+        assert!(persister
+            .save_agent_snapshot(AgentStateSnapshot::new(None))
+            .is_err());
+
+        persister.save(&state).unwrap();
+        assert_eq!(
+            persister
+                .load_agent_snapshot()
+                .unwrap()
+                .unwrap()
+                .top_chain_header()
+                .cloned(),
+            state.agent().top_chain_header()
+        );
+
+        persister
+            .save_agent_snapshot(AgentStateSnapshot::new(None))
+            .unwrap();
+        let restored = persister.load(context.clone()).unwrap().unwrap();
+        assert_eq!(restored.agent().top_chain_header(), None);
+        assert_eq!(state.nucleus(), restored.nucleus());
+    }
+
+    #[test]
+    fn load_reads_legacy_snapshots() {
+        let context = test_context_with_agent_state(None);
+        let state = context.state().unwrap().clone();
+        {
+            let mut store = context.dht_storage.write().unwrap();
+            store.add(&AgentStateSnapshot::from(&state)).unwrap();
+            store.add(&NucleusStateSnapshot::from(&state)).unwrap();
+            store.add(&DhtStoreSnapshot::from(&state)).unwrap();
+        }
+        let persister = SimplePersister::new(context.dht_storage.clone());
+        let restored = persister.load(context.clone()).unwrap().unwrap();
+        assert_eq!(state.agent(), restored.agent());
+
+        context
+            .dht_storage
+            .write()
+            .unwrap()
+            .add(&RawContent {
+                address: Address::from(AGENT_SNAPSHOT_ADDRESS),
+                content: JsonString::from(RawString::from("garbage")),
+            })
+            .unwrap();
+        assert!(persister.load(context).is_err());
+    }
+}
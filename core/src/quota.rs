@@ -0,0 +1,351 @@
+//! Storage accounting and quotas of an instance.
+//!
+//! The conductor wraps the chain, DHT and EAV storage of every instance in metering storages
+//! that record the bytes and the number of contents or rows they write into the instance's
+//! `StorageUsage`. Usage gets persisted in the chain storage under a fixed address so that it
+//! survives restarts of the conductor.
+//!
+//! A `StorageQuota` puts soft and hard limits on that usage. Crossing a soft limit only gets
+//! logged. Once a hard limit is reached, committing to the source chain and holding
+//! entries, links, updates and removes for the DHT fails with `HolochainError::QuotaExceeded`.
+
+use crate::persister::{is_snapshot_address, migration::STORAGE_VERSION_ADDRESS};
+use holochain_core_types::{
+    eav::{Attribute, EntityAttributeValueIndex},
+    error::HcResult,
+};
+use holochain_json_api::{
+    error::{JsonError, JsonResult},
+    json::JsonString,
+};
+use holochain_persistence_api::cas::{
+    content::{Address, AddressableContent, Content},
+    storage::ContentAddressableStorage,
+};
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+pub const STORAGE_USAGE_ADDRESS: &str = "StorageUsage";
+
+/// Whether the given address holds bookkeeping of the instance, i.e. its usage record,
+/// storage version stamp or state snapshots, rather than chain or DHT content.
+/// Bookkeeping does not get metered.
+pub fn is_bookkeeping_address(address: &Address) -> bool {
+    *address == Address::from(STORAGE_USAGE_ADDRESS)
+        || *address == Address::from(STORAGE_VERSION_ADDRESS)
+        || is_snapshot_address(address)
+}
+
+/// Whether the given EAV row is bookkeeping of the instance (the storage version stamp).
+pub fn is_bookkeeping_row(eavi: &EntityAttributeValueIndex) -> bool {
+    eavi.attribute() == Attribute::StorageVersion
+}
+
+/// The storages of an instance that usage gets accounted for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageKind {
+    Chain,
+    Dht,
+    Eav,
+}
+
+/// Bytes and number of contents (or EAV rows) written to one storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub bytes: u64,
+    pub count: u64,
+}
+
+impl Usage {
+    fn add(&mut self, bytes: u64) {
+        self.bytes = self.bytes.saturating_add(bytes);
+        self.count = self.count.saturating_add(1);
+    }
+
+    fn remove(&mut self, bytes: u64) {
+        self.bytes = self.bytes.saturating_sub(bytes);
+        self.count = self.count.saturating_sub(1);
+    }
+}
+
+/// Storage usage of an instance, per storage.
+/// Chain and DHT usually share a storage, in which case content counts towards the one
+/// it got written to first.
+/// Usage only gets tracked for instances with a storage quota. A record that was not
+/// `tracked` all along can't be trusted and gets replaced by a scan of the storages once
+/// the instance gets a quota.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, DefaultJson)]
+pub struct InstanceUsage {
+    pub chain: Usage,
+    pub dht: Usage,
+    pub eav: Usage,
+    #[serde(default)]
+    pub tracked: bool,
+}
+
+impl InstanceUsage {
+    /// Records that `bytes` got written to a new content or row of the given storage.
+    pub fn record(&mut self, kind: StorageKind, bytes: u64) {
+        self.usage_mut(kind).add(bytes);
+    }
+
+    fn usage_mut(&mut self, kind: StorageKind) -> &mut Usage {
+        match kind {
+            StorageKind::Chain => &mut self.chain,
+            StorageKind::Dht => &mut self.dht,
+            StorageKind::Eav => &mut self.eav,
+        }
+    }
+
+    /// Bytes written to all storages.
+    pub fn total_bytes(&self) -> u64 {
+        self.chain
+            .bytes
+            .saturating_add(self.dht.bytes)
+            .saturating_add(self.eav.bytes)
+    }
+
+    /// Number of chain and DHT contents. EAV rows are metadata of those and don't count.
+    pub fn total_entries(&self) -> u64 {
+        self.chain.count.saturating_add(self.dht.count)
+    }
+}
+
+impl AddressableContent for InstanceUsage {
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> JsonResult<Self> {
+        Self::try_from(content.to_owned())
+    }
+
+    fn address(&self) -> Address {
+        Address::from(STORAGE_USAGE_ADDRESS)
+    }
+}
+
+/// Keeps track of the storage usage of an instance.
+/// Gets shared between the metering storages and the instance's context.
+#[derive(Debug, Default)]
+pub struct StorageUsage {
+    usage: RwLock<InstanceUsage>,
+    changed: AtomicBool,
+}
+
+impl StorageUsage {
+    pub fn new(usage: InstanceUsage) -> Self {
+        StorageUsage {
+            usage: RwLock::new(usage),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts from usage that has not been saved yet, like usage counted by scanning
+    /// a storage, so that the next `save_if_changed()` writes it.
+    pub fn unsaved(usage: InstanceUsage) -> Self {
+        StorageUsage {
+            usage: RwLock::new(usage),
+            changed: AtomicBool::new(true),
+        }
+    }
+
+    /// Restores the usage that got saved in the given storage, if any.
+    pub fn load(cas: &Arc<RwLock<dyn ContentAddressableStorage>>) -> HcResult<Option<Self>> {
+        Ok(
+            match cas.read()?.fetch(&Address::from(STORAGE_USAGE_ADDRESS))? {
+                Some(content) => Some(Self::new(InstanceUsage::try_from_content(&content)?)),
+                None => None,
+            },
+        )
+    }
+
+    /// Records that `bytes` got written to a new content or row of the given storage.
+    pub fn record(&self, kind: StorageKind, bytes: u64) {
+        self.usage.write().unwrap().record(kind, bytes);
+        self.changed.store(true, Ordering::SeqCst);
+    }
+
+    /// Records that a content or row of `bytes` got removed from the given storage.
+    pub fn release(&self, kind: StorageKind, bytes: u64) {
+        self.usage.write().unwrap().usage_mut(kind).remove(bytes);
+        self.changed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn snapshot(&self) -> InstanceUsage {
+        self.usage.read().unwrap().clone()
+    }
+
+    /// Writes the usage to the given storage if it changed since it got last saved.
+    /// The usage record itself does not get metered.
+    pub fn save_if_changed(
+        &self,
+        cas: &Arc<RwLock<dyn ContentAddressableStorage>>,
+    ) -> HcResult<()> {
+        if self.changed.swap(false, Ordering::SeqCst) {
+            let usage = self.snapshot();
+            if let Err(err) = cas.write()?.add(&usage) {
+                self.changed.store(true, Ordering::SeqCst);
+                return Err(err.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Limits on the storage usage of an instance. All limits are optional and the default
+/// quota does not limit anything.
+/// Byte limits apply to the bytes of all storages, entry limits to the number of
+/// contents in chain and DHT storage.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StorageQuota {
+    pub soft_limit_bytes: Option<u64>,
+    pub hard_limit_bytes: Option<u64>,
+    pub soft_limit_entries: Option<u64>,
+    pub hard_limit_entries: Option<u64>,
+}
+
+/// Result of checking usage against a `StorageQuota`.
+/// Carries a description of the exceeded limit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuotaStatus {
+    Ok,
+    SoftLimitExceeded(String),
+    HardLimitExceeded(String),
+}
+
+fn exceeded(limit: Option<u64>, used: u64, unit: &str) -> Option<String> {
+    limit
+        .filter(|limit| used >= *limit)
+        .map(|limit| format!("{} of {} {} used", used, limit, unit))
+}
+
+impl StorageQuota {
+    pub fn is_enabled(&self) -> bool {
+        self.soft_limit_bytes.is_some()
+            || self.hard_limit_bytes.is_some()
+            || self.soft_limit_entries.is_some()
+            || self.hard_limit_entries.is_some()
+    }
+
+    pub fn check(&self, usage: &InstanceUsage) -> QuotaStatus {
+        let bytes = usage.total_bytes();
+        let entries = usage.total_entries();
+        if let Some(message) = exceeded(self.hard_limit_bytes, bytes, "bytes")
+            .or_else(|| exceeded(self.hard_limit_entries, entries, "entries"))
+        {
+            return QuotaStatus::HardLimitExceeded(message);
+        }
+        match exceeded(self.soft_limit_bytes, bytes, "bytes")
+            .or_else(|| exceeded(self.soft_limit_entries, entries, "entries"))
+        {
+            Some(message) => QuotaStatus::SoftLimitExceeded(message),
+            None => QuotaStatus::Ok,
+        }
+    }
+}
+
+/// Storage usage of an instance together with the quota it gets checked against.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub usage: InstanceUsage,
+    pub total_bytes: u64,
+    pub total_entries: u64,
+    pub quota: StorageQuota,
+}
+
+impl UsageReport {
+    pub fn new(usage: InstanceUsage, quota: StorageQuota) -> Self {
+        UsageReport {
+            total_bytes: usage.total_bytes(),
+            total_entries: usage.total_entries(),
+            usage,
+            quota,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_persistence_mem::cas::memory::MemoryStorage;
+
+    fn usage(bytes: u64, count: u64) -> InstanceUsage {
+        InstanceUsage {
+            chain: Usage { bytes, count },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn records_and_releases_usage() {
+        let usage = StorageUsage::default();
+        usage.record(StorageKind::Chain, 10);
+        usage.record(StorageKind::Dht, 20);
+        usage.record(StorageKind::Eav, 5);
+        usage.release(StorageKind::Dht, 20);
+        let snapshot = usage.snapshot();
+        assert_eq!(snapshot.chain, Usage { bytes: 10, count: 1 });
+        assert_eq!(snapshot.dht, Usage::default());
+        assert_eq!(snapshot.total_bytes(), 15);
+        assert_eq!(snapshot.total_entries(), 1);
+    }
+
+    #[test]
+    fn usage_survives_save_and_load() {
+        let cas: Arc<RwLock<dyn ContentAddressableStorage>> =
+            Arc::new(RwLock::new(MemoryStorage::new()));
+        assert!(StorageUsage::load(&cas).unwrap().is_none());
+
+        let usage = StorageUsage::default();
+        usage.record(StorageKind::Chain, 42);
+        usage.save_if_changed(&cas).unwrap();
+        let loaded = StorageUsage::load(&cas).unwrap().unwrap();
+        assert_eq!(loaded.snapshot(), usage.snapshot());
+    }
+
+    #[test]
+    fn bookkeeping_addresses_are_recognized() {
+        for address in &[
+            STORAGE_USAGE_ADDRESS,
+            STORAGE_VERSION_ADDRESS,
+            "AgentState",
+            "StateSnapshot-2",
+        ] {
+            assert!(is_bookkeeping_address(&Address::from(*address)));
+        }
+        assert!(!is_bookkeeping_address(&Address::from("QmStateSnapshot")));
+    }
+
+    #[test]
+    fn quota_checks_hard_before_soft_limits() {
+        let quota = StorageQuota {
+            soft_limit_bytes: Some(100),
+            hard_limit_bytes: Some(200),
+            soft_limit_entries: None,
+            hard_limit_entries: Some(3),
+        };
+        assert_eq!(quota.check(&usage(99, 1)), QuotaStatus::Ok);
+        assert_eq!(
+            quota.check(&usage(100, 1)),
+            QuotaStatus::SoftLimitExceeded("100 of 100 bytes used".to_string())
+        );
+        assert_eq!(
+            quota.check(&usage(200, 1)),
+            QuotaStatus::HardLimitExceeded("200 of 200 bytes used".to_string())
+        );
+        assert_eq!(
+            quota.check(&usage(10, 3)),
+            QuotaStatus::HardLimitExceeded("3 of 3 entries used".to_string())
+        );
+        assert_eq!(
+            StorageQuota::default().check(&usage(1000, 1000)),
+            QuotaStatus::Ok
+        );
+    }
+}
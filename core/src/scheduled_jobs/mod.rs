@@ -1,15 +1,20 @@
 pub mod pending_validations;
+pub mod retention;
 pub mod state_dump;
+pub mod storage_usage;
 
-use crate::context::Context;
+use crate::{context::Context, dht::retention::AuthoredEntries};
 use std::sync::Arc;
 
 pub fn create_callback(context: Arc<Context>) -> impl 'static + FnMut() + Sync + Send {
+    let mut authored = AuthoredEntries::default();
     move || {
         //log_debug!(context, "scheduled_jobs: tick");
         if context.state_dump_logging {
             state_dump::state_dump(context.clone());
         }
         pending_validations::run_pending_validations(context.clone());
+        retention::run_retention(context.clone(), &mut authored);
+        storage_usage::save_storage_usage(context.clone());
     }
 }
@@ -1,7 +1,14 @@
 use crate::{
     context::Context,
     network::entry_with_header::EntryWithHeader,
-    nucleus::actions::remove_pending_validation::remove_pending_validation,
+    nucleus::{
+        actions::{
+            add_pending_validation::count_pending_validation_attempt,
+            dead_letter_validation::dead_letter_validation,
+            remove_pending_validation::remove_pending_validation,
+        },
+        state::PendingValidationKey,
+    },
     workflows::{hold_entry::hold_entry_workflow, hold_link::hold_link_workflow},
 };
 use holochain_core_types::error::HolochainError;
@@ -10,10 +17,17 @@ use crate::workflows::{
     hold_entry_remove::hold_remove_workflow, hold_entry_update::hold_update_workflow,
     remove_link::remove_link_workflow,
 };
+use crossbeam_channel::{unbounded, Sender};
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
-use snowflake::ProcessUniqueId;
-use std::{convert::TryFrom, fmt, sync::Arc, thread};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 
 pub type PendingValidation = Arc<PendingValidationStruct>;
 
@@ -71,46 +85,220 @@ pub struct PendingValidationStruct {
     pub entry_with_header: EntryWithHeader,
     pub dependencies: Vec<Address>,
     pub workflow: ValidatingWorkflow,
+    /// Number of failed attempts to validate it so far
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl PendingValidationStruct {
+    pub fn key(&self) -> PendingValidationKey {
+        PendingValidationKey::new(
+            self.entry_with_header.entry.address(),
+            self.workflow.clone(),
+        )
+    }
+}
+
+/// A pending validation that was retried the maximum number of times without getting
+/// resolved. It does not get retried anymore, unless it gets retried through the
+/// admin interface.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, DefaultJson)]
+pub struct DeadLetterValidation {
+    pub pending: PendingValidation,
+    pub attempts: u32,
+}
+
+/// How pending validations get retried.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationRetryPolicy {
+    /// Number of threads that retry pending validations
+    pub workers: usize,
+    /// Number of failed attempts after which a pending validation gets dead-lettered
+    pub max_attempts: u32,
+    /// Time to wait after the first failed attempt, doubled after every further one
+    pub initial_backoff: Duration,
+    /// Longest time to wait between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for ValidationRetryPolicy {
+    fn default() -> Self {
+        ValidationRetryPolicy {
+            workers: 4,
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+impl ValidationRetryPolicy {
+    /// Time to wait before the next attempt after the given number of failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32
+            .checked_pow(attempts.saturating_sub(1))
+            .unwrap_or_else(u32::max_value);
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+type ValidationJob = (PendingValidation, Arc<Context>);
+
+struct RetryState {
+    next_attempt: Instant,
+}
+
+#[derive(Default)]
+struct WorkerState {
+    queue: Option<Sender<ValidationJob>>,
+    in_flight: HashSet<PendingValidationKey>,
+    retries: HashMap<PendingValidationKey, RetryState>,
+}
+
+/// The threads that retry the pending validations of an instance.
+/// Every pending validation is retried by at most one of them at a time and waits
+/// for an exponentially growing backoff after every failed attempt.
+/// The threads get started with the first retry and stop when this gets dropped.
+pub struct ValidationWorkers {
+    policy: ValidationRetryPolicy,
+    state: Mutex<WorkerState>,
+}
+
+impl Default for ValidationWorkers {
+    fn default() -> Self {
+        ValidationWorkers::new(ValidationRetryPolicy::default())
+    }
+}
+
+impl ValidationWorkers {
+    pub fn new(policy: ValidationRetryPolicy) -> Self {
+        ValidationWorkers {
+            policy,
+            state: Mutex::new(WorkerState::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &ValidationRetryPolicy {
+        &self.policy
+    }
+
+    /// Whether the given pending validation is neither being retried
+    /// nor waiting for its backoff.
+    pub fn is_due(&self, key: &PendingValidationKey) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.in_flight.contains(key)
+            && state
+                .retries
+                .get(key)
+                .map(|retry| retry.next_attempt <= Instant::now())
+                .unwrap_or(true)
+    }
+
+    /// Hands the given pending validation to a worker thread if it is due.
+    fn schedule(&self, pending: PendingValidation, context: Arc<Context>) {
+        let key = pending.key();
+        if !self.is_due(&key) {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.queue.is_none() {
+            state.queue = Some(self.spawn_workers());
+        }
+        state.in_flight.insert(key);
+        let _ = state.queue.as_ref().unwrap().send((pending, context));
+    }
+
+    fn spawn_workers(&self) -> Sender<ValidationJob> {
+        let (sender, receiver) = unbounded::<ValidationJob>();
+        for worker in 0..self.policy.workers.max(1) {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("validation_worker/{}", worker))
+                .spawn(move || {
+                    while let Ok((pending, context)) = receiver.recv() {
+                        retry_validation(pending, context);
+                    }
+                })
+                .expect("Could not spawn thread for validation worker");
+        }
+        sender
+    }
+
+    /// Lets the given pending validation wait for the backoff after its failed attempts.
+    /// The attempts themselves are counted in the pending validation so they survive
+    /// a restart.
+    fn failed(&self, key: &PendingValidationKey, attempts: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight.remove(key);
+        state.retries.insert(
+            key.clone(),
+            RetryState {
+                next_attempt: Instant::now() + self.policy.backoff(attempts),
+            },
+        );
+    }
+
+    /// Forgets the backoff of the given pending validation once it is resolved
+    /// or dead-lettered.
+    fn finished(&self, key: &PendingValidationKey) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight.remove(key);
+        state.retries.remove(key);
+    }
 }
 
 fn retry_validation(pending: PendingValidation, context: Arc<Context>) {
-    thread::Builder::new()
-        .name(format!(
-            "retry_validation/{}",
-            ProcessUniqueId::new().to_string()
-        ))
-        .spawn(move || {
-            let result = match pending.workflow {
-                ValidatingWorkflow::HoldLink => context.block_on(hold_link_workflow(
-                    &pending.entry_with_header,
-                    context.clone(),
-                )),
-                ValidatingWorkflow::HoldEntry => context.block_on(hold_entry_workflow(
-                    &pending.entry_with_header,
-                    context.clone(),
-                )),
-                ValidatingWorkflow::RemoveLink => context.block_on(remove_link_workflow(
-                    &pending.entry_with_header,
-                    context.clone(),
-                )),
-                ValidatingWorkflow::UpdateEntry => context.block_on(hold_update_workflow(
-                    &pending.entry_with_header,
-                    context.clone(),
-                )),
-                ValidatingWorkflow::RemoveEntry => context.block_on(hold_remove_workflow(
-                    &pending.entry_with_header,
-                    context.clone(),
-                )),
-            };
-            if Err(HolochainError::ValidationPending) != result {
-                remove_pending_validation(
-                    pending.entry_with_header.entry.address(),
-                    pending.workflow.clone(),
-                    &context,
-                );
-            }
-        })
-        .expect("Could not spawn thread for retry_validation");
+    let result = match pending.workflow {
+        ValidatingWorkflow::HoldLink => context.block_on(hold_link_workflow(
+            &pending.entry_with_header,
+            context.clone(),
+        )),
+        ValidatingWorkflow::HoldEntry => context.block_on(hold_entry_workflow(
+            &pending.entry_with_header,
+            context.clone(),
+        )),
+        ValidatingWorkflow::RemoveLink => context.block_on(remove_link_workflow(
+            &pending.entry_with_header,
+            context.clone(),
+        )),
+        ValidatingWorkflow::UpdateEntry => context.block_on(hold_update_workflow(
+            &pending.entry_with_header,
+            context.clone(),
+        )),
+        ValidatingWorkflow::RemoveEntry => context.block_on(hold_remove_workflow(
+            &pending.entry_with_header,
+            context.clone(),
+        )),
+    };
+    let workers = context.validation_workers.clone();
+    let key = pending.key();
+    if Err(HolochainError::ValidationPending) != result {
+        workers.finished(&key);
+        remove_pending_validation(key.address, key.workflow, &context);
+        return;
+    }
+    let attempts = pending.attempts + 1;
+    workers.failed(&key, attempts);
+    if attempts < workers.policy().max_attempts {
+        count_pending_validation_attempt(key.address, key.workflow, &context);
+    } else {
+        log_warn!(context,
+            "scheduled_jobs/run_pending_validations: giving up on {} of {} after {} attempts, moving it to the dead letters",
+            pending.workflow,
+            pending.entry_with_header.entry.address(),
+            attempts
+        );
+        workers.finished(&key);
+        // The workflow re-added the pending validation, which this removes again
+        let pending = context
+            .state()
+            .and_then(|state| state.nucleus().pending_validations.get(&key).cloned())
+            .unwrap_or(pending);
+        dead_letter_validation(DeadLetterValidation { pending, attempts }, &context);
+    }
 }
 
 pub fn run_pending_validations(context: Arc<Context>) {
@@ -121,12 +309,79 @@ pub fn run_pending_validations(context: Arc<Context>) {
         .pending_validations
         .clone();
 
-    pending_validations.iter().for_each(|(_, pending)| {
+    pending_validations.iter().for_each(|(key, pending)| {
+        if !context.validation_workers.is_due(key) {
+            return;
+        }
         log_debug!(context,
             "scheduled_jobs/run_pending_validations: found pending validation for {}: {}",
             pending.entry_with_header.entry.entry_type(),
             pending.entry_with_header.entry.address()
         );
-        retry_validation(pending.clone(), context.clone());
+        context
+            .validation_workers
+            .schedule(pending.clone(), context.clone());
     });
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{chain_header::test_chain_header, entry::Entry};
+    use holochain_json_api::json::RawString;
+
+    fn test_key() -> PendingValidationKey {
+        let entry = Entry::App("package_entry".into(), RawString::from("test value").into());
+        PendingValidationKey::new(entry.address(), ValidatingWorkflow::HoldEntry)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_maximum() {
+        let policy = ValidationRetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(5), Duration::from_secs(16));
+        assert_eq!(policy.backoff(9), Duration::from_secs(256));
+        assert_eq!(policy.backoff(10), Duration::from_secs(300));
+        assert_eq!(policy.backoff(100), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn failed_attempts_wait_for_backoff() {
+        let workers = ValidationWorkers::new(ValidationRetryPolicy {
+            initial_backoff: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let key = test_key();
+        assert!(workers.is_due(&key));
+
+        workers.failed(&key, 1);
+        assert!(!workers.is_due(&key));
+        thread::sleep(Duration::from_millis(30));
+        assert!(workers.is_due(&key));
+
+        // A pending validation that is being retried is not due either
+        workers.state.lock().unwrap().in_flight.insert(key.clone());
+        assert!(!workers.is_due(&key));
+        workers.failed(&key, 2);
+        assert!(!workers.is_due(&key));
+
+        workers.finished(&key);
+        assert!(workers.is_due(&key));
+    }
+
+    #[test]
+    fn pending_validation_key_matches_state_key() {
+        let entry = Entry::App("package_entry".into(), RawString::from("test value").into());
+        let pending = PendingValidationStruct {
+            entry_with_header: EntryWithHeader {
+                entry,
+                header: test_chain_header(),
+            },
+            dependencies: Vec::new(),
+            workflow: ValidatingWorkflow::HoldEntry,
+            attempts: 0,
+        };
+        assert_eq!(pending.key(), test_key());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    dht::retention::{location, select_evictions, AuthoredEntries},
+    instance::dispatch_action,
+};
+use chrono::Utc;
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::sync::Arc;
+
+/// Evicts the held entries that the instance's retention policy does not retain anymore.
+/// `authored` carries the entries of the instance's own chain over from the previous run.
+pub fn run_retention(context: Arc<Context>, authored: &mut AuthoredEntries) {
+    if !context.retention_policy.is_enabled() {
+        return;
+    }
+    let maybe_evictions = match context.state() {
+        Some(state) => {
+            authored.update(state.agent().iter_chain());
+            select_evictions(
+                &state.dht(),
+                authored.addresses(),
+                &context.retention_policy,
+                location(&context.agent_id().address()),
+                Utc::now().timestamp(),
+            )
+        }
+        None => return,
+    };
+
+    match maybe_evictions {
+        Ok(evictions) => {
+            for (address, reason) in evictions {
+                log_debug!(
+                    context,
+                    "scheduled_jobs/run_retention: evicting {} ({:?})",
+                    address,
+                    reason
+                );
+                dispatch_action(
+                    context.action_channel(),
+                    ActionWrapper::new(Action::EvictEntry(address)),
+                );
+            }
+        }
+        Err(err) => log_error!(
+            context,
+            "scheduled_jobs/run_retention: could not select entries to evict: {}",
+            err
+        ),
+    }
+}
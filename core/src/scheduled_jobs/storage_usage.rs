@@ -0,0 +1,16 @@
+use crate::context::Context;
+use std::sync::Arc;
+
+/// Persists the storage usage of the instance if it changed since the last run.
+pub fn save_storage_usage(context: Arc<Context>) {
+    if let Err(err) = context
+        .storage_usage
+        .save_if_changed(&context.chain_storage)
+    {
+        log_error!(
+            context,
+            "scheduled_jobs/save_storage_usage: could not save storage usage: {}",
+            err
+        );
+    }
+}
@@ -59,10 +59,16 @@ impl State {
         State {
             nucleus: Arc::new(NucleusState::new()),
             agent: Arc::new(AgentState::new(
-                ChainStore::new(chain_cas.clone()),
-                context.agent_id.address(),
+                ChainStore::new_with_index(
+                    chain_cas.clone(),
+                    context.chain_index_storage.clone(),
+                ),
+                context.agent_id().address(),
             )),
-            dht: Arc::new(DhtStore::new(dht_cas.clone(), eav)),
+            dht: Arc::new(
+                DhtStore::new(dht_cas.clone(), eav)
+                    .with_eviction_storage(context.eviction_storage.clone()),
+            ),
             network: Arc::new(NetworkState::new()),
             history: HashSet::new(),
             conductor_api: context.conductor_api.clone(),
@@ -81,7 +87,8 @@ impl State {
         let cas = context.dht_storage.clone();
         let eav = context.eav_storage.clone();
 
-        let dht_store = DhtStore::new(cas.clone(), eav.clone());
+        let dht_store = DhtStore::new(cas.clone(), eav.clone())
+            .with_eviction_storage(context.eviction_storage.clone());
         Self::new_with_agent_nucleus_dht(context, agent_state, nucleus_state, dht_store)
     }
 
@@ -172,16 +179,20 @@ impl State {
         dht_store_snapshot: DhtStoreSnapshot,
     ) -> HcResult<State> {
         let agent_state = AgentState::new_with_top_chain_header(
-            ChainStore::new(context.chain_storage.clone()),
+            ChainStore::new_with_index(
+                context.chain_storage.clone(),
+                context.chain_index_storage.clone(),
+            ),
             agent_snapshot.top_chain_header().map(|h| h.to_owned()),
-            context.agent_id.address(),
+            context.agent_id().address(),
         );
         let nucleus_state = NucleusState::from(nucleus_snapshot);
         let dht_store = DhtStore::new_with_holding_list(
             context.dht_storage.clone(),
             context.eav_storage.clone(),
             dht_store_snapshot.holding_list,
-        );
+        )
+        .with_eviction_storage(context.eviction_storage.clone());
         Ok(State::new_with_agent_nucleus_dht(
             context.clone(),
             agent_state,
@@ -9,6 +9,7 @@ use holochain_core_types::{
     error::{HcResult, HolochainError},
 };
 
+use holochain_persistence_api::cas::content::AddressableContent;
 use std::sync::Arc;
 
 pub async fn initialize(
@@ -27,6 +28,14 @@ pub async fn initialize(
         await!(initialize_chain(dna.clone(), &instance_context))?;
     }
     await!(initialize_network(&instance_context))?;
+    if let Some(dna) = instance_context.get_dna() {
+        if let Err(err) = instance_context
+            .validation_cache()
+            .purge_other_dnas(&dna.address())
+        {
+            log_warn!(context, "dna/initialize: Could not purge the validation cache: {:?}", err);
+        }
+    }
     await!(call_init(dna, &instance_context))?;
     Ok(instance_context)
 }
@@ -0,0 +1,128 @@
+use crate::{
+    agent::{
+        actions::bundle::{commit_bundle, discard_bundle},
+        bundle::BundleId,
+    },
+    context::Context,
+    entry::CanPublish,
+    network::actions::publish::publish,
+    nucleus::{
+        actions::build_validation_package::build_bundle_validation_package,
+        ribosome::callback::links_utils::get_link_entries,
+        validation::validate_entry_in_bundle,
+    },
+};
+
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::Entry,
+    error::HolochainError,
+    validation::{EntryLifecycle, ValidationData},
+};
+
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+
+use crate::agent::bundle::CommitBundle;
+use std::{sync::Arc, vec::Vec};
+
+/// Validates all entries of the bundle with the given id in the order they got staged in.
+/// Links can refer to entries staged in the same bundle, and the validation package of
+/// every entry sees the entries staged before it as part of the chain.
+async fn validate_bundle<'a>(
+    id: BundleId,
+    bundle: &'a CommitBundle,
+    context: &'a Arc<Context>,
+) -> Result<(), HolochainError> {
+    let mut preceding: Vec<(ChainHeader, Entry)> = Vec::new();
+    for staged in bundle.entries() {
+        let entry = &staged.entry;
+        log_debug!(context,
+            "workflow/author_bundle/{}: validating...",
+            entry.address()
+        );
+        if let Entry::LinkAdd(link_data) = entry {
+            get_link_entries(&link_data.link, Some(&id), context)?;
+        }
+        if let Entry::LinkRemove((link_data, _)) = entry {
+            get_link_entries(&link_data.link, Some(&id), context)?;
+        }
+        let validation_package = await!(build_bundle_validation_package(
+            entry,
+            context.clone(),
+            &staged.provenances,
+            &preceding
+        ))?;
+        let chain_header = validation_package.chain_header.clone();
+        let validation_data = ValidationData {
+            package: validation_package,
+            lifecycle: EntryLifecycle::Chain,
+        };
+        await!(validate_entry_in_bundle(
+            entry.clone(),
+            staged.maybe_link_update_delete.clone(),
+            validation_data,
+            Some(id.clone()),
+            context
+        ))?;
+        preceding.push((chain_header, entry.clone()));
+    }
+    Ok(())
+}
+
+/// Closes the commit bundle of the zome call with the given id by authoring all entries
+/// staged in it: if all of them are valid, they get committed together and published.
+/// Otherwise, or if the bundle timed out, nothing gets committed and the bundle is discarded.
+pub async fn author_bundle<'a>(
+    id: BundleId,
+    context: &'a Arc<Context>,
+) -> Result<Vec<Address>, HolochainError> {
+    let bundle = context
+        .state()
+        .ok_or_else(|| HolochainError::new("Could not get state"))?
+        .agent()
+        .bundle(&id)
+        .ok_or_else(|| HolochainError::new("No open bundle to close"))?;
+    log_debug!(context,
+        "workflow/author_bundle: closing bundle with {} entries, user param: {:?}",
+        bundle.entries().len(), bundle.user_param()
+    );
+
+    if bundle.is_expired() {
+        log_warn!(context, "workflow/author_bundle: bundle timed out, discarding it");
+        discard_bundle(id, context);
+        return Err(HolochainError::Timeout(None));
+    }
+
+    // Refuse to grow the chain of an instance that is out of storage
+    let checked = match context.check_storage_quota() {
+        Ok(()) => await!(validate_bundle(id.clone(), &bundle, context)),
+        Err(error) => Err(error),
+    };
+    if let Err(error) = checked {
+        log_debug!(context,
+            "workflow/author_bundle: discarding bundle: {}",
+            error
+        );
+        discard_bundle(id, context);
+        return Err(error);
+    }
+    log_debug!(context, "workflow/author_bundle: all entries are valid");
+
+    let addresses = await!(commit_bundle(id, context))?;
+    log_debug!(context, "workflow/author_bundle: committed");
+
+    // The entries are committed now, so failing to publish one of them must neither
+    // fail the bundle nor keep the others from getting published
+    for staged in bundle.entries() {
+        if staged.entry.entry_type().can_publish(context) {
+            if let Err(error) = await!(publish(staged.entry.address(), context)) {
+                log_warn!(context,
+                    "workflow/author_bundle: could not publish {}: {}",
+                    staged.entry.address(), error
+                );
+            }
+        }
+    }
+    log_debug!(context, "workflow/author_bundle: published");
+    Ok(addresses)
+}
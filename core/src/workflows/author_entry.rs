@@ -34,12 +34,47 @@ pub async fn author_entry<'a>(
         address, entry
     );
 
+    await!(validate_authored_entry(
+        entry,
+        maybe_link_update_delete.clone(),
+        context,
+        provenances
+    ))?;
+
+    // 3. Commit the entry
+    log_debug!(context,
+        "workflow/authoring_entry/{}: committing...",
+        address
+    );
+    let addr = await!(commit_entry(
+        entry.clone(),
+        maybe_link_update_delete,
+        &context
+    ))?;
+    log_debug!(context, "workflow/authoring_entry/{}: committed", address);
+
+    await!(publish_authored_entry(entry, context))?;
+    Ok(CommitEntryResult::new(addr))
+}
+
+/// Steps 0 to 2 of authoring an entry, which have to succeed before it may get committed.
+pub async fn validate_authored_entry<'a>(
+    entry: &'a Entry,
+    maybe_link_update_delete: Option<Address>,
+    context: &'a Arc<Context>,
+    provenances: &'a Vec<Provenance>,
+) -> Result<(), HolochainError> {
+    let address = entry.address();
+
+    // Refuse to grow the chain of an instance that is out of storage
+    context.check_storage_quota()?;
+
     // 0. If we are trying to author a link or link removal, make sure the linked entries exist:
     if let Entry::LinkAdd(link_data) = entry {
-        get_link_entries(&link_data.link, context)?;
+        get_link_entries(&link_data.link, None, context)?;
     }
     if let Entry::LinkRemove((link_data, _)) = entry {
-        get_link_entries(&link_data.link, context)?;
+        get_link_entries(&link_data.link, None, context)?;
     }
 
     // 1. Build the context needed for validation of the entry
@@ -60,24 +95,20 @@ pub async fn author_entry<'a>(
     );
     await!(validate_entry(
         entry.clone(),
-        maybe_link_update_delete.clone(),
+        maybe_link_update_delete,
         validation_data,
         &context
     ))?;
     log_debug!(context, "worflow/authoring_entry {}: is valid!", address);
+    Ok(())
+}
 
-    // 3. Commit the entry
-    log_debug!(context,
-        "workflow/authoring_entry/{}: committing...",
-        address
-    );
-    let addr = await!(commit_entry(
-        entry.clone(),
-        maybe_link_update_delete,
-        &context
-    ))?;
-    log_debug!(context, "workflow/authoring_entry/{}: committed", address);
-
+/// Step 4 of authoring an entry, once it is committed.
+pub async fn publish_authored_entry<'a>(
+    entry: &'a Entry,
+    context: &'a Arc<Context>,
+) -> Result<(), HolochainError> {
+    let address = entry.address();
     // 4. Publish the valid entry to DHT. This will call Hold to itself
     if entry.entry_type().can_publish(context) {
         log_debug!(context,
@@ -95,7 +126,7 @@ pub async fn author_entry<'a>(
           address
         );
     }
-    Ok(CommitEntryResult::new(addr))
+    Ok(())
 }
 
 #[cfg(test)]
@@ -0,0 +1,43 @@
+use crate::{context::Context, network::validation_receipt::ValidationReceipt};
+use holochain_core_types::error::{HcResult, HolochainError};
+use holochain_persistence_api::cas::content::Address;
+use std::sync::Arc;
+
+/// Gets called when a validator sends us a receipt for one of the aspects we published.
+/// Stores the receipt if it is signed by the agent that sent it and if it is about an
+/// entry in our source chain.
+pub fn handle_validation_receipt(
+    from: Address,
+    receipt: ValidationReceipt,
+    context: &Arc<Context>,
+) -> HcResult<()> {
+    if receipt.validator() != from {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Validation receipt of {} was sent by {}",
+            receipt.validator(),
+            from
+        )));
+    }
+    receipt.verify()?;
+
+    let state = context
+        .state()
+        .ok_or_else(|| HolochainError::ErrorGeneric("State not initialized".to_string()))?;
+    let agent = state.agent();
+    if !agent
+        .chain_store()
+        .contains_entry(&agent.top_chain_header(), &receipt.entry_address)
+    {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Validation receipt for entry {} which is not in our chain",
+            receipt.entry_address
+        )));
+    }
+
+    context.receipt_store().add(&receipt)?;
+    log_debug!(context, "workflow/handle_validation_receipt: Stored receipt of {} for {}",
+        from,
+        receipt.entry_address,
+    );
+    Ok(())
+}
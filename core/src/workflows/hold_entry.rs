@@ -1,7 +1,10 @@
 use crate::{
     context::Context,
     dht::actions::hold::hold_entry,
-    network::entry_with_header::EntryWithHeader,
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader,
+        validation_receipt::ValidationReceiptResult,
+    },
     nucleus::{
         actions::add_pending_validation::add_pending_validation, validation::validate_entry,
     },
@@ -9,7 +12,10 @@ use crate::{
 
 use crate::{
     nucleus::validation::ValidationError, scheduled_jobs::pending_validations::ValidatingWorkflow,
-    workflows::validation_package,
+    workflows::{
+        issue_warrant::issue_warrant, send_validation_receipt::send_validation_receipt,
+        validation_package,
+    },
 };
 use holochain_core_types::{
     error::HolochainError,
@@ -24,6 +30,9 @@ pub async fn hold_entry_workflow(
     entry_with_header: &EntryWithHeader,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
+    // 0. Refuse to hold anything more once the instance is out of storage
+    context.check_storage_quota()?;
+
     // 1. Get hold of validation package
     let maybe_validation_package = await!(validation_package(&entry_with_header, context.clone()))
         .map_err(|err| {
@@ -83,6 +92,7 @@ pub async fn hold_entry_workflow(
                 entry_with_header.entry.address(),
                 err,
             );
+            issue_warrant(entry_with_header, &err, &context);
             HolochainError::from(err)
         }
     })?;
@@ -100,6 +110,16 @@ pub async fn hold_entry_workflow(
         entry_with_header.entry.address()
     );
 
+    // 4. Let the author know
+    send_validation_receipt(
+        EntryAspect::Content(
+            entry_with_header.entry.clone(),
+            entry_with_header.header.clone(),
+        ),
+        ValidationReceiptResult::Valid,
+        &context,
+    );
+
     Ok(())
 }
 
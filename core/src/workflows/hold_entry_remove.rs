@@ -1,6 +1,11 @@
 use crate::{
-    context::Context, dht::actions::remove_entry::remove_entry,
-    network::entry_with_header::EntryWithHeader, nucleus::validation::validate_entry,
+    context::Context,
+    dht::actions::remove_entry::remove_entry,
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader,
+        validation_receipt::ValidationReceiptResult,
+    },
+    nucleus::validation::validate_entry,
 };
 
 use crate::{
@@ -8,7 +13,10 @@ use crate::{
         actions::add_pending_validation::add_pending_validation, validation::ValidationError,
     },
     scheduled_jobs::pending_validations::ValidatingWorkflow,
-    workflows::validation_package,
+    workflows::{
+        issue_warrant::issue_warrant, send_validation_receipt::send_validation_receipt,
+        validation_package,
+    },
 };
 use holochain_core_types::{
     entry::Entry,
@@ -22,6 +30,9 @@ pub async fn hold_remove_workflow(
     entry_with_header: &EntryWithHeader,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
+    // 0. Refuse to hold anything more once the instance is out of storage
+    context.check_storage_quota()?;
+
     // 1. Get hold of validation package
     let maybe_validation_package = await!(validation_package(entry_with_header, context.clone()))
         .map_err(|err| {
@@ -67,6 +78,7 @@ pub async fn hold_remove_workflow(
                 entry_with_header.entry,
                 err,
             );
+            issue_warrant(entry_with_header, &err, &context);
             HolochainError::from(err)
         }
 
@@ -80,5 +92,12 @@ pub async fn hold_remove_workflow(
         &context.clone(),
         deleted_entry_address,
         entry_with_header.entry.address().clone(),
-    ))
+    ))?;
+
+    send_validation_receipt(
+        EntryAspect::Deletion(entry_with_header.header.clone()),
+        ValidationReceiptResult::Valid,
+        &context,
+    );
+    Ok(())
 }
@@ -1,6 +1,11 @@
 use crate::{
-    context::Context, dht::actions::update_entry::update_entry,
-    network::entry_with_header::EntryWithHeader, nucleus::validation::validate_entry,
+    context::Context,
+    dht::actions::update_entry::update_entry,
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader,
+        validation_receipt::ValidationReceiptResult,
+    },
+    nucleus::validation::validate_entry,
 };
 use holochain_persistence_api::cas::content::AddressableContent;
 
@@ -9,7 +14,10 @@ use crate::{
         actions::add_pending_validation::add_pending_validation, validation::ValidationError,
     },
     scheduled_jobs::pending_validations::ValidatingWorkflow,
-    workflows::validation_package,
+    workflows::{
+        issue_warrant::issue_warrant, send_validation_receipt::send_validation_receipt,
+        validation_package,
+    },
 };
 use holochain_core_types::{
     error::HolochainError,
@@ -23,6 +31,9 @@ pub async fn hold_update_workflow(
 ) -> Result<(), HolochainError> {
     let EntryWithHeader { entry, header } = entry_with_header;
 
+    // 0. Refuse to hold anything more once the instance is out of storage
+    context.check_storage_quota()?;
+
     // 1. Get hold of validation package
     let maybe_validation_package = await!(validation_package(&entry_with_header, context.clone()))
         .map_err(|err| {
@@ -73,6 +84,7 @@ pub async fn hold_update_workflow(
                 entry_with_header.entry,
                 err,
             );
+            issue_warrant(entry_with_header, &err, &context);
             HolochainError::from(err)
         }
 
@@ -85,5 +97,10 @@ pub async fn hold_update_workflow(
         entry.address().clone()
     ))?;
 
+    send_validation_receipt(
+        EntryAspect::Update(entry.clone(), entry_with_header.header.clone()),
+        ValidationReceiptResult::Valid,
+        &context,
+    );
     Ok(())
 }
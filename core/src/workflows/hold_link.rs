@@ -1,6 +1,11 @@
 use crate::{
-    context::Context, dht::actions::add_link::add_link,
-    network::entry_with_header::EntryWithHeader, nucleus::validation::validate_entry,
+    context::Context,
+    dht::actions::add_link::add_link,
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader,
+        validation_receipt::ValidationReceiptResult,
+    },
+    nucleus::validation::validate_entry,
 };
 
 use crate::{
@@ -8,7 +13,10 @@ use crate::{
         actions::add_pending_validation::add_pending_validation, validation::ValidationError,
     },
     scheduled_jobs::pending_validations::ValidatingWorkflow,
-    workflows::{hold_entry::hold_entry_workflow, validation_package},
+    workflows::{
+        hold_entry::hold_entry_workflow, issue_warrant::issue_warrant,
+        send_validation_receipt::send_validation_receipt, validation_package,
+    },
 };
 use holochain_core_types::{
     entry::Entry,
@@ -30,6 +38,9 @@ pub async fn hold_link_workflow(
     let link = link_add.link().clone();
 
     log_debug!(context, "workflow/hold_link: {:?}", link);
+    // 0. Refuse to hold anything more once the instance is out of storage
+    context.check_storage_quota()?;
+
     log_debug!(context, "workflow/hold_link: getting validation package...");
     // 1. Get hold of validation package
     let maybe_validation_package = await!(validation_package(&entry_with_header, context.clone()))
@@ -87,6 +98,7 @@ pub async fn hold_link_workflow(
                 entry_with_header.entry,
                 err,
             );
+            issue_warrant(entry_with_header, &err, &context);
             HolochainError::from(err)
         }
 
@@ -101,7 +113,12 @@ pub async fn hold_link_workflow(
     await!(hold_entry_workflow(&entry_with_header, context.clone()))?;
     log_debug!(context, "workflow/hold_entry: added! {:?}", entry_with_header);
 
-    //5. Link has been added to EAV and LinkAdd Entry has been stored on the dht
+    //5. Link has been added to EAV and LinkAdd Entry has been stored on the dht, let the author know
+    send_validation_receipt(
+        EntryAspect::LinkAdd(link_add.clone(), entry_with_header.header.clone()),
+        ValidationReceiptResult::Valid,
+        &context,
+    );
     Ok(())
 }
 
@@ -0,0 +1,94 @@
+use crate::{
+    context::Context,
+    dht::actions::hold_warrant::hold_warrant,
+    network::warrant::Warrant,
+    nucleus::validation::{validate_entry, ValidationError},
+    workflows::validation_package,
+};
+use holochain_core_types::{
+    entry::entry_type::EntryType,
+    error::HolochainError,
+    validation::{EntryLifecycle, ValidationData},
+};
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::sync::Arc;
+
+/// The network asks us to hold a warrant against the author of an invalid entry.
+/// We only hold warrants that are signed correctly and of which we can confirm
+/// that the warranted entry is invalid by validating it ourselves.
+pub async fn hold_warrant_workflow(
+    warrant: &Warrant,
+    context: Arc<Context>,
+) -> Result<(), HolochainError> {
+    // 1. Check the signatures of the author and the validator
+    warrant.verify()?;
+    let author = warrant
+        .author()
+        .ok_or_else(|| HolochainError::ErrorGeneric("Warrant has no author".to_string()))?;
+    if context.state()?.dht().get_warrants(&author)?.contains(warrant) {
+        log_debug!(context, "workflow/hold_warrant: already holding warrant {}", warrant.address());
+        return Ok(());
+    }
+
+    // 2. Get hold of validation package
+    let entry_with_header = warrant.entry_with_header();
+    let validation_package = await!(validation_package(&entry_with_header, context.clone()))?
+        .ok_or_else(|| {
+            HolochainError::ErrorGeneric(
+                "Could not get validation package of warranted entry".to_string(),
+            )
+        })?;
+
+    // 3. Create validation data struct
+    let (link, lifecycle) = match entry_with_header.entry.entry_type() {
+        EntryType::LinkAdd | EntryType::LinkRemove | EntryType::Deletion => {
+            (None, EntryLifecycle::Meta)
+        }
+        _ => match entry_with_header.header.link_update_delete() {
+            Some(link) => (Some(link), EntryLifecycle::Meta),
+            None => (None, EntryLifecycle::Dht),
+        },
+    };
+    let validation_data = ValidationData {
+        package: validation_package,
+        lifecycle,
+    };
+
+    // 4. Validate the entry, only a failing validation confirms the warrant
+    match await!(validate_entry(
+        entry_with_header.entry.clone(),
+        link,
+        validation_data,
+        &context
+    )) {
+        Err(ValidationError::Fail(reason)) => {
+            log_debug!(context, "workflow/hold_warrant: confirmed warrant {}, entry {} is NOT valid: {}",
+                warrant.address(),
+                entry_with_header.entry.address(),
+                reason,
+            );
+        }
+        Ok(()) => {
+            log_warn!(context, "workflow/hold_warrant: Entry {} of warrant {} is valid! Ignoring warrant.",
+                entry_with_header.entry.address(),
+                warrant.address(),
+            );
+            return Err(HolochainError::ErrorGeneric(
+                "Warranted entry is valid".to_string(),
+            ));
+        }
+        Err(err) => {
+            log_debug!(context, "workflow/hold_warrant: Could not confirm warrant {}: {:?}",
+                warrant.address(),
+                err,
+            );
+            return Err(HolochainError::from(err));
+        }
+    }
+
+    // 5. Hold the warrant and reject the author's entries in the local DHT shard
+    await!(hold_warrant(warrant, &context))?;
+    log_debug!(context, "workflow/hold_warrant: HOLDING: {}", warrant.address());
+
+    Ok(())
+}
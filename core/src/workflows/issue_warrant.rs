@@ -0,0 +1,44 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    instance::dispatch_action,
+    network::{entry_with_header::EntryWithHeader, warrant::Warrant},
+    nucleus::validation::ValidationError,
+};
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::sync::Arc;
+
+/// Gets called by the hold workflows when the validation of an entry failed.
+/// Creates a warrant against the author of the entry, holds it in the local DHT shard
+/// and publishes it to the author's neighborhood.
+/// Does nothing for validation errors that don't show the entry to be invalid.
+pub fn issue_warrant(
+    entry_with_header: &EntryWithHeader,
+    error: &ValidationError,
+    context: &Arc<Context>,
+) {
+    let reason = match error {
+        ValidationError::Fail(reason) => reason.clone(),
+        _ => return,
+    };
+    match Warrant::create(entry_with_header, reason, context) {
+        Ok(warrant) => {
+            log_info!(context, "workflow/issue_warrant: Issuing warrant against {:?} for invalid entry {}",
+                warrant.author(),
+                entry_with_header.entry.address(),
+            );
+            dispatch_action(
+                context.action_channel(),
+                ActionWrapper::new(Action::HoldWarrant(warrant.clone())),
+            );
+            dispatch_action(
+                context.action_channel(),
+                ActionWrapper::new(Action::PublishWarrant(warrant)),
+            );
+        }
+        Err(err) => log_error!(context, "workflow/issue_warrant: Could not create warrant for invalid entry {}: {:?}",
+            entry_with_header.entry.address(),
+            err,
+        ),
+    }
+}
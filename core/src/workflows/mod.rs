@@ -1,15 +1,20 @@
 pub mod application;
+pub mod author_bundle;
 pub mod author_entry;
 pub mod get_entry_result;
 pub mod get_link_result;
 pub mod get_links_count;
 pub mod handle_custom_direct_message;
+pub mod handle_validation_receipt;
 pub mod hold_entry;
 pub mod hold_entry_remove;
 pub mod hold_entry_update;
 pub mod hold_link;
+pub mod hold_warrant;
+pub mod issue_warrant;
 pub mod remove_link;
 pub mod respond_validation_package_request;
+pub mod send_validation_receipt;
 
 use crate::{
     context::Context,
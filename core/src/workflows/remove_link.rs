@@ -1,6 +1,11 @@
 use crate::{
-    context::Context, dht::actions::remove_link::remove_link,
-    network::entry_with_header::EntryWithHeader, nucleus::validation::validate_entry,
+    context::Context,
+    dht::actions::remove_link::remove_link,
+    network::{
+        entry_aspect::EntryAspect, entry_with_header::EntryWithHeader,
+        validation_receipt::ValidationReceiptResult,
+    },
+    nucleus::validation::validate_entry,
     workflows::hold_entry::hold_entry_workflow,
 };
 
@@ -9,7 +14,10 @@ use crate::{
         actions::add_pending_validation::add_pending_validation, validation::ValidationError,
     },
     scheduled_jobs::pending_validations::ValidatingWorkflow,
-    workflows::validation_package,
+    workflows::{
+        issue_warrant::issue_warrant, send_validation_receipt::send_validation_receipt,
+        validation_package,
+    },
 };
 use holochain_core_types::{
     entry::Entry,
@@ -31,6 +39,9 @@ pub async fn remove_link_workflow(
     let link = link_remove.link().clone();
 
     log_debug!(context, "workflow/remove_link: {:?}", link);
+    // 0. Refuse to hold anything more once the instance is out of storage
+    context.check_storage_quota()?;
+
     // 1. Get hold of validation package
     log_debug!(context,
         "workflow/remove_link: getting validation package..."
@@ -82,6 +93,7 @@ pub async fn remove_link_workflow(
                 entry_with_header.entry,
                 err,
             );
+            issue_warrant(entry_with_header, &err, &context);
             HolochainError::from(err)
         }
 
@@ -97,5 +109,12 @@ pub async fn remove_link_workflow(
     await!(hold_entry_workflow(&entry_with_header, context.clone()))?;
     log_debug!(context, "workflow/hold_entry: added! {:?}", entry_with_header);
 
+    if let Entry::LinkRemove(link_remove) = &entry_with_header.entry {
+        send_validation_receipt(
+            EntryAspect::LinkRemove(link_remove.clone(), entry_with_header.header.clone()),
+            ValidationReceiptResult::Valid,
+            &context,
+        );
+    }
     Ok(())
 }
@@ -0,0 +1,62 @@
+use crate::{
+    action::{Action, ActionWrapper, DirectMessageData},
+    context::Context,
+    instance::dispatch_action,
+    network::{
+        direct_message::DirectMessage,
+        entry_aspect::EntryAspect,
+        validation_receipt::{ValidationReceipt, ValidationReceiptResult},
+    },
+};
+use holochain_persistence_api::cas::content::AddressableContent;
+use snowflake::ProcessUniqueId;
+use std::sync::Arc;
+
+/// Gets called by the hold workflows once they have validated an aspect.
+/// Sends a signed receipt with the result to the author of the aspect, without waiting
+/// for a response. Does nothing for aspects we authored ourselves.
+pub fn send_validation_receipt(
+    aspect: EntryAspect,
+    result: ValidationReceiptResult,
+    context: &Arc<Context>,
+) {
+    let author = match aspect
+        .header()
+        .provenances()
+        .last()
+        .map(|provenance| provenance.source())
+    {
+        Some(author) => author,
+        None => return,
+    };
+    if author == context.agent_id().address() {
+        return;
+    }
+
+    let receipt = match ValidationReceipt::create(&aspect, result, context) {
+        Ok(receipt) => receipt,
+        Err(err) => {
+            log_error!(context, "workflow/send_validation_receipt: Could not create receipt for {:?}: {:?}",
+                aspect,
+                err,
+            );
+            return;
+        }
+    };
+
+    let id = ProcessUniqueId::new().to_string();
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::SendDirectMessage(DirectMessageData {
+            address: author,
+            message: DirectMessage::ValidationReceipt(receipt),
+            msg_id: id.clone(),
+            is_response: false,
+        })),
+    );
+    // No response is expected, so we don't keep the connection around
+    dispatch_action(
+        context.action_channel(),
+        ActionWrapper::new(Action::ResolveDirectConnection(id)),
+    );
+}
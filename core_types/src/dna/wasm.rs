@@ -10,6 +10,7 @@ use serde::{
     ser::Serializer,
 };
 use std::{
+    collections::HashMap,
     fmt,
     hash::{Hash, Hasher},
     ops::Deref,
@@ -19,11 +20,21 @@ use wasmi::Module;
 
 /// Wrapper around wasmi::Module since it does not implement Clone, Debug, PartialEq, Eq,
 /// which are all needed to add it to the DnaWasm below, and hence to the state.
+/// Also holds the names of the module's functions, if the binary came with a name section.
 #[derive(Clone)]
-pub struct ModuleArc(Arc<Module>);
+pub struct ModuleArc(Arc<Module>, Arc<HashMap<u32, String>>);
 impl ModuleArc {
     pub fn new(module: Module) -> Self {
-        ModuleArc(Arc::new(module))
+        ModuleArc(Arc::new(module), Arc::new(HashMap::new()))
+    }
+
+    pub fn with_function_names(module: Module, function_names: HashMap<u32, String>) -> Self {
+        ModuleArc(Arc::new(module), Arc::new(function_names))
+    }
+
+    /// Names of functions by their index in the binary the module got created from.
+    pub fn function_names(&self) -> &HashMap<u32, String> {
+        &self.1
     }
 }
 impl PartialEq for ModuleArc {
@@ -150,19 +161,24 @@ impl DnaWasm {
 
     /// This returns a parsed WASMi representation of the code, ready to be
     /// run in a WASMi ModuleInstance.
-    /// The first call will create the module from the binary.
-    pub fn get_wasm_module(&self) -> Result<ModuleArc, HolochainError> {
+    /// The first call will create the module from the binary with the given factory,
+    /// which lets the caller instrument the code before it gets cached.
+    pub fn get_wasm_module<F>(&self, factory: F) -> Result<ModuleArc, HolochainError>
+    where
+        F: FnOnce(Arc<Vec<u8>>) -> Result<ModuleArc, HolochainError>,
+    {
         if self.module.read().unwrap().is_none() {
-            self.create_module()?;
+            self.create_module(factory)?;
         }
 
         Ok(self.module.read().unwrap().as_ref().unwrap().clone())
     }
 
-    fn create_module(&self) -> Result<(), HolochainError> {
-        let module = wasmi::Module::from_buffer(&*self.code)
-            .map_err(|e| HolochainError::ErrorGeneric(e.into()))?;
-        let module_arc = ModuleArc::new(module);
+    fn create_module<F>(&self, factory: F) -> Result<(), HolochainError>
+    where
+        F: FnOnce(Arc<Vec<u8>>) -> Result<ModuleArc, HolochainError>,
+    {
+        let module_arc = factory(self.code.clone())?;
         let mut lock = self.module.write().unwrap();
         *lock = Some(module_arc);
         Ok(())
@@ -45,6 +45,18 @@ pub enum Attribute {
     RemovedLink(String, String),
     PendingEntry,
     Target,
+    /// Source chain index row: chain root header address and entry type name.
+    ChainIndex(String, String),
+    /// Points from an agent address to a warrant against that agent.
+    Warrant,
+    /// Points from an agent address to a held entry that agent authored.
+    AuthoredEntry,
+    /// Points from the address of an entry we authored to a validation receipt for it.
+    ValidationReceipt,
+    /// Points from the address of a validation cache key to the cached validation result.
+    ValidationResult,
+    /// Stamps EAV storage with the version of its format.
+    StorageVersion,
 }
 
 impl Default for Attribute {
@@ -84,6 +96,14 @@ impl fmt::Display for Attribute {
             }
             Attribute::PendingEntry => write!(f, "pending-entry"),
             Attribute::Target => write!(f, "target"),
+            Attribute::ChainIndex(root, entry_type) => {
+                write!(f, "chain_index__{}__{}", root, entry_type)
+            }
+            Attribute::Warrant => write!(f, "warrant"),
+            Attribute::AuthoredEntry => write!(f, "authored_entry"),
+            Attribute::ValidationReceipt => write!(f, "validation_receipt"),
+            Attribute::ValidationResult => write!(f, "validation_result"),
+            Attribute::StorageVersion => write!(f, "storage_version"),
         }
     }
 }
@@ -93,6 +113,8 @@ lazy_static! {
         Regex::new(r"^link__(.*)__(.*)$").expect("This string literal is a valid regex");
     static ref REMOVED_LINK_REGEX: Regex =
         Regex::new(r"^removed_link__(.*)__(.*)$").expect("This string literal is a valid regex");
+    static ref CHAIN_INDEX_REGEX: Regex =
+        Regex::new(r"^chain_index__(.*?)__(.*)$").expect("This string literal is a valid regex");
 }
 
 impl TryFrom<&str> for Attribute {
@@ -108,6 +130,10 @@ impl TryFrom<&str> for Attribute {
             let link_type = REMOVED_LINK_REGEX.captures(s)?.get(1)?.as_str().to_string();
             let link_tag = REMOVED_LINK_REGEX.captures(s)?.get(2)?.as_str().to_string();
             Ok(RemovedLink(link_type, link_tag))
+        } else if CHAIN_INDEX_REGEX.is_match(s) {
+            let root = CHAIN_INDEX_REGEX.captures(s)?.get(1)?.as_str().to_string();
+            let entry_type = CHAIN_INDEX_REGEX.captures(s)?.get(2)?.as_str().to_string();
+            Ok(ChainIndex(root, entry_type))
         } else {
             match s {
                 "crud-status" => Ok(CrudStatus),
@@ -117,6 +143,11 @@ impl TryFrom<&str> for Attribute {
                 "link_remove" => Ok(LinkRemove),
                 "pending-entry" => Ok(PendingEntry),
                 "target" => Ok(Target),
+                "warrant" => Ok(Warrant),
+                "authored_entry" => Ok(AuthoredEntry),
+                "validation_receipt" => Ok(ValidationReceipt),
+                "validation_result" => Ok(ValidationResult),
+                "storage_version" => Ok(StorageVersion),
                 a => Err(AttributeError::Unrecognized(a.to_string())),
             }
         }
@@ -376,6 +407,21 @@ pub mod tests {
             "link__sometype__tagalog".try_into(),
             Ok(Attribute::LinkTag("sometype".into(), "tagalog".into()))
         );
+        assert_eq!(
+            "chain_index__QmRoot__fizz/qux".try_into(),
+            Ok(Attribute::ChainIndex("QmRoot".into(), "fizz/qux".into()))
+        );
+        assert_eq!("warrant".try_into(), Ok(Attribute::Warrant));
+        assert_eq!("authored_entry".try_into(), Ok(Attribute::AuthoredEntry));
+        assert_eq!(
+            "validation_receipt".try_into(),
+            Ok(Attribute::ValidationReceipt)
+        );
+        assert_eq!(
+            "validation_result".try_into(),
+            Ok(Attribute::ValidationResult)
+        );
+        assert_eq!("storage_version".try_into(), Ok(Attribute::StorageVersion));
         assert!(
             (r"unknown \\and// invalid / attribute".try_into() as Result<Attribute, _>).is_err(),
         );
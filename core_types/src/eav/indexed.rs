@@ -0,0 +1,395 @@
+//! An in-memory EAV store that keeps secondary indexes on entity, attribute and value.
+//!
+//! Queries are answered by checking the `EaviQuery` filters against the distinct keys
+//! of the cheapest indexes instead of against every stored row, and intersecting the
+//! row sets of the matching keys. Only the remaining candidate rows get cloned and
+//! handed to `EaviQuery::run`, which applies all filters and does the index/tombstone
+//! resolution.
+
+use holochain_persistence_api::{
+    eav::storage::EntityAttributeValueStorage as GenericStorage,
+    error::{PersistenceError, PersistenceResult},
+};
+
+use crate::{
+    eav::{
+        eavi::{Attribute, Entity, EntityAttributeValueIndex, Index, Value},
+        query::EaviQuery,
+        storage::increment_key_till_no_collision,
+    },
+    error::HcResult,
+};
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+use uuid::Uuid;
+
+/// The index structure shared by all clones of an `EavIndexedStorage`.
+/// Rows are keyed by their EAVI index, which is unique within the store.
+#[derive(Clone, Debug, Default)]
+pub struct EavIndex {
+    rows: BTreeMap<Index, EntityAttributeValueIndex>,
+    by_entity: HashMap<Entity, BTreeSet<Index>>,
+    by_attribute: HashMap<Attribute, BTreeSet<Index>>,
+    by_value: HashMap<Value, BTreeSet<Index>>,
+}
+
+impl EavIndex {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn contains_index(&self, index: Index) -> bool {
+        self.rows.contains_key(&index)
+    }
+
+    /// Inserts the given EAVI, moving its index forward until it does not collide
+    /// with a row that is already stored. Returns the EAVI as it got stored.
+    pub fn insert(
+        &mut self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> HcResult<EntityAttributeValueIndex> {
+        let new_eavi = increment_key_till_no_collision(eavi.clone(), &self.rows)?;
+        let index = new_eavi.index();
+        self.by_entity
+            .entry(new_eavi.entity())
+            .or_insert_with(BTreeSet::new)
+            .insert(index);
+        self.by_attribute
+            .entry(new_eavi.attribute())
+            .or_insert_with(BTreeSet::new)
+            .insert(index);
+        self.by_value
+            .entry(new_eavi.value())
+            .or_insert_with(BTreeSet::new)
+            .insert(index);
+        self.rows.insert(index, new_eavi.clone());
+        Ok(new_eavi)
+    }
+
+    /// Removes the row with the given index from the store and from all secondary indexes.
+    pub fn remove(&mut self, index: Index) -> Option<EntityAttributeValueIndex> {
+        let eavi = self.rows.remove(&index)?;
+        remove_from_index(&mut self.by_entity, &eavi.entity(), index);
+        remove_from_index(&mut self.by_attribute, &eavi.attribute(), index);
+        remove_from_index(&mut self.by_value, &eavi.value(), index);
+        Some(eavi)
+    }
+
+    /// All rows in index order.
+    pub fn iter(&self) -> impl Iterator<Item = &EntityAttributeValueIndex> {
+        self.rows.values()
+    }
+
+    /// Answers the given query.
+    /// The indexes get used in the order of their number of distinct keys. Each one that
+    /// is used checks its filter once per distinct key and intersects the row sets of the
+    /// matching keys with the candidates so far, by reference. As soon as there are fewer
+    /// candidate rows than distinct keys in the next index, the remaining filters are left
+    /// to `EaviQuery::run`, so an index with a filter that matches everything (i.e. `None`)
+    /// only gets scanned if it is the cheapest way to narrow down the rows.
+    /// Only the candidate rows get cloned; `EaviQuery::run` then checks all filters on them
+    /// and applies the index filter and tombstones.
+    pub fn fetch(&self, query: &EaviQuery) -> BTreeSet<EntityAttributeValueIndex> {
+        let by_entity: KeyLookup =
+            Box::new(move || matching_keys(&self.by_entity, |e| query.entity().check(e)));
+        let by_attribute: KeyLookup =
+            Box::new(move || matching_keys(&self.by_attribute, |a| query.attribute().check(a)));
+        let by_value: KeyLookup =
+            Box::new(move || matching_keys(&self.by_value, |v| query.value().check(v)));
+        let mut lookups = vec![
+            (self.by_entity.len(), by_entity),
+            (self.by_attribute.len(), by_attribute),
+            (self.by_value.len(), by_value),
+        ];
+        lookups.sort_by_key(|(distinct_keys, _)| *distinct_keys);
+
+        // Starts out borrowing the row sets of the first index's matching keys,
+        // which are disjoint since every row has exactly one key per index
+        let mut candidates: Option<Vec<Cow<BTreeSet<Index>>>> = None;
+        for (distinct_keys, lookup) in lookups {
+            if let Some(ref candidates) = candidates {
+                if row_count(candidates) <= distinct_keys {
+                    break;
+                }
+            }
+            let sets: Vec<Cow<BTreeSet<Index>>> =
+                lookup().into_iter().map(Cow::Borrowed).collect();
+            candidates = Some(match candidates {
+                None => sets,
+                Some(candidates) => vec![Cow::Owned(intersect(&candidates, &sets))],
+            });
+        }
+
+        let candidates = candidates.unwrap_or_default();
+        query.run(
+            candidates
+                .iter()
+                .flat_map(|set| set.iter())
+                .filter_map(|index| self.rows.get(index).cloned()),
+        )
+    }
+}
+
+/// Looks up the row sets of all keys of one index that pass the query's filter for it.
+type KeyLookup<'a> = Box<dyn FnOnce() -> Vec<&'a BTreeSet<Index>> + 'a>;
+
+/// The row sets of all keys of `index` that pass the given filter.
+/// Only keys get cloned, for the filter check, the row sets are borrowed.
+fn matching_keys<K, F>(index: &HashMap<K, BTreeSet<Index>>, filter: F) -> Vec<&BTreeSet<Index>>
+where
+    K: Clone + Eq + Hash,
+    F: Fn(K) -> bool,
+{
+    index
+        .iter()
+        .filter(|(key, _)| filter((*key).clone()))
+        .map(|(_, indexes)| indexes)
+        .collect()
+}
+
+fn row_count(sets: &[Cow<BTreeSet<Index>>]) -> usize {
+    sets.iter().map(|set| set.len()).sum()
+}
+
+/// The rows that are contained in both collections of disjoint row sets.
+/// Walks whichever side has fewer rows and probes the other one.
+fn intersect(left: &[Cow<BTreeSet<Index>>], right: &[Cow<BTreeSet<Index>>]) -> BTreeSet<Index> {
+    let (walked, probed) = if row_count(left) <= row_count(right) {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    walked
+        .iter()
+        .flat_map(|set| set.iter())
+        .filter(|index| probed.iter().any(|set| set.contains(index)))
+        .cloned()
+        .collect()
+}
+
+fn remove_from_index<K: Eq + Hash>(index: &mut HashMap<K, BTreeSet<Index>>, key: &K, row: Index) {
+    let now_empty = index
+        .get_mut(key)
+        .map(|indexes| {
+            indexes.remove(&row);
+            indexes.is_empty()
+        })
+        .unwrap_or(false);
+    if now_empty {
+        index.remove(key);
+    }
+}
+
+/// Transient EAV storage backed by an `EavIndex`.
+/// Clones share the same underlying index.
+#[derive(Clone, Debug)]
+pub struct EavIndexedStorage {
+    index: Arc<RwLock<EavIndex>>,
+    id: Uuid,
+}
+
+impl PartialEq for EavIndexedStorage {
+    fn eq(&self, other: &EavIndexedStorage) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Default for EavIndexedStorage {
+    fn default() -> Self {
+        EavIndexedStorage {
+            index: Arc::new(RwLock::new(EavIndex::new())),
+            id: Uuid::new_v4(),
+        }
+    }
+}
+
+impl EavIndexedStorage {
+    pub fn new() -> EavIndexedStorage {
+        Default::default()
+    }
+}
+
+impl GenericStorage<Attribute> for EavIndexedStorage {
+    fn add_eavi(
+        &mut self,
+        eav: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let mut index = self.index.write()?;
+        let eavi = index
+            .insert(eav)
+            .map_err(|err| PersistenceError::ErrorGeneric(err.to_string()))?;
+        Ok(Some(eavi))
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        let index = self.index.read()?;
+        Ok(index.fetch(query))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::eav::eavi::{test_eav, test_eav_attribute, test_eav_entity, test_eav_value};
+    use holochain_json_api::json::{JsonString, RawString};
+    use holochain_persistence_api::{
+        cas::{
+            content::{Address, AddressableContent, ExampleAddressableContent},
+            storage::EavTestSuite,
+        },
+        eav::IndexFilter,
+    };
+
+    #[test]
+    fn indexed_eav_round_trip() {
+        let entity =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("foo")))
+                .unwrap();
+        let attribute = Attribute::LinkTag("abc".to_string(), "favourite-color".to_string());
+        let value =
+            ExampleAddressableContent::try_from_content(&JsonString::from(RawString::from("blue")))
+                .unwrap();
+
+        EavTestSuite::test_round_trip(EavIndexedStorage::new(), entity, attribute, value)
+    }
+
+    #[test]
+    fn indexed_eav_one_to_many() {
+        EavTestSuite::test_one_to_many::<ExampleAddressableContent, Attribute, EavIndexedStorage>(
+            EavIndexedStorage::new(),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn indexed_eav_many_to_one() {
+        EavTestSuite::test_many_to_one::<ExampleAddressableContent, Attribute, EavIndexedStorage>(
+            EavIndexedStorage::new(),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn indexed_eav_range() {
+        EavTestSuite::test_range::<ExampleAddressableContent, Attribute, EavIndexedStorage>(
+            EavIndexedStorage::new(),
+            &Attribute::default(),
+        );
+    }
+
+    #[test]
+    fn indexed_eav_prefixes() {
+        EavTestSuite::test_multiple_attributes::<
+            ExampleAddressableContent,
+            Attribute,
+            EavIndexedStorage,
+        >(
+            EavIndexedStorage::new(),
+            vec!["a_", "b_", "c_", "d_"]
+                .into_iter()
+                .map(|p| Attribute::LinkTag(p.to_string() + "one_to_many", "".into()))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn insert_moves_colliding_index_forward() {
+        let mut index = EavIndex::new();
+        let first = index.insert(&test_eav()).unwrap();
+        let second = index.insert(&test_eav()).unwrap();
+        assert_eq!(first.index(), 0);
+        assert_eq!(second.index(), 1);
+        assert_eq!(index.len(), 2);
+
+        assert_eq!(index.remove(0), Some(first));
+        assert_eq!(index.len(), 1);
+        assert!(!index.contains_index(0));
+    }
+
+    #[test]
+    fn fetch_only_returns_rows_matching_all_filters() {
+        let mut storage = EavIndexedStorage::new();
+        let eavi = test_eav();
+        storage.add_eavi(&eavi).unwrap();
+        storage
+            .add_eavi(
+                &EntityAttributeValueIndex::new(
+                    &test_eav_value().address(),
+                    &test_eav_attribute(),
+                    &test_eav_entity().address(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let result = storage
+            .fetch_eavi(&EaviQuery::new(
+                Some(test_eav_entity().address()).into(),
+                Some(test_eav_attribute()).into(),
+                None.into(),
+                IndexFilter::LatestByAttribute,
+                None,
+            ))
+            .unwrap();
+        assert_eq!(result, btreeset! {eavi});
+    }
+
+    #[test]
+    fn fetch_intersects_indexes_until_candidates_are_fewer_than_keys() {
+        let mut index = EavIndex::new();
+        let entities: Vec<Address> = (0..5)
+            .map(|i| Address::from(format!("entity-{}", i)))
+            .collect();
+        let values: Vec<Address> = (0..5)
+            .map(|i| Address::from(format!("value-{}", i)))
+            .collect();
+        for entity in entities.iter() {
+            for value in values.iter() {
+                index
+                    .insert(
+                        &EntityAttributeValueIndex::new(entity, &test_eav_attribute(), value)
+                            .unwrap(),
+                    )
+                    .unwrap();
+            }
+        }
+
+        let result = index.fetch(&EaviQuery::new(
+            Some(entities[1].clone()).into(),
+            None.into(),
+            Some(values[3].clone()).into(),
+            IndexFilter::Range(None, None),
+            None,
+        ));
+        assert_eq!(result.len(), 1);
+        let row = result.iter().next().unwrap();
+        assert_eq!(row.entity(), entities[1]);
+        assert_eq!(row.value(), values[3]);
+
+        let result = index.fetch(&EaviQuery::new(
+            Some(entities[2].clone()).into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        ));
+        assert_eq!(result.len(), 5);
+        assert!(result.iter().all(|row| row.entity() == entities[2]));
+    }
+}
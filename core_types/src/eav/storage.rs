@@ -1,8 +1,12 @@
-use eav::{eavi::EntityAttributeValueIndex, query::EaviQuery};
+use eav::{
+    eavi::{EntityAttributeValueIndex, Index},
+    indexed::EavIndex,
+    query::EaviQuery,
+};
 use error::{HcResult, HolochainError};
 use objekt;
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
     sync::{Arc, RwLock},
 };
@@ -38,7 +42,7 @@ clone_trait_object!(EntityAttributeValueStorage);
 
 #[derive(Clone, Debug, Default)]
 pub struct ExampleEntityAttributeValueStorage {
-    storage: Arc<RwLock<BTreeSet<EntityAttributeValueIndex>>>,
+    storage: Arc<RwLock<EavIndex>>,
 }
 
 impl ExampleEntityAttributeValueStorage {
@@ -52,20 +56,16 @@ impl EntityAttributeValueStorage for ExampleEntityAttributeValueStorage {
         &mut self,
         eav: &EntityAttributeValueIndex,
     ) -> Result<Option<EntityAttributeValueIndex>, HolochainError> {
-        let mut map = self.storage.write()?;
-        let new_eav = increment_key_till_no_collision(eav.clone(), map.clone())?;
-        map.insert(new_eav.clone());
-        Ok(Some(new_eav.clone()))
+        let mut index = self.storage.write()?;
+        Ok(Some(index.insert(eav)?))
     }
 
     fn fetch_eavi(
         &self,
         query: &EaviQuery,
     ) -> Result<BTreeSet<EntityAttributeValueIndex>, HolochainError> {
-        let lock = self.storage.read()?;
-        let set = (*lock).clone();
-        let iter = set.iter().cloned();
-        Ok(query.run(iter))
+        let index = self.storage.read()?;
+        Ok(index.fetch(query))
     }
 }
 
@@ -76,15 +76,15 @@ impl PartialEq for dyn EntityAttributeValueStorage {
     }
 }
 
+/// Moves the index of the given EAVI forward until it does not collide with a key of `map`.
+/// Every probe is a lookup in the ordered map, so no scan over the stored rows is needed.
 pub fn increment_key_till_no_collision(
     mut eav: EntityAttributeValueIndex,
-    map: BTreeSet<EntityAttributeValueIndex>,
+    map: &BTreeMap<Index, EntityAttributeValueIndex>,
 ) -> HcResult<EntityAttributeValueIndex> {
-    if map.iter().any(|e| e.index() == eav.index()) {
-        let timestamp = eav.clone().index() + 1;
+    while map.contains_key(&eav.index()) {
+        let timestamp = eav.index() + 1;
         eav.set_index(timestamp);
-        increment_key_till_no_collision(eav, map)
-    } else {
-        Ok(eav)
     }
+    Ok(eav)
 }
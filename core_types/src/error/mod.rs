@@ -112,12 +112,21 @@ pub enum HolochainError {
     Ribosome(RibosomeErrorCode),
     RibosomeFailed(String),
     ConfigError(String),
-    Timeout,
+    /// Something took too long. Zome calls that get aborted because they ran past
+    /// their deadline carry the backtrace of where they were, if the instance traces them.
+    Timeout(Option<String>),
     InitializationFailed(String),
     LifecycleError(String),
     DnaHashMismatch(HashString, HashString),
     EntryNotFoundLocally,
     EntryIsPrivate,
+    CorruptState(String),
+    QuotaExceeded(String),
+    /// Fuel used by the aborted call and its backtrace, if the instance traces calls
+    OutOfFuel(u64, Option<String>),
+    /// Memory limit in pages and the backtrace of the aborted call, if the instance
+    /// traces calls
+    MemoryLimitExceeded(u32, Option<String>),
     List(Vec<HolochainError>),
 }
 
@@ -135,6 +144,14 @@ impl From<rust_base58::base58::FromBase58Error> for HolochainError {
     }
 }
 
+/// Puts the backtrace of an aborted WASM call, if there is one, on the lines after its error.
+fn write_backtrace(f: &mut fmt::Formatter, backtrace: &Option<String>) -> fmt::Result {
+    match backtrace {
+        Some(backtrace) => write!(f, "\n{}", backtrace),
+        None => Ok(()),
+    }
+}
+
 impl fmt::Display for HolochainError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -155,7 +172,10 @@ impl fmt::Display for HolochainError {
             Ribosome(err_code) => write!(f, "{}", err_code.as_str()),
             RibosomeFailed(fail_msg) => write!(f, "{}", fail_msg),
             ConfigError(err_msg) => write!(f, "{}", err_msg),
-            Timeout => write!(f, "timeout"),
+            Timeout(backtrace) => {
+                write!(f, "timeout")?;
+                write_backtrace(f, backtrace)
+            }
             InitializationFailed(err_msg) => write!(f, "{}", err_msg),
             LifecycleError(err_msg) => write!(f, "{}", err_msg),
             DnaHashMismatch(hash1, hash2) => write!(
@@ -168,6 +188,16 @@ impl fmt::Display for HolochainError {
                 f,
                 "The requested entry is private and should not be shared via gossip"
             ),
+            CorruptState(err_msg) => write!(f, "persisted state is corrupt: {}", err_msg),
+            QuotaExceeded(err_msg) => write!(f, "storage quota exceeded: {}", err_msg),
+            OutOfFuel(used, backtrace) => {
+                write!(f, "WASM execution ran out of fuel after {} units", used)?;
+                write_backtrace(f, backtrace)
+            }
+            MemoryLimitExceeded(limit, backtrace) => {
+                write!(f, "WASM memory limit of {} pages exceeded", limit)?;
+                write_backtrace(f, backtrace)
+            }
             List(list) => {
                 //most windows system know that \n is a newline so we should be good.
                 let error_list = list
@@ -404,7 +434,11 @@ mod tests {
                 HolochainError::CapabilityCheckFailed,
                 "Caller does not have Capability to make that call",
             ),
-            (HolochainError::Timeout, "timeout"),
+            (HolochainError::Timeout(None), "timeout"),
+            (
+                HolochainError::Timeout(Some(String::from("WASM backtrace:"))),
+                "timeout\nWASM backtrace:",
+            ),
             (
                 HolochainError::ValidationPending,
                 "Entry validation could not be completed",
@@ -417,6 +451,22 @@ mod tests {
                 HolochainError::EntryIsPrivate,
                 "The requested entry is private and should not be shared via gossip",
             ),
+            (
+                HolochainError::CorruptState(String::from("foo")),
+                "persisted state is corrupt: foo",
+            ),
+            (
+                HolochainError::QuotaExceeded(String::from("foo")),
+                "storage quota exceeded: foo",
+            ),
+            (
+                HolochainError::OutOfFuel(1000, None),
+                "WASM execution ran out of fuel after 1000 units",
+            ),
+            (
+                HolochainError::MemoryLimitExceeded(16, None),
+                "WASM memory limit of 16 pages exceeded",
+            ),
         ] {
             assert_eq!(output, &input.to_string());
         }
@@ -158,6 +158,8 @@ pub enum RibosomeErrorCode {
     MismatchWasmCallDataType        = 11 << 32,
     EntryNotFound                   = 12 << 32,
     WorkflowFailed                  = 13 << 32,
+    OutOfFuel                       = 14 << 32,
+    MemoryLimitExceeded             = 15 << 32,
 }
 
 #[rustfmt::skip]
@@ -177,6 +179,8 @@ impl RibosomeErrorCode {
             MismatchWasmCallDataType        => "Mismatched WasmCallData type",
             EntryNotFound                   => "Entry Could Not Be Found",
             WorkflowFailed                  => "Workflow failed",
+            OutOfFuel                       => "Out of fuel",
+            MemoryLimitExceeded             => "Memory limit exceeded",
         }
     }
 }
@@ -202,12 +206,16 @@ impl From<HolochainError> for RibosomeErrorCode {
             HolochainError::Ribosome(e) => e,
             HolochainError::RibosomeFailed(_) => RibosomeErrorCode::CallbackFailed,
             HolochainError::ConfigError(_) => RibosomeErrorCode::Unspecified,
-            HolochainError::Timeout => RibosomeErrorCode::Unspecified,
+            HolochainError::Timeout(_) => RibosomeErrorCode::Unspecified,
             HolochainError::InitializationFailed(_) => RibosomeErrorCode::Unspecified,
             HolochainError::LifecycleError(_) => RibosomeErrorCode::Unspecified,
             HolochainError::DnaHashMismatch(_, _) => RibosomeErrorCode::Unspecified,
             HolochainError::EntryNotFoundLocally => RibosomeErrorCode::Unspecified,
             HolochainError::EntryIsPrivate => RibosomeErrorCode::Unspecified,
+            HolochainError::CorruptState(_) => RibosomeErrorCode::Unspecified,
+            HolochainError::QuotaExceeded(_) => RibosomeErrorCode::Unspecified,
+            HolochainError::OutOfFuel(_, _) => RibosomeErrorCode::OutOfFuel,
+            HolochainError::MemoryLimitExceeded(_, _) => RibosomeErrorCode::MemoryLimitExceeded,
             HolochainError::List(_) => RibosomeErrorCode::Unspecified,
         }
     }
@@ -240,6 +248,8 @@ impl RibosomeErrorCode {
             10 => UnknownEntryType,
             12 => EntryNotFound,
             13 => WorkflowFailed,
+            14 => OutOfFuel,
+            15 => MemoryLimitExceeded,
             1 | _ => Unspecified,
         }
     }
@@ -295,6 +305,8 @@ impl FromStr for RibosomeErrorCode {
             "Unknown entry type" => Ok(RibosomeErrorCode::UnknownEntryType),
             "Entry Could Not Be Found" => Ok(EntryNotFound),
             "Workflow failed" => Ok(WorkflowFailed),
+            "Out of fuel" => Ok(OutOfFuel),
+            "Memory limit exceeded" => Ok(MemoryLimitExceeded),
             _ => Err(HolochainError::ErrorGeneric(String::from(
                 "Unknown RibosomeErrorCode",
             ))),
@@ -319,7 +331,7 @@ pub mod tests {
     fn error_conversion() {
         // TODO could use strum crate to iteratively
         // gather all known codes.
-        for code in 1..=13 {
+        for code in 1..=15 {
             let mut err = RibosomeErrorCode::from_code_int(code);
 
             let err_str = err.as_str().to_owned();
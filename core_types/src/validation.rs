@@ -19,6 +19,10 @@ use chain_header::test_chain_header;
 
 use std::convert::TryFrom;
 
+/// Maximum size in bytes of a validation package built from a
+/// `ValidationPackageDefinition::Custom`, serialized as JSON.
+pub const MAX_CUSTOM_VALIDATION_PACKAGE_SIZE: usize = 1024 * 1024;
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, DefaultJson)]
 pub struct ValidationPackage {
     pub chain_header: ChainHeader,
@@ -36,6 +40,11 @@ impl ValidationPackage {
             custom: None,
         }
     }
+
+    /// Size in bytes of the package serialized as JSON, as it gets sent to validators.
+    pub fn size(&self) -> usize {
+        String::from(JsonString::from(self.to_owned())).len()
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, DefaultJson)]
@@ -48,7 +57,8 @@ pub enum ValidationPackageDefinition {
     ChainHeaders,
     /// sending the whole chain: public entries and all headers
     ChainFull,
-    /// sending something custom
+    /// sending the headers and public entries selected by the zome's
+    /// custom validation package callback, which gets the given string
     Custom(String),
 }
 
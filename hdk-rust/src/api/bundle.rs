@@ -1,12 +1,68 @@
+use super::Dispatch;
 use api::BundleOnClose;
-use error::{ZomeApiError, ZomeApiResult};
+use error::ZomeApiResult;
+use holochain_json_api::json::JsonString;
+use holochain_wasm_utils::api_serialization::bundle::StartBundleArgs;
 
-/// NOT YET AVAILABLE
-pub fn start_bundle(_timeout: usize, _user_param: serde_json::Value) -> ZomeApiResult<()> {
-    Err(ZomeApiError::FunctionNotImplemented)
+/// Starts a bundle of commits that get written to the source chain all together or not at all.
+///
+/// Until the bundle gets closed with [close_bundle](fn.close_bundle.html), everything the
+/// calling zome function commits, updates, removes or links is staged in the bundle instead of
+/// being written to the chain. The addresses returned by these calls are the addresses the
+/// entries will have once the bundle gets committed.
+///
+/// `timeout` is the number of milliseconds after which the bundle can't be committed anymore,
+/// 0 for no timeout. `user_param` is any value the app wants to associate with the bundle;
+/// it shows up in the logs when the bundle gets closed.
+///
+/// Only one bundle can be open at a time. A bundle that is still open when the zome function
+/// returns gets discarded.
+/// # Examples
+/// ```rust
+/// # #[macro_use]
+/// # extern crate hdk;
+/// # extern crate serde_json;
+/// # use hdk::error::ZomeApiResult;
+/// # use hdk::holochain_core_types::entry::Entry;
+/// # use hdk::holochain_persistence_api::cas::content::Address;
+/// # use hdk::api::BundleOnClose;
+/// # use hdk::holochain_core_types::error::RibosomeEncodingBits;
+/// # use hdk::holochain_core_types::error::RibosomeEncodedValue;
+/// # #[no_mangle]
+/// # pub fn hc_start_bundle(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_close_bundle(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_commit_entry(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_link_entries(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+///
+/// # fn main() {
+/// pub fn handle_create_post(post: Entry, author: Address) -> ZomeApiResult<Address> {
+///     hdk::start_bundle(10_000, serde_json::Value::Null)?;
+///     let address = hdk::commit_entry(&post)?;
+///     hdk::link_entries(&author, &address, "authored_posts", "")?;
+///     // Commits the post and the link together, or neither of them if one is invalid
+///     hdk::close_bundle(BundleOnClose::Commit)?;
+///     Ok(address)
+/// }
+/// # }
+/// ```
+pub fn start_bundle(timeout: usize, user_param: serde_json::Value) -> ZomeApiResult<()> {
+    Dispatch::StartBundle.with_input(StartBundleArgs {
+        timeout,
+        user_param: JsonString::from_json(&user_param.to_string()),
+    })
 }
 
-/// NOT YET AVAILABLE
-pub fn close_bundle(_action: BundleOnClose) -> ZomeApiResult<()> {
-    Err(ZomeApiError::FunctionNotImplemented)
+/// Closes the bundle started with [start_bundle](fn.start_bundle.html).
+///
+/// With `BundleOnClose::Commit`, all entries staged in the bundle get validated. Only if all
+/// of them are valid, they get written to the source chain and published. Otherwise none of
+/// them get committed and the validation error is returned.
+/// With `BundleOnClose::Discard`, all staged entries get dropped.
+///
+/// Committing a bundle after its timeout fails with a timeout error and discards the bundle.
+pub fn close_bundle(action: BundleOnClose) -> ZomeApiResult<()> {
+    Dispatch::CloseBundle.with_input(action)
 }
@@ -34,11 +34,14 @@ mod keystore;
 mod link_entries;
 mod property;
 mod query;
+mod random_bytes;
 mod remove_link;
 mod send;
 mod sign;
 mod sleep;
+mod sys_time;
 mod update_remove;
+mod validation_receipt_count;
 mod version;
 
 pub use self::{
@@ -64,11 +67,14 @@ pub use self::{
     link_entries::link_entries,
     property::property,
     query::{query, query_result},
+    random_bytes::random_bytes,
     remove_link::remove_link,
     send::send,
     sign::{sign, sign_one_time, verify_signature},
     sleep::sleep,
+    sys_time::sys_time,
     update_remove::{remove_entry, update_agent, update_entry},
+    validation_receipt_count::validation_receipt_count,
     version::version,
 };
 
@@ -135,8 +141,6 @@ macro_rules! def_api_fns {
         #[allow(dead_code)]
         extern "C" {
             pub(crate) fn hc_property(_: RibosomeEncodingBits) -> RibosomeEncodingBits;
-            pub(crate) fn hc_start_bundle(_: RibosomeEncodingBits) -> RibosomeEncodingBits;
-            pub(crate) fn hc_close_bundle(_: RibosomeEncodingBits) -> RibosomeEncodingBits;
             $( pub(crate) fn $function_name (_: RibosomeEncodingBits) -> RibosomeEncodingBits;) *
         }
 
@@ -173,6 +177,9 @@ def_api_fns! {
     hc_entry_address, EntryAddress;
     hc_query, Query;
     hc_update_entry, UpdateEntry;
+    hc_update_agent, UpdateAgent;
+    hc_sys_time, SysTime;
+    hc_random_bytes, RandomBytes;
     hc_remove_entry, RemoveEntry;
     hc_send, Send;
     hc_debug, Debug;
@@ -195,6 +202,9 @@ def_api_fns! {
     hc_commit_capability_grant, CommitCapabilityGrant;
     hc_commit_capability_claim, CommitCapabilityClaim;
     hc_emit_signal, EmitSignal;
+    hc_start_bundle, StartBundle;
+    hc_close_bundle, CloseBundle;
+    hc_validation_receipt_count, ValidationReceiptCount;
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -228,7 +238,6 @@ lazy_static! {
     /// This is your peer's identity on the DHT.
     pub static ref AGENT_INITIAL_HASH: &'static HashString = &GLOBALS.agent_initial_hash;
 
-    #[doc(hidden)]
     /// The hash of the most recent identity entry that has been committed to your chain.
     /// Starts with the same value as AGENT_INITIAL_HASH.
     /// After a call to `update_agent` it will have the value of the hash of the newly committed identity entry.
@@ -365,7 +374,4 @@ impl Default for GetEntryMask {
 //}
 
 /// Allowed input for close_bundle()
-pub enum BundleOnClose {
-    Commit,
-    Discard,
-}
+pub use holochain_wasm_utils::api_serialization::bundle::BundleOnClose;
@@ -56,6 +56,20 @@ use holochain_wasm_utils::api_serialization::{
 /// // }
 /// ```
 ///
+/// Results come latest first by default.  Set `ordering` to `QueryOrdering::Forward` to get the
+/// oldest first, and `filter_by` to a `QueryFilter` to only get entries committed within a time
+/// range (both bounds are inclusive, at a granularity of whole seconds):
+///
+/// ```
+/// // pub fn get_posts_since(since: Iso8601) -> ZomeApiResult<QueryResult> {
+/// //    hdk::query_result("post".into(), QueryArgsOptions{
+/// //        ordering: QueryOrdering::Forward,
+/// //        filter_by: QueryFilter{ since: Some(since), until: None },
+/// //        ..Default::default()
+/// //    })
+/// // }
+/// ```
+///
 /// The types of the results available depend on whether `headers` and/or `entries` is set:
 ///
 /// ```
@@ -80,6 +94,7 @@ pub fn query(
             limit,
             headers: false,
             entries: false,
+            ..Default::default()
         },
     )
     .and_then(|result| match result {
@@ -0,0 +1,25 @@
+use super::Dispatch;
+use error::ZomeApiResult;
+use holochain_wasm_utils::api_serialization::random_bytes::{RandomBytesArgs, RandomBytesResult};
+
+/// Returns `length` cryptographically secure random bytes generated by the host.
+///
+/// Not available in validation callbacks since validation has to give the same result
+/// on every node.
+/// # Examples
+/// ```rust
+/// # #[macro_use]
+/// # extern crate hdk;
+/// # use hdk::error::ZomeApiResult;
+///
+/// # fn main() {
+/// pub fn handle_new_nonce() -> ZomeApiResult<Vec<u8>> {
+///     hdk::random_bytes(32)
+/// }
+/// # }
+/// ```
+pub fn random_bytes(length: usize) -> ZomeApiResult<Vec<u8>> {
+    Dispatch::RandomBytes
+        .with_input(RandomBytesArgs { length })
+        .map(|result: RandomBytesResult| result.bytes)
+}
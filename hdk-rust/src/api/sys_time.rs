@@ -0,0 +1,27 @@
+use super::Dispatch;
+use error::ZomeApiResult;
+use holochain_wasm_utils::api_serialization::sys_time::SysTimeResult;
+
+/// Returns the current time of the host running the DNA, together with the timestamp of
+/// the header at the head of the agent's source chain.
+///
+/// The host's time can't be checked by other nodes, so it is only meant for what the
+/// agent itself sees. Validation callbacks can't call `sys_time` because validation has to
+/// give the same result on every node; they should use the timestamps of the headers in
+/// their validation data instead.
+/// # Examples
+/// ```rust
+/// # #[macro_use]
+/// # extern crate hdk;
+/// # use hdk::error::ZomeApiResult;
+/// # use hdk::holochain_core_types::time::Iso8601;
+///
+/// # fn main() {
+/// pub fn handle_now() -> ZomeApiResult<Iso8601> {
+///     Ok(hdk::sys_time()?.now)
+/// }
+/// # }
+/// ```
+pub fn sys_time() -> ZomeApiResult<SysTimeResult> {
+    Dispatch::SysTime.without_input()
+}
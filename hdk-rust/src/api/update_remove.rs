@@ -1,5 +1,5 @@
 use super::Dispatch;
-use error::{ZomeApiError, ZomeApiResult};
+use error::ZomeApiResult;
 use holochain_core_types::entry::Entry;
 use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::UpdateEntryArgs;
@@ -17,9 +17,18 @@ pub fn update_entry(new_entry: Entry, address: &Address) -> ZomeApiResult<Addres
     })
 }
 
-/// NOT YET AVAILABLE
+/// Rotates the agent's key, e.g. after a device key got compromised.
+/// The conductor generates a new keypair in the agent's keystore and a new agent entry with the
+/// new public key gets committed as an update of the current agent entry. The entry is signed
+/// with both the old and the new key, and on the DHT the old agent entry points to the new one.
+/// Once committed, the conductor switches the agent to the new key, so everything committed
+/// afterwards gets signed with it, and stores it in the keystore file and the agent config.
+///
+/// Returns the address of the new agent entry, which is the agent's new address.
+/// Agent validation callbacks see the rotation as `EntryValidationData::Modify`.
+/// Agents that run several instances in the conductor can't rotate their key.
 pub fn update_agent() -> ZomeApiResult<Address> {
-    Err(ZomeApiError::FunctionNotImplemented)
+    Dispatch::UpdateAgent.without_input()
 }
 
 /// Commit a DeletionEntry to your local source chain that marks an entry as 'deleted' by setting
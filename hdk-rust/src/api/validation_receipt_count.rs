@@ -0,0 +1,31 @@
+use super::Dispatch;
+use error::ZomeApiResult;
+use holochain_persistence_api::cas::content::Address;
+use holochain_wasm_utils::api_serialization::validation_receipt_count::{
+    ValidationReceiptCountArgs, ValidationReceiptCountResult,
+};
+
+/// Returns the number of distinct validators that sent this agent a receipt saying they
+/// found the entry with the given address valid, i.e. how many peers hold an entry that
+/// this agent authored.
+///
+/// Receipts are only kept by the author, so this is always 0 for entries of other agents.
+/// Not available in validation callbacks.
+/// # Examples
+/// ```rust
+/// # #[macro_use]
+/// # extern crate hdk;
+/// # use hdk::error::ZomeApiResult;
+/// # use hdk::holochain_persistence_api::cas::content::Address;
+///
+/// # fn main() {
+/// pub fn handle_is_replicated(address: Address) -> ZomeApiResult<bool> {
+///     Ok(hdk::validation_receipt_count(address)? >= 3)
+/// }
+/// # }
+/// ```
+pub fn validation_receipt_count(address: Address) -> ZomeApiResult<usize> {
+    Dispatch::ValidationReceiptCount
+        .with_input(ValidationReceiptCountArgs { address })
+        .map(|result: ValidationReceiptCountResult| result.count)
+}
@@ -8,11 +8,17 @@ use holochain_core_types::{
     entry::{entry_type::EntryType, AppEntryValue, Entry},
     validation::{EntryValidationData, LinkValidationData, ValidationPackageDefinition},
 };
-use holochain_wasm_utils::api_serialization::validation::LinkDirection;
+use holochain_wasm_utils::api_serialization::validation::{
+    CustomValidationPackage, CustomValidationPackageArgs, LinkDirection,
+};
 use std::convert::TryFrom;
 
 pub type PackageCreator = Box<dyn FnMut() -> ValidationPackageDefinition + Sync>;
 
+pub type CustomPackageBuilder = Box<
+    dyn FnMut(CustomValidationPackageArgs) -> Result<CustomValidationPackage, String> + Sync,
+>;
+
 pub type Validator = Box<dyn FnMut(EntryValidationData<Entry>) -> Result<(), String> + Sync>;
 
 pub type AgentValidator = Box<dyn FnMut(EntryValidationData<AgentId>) -> Result<(), String> + Sync>;
@@ -36,6 +42,9 @@ pub struct ValidatingEntryType {
     /// Callback that returns a validation package definition that Holochain reads in order
     /// to create the right validation package to pass in to the validator callback on validation.
     pub package_creator: PackageCreator,
+    /// Optional callback that runs on the author's node if the package creator returns
+    /// `ValidationPackageDefinition::Custom` and selects the chain headers to send.
+    pub custom_package_builder: Option<CustomPackageBuilder>,
     /// This is the validation callback that is used to determine if an entry is valid.
     pub validator: Validator,
 
@@ -66,7 +75,7 @@ pub struct ValidatingLinkDefinition {
 
 /// The `entry` macro is a helper for creating `ValidatingEntryType` definitions
 /// for use within the [define_zome](macro.define_zome.html) macro.
-/// It has 7 component parts:
+/// It has 7 component parts, plus an optional one:
 /// 1. name: `name` is simply the descriptive name of the entry type, such as "post", or "user".
 ///      It is what must be given as the `entry_type_name` argument when calling [commit_entry](fn.commit_entry.html) and the other data read/write functions.
 /// 2. description: `description` is something that is primarily for human readers of your code, just describe this entry type
@@ -86,6 +95,13 @@ pub struct ValidatingLinkDefinition {
 ///     to define an association pointing from this entry type to another, or one that points back from
 ///     the other entry type to this one.
 ///     See [link!](macro.link.html), [to!](macro.to.html) and [from!](macro.to.html) for more details.
+///
+/// If `validation_package` returns `ValidationPackageDefinition::Custom`, a
+/// `custom_validation_package` callback can be given right after `validation`.
+/// It runs on the author's node with a [CustomValidationPackageArgs](struct.CustomValidationPackageArgs.html)
+/// and returns the addresses of the chain headers (typically found with
+/// [query_result](fn.query_result.html)) that the validators need, which Holochain then sends
+/// along with their public entries.
 /// # Examples
 /// The following is a standalone Rust file that exports a function which can be called
 /// to get a `ValidatingEntryType` of a "post".
@@ -180,6 +196,11 @@ macro_rules! entry {
         validation_package: || $package_creator:expr,
         validation: | $validation_data:ident : hdk::EntryValidationData<$native_type:ty> | $entry_validation:expr
 
+        $(
+            ,
+            custom_validation_package: | $custom_package_args:ident : hdk::CustomValidationPackageArgs | $custom_package_builder:expr
+        )*
+
         $(
             ,
             links : [
@@ -220,6 +241,13 @@ macro_rules! entry {
                 $package_creator
             });
 
+            let custom_package_builder: Option<hdk::entry_definition::CustomPackageBuilder> = None
+            $(
+                .or(Some(Box::new(|$custom_package_args: hdk::CustomValidationPackageArgs| -> Result<hdk::CustomValidationPackage, String> {
+                    $custom_package_builder
+                })))
+            )*;
+
             let validator = Box::new(|validation_data: hdk::holochain_wasm_utils::holochain_core_types::validation::EntryValidationData<hdk::holochain_core_types::entry::Entry>| {
                 let $validation_data = hdk::entry_definition::entry_to_native_type::<$native_type>(validation_data.clone())?;
                 use std::convert::TryFrom;
@@ -242,6 +270,7 @@ macro_rules! entry {
                 name: hdk::holochain_core_types::entry::entry_type::EntryType::App(hdk::holochain_core_types::entry::entry_type::AppEntryType::from($name.to_string())),
                 entry_type_definition: entry_type,
                 package_creator,
+                custom_package_builder,
                 validator,
                 links: vec![
                     $($(
@@ -38,7 +38,7 @@ impl From<HolochainError> for ZomeApiError {
     fn from(holochain_error: HolochainError) -> Self {
         match holochain_error {
             HolochainError::ValidationFailed(s) => ZomeApiError::ValidationFailed(s),
-            HolochainError::Timeout => ZomeApiError::Timeout,
+            HolochainError::Timeout(_) => ZomeApiError::Timeout,
             _ => ZomeApiError::Internal(holochain_error.to_string()),
         }
     }
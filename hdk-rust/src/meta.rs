@@ -19,7 +19,8 @@ use holochain_json_api::{error::JsonError, json::JsonString};
 
 use holochain_wasm_utils::{
     api_serialization::validation::{
-        AgentIdValidationArgs, EntryValidationArgs, LinkValidationArgs, LinkValidationPackageArgs,
+        AgentIdValidationArgs, CustomValidationPackage, CustomValidationPackageArgs,
+        EntryValidationArgs, LinkValidationArgs, LinkValidationPackageArgs,
     },
     holochain_core_types::error::RibosomeErrorCode,
     memory::{
@@ -103,6 +104,52 @@ pub extern "C" fn __hdk_get_validation_package_for_entry_type(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn __hdk_get_custom_validation_package(
+    encoded_allocation_of_input: RibosomeEncodingBits,
+) -> RibosomeEncodingBits {
+    if let Err(allocation_error) =
+        ::global_fns::init_global_memory_from_ribosome_encoding(encoded_allocation_of_input)
+    {
+        return allocation_error.as_ribosome_encoding();
+    }
+
+    let mut zd = ZomeDefinition::new();
+    unsafe { zome_setup(&mut zd) };
+
+    let input: CustomValidationPackageArgs =
+        match load_ribosome_encoded_json(encoded_allocation_of_input) {
+            Ok(v) => v,
+            Err(e) => return RibosomeEncodedValue::from(e).into(),
+        };
+
+    match zd
+        .entry_types
+        .into_iter()
+        .find(|ref validating_entry_type| {
+            validating_entry_type.name
+                == EntryType::App(AppEntryType::from(input.entry_type.clone()))
+        })
+        .map(|entry_type_definition| entry_type_definition.custom_package_builder)
+    {
+        None => RibosomeEncodedValue::Failure(RibosomeErrorCode::CallbackFailed).into(),
+        // Entry types without a builder send just the definition string, as before
+        Some(None) => return_code_for_allocation_result(crate::global_fns::write_json(
+            CustomValidationPackage::default(),
+        ))
+        .into(),
+        Some(Some(mut custom_package_builder)) => match (*custom_package_builder)(input) {
+            Ok(package) => {
+                return_code_for_allocation_result(crate::global_fns::write_json(package)).into()
+            }
+            Err(fail_string) => return_code_for_allocation_result(crate::global_fns::write_json(
+                JsonString::from_json(&fail_string),
+            ))
+            .into(),
+        },
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn __hdk_validate_app_entry(
     encoded_allocation_of_input: RibosomeEncodingBits,
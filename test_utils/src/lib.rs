@@ -302,7 +302,7 @@ pub fn hc_setup_and_call_zome_fn<J: Into<JsonString>>(
     let params_string = String::from(params.into());
     let cap_request = make_cap_request_for_call(
         context.clone(),
-        context.clone().agent_id.address(),
+        context.clone().agent_id().address(),
         fn_name,
         JsonString::from_json(&params_string.clone()),
     );
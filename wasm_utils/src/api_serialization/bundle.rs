@@ -0,0 +1,17 @@
+use holochain_json_api::{error::JsonError, json::*};
+
+/// Struct for input data received when start_bundle is called
+#[derive(Deserialize, Clone, PartialEq, Debug, Serialize, DefaultJson)]
+pub struct StartBundleArgs {
+    /// Milliseconds after which the bundle can't be committed anymore, 0 for no timeout
+    pub timeout: usize,
+    /// Any value the app wants to associate with the bundle
+    pub user_param: JsonString,
+}
+
+/// Allowed input for close_bundle()
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Serialize, DefaultJson)]
+pub enum BundleOnClose {
+    Commit,
+    Discard,
+}
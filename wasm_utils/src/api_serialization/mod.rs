@@ -9,6 +9,7 @@ mod call;
 ///
 /// For the case of HDK-rust we can use the exact same types by
 /// importing this module.
+pub mod bundle;
 pub mod capabilities;
 pub mod commit_entry;
 pub mod crypto;
@@ -19,11 +20,14 @@ pub mod keystore;
 pub mod link_entries;
 pub mod meta;
 pub mod query;
+pub mod random_bytes;
 pub mod receive;
 pub mod send;
 pub mod sign;
+pub mod sys_time;
 mod update_entry;
 pub mod validation;
+pub mod validation_receipt_count;
 pub mod verify_signature;
 mod zome_api_globals;
 
@@ -1,6 +1,7 @@
 use holochain_core_types::{
     chain_header::ChainHeader,
     entry::{entry_type::EntryType, Entry},
+    time::Iso8601,
 };
 
 use holochain_json_api::{error::JsonError, json::*};
@@ -62,12 +63,34 @@ pub struct QueryArgs {
 pub struct QueryArgsOptions {
     pub start: usize,
     pub limit: usize,
-    // pub ordering: bool,  // forward or reverse (requires doubly-linked chain perhaps)
-    // pub filter_by: ???,
+    #[serde(default)]
+    pub ordering: QueryOrdering,
+    #[serde(default)]
+    pub filter_by: QueryFilter,
     pub headers: bool,
     pub entries: bool,
 }
 
+// QueryOrdering -- Reverse returns the latest entries first, Forward the oldest
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum QueryOrdering {
+    Reverse,
+    Forward,
+}
+
+impl Default for QueryOrdering {
+    fn default() -> QueryOrdering {
+        QueryOrdering::Reverse
+    }
+}
+
+// QueryFilter -- only return entries whose header timestamp lies within [since, until]
+#[derive(Deserialize, Default, Debug, Serialize, Clone, PartialEq)]
+pub struct QueryFilter {
+    pub since: Option<Iso8601>,
+    pub until: Option<Iso8601>,
+}
+
 #[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq)]
 pub enum QueryResult {
     Addresses(Vec<Address>),
@@ -0,0 +1,11 @@
+use holochain_json_api::{error::JsonError, json::*};
+
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Hash, Debug, Serialize, DefaultJson)]
+pub struct RandomBytesArgs {
+    pub length: usize,
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Hash, Debug, Serialize, DefaultJson)]
+pub struct RandomBytesResult {
+    pub bytes: Vec<u8>,
+}
@@ -0,0 +1,11 @@
+use holochain_core_types::time::Iso8601;
+use holochain_json_api::{error::JsonError, json::*};
+
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug, Serialize, DefaultJson)]
+pub struct SysTimeResult {
+    /// The host's current time
+    pub now: Iso8601,
+    /// The timestamp of the header at the head of the agent's source chain,
+    /// None if the chain is empty
+    pub chain_head_timestamp: Option<Iso8601>,
+}
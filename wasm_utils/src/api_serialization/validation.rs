@@ -1,11 +1,13 @@
 use holochain_core_types::{
     agent::AgentId,
+    chain_header::ChainHeader,
     entry::Entry,
     link::Link,
     validation::{EntryValidationData, LinkValidationData},
 };
 
 use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
 
 #[derive(Deserialize, Debug, Serialize, DefaultJson, Clone)]
 pub struct EntryValidationArgs {
@@ -30,6 +32,29 @@ pub struct LinkValidationPackageArgs {
     pub direction: LinkDirection,
 }
 
+/// Input of the custom validation package callback which runs on the author's node
+/// for entries whose validation package definition is `Custom`.
+#[derive(Deserialize, Debug, Serialize, DefaultJson, Clone)]
+pub struct CustomValidationPackageArgs {
+    pub entry_type: String,
+    pub entry: Entry,
+    /// Header of the entry the package gets built for
+    pub chain_header: ChainHeader,
+    /// The string given in `ValidationPackageDefinition::Custom`
+    pub definition: String,
+}
+
+/// Result of the custom validation package callback.
+/// Holochain looks up the listed headers in the author's chain and sends them,
+/// together with their public entries, in the validation package.
+#[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq, Default)]
+pub struct CustomValidationPackage {
+    /// Addresses of headers that precede the entry's header in the author's chain
+    pub chain_headers: Vec<Address>,
+    /// Sent as the package's `custom` field, defaults to the definition string
+    pub custom: Option<String>,
+}
+
 #[derive(Deserialize, Debug, Serialize, DefaultJson, Clone)]
 pub struct LinkValidationArgs {
     pub entry_type: String,
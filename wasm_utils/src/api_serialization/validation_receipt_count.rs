@@ -0,0 +1,12 @@
+use holochain_json_api::{error::JsonError, json::*};
+use holochain_persistence_api::cas::content::Address;
+
+#[derive(Deserialize, Clone, PartialEq, Debug, Serialize, DefaultJson)]
+pub struct ValidationReceiptCountArgs {
+    pub address: Address,
+}
+
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Hash, Debug, Serialize, DefaultJson)]
+pub struct ValidationReceiptCountResult {
+    pub count: usize,
+}